@@ -14,6 +14,14 @@ pub fn delay_ms(ms: u32) {
 	Delay::new().delay_ms(ms)
 }
 
+/// Delay execution for a number of milliseconds, calling `f` while waiting.
+///
+/// `f` is called once per millisecond, e.g. to feed the watchdog during a long delay.  The timing
+/// is not precise, see [`Delay::delay_ms_with()`][avr_hal_generic::delay::Delay::delay_ms_with].
+pub fn delay_ms_with<F: FnMut()>(ms: u32, f: F) {
+	Delay::new().delay_ms_with(ms, f)
+}
+
 /// Delay execution for a number of microseconds.
 ///
 /// Busy-loop for the given time.  This function assumes the default clock speed defined by
@@ -124,6 +124,8 @@ mod delay;
 #[cfg(feature = "board-selected")]
 pub use delay::delay_ms;
 #[cfg(feature = "board-selected")]
+pub use delay::delay_ms_with;
+#[cfg(feature = "board-selected")]
 pub use delay::delay_ns;
 #[cfg(feature = "board-selected")]
 pub use delay::delay_us;
@@ -141,15 +143,29 @@ pub use port::Pins;
 #[cfg(feature = "mcu-atmega")]
 pub mod adc {
 	pub use crate::hal::adc::channel;
+	pub use crate::hal::adc::trimmed_mean;
 	pub use crate::hal::adc::AdcChannel;
 	pub use crate::hal::adc::AdcOps;
 	pub use crate::hal::adc::AdcSettings;
+	pub use crate::hal::adc::AnalogInput;
+	pub use crate::hal::adc::CalibratedDiffAdc;
 	pub use crate::hal::adc::Channel;
 	pub use crate::hal::adc::ClockDivider;
+	pub use crate::hal::adc::DiffCoding;
+	pub use crate::hal::adc::RateOutOfRange;
 	pub use crate::hal::adc::ReferenceVoltage;
+	pub use crate::hal::adc::SchmittInput;
+	pub use crate::hal::adc::TwoPointCalibration;
+	pub use crate::hal::adc::Window;
+	pub use crate::hal::adc::WindowEvent;
 
 	/// Check the [`avr_hal_generic::adc::Adc`] documentation.
 	pub type Adc = crate::hal::Adc<crate::DefaultClock>;
+	/// Check the [`avr_hal_generic::adc::AdcInput`] documentation.
+	pub type AdcInput<'a> = crate::hal::adc::AdcInput<'a, crate::DefaultClock>;
+	/// Check the [`atmega_hal::adc::SampledBuffer`] documentation.
+	pub type SampledBuffer<const CHANNELS: usize> =
+		crate::hal::adc::SampledBuffer<crate::DefaultClock, CHANNELS>;
 }
 #[doc(no_inline)]
 #[cfg(feature = "mcu-atmega")]
@@ -180,6 +196,10 @@ pub use spi::Spi;
 #[cfg(feature = "mcu-atmega")]
 pub mod usart {
 	pub use crate::hal::usart::Baudrate;
+	pub use crate::hal::usart::Frame;
+	pub use crate::hal::usart::IdleDetector;
+	pub use crate::hal::usart::LineReader;
+	pub use crate::hal::usart::TimeoutError;
 	pub use crate::hal::usart::UsartOps;
 
 	pub type Usart<USART, RX, TX> =
@@ -188,6 +208,10 @@ pub mod usart {
 		crate::hal::usart::UsartWriter<USART, RX, TX, crate::DefaultClock>;
 	pub type UsartReader<USART, RX, TX> =
 		crate::hal::usart::UsartReader<USART, RX, TX, crate::DefaultClock>;
+	pub type Rs485Usart<USART, RX, TX, DIR> =
+		crate::hal::usart::Rs485Usart<USART, RX, TX, crate::DefaultClock, DIR>;
+	pub type BackgroundWriter<USART, RX, TX> =
+		crate::hal::usart::BackgroundWriter<USART, RX, TX, crate::DefaultClock>;
 }
 
 #[doc(no_inline)]
@@ -196,9 +220,18 @@ pub use usart::Usart;
 
 #[cfg(feature = "board-selected")]
 pub mod eeprom {
+	pub use crate::hal::eeprom::Config;
 	pub use crate::hal::eeprom::Eeprom;
 	pub use crate::hal::eeprom::EepromOps;
+	pub use crate::hal::eeprom::EepromRing;
+	pub use crate::hal::eeprom::EepromWriteModeOps;
+	pub use crate::hal::eeprom::Error;
+	pub use crate::hal::eeprom::GuardedEeprom;
+	pub use crate::hal::eeprom::LoadError;
 	pub use crate::hal::eeprom::OutOfBoundsError;
+	pub use crate::hal::eeprom::Persist;
+	pub use crate::hal::eeprom::SupplyVoltage;
+	pub use crate::hal::eeprom::WriteMode;
 }
 #[doc(no_inline)]
 #[cfg(feature = "board-selected")]
@@ -213,6 +246,73 @@ pub mod simple_pwm {
 	pub use attiny_hal::simple_pwm::*;
 }
 
+pub use avr_hal_generic::cobs;
+pub use avr_hal_generic::drivers;
+pub use avr_hal_generic::executor;
+pub use avr_hal_generic::interrupt;
+pub use avr_hal_generic::modbus;
+pub use avr_hal_generic::noinit;
+pub use avr_hal_generic::pin_change;
+pub use avr_hal_generic::progmem;
+pub use avr_hal_generic::rate_limit;
+
+#[doc(no_inline)]
+pub use avr_hal_generic::debug;
+#[doc(no_inline)]
+pub use avr_hal_generic::error;
+#[doc(no_inline)]
+pub use avr_hal_generic::info;
+pub use avr_hal_generic::log;
+#[doc(no_inline)]
+pub use avr_hal_generic::warn;
+
+pub use avr_hal_generic::shift_register;
+pub use avr_hal_generic::soft_pwm;
+pub use avr_hal_generic::util;
+
+#[cfg(feature = "board-selected")]
+pub use hal::mcu;
+
+#[cfg(feature = "mcu-atmega")]
+pub mod analog_comparator {
+	pub use atmega_hal::analog_comparator::*;
+}
+
+#[cfg(feature = "mcu-atmega")]
+pub mod clock_source {
+	pub use atmega_hal::clock_source::*;
+}
+
+#[cfg(feature = "mcu-atmega")]
+pub mod fuse {
+	pub use atmega_hal::fuse::*;
+}
+
+#[cfg(feature = "mcu-atmega")]
+pub mod counter {
+	pub use atmega_hal::counter::*;
+}
+
+#[cfg(feature = "mcu-atmega")]
+pub mod init {
+	pub use atmega_hal::init::*;
+}
+
+#[cfg(feature = "mcu-atmega")]
+pub mod oneshot {
+	pub use atmega_hal::oneshot::*;
+}
+
+#[cfg(feature = "mcu-atmega")]
+pub mod power {
+	pub use atmega_hal::power::*;
+}
+
+#[cfg(feature = "mcu-atmega")]
+pub mod square_wave {
+	pub use atmega_hal::square_wave::*;
+}
+
 #[cfg(feature = "mcu-atmega")]
 pub mod prelude {
 	pub use crate::hal::prelude::*;
@@ -2,6 +2,8 @@ pub use atmega_hal::port::mode;
 pub use atmega_hal::port::Pin;
 pub use atmega_hal::port::PinMode;
 pub use atmega_hal::port::PinOps;
+pub use atmega_hal::port::PortSnapshot;
+pub use atmega_hal::port::PortTransaction;
 
 avr_hal_generic::renamed_pins! {
     /// Pins of the **Arduino Mega 2560** and **Arduino Mega 1280**.
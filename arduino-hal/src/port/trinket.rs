@@ -2,6 +2,8 @@ pub use attiny_hal::port::mode;
 pub use attiny_hal::port::Pin;
 pub use attiny_hal::port::PinMode;
 pub use attiny_hal::port::PinOps;
+pub use attiny_hal::port::PortSnapshot;
+pub use attiny_hal::port::PortTransaction;
 
 avr_hal_generic::renamed_pins! {
     pub struct Pins {
@@ -1,4 +1,5 @@
 /// Analog-to-Digial converter
+use core::cell::RefCell;
 use core::marker::PhantomData;
 
 /// The division factor between the system clock frequency and the input clock to the AD converter.
@@ -63,7 +64,9 @@ pub trait AdcOps<H> {
 
 	/// Set the DIDR (Digital Input Disable) for a certain channel.
 	///
-	/// This disabled digital logic on the corresponding pin and allows measuring analog signals.
+	/// This disables digital logic on the corresponding pin and allows measuring analog signals.
+	/// It saves power and reduces noise on the analog input.  On MCUs without a DIDR this is a
+	/// no-op.
 	///
 	/// **Warning**: This is a low-level method and should not be called directly from user code.
 	fn raw_enable_channel(&mut self, channel: Self::Channel);
@@ -141,9 +144,53 @@ impl<H, ADC: AdcOps<H>> AdcChannel<H, ADC> for Channel<H, ADC> {
 /// // alternatively, a non-blocking interface exists
 /// let voltage = nb::block!(adc.read_nonblocking(&a0)).unwrap_infallible();
 /// ```
+///
+/// # Source Impedance
+/// During a conversion, the input is sampled onto an internal capacitor of about 14 pF.  The ADC
+/// is optimized for sources with an output impedance of 10 kΩ or less, which charge this
+/// capacitor quickly enough.  Sources with a higher impedance, like a voltage divider made of
+/// large resistors, need more time and otherwise read too low or are influenced by the previously
+/// selected channel.
+///
+/// To reach 10-bit accuracy, the capacitor needs roughly 8 RC time constants, so the required
+/// settling time grows linearly with the source impedance:
+///
+/// | Source impedance | Settling time |
+/// | --- | --- |
+/// | 10 kΩ | ~1 µs (covered by the sample period) |
+/// | 50 kΩ | ~6 µs |
+/// | 100 kΩ | ~11 µs |
+/// | 200 kΩ | ~23 µs |
+///
+/// An extra settling delay after each channel switch can be configured with
+/// [`set_settling_time_us()`][Adc::set_settling_time_us], or using
+/// [`set_source_impedance_high()`][Adc::set_source_impedance_high] for a default suitable for
+/// sources up to about 200 kΩ.  Alternatively, a capacitor of 10-100 nF from the input to ground
+/// makes the source appear low-impedance to the ADC, at the cost of slower response.
+///
+/// # Discarding conversions after a switch
+/// The datasheets advise to discard the first conversion after switching the reference voltage,
+/// and after selecting a differential channel with gain, because the reference and the gain
+/// stage need some time to settle.  The first conversion after switching a single-ended channel
+/// is also affected by the charge the sample-and-hold capacitor kept from the previous channel,
+/// see above.  The paths which switch channels on their own therefore drop the conversions right
+/// after each switch:
+///
+/// - [`read_sequence()`][Adc::read_sequence] throws away this many conversions on each channel
+///   before the one it returns.
+/// - Timer-triggered sampling (`SampledBuffer` in `atmega-hal`) drops them after it started and
+///   after every channel switch.
+///
+/// The number is set with [`set_discard_after_switch()`][Adc::set_discard_after_switch] and
+/// defaults to one, which is what the datasheets ask for.  Slowly settling sources (e.g. a
+/// differential channel with 200x gain) may need more; 0 trades accuracy for speed.
 pub struct Adc<H, ADC: AdcOps<H>, CLOCK> {
 	p: ADC,
 	reading_channel: Option<ADC::Channel>,
+	/// The channel the multiplexer was last switched to, if known.
+	selected_channel: Option<ADC::Channel>,
+	settling_cycles: u32,
+	discard_after_switch: u8,
 	_clock: PhantomData<CLOCK>,
 	_h: PhantomData<H>,
 }
@@ -157,6 +204,9 @@ where
 		let mut adc = Self {
 			p,
 			reading_channel: None,
+			selected_channel: None,
+			settling_cycles: 0,
+			discard_after_switch: 1,
 			_clock: PhantomData,
 			_h: PhantomData,
 		};
@@ -166,6 +216,57 @@ where
 
 	pub fn initialize(&mut self, settings: ADC::Settings) {
 		self.p.raw_init(settings);
+		self.selected_channel = None;
+	}
+
+	/// Wait for `us` microseconds after switching to a different channel, before starting a
+	/// conversion.  Further readings of the same channel start right away.
+	///
+	/// See [Source Impedance](Adc#source-impedance) for choosing a value.  The default is 0.
+	pub fn set_settling_time_us(&mut self, us: u16) {
+		self.settling_cycles = u32::from(us) * (CLOCK::FREQ / 1_000_000);
+	}
+
+	/// Configure a settling time suitable for high-impedance sources of up to about 200 kΩ.
+	///
+	/// This is a shortcut for [`set_settling_time_us(25)`][Adc::set_settling_time_us] or
+	/// `set_settling_time_us(0)` respectively.
+	pub fn set_source_impedance_high(&mut self, high: bool) {
+		self.set_settling_time_us(if high { 25 } else { 0 });
+	}
+
+	/// Set how many conversions are discarded after a channel switch, see [Discarding
+	/// conversions after a switch](Adc#discarding-conversions-after-a-switch).  The default is 1.
+	pub fn set_discard_after_switch(&mut self, conversions: u8) {
+		self.discard_after_switch = conversions;
+	}
+
+	/// How many conversions are discarded after a channel switch.
+	pub fn discard_after_switch(&self) -> u8 {
+		self.discard_after_switch
+	}
+
+	/// Forget which channel is selected, so the next reading waits for the settling time and
+	/// discards conversions as after a channel switch.
+	///
+	/// Only needed after the multiplexer was switched without going through this `Adc`, e.g. by
+	/// writing `ADMUX` directly.
+	pub fn forget_selected_channel(&mut self) {
+		self.selected_channel = None;
+	}
+
+	/// Switch the multiplexer to `channel`, waiting for the settling time if it was not selected
+	/// before.  Returns whether the channel changed.
+	fn select_channel(&mut self, channel: ADC::Channel) -> bool {
+		self.p.raw_set_channel(channel);
+		if self.selected_channel == Some(channel) {
+			return false;
+		}
+		self.selected_channel = Some(channel);
+		if self.settling_cycles != 0 {
+			avr_device::asm::delay_cycles(self.settling_cycles);
+		}
+		true
 	}
 
 	#[inline]
@@ -180,12 +281,93 @@ where
 
 	pub fn read_blocking<PIN: AdcChannel<H, ADC>>(&mut self, pin: &PIN) -> u16 {
 		// assert!(self.reading_channel.is_none());
-		self.p.raw_set_channel(pin.channel());
+		self.select_channel(pin.channel());
+		self.p.raw_start_conversion();
+		while self.p.raw_is_converting() {}
+		self.p.raw_read_adc()
+	}
+
+	/// Like [`read_blocking()`][Adc::read_blocking], but discards
+	/// [`discard_after_switch()`][Adc::discard_after_switch] conversions first if the channel
+	/// changed.
+	fn read_settled<PIN: AdcChannel<H, ADC>>(&mut self, pin: &PIN) -> u16 {
+		if self.select_channel(pin.channel()) {
+			for _ in 0..self.discard_after_switch {
+				self.p.raw_start_conversion();
+				while self.p.raw_is_converting() {}
+			}
+		}
 		self.p.raw_start_conversion();
 		while self.p.raw_is_converting() {}
 		self.p.raw_read_adc()
 	}
 
+	/// Read several channels one after another, storing the results in `results`.
+	///
+	/// After the multiplexer switched to a new channel, the sample-and-hold capacitor still
+	/// carries some charge from the previous one.  The first conversion on each channel is
+	/// therefore discarded and a second one is taken, which avoids crosstalk between channels
+	/// driven by high-impedance sources.  The number of discarded conversions can be changed with
+	/// [`set_discard_after_switch()`][Adc::set_discard_after_switch].
+	///
+	/// `results[i]` receives the reading of `channels[i]`.  If the slices differ in length, only
+	/// the length of the shorter one is processed.
+	///
+	/// ```
+	/// let channels: [atmega_hal::adc::Channel; 3] = [
+	///     a0.into_channel(),
+	///     a1.into_channel(),
+	///     a2.into_channel(),
+	/// ];
+	/// let mut results = [0u16; 3];
+	/// adc.read_sequence(&channels, &mut results);
+	/// ```
+	pub fn read_sequence<PIN: AdcChannel<H, ADC>>(
+		&mut self,
+		channels: &[PIN],
+		results: &mut [u16],
+	) {
+		// A pending non-blocking conversion is clobbered by this.
+		self.reading_channel = None;
+		for (pin, result) in channels.iter().zip(results.iter_mut()) {
+			self.select_channel(pin.channel());
+			for _ in 0..self.discard_after_switch {
+				self.p.raw_start_conversion();
+				while self.p.raw_is_converting() {}
+			}
+			self.p.raw_start_conversion();
+			while self.p.raw_is_converting() {}
+			*result = self.p.raw_read_adc();
+		}
+	}
+
+	/// Read a noisy channel robustly: take `samples.len()` readings, discard the `trim` highest
+	/// and the `trim` lowest, and return the mean of the rest.
+	///
+	/// A plain average is pulled away by single spikes, e.g. from a motor or relay switching
+	/// while converting.  Dropping the extremes first removes up to `trim` such outliers in each
+	/// direction.  `samples` is the working buffer, so no memory is allocated; see
+	/// [`trimmed_mean()`] for the details.
+	///
+	/// ```
+	/// // 16 readings, ignoring the 4 highest and the 4 lowest
+	/// let value = adc.read_trimmed_mean(&a0, &mut [0; 16], 4);
+	/// ```
+	///
+	/// # Panics
+	/// Panics if `samples` is empty.
+	pub fn read_trimmed_mean<PIN: AdcChannel<H, ADC>>(
+		&mut self,
+		pin: &PIN,
+		samples: &mut [u16],
+		trim: usize,
+	) -> u16 {
+		for sample in samples.iter_mut() {
+			*sample = self.read_blocking(pin);
+		}
+		trimmed_mean(samples, trim)
+	}
+
 	pub fn read_nonblocking<PIN: AdcChannel<H, ADC>>(
 		&mut self,
 		pin: &PIN,
@@ -208,7 +390,7 @@ where
 			// Start measurement
 			(None, _) => {
 				self.reading_channel = Some(pin.channel());
-				self.p.raw_set_channel(pin.channel());
+				self.select_channel(pin.channel());
 				self.p.raw_start_conversion();
 				Err(nb::Error::WouldBlock)
 			}
@@ -216,6 +398,548 @@ where
 	}
 }
 
+/// Mean of `samples` without the `trim` highest and the `trim` lowest values.
+///
+/// `samples` is sorted in place with an insertion sort, which is small in flash and fast for the
+/// few dozen values this is meant for.  `trim` is reduced if needed so at least one value
+/// remains.  The mean is rounded to the nearest integer.
+///
+/// # Panics
+/// Panics if `samples` is empty.
+pub fn trimmed_mean(samples: &mut [u16], trim: usize) -> u16 {
+	assert!(!samples.is_empty());
+	for i in 1..samples.len() {
+		let value = samples[i];
+		let mut j = i;
+		while j > 0 && samples[j - 1] > value {
+			samples[j] = samples[j - 1];
+			j -= 1;
+		}
+		samples[j] = value;
+	}
+
+	let trim = trim.min((samples.len() - 1) / 2);
+	let kept = &samples[trim..samples.len() - trim];
+	let sum: u32 = kept.iter().map(|&v| u32::from(v)).sum();
+	let count = kept.len() as u32;
+	((sum + count / 2) / count) as u16
+}
+
+/// Number representation of differential conversion results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffCoding {
+	/// Results range from 0 to 1023 (ATtiny with `BIN` cleared).
+	Unipolar,
+	/// Results are 10-bit two's complement, from -512 to 511 (ATmega, ATtiny with `BIN` set).
+	Bipolar,
+}
+
+/// A differential ADC channel with zero-offset calibration.
+///
+/// The differential amplifier has an offset error of a few LSB, which gets multiplied by the
+/// gain.  It is measured by converting a channel where both inputs are connected to the same pin
+/// (like `ADC0-ADC0 10x` on the ATmega2560).  This reading should be zero, so whatever comes out
+/// is the offset and gets subtracted from all later readings of the actual channel.
+///
+/// Both channels need to use the same gain, as the offset depends on it.
+///
+/// # When to recalibrate
+/// The offset drifts with temperature and supply voltage.  Calibrate once after startup and
+/// repeat whenever the die temperature changed by more than about 10 °C, the supply voltage
+/// changed noticeably, or the reference voltage was switched.  Calibration is cheap, so for
+/// the best accuracy it can also simply be done before every burst of measurements.
+///
+/// # Example
+/// ```ignore
+/// let mut diff = CalibratedDiffAdc::new(adc1_adc0_10x, adc0_adc0_10x, DiffCoding::Bipolar);
+/// diff.calibrate(&mut adc);
+/// let value: i16 = diff.read(&mut adc);
+/// ```
+pub struct CalibratedDiffAdc<H, ADC: AdcOps<H>> {
+	channel: Channel<H, ADC>,
+	zero: Channel<H, ADC>,
+	coding: DiffCoding,
+	offset: i16,
+}
+
+impl<H, ADC: AdcOps<H>> CalibratedDiffAdc<H, ADC> {
+	/// Number of readings averaged by [`calibrate()`][CalibratedDiffAdc::calibrate].
+	pub const CALIBRATION_SAMPLES: i16 = 16;
+
+	/// Measure `channel`, using `zero` (both inputs on the same pin, same gain) for calibration.
+	///
+	/// The offset starts out as 0 until [`calibrate()`][CalibratedDiffAdc::calibrate] is called.
+	pub fn new<CH, Z>(channel: CH, zero: Z, coding: DiffCoding) -> Self
+	where
+		CH: AdcChannel<H, ADC>,
+		Z: AdcChannel<H, ADC>,
+	{
+		Self {
+			channel: Channel::new(channel),
+			zero: Channel::new(zero),
+			coding,
+			offset: 0,
+		}
+	}
+
+	fn decode(&self, raw: u16) -> i16 {
+		match self.coding {
+			DiffCoding::Unipolar => raw as i16,
+			// Sign-extend the 10-bit result.
+			DiffCoding::Bipolar => ((raw << 6) as i16) >> 6,
+		}
+	}
+
+	/// Measure the zero offset by averaging readings of the shorted channel.
+	///
+	/// Like [`read()`][CalibratedDiffAdc::read], the conversions right after switching channels
+	/// are discarded, see [`Adc::set_discard_after_switch()`].
+	pub fn calibrate<CLOCK: crate::clock::Clock>(&mut self, adc: &mut Adc<H, ADC, CLOCK>) {
+		let mut sum: i16 = 0;
+		for _ in 0..Self::CALIBRATION_SAMPLES {
+			sum += self.decode(adc.read_settled(&self.zero));
+		}
+		self.offset = sum / Self::CALIBRATION_SAMPLES;
+	}
+
+	/// Read the channel and subtract the calibrated offset.
+	///
+	/// After switching back from the zero channel (or any other), the first
+	/// [`discard_after_switch()`][Adc::discard_after_switch] conversions are discarded while
+	/// the gain stage settles.
+	pub fn read<CLOCK: crate::clock::Clock>(&self, adc: &mut Adc<H, ADC, CLOCK>) -> i16 {
+		self.decode(adc.read_settled(&self.channel)) - self.offset
+	}
+
+	/// Current offset, in LSB.
+	pub fn offset(&self) -> i16 {
+		self.offset
+	}
+
+	/// Set the offset directly, e.g. to a value stored in EEPROM.
+	pub fn set_offset(&mut self, offset: i16) {
+		self.offset = offset;
+	}
+
+	/// Give back the channels.
+	pub fn release(self) -> (Channel<H, ADC>, Channel<H, ADC>) {
+		(self.channel, self.zero)
+	}
+}
+
+/// Gain and offset correction of ADC readings, determined from two reference measurements.
+///
+/// Besides the offset error, the ADC has a gain error: the slope of its transfer function
+/// deviates slightly from the ideal one, e.g. due to the tolerance of the reference voltage.
+/// Both are corrected with
+///
+/// ```text
+/// corrected = (raw - offset) * gain
+/// ```
+///
+/// where `gain` is a fixed-point number with 14 fractional bits, so no floating point code is
+/// needed.
+///
+/// # Calibration procedure
+/// 1. Apply a precisely known voltage near the bottom of the range (about 10 % of the reference)
+///    to the input and average a number of raw readings.
+/// 2. Apply a second known voltage near the top of the range (about 90 %) and average again.
+/// 3. Compute the ideal reading for both voltages, `V * 1024 / V_ref`, and pass all four values
+///    to [`TwoPointCalibration::from_points()`].
+/// 4. Store the result in EEPROM with [`to_bytes()`][TwoPointCalibration::to_bytes] and load it
+///    at startup with [`from_bytes()`][TwoPointCalibration::from_bytes].
+///
+/// Avoid points right at the ends of the range, where the ADC saturates.  The calibration is
+/// only valid for the reference voltage and ADC clock it was measured with.
+///
+/// # Example
+/// ```ignore
+/// // 0.5 V and 4.5 V applied, with a 5 V reference: ideal readings are 102 and 922
+/// let calibration = TwoPointCalibration::from_points(98, 102, 911, 922).unwrap();
+/// eeprom.write(CALIBRATION_OFFSET, &calibration.to_bytes()).unwrap();
+///
+/// // At startup:
+/// let mut bytes = [0; 4];
+/// eeprom.read(CALIBRATION_OFFSET, &mut bytes).unwrap();
+/// let calibration = TwoPointCalibration::from_bytes(bytes).unwrap_or_default();
+/// let value = calibration.apply(adc.read_blocking(&a0));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TwoPointCalibration {
+	offset: i16,
+	gain: u16,
+}
+
+impl Default for TwoPointCalibration {
+	fn default() -> Self {
+		Self::IDENTITY
+	}
+}
+
+impl TwoPointCalibration {
+	/// Number of fractional bits of the gain.
+	pub const GAIN_SHIFT: u32 = 14;
+
+	/// No correction: zero offset and a gain of 1.
+	pub const IDENTITY: Self = Self {
+		offset: 0,
+		gain: 1 << Self::GAIN_SHIFT,
+	};
+
+	/// Create a calibration from an offset in LSB and a gain with [`GAIN_SHIFT`] fractional
+	/// bits.
+	///
+	/// [`GAIN_SHIFT`]: TwoPointCalibration::GAIN_SHIFT
+	pub const fn new(offset: i16, gain: u16) -> Self {
+		Self { offset, gain }
+	}
+
+	/// Compute the calibration from two raw readings and the ideal readings for the same inputs.
+	///
+	/// Returns `None` if `raw_high` is not above `raw_low`, the gain is out of range (below 0.25
+	/// or 4 and above), or the offset does not fit into an `i16`, which indicates a measurement
+	/// error.
+	pub fn from_points(
+		raw_low: u16,
+		ideal_low: u16,
+		raw_high: u16,
+		ideal_high: u16,
+	) -> Option<Self> {
+		let raw_span = i32::from(raw_high) - i32::from(raw_low);
+		let ideal_span = i32::from(ideal_high) - i32::from(ideal_low);
+		if raw_span <= 0 || ideal_span <= 0 {
+			return None;
+		}
+
+		let gain = ((ideal_span << Self::GAIN_SHIFT) + raw_span / 2) / raw_span;
+		if !(1 << (Self::GAIN_SHIFT - 2)..1 << (Self::GAIN_SHIFT + 2)).contains(&gain) {
+			return None;
+		}
+		// raw_low - offset = ideal_low / gain
+		let offset = i32::from(raw_low)
+			- ((i32::from(ideal_low) << Self::GAIN_SHIFT) + gain / 2) / gain;
+
+		Some(Self {
+			offset: i16::try_from(offset).ok()?,
+			gain: gain as u16,
+		})
+	}
+
+	/// Offset in LSB, subtracted from the raw reading.
+	pub fn offset(&self) -> i16 {
+		self.offset
+	}
+
+	/// Gain with [`GAIN_SHIFT`][TwoPointCalibration::GAIN_SHIFT] fractional bits.
+	pub fn gain(&self) -> u16 {
+		self.gain
+	}
+
+	/// Correct a raw reading.  Results are clamped to the range of a `u16`.
+	pub fn apply(&self, raw: u16) -> u16 {
+		// Up to 17 bits times 16 bits of gain, which doesn't fit into an `i32`.
+		let shifted = i64::from(raw) - i64::from(self.offset);
+		let corrected = (shifted * i64::from(self.gain) + (1 << (Self::GAIN_SHIFT - 1)))
+			>> Self::GAIN_SHIFT;
+		corrected.clamp(0, i64::from(u16::MAX)) as u16
+	}
+
+	/// Serialize for storing in EEPROM.
+	pub fn to_bytes(&self) -> [u8; 4] {
+		let [o0, o1] = self.offset.to_le_bytes();
+		let [g0, g1] = self.gain.to_le_bytes();
+		[o0, o1, g0, g1]
+	}
+
+	/// Deserialize from bytes written by [`to_bytes()`][TwoPointCalibration::to_bytes].
+	///
+	/// Returns `None` for erased EEPROM (all bytes `0xFF`) or a zero gain.
+	pub fn from_bytes(bytes: [u8; 4]) -> Option<Self> {
+		let gain = u16::from_le_bytes([bytes[2], bytes[3]]);
+		if bytes == [0xff; 4] || gain == 0 {
+			return None;
+		}
+		Some(Self {
+			offset: i16::from_le_bytes([bytes[0], bytes[1]]),
+			gain,
+		})
+	}
+}
+
+/// A source of analog readings, e.g. an ADC channel.
+///
+/// Sensor helpers (thermistors, voltage dividers, ...) can be written against this trait instead
+/// of the internal ADC, so they work with any analog source.  For the internal ADC, it is
+/// implemented by [`AdcInput`].
+///
+/// # Implementing for an external ADC
+/// A driver for an external ADC implements it on a type representing one of its channels,
+/// reporting its own resolution through [`max_value()`][AnalogInput::max_value].  As `read()`
+/// cannot fail, a driver for a bus-attached ADC has to decide what a failed transfer returns,
+/// e.g. the last good reading:
+///
+/// ```ignore
+/// impl<I2C: embedded_hal::i2c::I2c> AnalogInput for Ads1115Channel<I2C> {
+///     fn max_value(&self) -> u16 {
+///         0x7fff
+///     }
+///
+///     fn read(&mut self) -> u16 {
+///         if let Ok(value) = self.convert() {
+///             self.last = value;
+///         }
+///         self.last
+///     }
+/// }
+/// ```
+pub trait AnalogInput {
+	/// The reading at full scale, e.g. `1023` for a 10-bit ADC.
+	fn max_value(&self) -> u16;
+
+	/// Take a reading, blocking until the conversion is done.
+	fn read(&mut self) -> u16;
+}
+
+impl<T: AnalogInput + ?Sized> AnalogInput for &mut T {
+	fn max_value(&self) -> u16 {
+		(**self).max_value()
+	}
+
+	fn read(&mut self) -> u16 {
+		(**self).read()
+	}
+}
+
+/// An internal ADC channel implementing [`AnalogInput`].
+///
+/// The ADC is shared between all inputs through a [`RefCell`], so several of them can exist at
+/// the same time.  Using the ADC from an interrupt handler while a reading is in progress in the
+/// main program panics, like any other nested `RefCell` borrow.
+///
+/// ```ignore
+/// use core::cell::RefCell;
+///
+/// let adc = RefCell::new(arduino_hal::Adc::new(dp.ADC, Default::default()));
+/// let a0 = pins.a0.into_analog_input(&mut adc.borrow_mut());
+///
+/// let mut thermistor = Thermistor::new(AdcInput::new(&adc, a0));
+/// ```
+pub struct AdcInput<'a, H, ADC: AdcOps<H>, CLOCK> {
+	adc: &'a RefCell<Adc<H, ADC, CLOCK>>,
+	channel: Channel<H, ADC>,
+}
+
+impl<'a, H, ADC: AdcOps<H>, CLOCK> AdcInput<'a, H, ADC, CLOCK> {
+	/// Read `channel` with the shared `adc`.
+	pub fn new<CH: AdcChannel<H, ADC>>(
+		adc: &'a RefCell<Adc<H, ADC, CLOCK>>,
+		channel: CH,
+	) -> Self {
+		Self {
+			adc,
+			channel: Channel::new(channel),
+		}
+	}
+
+	/// Give back the channel.
+	pub fn release(self) -> Channel<H, ADC> {
+		self.channel
+	}
+}
+
+impl<H, ADC, CLOCK> AnalogInput for AdcInput<'_, H, ADC, CLOCK>
+where
+	ADC: AdcOps<H>,
+	CLOCK: crate::clock::Clock,
+{
+	fn max_value(&self) -> u16 {
+		1023
+	}
+
+	fn read(&mut self) -> u16 {
+		self.adc.borrow_mut().read_blocking(&self.channel)
+	}
+}
+
+/// Transition reported by [`Window::check()`].
+#[derive(ufmt::derive::uDebug, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowEvent {
+	/// The reading entered the window.
+	Entered,
+	/// The reading dropped below the lower bound.
+	LeftBelow,
+	/// The reading rose above the upper bound.
+	LeftAbove,
+}
+
+/// Software window comparator for analog readings, e.g. for over- and under-voltage monitoring.
+///
+/// Feed every reading to [`check()`][Window::check], which reports an event only when the reading
+/// leaves or re-enters the `[low, high]` window.  A reading leaves the window as soon as it is
+/// outside the bounds, but only counts as back inside once it is at least `hysteresis` away from
+/// them.  Without hysteresis, a noisy signal sitting right at a bound would report a stream of
+/// alternating events:
+///
+/// ```text
+///                 ┌ high
+///  LeftAbove ─────┤
+///                 │ high - hysteresis ┐
+///                 │                   ├── Entered
+///                 │ low + hysteresis  ┘
+///  LeftBelow ─────┤
+///                 └ low
+/// ```
+///
+/// The first reading always reports where it is, so an out-of-range supply is noticed right
+/// after startup.  The window has no way of its own to read the ADC; readings come from a loop,
+/// a timer-triggered conversion, or [`poll()`][Window::poll] with an [`AnalogInput`]:
+///
+/// ```ignore
+/// // 4.5 V to 5.5 V through a 1:2 divider, with 5 V reference
+/// let mut supply = Window::new(460, 563, 5);
+///
+/// loop {
+///     match supply.poll(&mut supply_input) {
+///         Some(WindowEvent::LeftBelow) => alarm.undervoltage(),
+///         Some(WindowEvent::LeftAbove) => alarm.overvoltage(),
+///         Some(WindowEvent::Entered) => alarm.clear(),
+///         None => (),
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+	low: u16,
+	high: u16,
+	hysteresis: u16,
+	last: Option<WindowEvent>,
+}
+
+impl Window {
+	/// Create a window from `low` to `high` (inclusive) with the given hysteresis.
+	///
+	/// The hysteresis has to be smaller than half the window, otherwise a reading can never
+	/// re-enter it.
+	pub const fn new(low: u16, high: u16, hysteresis: u16) -> Self {
+		Self {
+			low,
+			high,
+			hysteresis,
+			last: None,
+		}
+	}
+
+	/// Check a new reading and return an event if it left or re-entered the window.
+	pub fn check(&mut self, reading: u16) -> Option<WindowEvent> {
+		let outside = matches!(
+			self.last,
+			Some(WindowEvent::LeftBelow | WindowEvent::LeftAbove)
+		);
+		let event = if reading < self.low {
+			WindowEvent::LeftBelow
+		} else if reading > self.high {
+			WindowEvent::LeftAbove
+		} else if !outside
+			|| (reading >= self.low.saturating_add(self.hysteresis)
+				&& reading <= self.high.saturating_sub(self.hysteresis))
+		{
+			WindowEvent::Entered
+		} else {
+			// Still in the hysteresis band after leaving the window.
+			return None;
+		};
+
+		if self.last == Some(event) {
+			None
+		} else {
+			self.last = Some(event);
+			Some(event)
+		}
+	}
+
+	/// Take a reading from `input` and [`check()`][Window::check] it.
+	pub fn poll<A: AnalogInput>(&mut self, input: &mut A) -> Option<WindowEvent> {
+		self.check(input.read())
+	}
+
+	/// Whether the last reading was inside the window.  `false` before the first reading.
+	pub fn is_inside(&self) -> bool {
+		self.last == Some(WindowEvent::Entered)
+	}
+
+	/// Change the bounds.  The current state is kept, so the next reading reports an event only
+	/// if it is on a different side of the new bounds.
+	pub fn set_bounds(&mut self, low: u16, high: u16) {
+		self.low = low;
+		self.high = high;
+	}
+}
+
+/// Digital level from an analog reading with hysteresis, like a Schmitt trigger input.
+///
+/// Comparing a noisy signal against a single threshold makes the result chatter while the signal
+/// is close to it, e.g. a light sensor at dusk.  [`update()`][SchmittInput::update] switches the
+/// output high only once a reading is above `high_thresh` and back low only once it is below
+/// `low_thresh`; readings in between keep the previous level.  Noise smaller than the distance
+/// between the thresholds thus can't toggle the output.
+///
+/// ```ignore
+/// // Dark below ~30%, light again above ~40%
+/// let mut daylight = SchmittInput::new(300, 400);
+///
+/// loop {
+///     let is_light = daylight.update(adc.read_blocking(&a0));
+///     lamp.set_state((!is_light).into()).unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchmittInput {
+	low_thresh: u16,
+	high_thresh: u16,
+	state: bool,
+}
+
+impl SchmittInput {
+	/// Create an input switching high above `high_thresh` and low below `low_thresh`.  The
+	/// output starts out low.
+	///
+	/// # Panics
+	/// Panics if `low_thresh` is greater than `high_thresh`.
+	pub const fn new(low_thresh: u16, high_thresh: u16) -> Self {
+		assert!(low_thresh <= high_thresh);
+		Self {
+			low_thresh,
+			high_thresh,
+			state: false,
+		}
+	}
+
+	/// Feed a new reading and return the resulting level.
+	pub fn update(&mut self, reading: u16) -> bool {
+		if reading > self.high_thresh {
+			self.state = true;
+		} else if reading < self.low_thresh {
+			self.state = false;
+		}
+		self.state
+	}
+
+	/// Take a reading from `input` and [`update()`][SchmittInput::update] with it.
+	pub fn poll<A: AnalogInput>(&mut self, input: &mut A) -> bool {
+		self.update(input.read())
+	}
+
+	/// The current level, i.e. the result of the last update.
+	pub fn is_high(&self) -> bool {
+		self.state
+	}
+
+	/// Force the level, e.g. to the value expected at startup before the first reading.
+	pub fn set_state(&mut self, state: bool) {
+		self.state = state;
+	}
+}
+
 #[macro_export]
 macro_rules! impl_adc {
     (
@@ -331,3 +1055,285 @@ macro_rules! impl_adc {
         )*)?
     };
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// An ADC which records what the driver does with it.
+	#[derive(Default)]
+	struct MockAdc {
+		channel: u8,
+		conversions: usize,
+		/// The reading of each channel.
+		readings: [u16; 4],
+	}
+
+	impl AdcOps<()> for MockAdc {
+		type Channel = u8;
+		type Settings = ();
+
+		fn raw_init(&mut self, _settings: ()) {}
+
+		fn raw_read_adc(&self) -> u16 {
+			self.readings[usize::from(self.channel)]
+		}
+
+		fn raw_is_converting(&self) -> bool {
+			false
+		}
+
+		fn raw_start_conversion(&mut self) {
+			self.conversions += 1;
+		}
+
+		fn raw_set_channel(&mut self, channel: u8) {
+			self.channel = channel;
+		}
+
+		fn raw_enable_channel(&mut self, _channel: u8) {}
+
+		fn raw_disable_channel(&mut self, _channel: u8) {}
+	}
+
+	struct Ch(u8);
+
+	impl AdcChannel<(), MockAdc> for Ch {
+		fn channel(&self) -> u8 {
+			self.0
+		}
+	}
+
+	type TestAdc = Adc<(), MockAdc, crate::clock::MHz16>;
+
+	#[test]
+	fn select_channel_reports_switches() {
+		let mut adc = TestAdc::new(MockAdc::default(), ());
+		assert!(adc.select_channel(1));
+		assert!(!adc.select_channel(1));
+		assert!(adc.select_channel(2));
+		adc.forget_selected_channel();
+		assert!(adc.select_channel(2));
+		adc.initialize(());
+		assert!(adc.select_channel(2));
+	}
+
+	#[test]
+	fn settling_delay_only_after_switch() {
+		let mut adc = TestAdc::new(
+			MockAdc {
+				readings: [0, 512, 0, 0],
+				..Default::default()
+			},
+			(),
+		);
+		assert_eq!(adc.read_blocking(&Ch(1)), 512);
+		// The busy-wait is not available on the host, so these reads would panic if they
+		// waited for the settling time again.
+		adc.set_settling_time_us(25);
+		for _ in 0..3 {
+			assert_eq!(adc.read_blocking(&Ch(1)), 512);
+		}
+		assert_eq!(adc.p.conversions, 4);
+	}
+
+	#[test]
+	fn calibrated_diff_discards_after_switch() {
+		let mut adc = TestAdc::new(
+			MockAdc {
+				// Bipolar: 0x3fe is -2
+				readings: [0, 100, 0x3fe, 0],
+				..Default::default()
+			},
+			(),
+		);
+		adc.set_discard_after_switch(2);
+		let mut diff = CalibratedDiffAdc::new(Ch(1), Ch(2), DiffCoding::Bipolar);
+
+		diff.calibrate(&mut adc);
+		assert_eq!(diff.offset(), -2);
+		let samples = CalibratedDiffAdc::<(), MockAdc>::CALIBRATION_SAMPLES as usize;
+		assert_eq!(adc.p.conversions, 2 + samples);
+
+		assert_eq!(diff.read(&mut adc), 102);
+		assert_eq!(adc.p.conversions, 2 + samples + 3);
+		assert_eq!(diff.read(&mut adc), 102);
+		assert_eq!(adc.p.conversions, 2 + samples + 4);
+	}
+
+	#[test]
+	fn read_sequence_discards_first_conversion() {
+		let mut adc = TestAdc::new(
+			MockAdc {
+				readings: [10, 20, 30, 40],
+				..Default::default()
+			},
+			(),
+		);
+		let mut results = [0; 3];
+		adc.read_sequence(&[Ch(2), Ch(0), Ch(3)], &mut results);
+		assert_eq!(results, [30, 10, 40]);
+		assert_eq!(adc.p.conversions, 6);
+
+		// Only as many channels as there are results
+		let mut results = [0; 1];
+		adc.read_sequence(&[Ch(1), Ch(2)], &mut results);
+		assert_eq!(results, [20]);
+		assert_eq!(adc.p.conversions, 8);
+	}
+
+	#[test]
+	fn two_point_calibration() {
+		// 0.5 V and 4.5 V with a 5 V reference, see the example in the docs
+		let calibration = TwoPointCalibration::from_points(98, 102, 911, 922).unwrap();
+		assert_eq!(calibration.offset(), -3);
+		assert_eq!(calibration.gain(), 16525);
+		assert_eq!(calibration.apply(98), 102);
+		assert_eq!(calibration.apply(911), 922);
+		assert_eq!(calibration.apply(0), 3);
+
+		assert_eq!(TwoPointCalibration::IDENTITY.apply(512), 512);
+		assert_eq!(TwoPointCalibration::new(10, 1 << 14).apply(5), 0);
+	}
+
+	#[test]
+	fn two_point_calibration_rejects_bad_points() {
+		assert_eq!(TwoPointCalibration::from_points(500, 100, 500, 900), None);
+		assert_eq!(TwoPointCalibration::from_points(600, 100, 500, 900), None);
+		// Gain of 8
+		assert_eq!(TwoPointCalibration::from_points(100, 100, 200, 900), None);
+		// Gain of 1/8
+		assert_eq!(TwoPointCalibration::from_points(100, 100, 900, 200), None);
+		// Gain of 3, but an offset of 40000
+		assert_eq!(
+			TwoPointCalibration::from_points(40000, 0, 50000, 30000),
+			None
+		);
+		// Offset of -40000
+		assert_eq!(
+			TwoPointCalibration::from_points(0, 40000, 10000, 50000),
+			None
+		);
+	}
+
+	#[test]
+	fn two_point_calibration_large_gain() {
+		let calibration = TwoPointCalibration::from_points(100, 0, 20000, 59700).unwrap();
+		assert_eq!(calibration.gain(), 3 << 14);
+		assert_eq!(calibration.apply(20000), 59700);
+		assert_eq!(calibration.apply(65535), u16::MAX);
+
+		let calibration = TwoPointCalibration::new(i16::MIN, u16::MAX);
+		assert_eq!(calibration.apply(u16::MAX), u16::MAX);
+		assert_eq!(TwoPointCalibration::new(i16::MAX, u16::MAX).apply(0), 0);
+	}
+
+	#[test]
+	fn two_point_calibration_bytes() {
+		let calibration = TwoPointCalibration::new(-3, 16525);
+		assert_eq!(
+			TwoPointCalibration::from_bytes(calibration.to_bytes()),
+			Some(calibration)
+		);
+		assert_eq!(TwoPointCalibration::from_bytes([0xff; 4]), None);
+		assert_eq!(TwoPointCalibration::from_bytes([1, 0, 0, 0]), None);
+	}
+
+	#[test]
+	fn window_with_hysteresis() {
+		let mut window = Window::new(460, 563, 5);
+		assert!(!window.is_inside());
+		assert_eq!(window.check(500), Some(WindowEvent::Entered));
+		assert_eq!(window.check(501), None);
+		assert!(window.is_inside());
+
+		assert_eq!(window.check(459), Some(WindowEvent::LeftBelow));
+		assert_eq!(window.check(458), None);
+		// Within the hysteresis band, still outside
+		assert_eq!(window.check(462), None);
+		assert!(!window.is_inside());
+		assert_eq!(window.check(465), Some(WindowEvent::Entered));
+
+		assert_eq!(window.check(564), Some(WindowEvent::LeftAbove));
+		assert_eq!(window.check(560), None);
+		assert_eq!(window.check(558), Some(WindowEvent::Entered));
+	}
+
+	#[test]
+	fn window_reports_first_reading() {
+		assert_eq!(
+			Window::new(460, 563, 5).check(100),
+			Some(WindowEvent::LeftBelow)
+		);
+		assert_eq!(
+			Window::new(460, 563, 5).check(1000),
+			Some(WindowEvent::LeftAbove)
+		);
+		// Inside, but in the band: nothing was outside before
+		assert_eq!(
+			Window::new(460, 563, 5).check(461),
+			Some(WindowEvent::Entered)
+		);
+	}
+
+	#[test]
+	fn trimmed_mean_drops_outliers() {
+		assert_eq!(trimmed_mean(&mut [500, 502, 1023, 501, 0, 499], 1), 501);
+		assert_eq!(trimmed_mean(&mut [7], 3), 7);
+		// The trim is reduced so one value remains; rounded to nearest
+		assert_eq!(trimmed_mean(&mut [1, 2], 5), 2);
+		assert_eq!(trimmed_mean(&mut [1, 2, 9], 5), 2);
+		assert_eq!(trimmed_mean(&mut [4, 1, 3, 2], 0), 3);
+	}
+
+	#[test]
+	fn read_trimmed_mean_takes_all_samples() {
+		let mut adc = TestAdc::new(
+			MockAdc {
+				readings: [0, 300, 0, 0],
+				..Default::default()
+			},
+			(),
+		);
+		assert_eq!(adc.read_trimmed_mean(&Ch(1), &mut [0; 8], 2), 300);
+		assert_eq!(adc.p.conversions, 8);
+	}
+
+	#[test]
+	fn schmitt_input_hysteresis() {
+		let mut daylight = SchmittInput::new(300, 400);
+		assert!(!daylight.update(350));
+		assert!(!daylight.update(400));
+		assert!(daylight.update(401));
+		assert!(daylight.update(350));
+		assert!(daylight.update(300));
+		assert!(!daylight.update(299));
+		assert!(!daylight.is_high());
+
+		daylight.set_state(true);
+		assert!(daylight.update(350));
+	}
+
+	#[test]
+	fn discard_after_switch_setting() {
+		let mut adc = TestAdc::new(
+			MockAdc {
+				readings: [10, 20, 30, 40],
+				..Default::default()
+			},
+			(),
+		);
+		assert_eq!(adc.discard_after_switch(), 1);
+
+		adc.set_discard_after_switch(0);
+		let mut results = [0; 2];
+		adc.read_sequence(&[Ch(0), Ch(3)], &mut results);
+		assert_eq!(results, [10, 40]);
+		assert_eq!(adc.p.conversions, 2);
+
+		adc.set_discard_after_switch(3);
+		adc.read_sequence(&[Ch(1), Ch(2)], &mut results);
+		assert_eq!(results, [20, 30]);
+		assert_eq!(adc.p.conversions, 2 + 8);
+	}
+}
@@ -0,0 +1,363 @@
+//! COBS framing for binary protocols over serial links
+//!
+//! A serial link (USART, or a USB CDC-ACM port) only carries a stream of bytes.  To send
+//! binary packets over it, the receiver needs to find out where a packet starts and ends, and
+//! after a disconnect, a reset, or lost bytes, it has to get back in sync with the sender.
+//! Consistent overhead byte stuffing (COBS) does this with a simple rule: the encoded packet
+//! contains no zero bytes, so a single `0x00` can delimit the frames.  A receiver which joins
+//! in the middle of a frame drops everything up to the next zero and receives the following
+//! frames intact.
+//!
+//! ```ignore
+//! let mut out = [0; cobs::max_encoded_len(4)];
+//! let len = cobs::encode(&[0x11, 0x00, 0x22, 0x33], &mut out);
+//! assert_eq!(&out[..len], &[0x02, 0x11, 0x03, 0x22, 0x33, 0x00]);
+//!
+//! // Or without a buffer, straight to the USART:
+//! cobs::send_frame(&mut serial, &[0x11, 0x00, 0x22, 0x33])?;
+//! ```
+//!
+//! On the receiving side, [`decode()`] unpacks a complete frame, while [`CobsReceiver`] takes
+//! the bytes one at a time as they arrive, e.g. from the `USART_RX` interrupt:
+//!
+//! ```ignore
+//! let mut receiver = CobsReceiver::<64>::new();
+//!
+//! loop {
+//!     if let Ok(byte) = serial.read() {
+//!         match receiver.receive(byte) {
+//!             Some(Ok(packet)) => handle_packet(packet),
+//!             // Corrupted, or the first frame after connecting
+//!             Some(Err(_)) => (),
+//!             None => (),
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! # Encoding and overhead
+//! The data is split at its zero bytes into blocks.  Each block is sent as a code byte, which is
+//! its length plus one, followed by its non-zero bytes; the zero at the end of a block is
+//! implied by the next code byte.  A block without a zero at its end may be up to 254 bytes
+//! long, with the code `0xff`.  The frame ends with the `0x00` delimiter.
+//!
+//! This costs one code byte per frame, one more for every 254 bytes without a zero, and the
+//! delimiter: at most `len + len / 254 + 2` bytes, see [`max_encoded_len()`].  For short packets,
+//! that's 2 bytes no matter what the data contains, unlike escaping schemes (SLIP, HDLC), which
+//! can double the size of a packet full of special bytes.
+//!
+//! COBS only frames the data, it does not detect corrupted bytes.  Append a checksum, e.g.
+//! [`crc16_modbus()`][crate::util::crc16_modbus], to the packet before encoding it if the link
+//! is unreliable.  Sending an extra `0x00` before the first frame after connecting ends any
+//! garbage the host still has in its buffer, so that frame isn't lost.
+use unwrap_infallible::UnwrapInfallible;
+
+/// Longest possible block, a code byte of `0xff` followed by this many non-zero bytes.
+const MAX_BLOCK: usize = 254;
+
+/// Maximum size of `len` bytes of data after encoding, including the delimiter.
+pub const fn max_encoded_len(len: usize) -> usize {
+	len + len / MAX_BLOCK + 2
+}
+
+/// Reasons for dropping a received frame.
+#[derive(ufmt::derive::uDebug, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+	/// The frame ended in the middle of a block, or it is empty.
+	Truncated,
+	/// There is a zero byte before the end of the frame.
+	UnexpectedZero,
+	/// The decoded data doesn't fit into the buffer.
+	Overflow,
+}
+
+/// Encode `data` into `out`, followed by the delimiter, and return the number of bytes used.
+///
+/// # Panics
+/// Panics if `out` is too short.  [`max_encoded_len()`] bytes are always enough.
+pub fn encode(data: &[u8], out: &mut [u8]) -> usize {
+	let mut len = 0;
+	encode_with(data, |byte| {
+		out[len] = byte;
+		len += 1;
+		Ok::<_, core::convert::Infallible>(())
+	})
+	.unwrap_infallible();
+	len
+}
+
+/// Encode `data` and send it as one frame, including the delimiter.
+///
+/// The encoding happens while sending, so no additional buffer is needed.  This works with the
+/// USARTs as well as any other [`Write`][embedded_hal_v0::serial::Write] implementation, like
+/// the serial port of a USB stack.
+pub fn send_frame<W>(serial: &mut W, data: &[u8]) -> Result<(), W::Error>
+where
+	W: embedded_hal_v0::serial::Write<u8>,
+{
+	encode_with(data, |byte| nb::block!(serial.write(byte)))
+}
+
+fn encode_with<E>(data: &[u8], mut emit: impl FnMut(u8) -> Result<(), E>) -> Result<(), E> {
+	let mut rest = data;
+	loop {
+		let run = rest
+			.iter()
+			.take(MAX_BLOCK)
+			.position(|&byte| byte == 0)
+			.unwrap_or(rest.len().min(MAX_BLOCK));
+		emit(run as u8 + 1)?;
+		for &byte in &rest[..run] {
+			emit(byte)?;
+		}
+		if run == MAX_BLOCK {
+			// No zero to skip, the next block continues the data.
+			rest = &rest[run..];
+			if rest.is_empty() {
+				break;
+			}
+		} else if run < rest.len() {
+			// Skip the zero implied by the next code byte.
+			rest = &rest[run + 1..];
+		} else {
+			break;
+		}
+	}
+	emit(0)
+}
+
+/// Decode one frame into `out` and return the length of the data.  The delimiter at the end of
+/// `frame` is optional.
+pub fn decode(frame: &[u8], out: &mut [u8]) -> Result<usize, DecodeError> {
+	let frame = frame.strip_suffix(&[0]).unwrap_or(frame);
+	let mut state = DecodeState::new();
+	let mut len = 0;
+	for &byte in frame {
+		if byte == 0 {
+			return Err(DecodeError::UnexpectedZero);
+		}
+		state.decode(byte, out, &mut len)?;
+	}
+	state.finish()?;
+	Ok(len)
+}
+
+/// Where the decoder is within a frame.
+#[derive(Clone, Copy)]
+struct DecodeState {
+	/// Data bytes left in the current block, 0 if the next byte is a code byte.
+	remaining: u8,
+	/// Whether the current block ends with an implied zero, which is added once another block
+	/// follows.
+	zero_next: bool,
+	/// Whether any byte of the frame was seen.
+	started: bool,
+}
+
+impl DecodeState {
+	const fn new() -> Self {
+		Self {
+			remaining: 0,
+			zero_next: false,
+			started: false,
+		}
+	}
+
+	/// Decode a non-zero byte, appending data to `out[..len]`.
+	fn decode(&mut self, byte: u8, out: &mut [u8], len: &mut usize) -> Result<(), DecodeError> {
+		self.started = true;
+		let data = if self.remaining == 0 {
+			let zero_next = core::mem::replace(&mut self.zero_next, byte != 0xff);
+			self.remaining = byte - 1;
+			if !zero_next {
+				return Ok(());
+			}
+			0
+		} else {
+			self.remaining -= 1;
+			byte
+		};
+		*out.get_mut(*len).ok_or(DecodeError::Overflow)? = data;
+		*len += 1;
+		Ok(())
+	}
+
+	/// Check that the frame ended after a complete block.
+	fn finish(&self) -> Result<(), DecodeError> {
+		if !self.started || self.remaining != 0 {
+			return Err(DecodeError::Truncated);
+		}
+		Ok(())
+	}
+}
+
+/// Receives COBS frames byte by byte into a buffer for `N` bytes of decoded data.  See the
+/// [module documentation](self).
+pub struct CobsReceiver<const N: usize> {
+	buffer: [u8; N],
+	len: usize,
+	state: DecodeState,
+	error: Option<DecodeError>,
+}
+
+impl<const N: usize> CobsReceiver<N> {
+	/// Create a receiver waiting for the start of a frame.
+	pub const fn new() -> Self {
+		Self {
+			buffer: [0; N],
+			len: 0,
+			state: DecodeState::new(),
+			error: None,
+		}
+	}
+
+	/// Decode a received byte.  Returns the frame's data once its delimiter is received.
+	///
+	/// Empty frames (two delimiters in a row) are ignored.  After an error, the rest of the
+	/// frame is dropped, and the next byte after the delimiter starts a new frame.
+	pub fn receive(&mut self, byte: u8) -> Option<Result<&[u8], DecodeError>> {
+		if byte != 0 {
+			if self.error.is_none() {
+				if let Err(e) =
+					self.state.decode(byte, &mut self.buffer, &mut self.len)
+				{
+					self.error = Some(e);
+				}
+			}
+			return None;
+		}
+
+		let state = core::mem::replace(&mut self.state, DecodeState::new());
+		let len = core::mem::replace(&mut self.len, 0);
+		if let Some(e) = self.error.take() {
+			return Some(Err(e));
+		}
+		if !state.started {
+			return None;
+		}
+		Some(state.finish().map(|()| &self.buffer[..len]))
+	}
+
+	/// Drop the partially received frame.
+	pub fn reset(&mut self) {
+		self.state = DecodeState::new();
+		self.len = 0;
+		self.error = None;
+	}
+}
+
+impl<const N: usize> Default for CobsReceiver<N> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::vec::Vec;
+
+	fn round_trip(data: &[u8]) -> Vec<u8> {
+		let mut encoded = std::vec![0; max_encoded_len(data.len())];
+		let len = encode(data, &mut encoded);
+		encoded.truncate(len);
+		assert!(len <= max_encoded_len(data.len()));
+		assert_eq!(encoded.last(), Some(&0));
+		assert!(!encoded[..len - 1].contains(&0));
+
+		let mut decoded = std::vec![0; data.len()];
+		assert_eq!(decode(&encoded, &mut decoded), Ok(data.len()));
+		assert_eq!(decoded, data);
+
+		let mut receiver = CobsReceiver::<600>::new();
+		let (last, rest) = encoded.split_last().unwrap();
+		for &byte in rest {
+			assert_eq!(receiver.receive(byte), None);
+		}
+		assert_eq!(receiver.receive(*last), Some(Ok(data)));
+		encoded
+	}
+
+	#[test]
+	fn encode_examples() {
+		assert_eq!(round_trip(&[]), [0x01, 0x00]);
+		assert_eq!(round_trip(&[0x00]), [0x01, 0x01, 0x00]);
+		assert_eq!(round_trip(&[0x00, 0x00]), [0x01, 0x01, 0x01, 0x00]);
+		assert_eq!(
+			round_trip(&[0x11, 0x00, 0x22, 0x33]),
+			[0x02, 0x11, 0x03, 0x22, 0x33, 0x00]
+		);
+		assert_eq!(
+			round_trip(&[0x11, 0x22, 0x00]),
+			[0x03, 0x11, 0x22, 0x01, 0x00]
+		);
+	}
+
+	#[test]
+	fn long_runs() {
+		for (len, encoded_len) in
+			[(253, 255), (254, 256), (255, 258), (508, 511), (509, 513)]
+		{
+			let data: Vec<u8> = (0..len).map(|i| (i % 255 + 1) as u8).collect();
+			assert_eq!(round_trip(&data).len(), encoded_len);
+		}
+		assert_eq!(max_encoded_len(509), 513);
+
+		// A full block with the code 0xff, then the rest
+		let data = [0x42; 255];
+		let encoded = round_trip(&data);
+		assert_eq!(encoded[0], 0xff);
+		assert_eq!(encoded[255], 0x02);
+		assert_eq!(encoded.len(), 258);
+
+		// A zero right after a full block
+		let mut data = [0x42; 255];
+		data[254] = 0x00;
+		let encoded = round_trip(&data);
+		assert_eq!(&encoded[255..], [0x01, 0x01, 0x00]);
+	}
+
+	#[test]
+	fn embedded_zeros() {
+		let mut data = [0x55u8; 600];
+		for i in [0, 1, 100, 253, 254, 300, 598, 599] {
+			data[i] = 0;
+		}
+		round_trip(&data);
+	}
+
+	#[test]
+	fn decode_errors() {
+		let mut out = [0; 4];
+		assert_eq!(decode(&[], &mut out), Err(DecodeError::Truncated));
+		assert_eq!(
+			decode(&[0x03, 0x11, 0x00], &mut out),
+			Err(DecodeError::Truncated)
+		);
+		assert_eq!(
+			decode(&[0x03, 0x11, 0x00, 0x22, 0x00], &mut out),
+			Err(DecodeError::UnexpectedZero)
+		);
+		assert_eq!(
+			decode(&[0x06, 1, 2, 3, 4, 5, 0x00], &mut out),
+			Err(DecodeError::Overflow)
+		);
+	}
+
+	#[test]
+	fn receiver_resyncs() {
+		let mut receiver = CobsReceiver::<4>::new();
+		// The end of a frame the receiver joined in the middle of
+		for &byte in &[0x22, 0x05, 0x33] {
+			assert_eq!(receiver.receive(byte), None);
+		}
+		assert_eq!(receiver.receive(0x00), Some(Err(DecodeError::Truncated)));
+		// Empty frames are ignored
+		assert_eq!(receiver.receive(0x00), None);
+
+		for &byte in &[0x02, 0x11, 0x01] {
+			assert_eq!(receiver.receive(byte), None);
+		}
+		assert_eq!(receiver.receive(0x00), Some(Ok(&[0x11, 0x00][..])));
+	}
+}
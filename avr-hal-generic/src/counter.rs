@@ -0,0 +1,122 @@
+//! Hardware event counters
+//!
+//! A timer/counter can be clocked from its external clock input pin (`T0`, `T1`, ...) instead of
+//! the system clock.  Every edge on that pin then increments the counter register, which can be
+//! read at any time.  This makes for a raw event counter, e.g. for flow-meter pulses or geiger
+//! counter clicks, which keeps counting without any CPU involvement.
+//!
+//! # Maximum edge rate
+//! The external clock input is synchronized to and sampled by the system clock.  To reliably
+//! detect every edge, the input frequency must stay below `CLK_io / 2.5` (6.4 MHz at 16 MHz) and
+//! each high and low phase must last longer than one system clock cycle.  Faster signals will
+//! silently lose counts.
+
+/// Which edge of the external clock input increments the counter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+	/// Count falling edges.
+	Falling,
+	/// Count rising edges.
+	Rising,
+}
+
+/// Turn a timer peripheral into an event counter clocked from its external clock input pin.
+///
+/// This is an alternative spelling of the counter's `new()` constructor:
+///
+/// ```ignore
+/// let mut pulses = dp.TC1.into_counter(pins.pd5, Edge::Rising);
+/// ```
+///
+/// The input pin is still required so the counter owns it and nothing else can drive it.
+pub trait IntoCounter<COUNTER>: Sized {
+	/// The external clock input pin of this timer.
+	type Pin;
+
+	/// Start counting edges on the external clock input pin of this timer.
+	fn into_counter<IMODE: crate::port::mode::InputMode>(
+		self,
+		pin: crate::port::Pin<crate::port::mode::Input<IMODE>, Self::Pin>,
+		edge: Edge,
+	) -> COUNTER;
+}
+
+#[macro_export]
+macro_rules! impl_counter {
+    (
+        $(#[$counter_attr:meta])*
+        pub struct $Counter:ident {
+            timer: $TIMER:ty,
+            pin: $PIN:ty,
+            counter: $tcnt:ident,
+            init: |$init_timer:ident, $edge:ident| $init_block:block,
+            stop: |$stop_timer:ident| $stop_block:block,
+        }
+    ) => {
+        $(#[$counter_attr])*
+        pub struct $Counter {
+            timer: $TIMER,
+            pin: $crate::port::Pin<$crate::port::mode::Input, $PIN>,
+        }
+
+        impl $Counter {
+            /// Start counting edges on the external clock input pin of this timer.
+            ///
+            /// The counter starts at zero.
+            pub fn new<IMODE: $crate::port::mode::InputMode>(
+                timer: $TIMER,
+                pin: $crate::port::Pin<$crate::port::mode::Input<IMODE>, $PIN>,
+                edge: $crate::counter::Edge,
+            ) -> $Counter {
+                let mut c = $Counter {
+                    timer,
+                    pin: pin.forget_imode(),
+                };
+
+                c.timer.$tcnt().reset();
+                {
+                    let $init_timer = &mut c.timer;
+                    let $edge = edge;
+                    $init_block
+                }
+
+                c
+            }
+
+            /// Number of edges counted since the last reset.
+            ///
+            /// The counter silently wraps around once it overflows its register width.
+            pub fn count(&self) -> u16 {
+                // 16-bit timer registers are read through a shared temporary register which an
+                // interrupt handler accessing the same timer could clobber.
+                $crate::avr_device::interrupt::free(|_| self.timer.$tcnt().read().bits() as u16)
+            }
+
+            /// Reset the counter to zero.
+            pub fn reset(&mut self) {
+                $crate::avr_device::interrupt::free(|_| self.timer.$tcnt().reset());
+            }
+
+            /// Stop counting and return the timer peripheral and the input pin.
+            pub fn release(mut self) -> ($TIMER, $crate::port::Pin<$crate::port::mode::Input, $PIN>) {
+                {
+                    let $stop_timer = &mut self.timer;
+                    $stop_block
+                }
+                (self.timer, self.pin)
+            }
+        }
+
+        impl $crate::counter::IntoCounter<$Counter> for $TIMER {
+            type Pin = $PIN;
+
+            fn into_counter<IMODE: $crate::port::mode::InputMode>(
+                self,
+                pin: $crate::port::Pin<$crate::port::mode::Input<IMODE>, $PIN>,
+                edge: $crate::counter::Edge,
+            ) -> $Counter {
+                $Counter::new(self, pin, edge)
+            }
+        }
+    }
+}
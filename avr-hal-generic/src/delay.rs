@@ -42,6 +42,29 @@ impl<SPEED> Delay<SPEED> {
 	}
 }
 
+impl<SPEED> Delay<SPEED>
+where
+	Delay<SPEED>: delay_v0::DelayUs<u16>,
+{
+	/// Delay for `ms` milliseconds, calling `f` once per millisecond while waiting.
+	///
+	/// This keeps things like a watchdog or USB polling going during a long delay, without
+	/// needing an executor.  The delay is busy-waited in 1 ms steps and `f` runs between them,
+	/// so its run time adds to the total: the delay takes `ms` milliseconds plus `ms` calls of
+	/// `f`.  Neither the delay nor the interval between calls is precise, so keep `f` short and
+	/// don't use this for timing-critical waits.
+	///
+	/// ```ignore
+	/// delay.delay_ms_with(500, || watchdog.feed());
+	/// ```
+	pub fn delay_ms_with<F: FnMut()>(&mut self, ms: u32, mut f: F) {
+		for _ in 0..ms {
+			delay_v0::DelayUs::<u16>::delay_us(self, 1000);
+			f();
+		}
+	}
+}
+
 // based on https://github.com/arduino/ArduinoCore-avr/blob/master/cores/arduino/wiring.c
 
 #[cfg(target_arch = "avr")]
@@ -0,0 +1,243 @@
+//! AT24C-series I2C EEPROM
+//!
+//! Covers the 24C32 to 24C512 chips (and compatible ones from other vendors), which are addressed
+//! with two bytes.
+//!
+//! # Page writes
+//! The EEPROM is organized in pages, e.g. 32 bytes for the 24C32.  A single write transaction can
+//! only write within one page: when it reaches the end of a page, the chip's address counter wraps
+//! around to the *start of the same page* and silently overwrites data there.  [`At24::write()`]
+//! therefore splits the data at page boundaries and waits for the internal write cycle (at most
+//! 5 ms) after each page.  Reads have no such restriction and can cross pages freely.
+//!
+//! # Example
+//! ```ignore
+//! let mut eeprom = At24::new(i2c, at24::DEFAULT_ADDRESS, at24::AT24C256);
+//! let mut delay = arduino_hal::Delay::new();
+//!
+//! eeprom.write(0x0100, b"hello", &mut delay)?;
+//!
+//! let mut buffer = [0; 5];
+//! eeprom.read(0x0100, &mut buffer)?;
+//! ```
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+use embedded_hal::i2c::Operation;
+
+use crate::util::ByteOrder;
+
+/// I2C address with `A0`-`A2` pulled low.  The address pins add to this address.
+pub const DEFAULT_ADDRESS: u8 = 0x50;
+
+/// Maximum duration of the internal write cycle, in milliseconds.
+const WRITE_CYCLE_MS: u32 = 5;
+
+/// Size and page size of an EEPROM chip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Geometry {
+	/// Capacity in bytes.
+	pub capacity: u32,
+	/// Page size in bytes.
+	pub page_size: u16,
+}
+
+/// 24C32: 4 KiB, 32 byte pages.
+pub const AT24C32: Geometry = Geometry {
+	capacity: 4096,
+	page_size: 32,
+};
+/// 24C64: 8 KiB, 32 byte pages.
+pub const AT24C64: Geometry = Geometry {
+	capacity: 8192,
+	page_size: 32,
+};
+/// 24C128: 16 KiB, 64 byte pages.
+pub const AT24C128: Geometry = Geometry {
+	capacity: 16384,
+	page_size: 64,
+};
+/// 24C256: 32 KiB, 64 byte pages.
+pub const AT24C256: Geometry = Geometry {
+	capacity: 32768,
+	page_size: 64,
+};
+/// 24C512: 64 KiB, 128 byte pages.
+pub const AT24C512: Geometry = Geometry {
+	capacity: 65536,
+	page_size: 128,
+};
+
+#[derive(ufmt::derive::uDebug, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<E> {
+	/// The I2C transaction failed.
+	I2c(E),
+	/// The access reaches beyond the capacity of the chip.
+	OutOfRange,
+}
+
+impl<E> From<E> for Error<E> {
+	fn from(e: E) -> Self {
+		Error::I2c(e)
+	}
+}
+
+/// AT24C-series EEPROM.
+pub struct At24<I2C> {
+	i2c: I2C,
+	address: u8,
+	geometry: Geometry,
+}
+
+impl<I2C: I2c> At24<I2C> {
+	pub fn new(i2c: I2C, address: u8, geometry: Geometry) -> Self {
+		Self {
+			i2c,
+			address,
+			geometry,
+		}
+	}
+
+	/// Size and page size of the chip.
+	pub fn geometry(&self) -> Geometry {
+		self.geometry
+	}
+
+	/// Read `buffer.len()` bytes starting at `address`.
+	pub fn read(&mut self, address: u16, buffer: &mut [u8]) -> Result<(), Error<I2C::Error>> {
+		self.check_range(address, buffer.len())?;
+		if buffer.is_empty() {
+			return Ok(());
+		}
+		let address = ByteOrder::BigEndian.u16_to_bytes(address);
+		self.i2c.write_read(self.address, &address, buffer)?;
+		Ok(())
+	}
+
+	/// Write `data` starting at `address`, split into page writes.
+	///
+	/// Waits for the write cycle to finish after each page, so this takes about 5 ms per page
+	/// touched.
+	pub fn write<D: DelayNs>(
+		&mut self,
+		address: u16,
+		data: &[u8],
+		delay: &mut D,
+	) -> Result<(), Error<I2C::Error>> {
+		self.check_range(address, data.len())?;
+
+		let page_size = usize::from(self.geometry.page_size);
+		let mut address = usize::from(address);
+		let mut data = data;
+		while !data.is_empty() {
+			// Never write past the end of the current page, or the chip wraps around within it.
+			let space_in_page = page_size - address % page_size;
+			let (chunk, rest) = data.split_at(space_in_page.min(data.len()));
+
+			let address_bytes = ByteOrder::BigEndian.u16_to_bytes(address as u16);
+			self.i2c.transaction(
+				self.address,
+				&mut [Operation::Write(&address_bytes), Operation::Write(chunk)],
+			)?;
+			delay.delay_ms(WRITE_CYCLE_MS);
+
+			address += chunk.len();
+			data = rest;
+		}
+		Ok(())
+	}
+
+	/// Give back the I2C bus.
+	pub fn release(self) -> I2C {
+		self.i2c
+	}
+
+	fn check_range(&self, address: u16, len: usize) -> Result<(), Error<I2C::Error>> {
+		if u32::from(address) + len as u32 > self.geometry.capacity {
+			Err(Error::OutOfRange)
+		} else {
+			Ok(())
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use core::cell::RefCell;
+	use std::vec::Vec;
+
+	#[derive(Debug, PartialEq, Eq)]
+	enum Event {
+		/// A transaction with all its written bytes, concatenated.
+		Write(u8, Vec<u8>),
+		DelayNs(u32),
+	}
+
+	struct MockI2c<'a>(&'a RefCell<Vec<Event>>);
+
+	impl embedded_hal::i2c::ErrorType for MockI2c<'_> {
+		type Error = core::convert::Infallible;
+	}
+
+	impl I2c for MockI2c<'_> {
+		fn transaction(
+			&mut self,
+			address: u8,
+			operations: &mut [Operation<'_>],
+		) -> Result<(), Self::Error> {
+			let mut bytes = Vec::new();
+			for operation in operations {
+				match operation {
+					Operation::Write(data) => bytes.extend_from_slice(data),
+					Operation::Read(_) => unimplemented!(),
+				}
+			}
+			self.0.borrow_mut().push(Event::Write(address, bytes));
+			Ok(())
+		}
+	}
+
+	struct MockDelay<'a>(&'a RefCell<Vec<Event>>);
+
+	impl DelayNs for MockDelay<'_> {
+		fn delay_ns(&mut self, ns: u32) {
+			self.0.borrow_mut().push(Event::DelayNs(ns));
+		}
+
+		fn delay_ms(&mut self, ms: u32) {
+			self.delay_ns(ms * 1_000_000);
+		}
+	}
+
+	#[test]
+	fn write_across_page_boundary() {
+		let events = RefCell::new(Vec::new());
+		let mut eeprom = At24::new(MockI2c(&events), DEFAULT_ADDRESS + 1, AT24C32);
+
+		// Starts 4 bytes before the end of the 32 byte page at 0x0020
+		let data = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+		eeprom.write(0x003c, &data, &mut MockDelay(&events))
+			.unwrap();
+
+		assert_eq!(
+			events.into_inner(),
+			[
+				Event::Write(0x51, std::vec![0x00, 0x3c, 1, 2, 3, 4]),
+				Event::DelayNs(5_000_000),
+				Event::Write(0x51, std::vec![0x00, 0x40, 5, 6, 7, 8, 9, 10]),
+				Event::DelayNs(5_000_000),
+			]
+		);
+	}
+
+	#[test]
+	fn write_out_of_range() {
+		let events = RefCell::new(Vec::new());
+		let mut eeprom = At24::new(MockI2c(&events), DEFAULT_ADDRESS, AT24C32);
+		assert_eq!(
+			eeprom.write(4090, &[0; 7], &mut MockDelay(&events)),
+			Err(Error::OutOfRange)
+		);
+		assert!(events.into_inner().is_empty());
+	}
+}
@@ -0,0 +1,412 @@
+//! BME280/BMP280 temperature, pressure, and humidity sensor
+//!
+//! Both sensors are supported over I2C ([`I2cInterface`]) and SPI ([`SpiInterface`]).  The BMP280
+//! is the same sensor without humidity measurement.
+//!
+//! # Example
+//! ```ignore
+//! let mut sensor = Bme280::new(I2cInterface::new(i2c, bme280::DEFAULT_ADDRESS));
+//! let mut delay = arduino_hal::Delay::new();
+//! sensor.init(&mut delay)?;
+//!
+//! let m = sensor.measure(&mut delay)?;
+//! ufmt::uwriteln!(&mut serial, "{} centi-°C, {} Pa", m.temperature, m.pressure);
+//! ```
+//!
+//! # Compensation
+//! The sensor returns raw ADC values, which have to be compensated with factory calibration data
+//! stored in the sensor.  This is done with the integer formulas from section 4.2.3 of the BME280
+//! datasheet (64-bit variant for pressure), which avoid floating point entirely.
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+use embedded_hal::spi::Operation;
+use embedded_hal::spi::SpiDevice;
+
+use crate::util::ByteOrder;
+
+/// I2C address with the `SDO` pin pulled low.
+pub const DEFAULT_ADDRESS: u8 = 0x76;
+/// I2C address with the `SDO` pin pulled high.
+pub const ALTERNATE_ADDRESS: u8 = 0x77;
+
+const REG_CALIB_00: u8 = 0x88;
+const REG_ID: u8 = 0xd0;
+const REG_RESET: u8 = 0xe0;
+const REG_CALIB_26: u8 = 0xe1;
+const REG_CTRL_HUM: u8 = 0xf2;
+const REG_STATUS: u8 = 0xf3;
+const REG_CTRL_MEAS: u8 = 0xf4;
+const REG_PRESS_MSB: u8 = 0xf7;
+
+const CHIP_ID_BME280: u8 = 0x60;
+const CHIP_ID_BMP280: u8 = 0x58;
+
+/// Register access to the sensor.
+pub trait Interface {
+	type Error;
+
+	/// Read consecutive registers, starting at `register`.
+	fn read_registers(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), Self::Error>;
+
+	fn write_register(&mut self, register: u8, value: u8) -> Result<(), Self::Error>;
+}
+
+/// Sensor connected over I2C.
+pub struct I2cInterface<I2C> {
+	i2c: I2C,
+	address: u8,
+}
+
+impl<I2C: I2c> I2cInterface<I2C> {
+	pub fn new(i2c: I2C, address: u8) -> Self {
+		Self { i2c, address }
+	}
+
+	pub fn release(self) -> I2C {
+		self.i2c
+	}
+}
+
+impl<I2C: I2c> Interface for I2cInterface<I2C> {
+	type Error = I2C::Error;
+
+	fn read_registers(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+		self.i2c.write_read(self.address, &[register], buffer)
+	}
+
+	fn write_register(&mut self, register: u8, value: u8) -> Result<(), Self::Error> {
+		self.i2c.write(self.address, &[register, value])
+	}
+}
+
+/// Sensor connected over SPI (mode 0 or 3).
+pub struct SpiInterface<SPI> {
+	spi: SPI,
+}
+
+impl<SPI: SpiDevice> SpiInterface<SPI> {
+	pub fn new(spi: SPI) -> Self {
+		Self { spi }
+	}
+
+	pub fn release(self) -> SPI {
+		self.spi
+	}
+}
+
+impl<SPI: SpiDevice> Interface for SpiInterface<SPI> {
+	type Error = SPI::Error;
+
+	fn read_registers(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+		self.spi.transaction(&mut [
+			Operation::Write(&[register | 0x80]),
+			Operation::Read(buffer),
+		])
+	}
+
+	fn write_register(&mut self, register: u8, value: u8) -> Result<(), Self::Error> {
+		self.spi.write(&[register & 0x7f, value])
+	}
+}
+
+#[derive(ufmt::derive::uDebug, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<E> {
+	/// Communication with the sensor failed.
+	Interface(E),
+	/// The chip ID is neither the one of a BME280 nor of a BMP280.
+	WrongDevice(u8),
+}
+
+impl<E> From<E> for Error<E> {
+	fn from(e: E) -> Self {
+		Error::Interface(e)
+	}
+}
+
+/// Compensated measurement result.
+#[derive(ufmt::derive::uDebug, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Measurement {
+	/// Temperature in hundredths of a degree Celsius.
+	pub temperature: i32,
+	/// Pressure in Pa.
+	pub pressure: u32,
+	/// Relative humidity in thousandths of a percent, `None` on a BMP280.
+	pub humidity: Option<u32>,
+}
+
+/// Factory calibration data, named like in the datasheet.
+#[derive(Debug, Clone, Copy, Default)]
+struct Calibration {
+	t1: u16,
+	t2: i16,
+	t3: i16,
+	p1: u16,
+	p2: i16,
+	p3: i16,
+	p4: i16,
+	p5: i16,
+	p6: i16,
+	p7: i16,
+	p8: i16,
+	p9: i16,
+	h1: u8,
+	h2: i16,
+	h3: u8,
+	h4: i16,
+	h5: i16,
+	h6: i8,
+}
+
+impl Calibration {
+	fn from_bytes(a: &[u8; 26], b: &[u8; 7]) -> Self {
+		let le = ByteOrder::LittleEndian;
+		let u = |i: usize| le.u16_from_bytes([a[i], a[i + 1]]);
+		let s = |i: usize| le.i16_from_bytes([a[i], a[i + 1]]);
+		Self {
+			t1: u(0),
+			t2: s(2),
+			t3: s(4),
+			p1: u(6),
+			p2: s(8),
+			p3: s(10),
+			p4: s(12),
+			p5: s(14),
+			p6: s(16),
+			p7: s(18),
+			p8: s(20),
+			p9: s(22),
+			h1: a[25],
+			h2: le.i16_from_bytes([b[0], b[1]]),
+			h3: b[2],
+			// Two 12-bit values sharing the nibbles of 0xe5.
+			h4: (i16::from(b[3] as i8) << 4) | i16::from(b[4] & 0x0f),
+			h5: (i16::from(b[5] as i8) << 4) | i16::from(b[4] >> 4),
+			h6: b[6] as i8,
+		}
+	}
+
+	/// Returns `t_fine` and the temperature in 0.01 °C.
+	fn temperature(&self, adc_t: i32) -> (i32, i32) {
+		let t1 = i32::from(self.t1);
+		let var1 = (((adc_t >> 3) - (t1 << 1)) * i32::from(self.t2)) >> 11;
+		let var2 = (((((adc_t >> 4) - t1) * ((adc_t >> 4) - t1)) >> 12)
+			* i32::from(self.t3)) >> 14;
+		let t_fine = var1 + var2;
+		(t_fine, (t_fine * 5 + 128) >> 8)
+	}
+
+	/// Returns the pressure in Pa as a Q24.8 fixed-point value.
+	fn pressure(&self, t_fine: i32, adc_p: i32) -> u32 {
+		let mut var1 = i64::from(t_fine) - 128000;
+		let mut var2 = var1 * var1 * i64::from(self.p6);
+		var2 += (var1 * i64::from(self.p5)) << 17;
+		var2 += i64::from(self.p4) << 35;
+		var1 = ((var1 * var1 * i64::from(self.p3)) >> 8)
+			+ ((var1 * i64::from(self.p2)) << 12);
+		var1 = (((1i64 << 47) + var1) * i64::from(self.p1)) >> 33;
+		if var1 == 0 {
+			// Avoid a division by zero
+			return 0;
+		}
+		let mut p = 1048576 - i64::from(adc_p);
+		p = (((p << 31) - var2) * 3125) / var1;
+		var1 = (i64::from(self.p9) * (p >> 13) * (p >> 13)) >> 25;
+		var2 = (i64::from(self.p8) * p) >> 19;
+		p = ((p + var1 + var2) >> 8) + (i64::from(self.p7) << 4);
+		p as u32
+	}
+
+	/// Returns the relative humidity in % as a Q22.10 fixed-point value.
+	fn humidity(&self, t_fine: i32, adc_h: i32) -> u32 {
+		let mut v = t_fine - 76800;
+		let h2 = i32::from(self.h2);
+		let h3 = i32::from(self.h3);
+		let h4 = i32::from(self.h4);
+		let h5 = i32::from(self.h5);
+		let h6 = i32::from(self.h6);
+		let a = ((adc_h << 14) - (h4 << 20) - (h5 * v) + 16384) >> 15;
+		let b = ((((((v * h6) >> 10) * (((v * h3) >> 11) + 32768)) >> 10) + 2097152) * h2
+			+ 8192) >> 14;
+		v = a * b;
+		v -= ((((v >> 15) * (v >> 15)) >> 7) * i32::from(self.h1)) >> 4;
+		(v.clamp(0, 419430400) >> 12) as u32
+	}
+}
+
+/// BME280 or BMP280 sensor.
+///
+/// Measurements are taken in forced mode with 1x oversampling and without IIR filter, the
+/// settings recommended by the datasheet for weather monitoring.  Between measurements, the
+/// sensor sleeps and draws less than 1 µA.
+pub struct Bme280<IFACE> {
+	iface: IFACE,
+	calibration: Calibration,
+	has_humidity: bool,
+}
+
+impl<IFACE: Interface> Bme280<IFACE> {
+	pub fn new(iface: IFACE) -> Self {
+		Self {
+			iface,
+			calibration: Calibration::default(),
+			has_humidity: false,
+		}
+	}
+
+	/// Reset the sensor, check its identity, and read the calibration data.
+	pub fn init<D: DelayNs>(&mut self, delay: &mut D) -> Result<(), Error<IFACE::Error>> {
+		let mut id = [0];
+		self.iface.read_registers(REG_ID, &mut id)?;
+		self.has_humidity = match id[0] {
+			CHIP_ID_BME280 => true,
+			CHIP_ID_BMP280 => false,
+			id => return Err(Error::WrongDevice(id)),
+		};
+
+		self.iface.write_register(REG_RESET, 0xb6)?;
+		// Start-up time is 2 ms, then the calibration data is copied from NVM.
+		delay.delay_ms(2);
+		while self.is_busy()? {
+			delay.delay_ms(1);
+		}
+
+		let mut a = [0; 26];
+		let mut b = [0; 7];
+		self.iface.read_registers(REG_CALIB_00, &mut a)?;
+		if self.has_humidity {
+			self.iface.read_registers(REG_CALIB_26, &mut b)?;
+		}
+		self.calibration = Calibration::from_bytes(&a, &b);
+		Ok(())
+	}
+
+	/// Whether the sensor measures humidity, i.e. is a BME280.
+	pub fn has_humidity(&self) -> bool {
+		self.has_humidity
+	}
+
+	/// Take a measurement and return the compensated values.
+	///
+	/// Blocks for the duration of the measurement, about 10 ms.
+	pub fn measure<D: DelayNs>(&mut self, delay: &mut D) -> Result<Measurement, IFACE::Error> {
+		// ctrl_hum only takes effect after the following write to ctrl_meas.
+		if self.has_humidity {
+			self.iface.write_register(REG_CTRL_HUM, 0b001)?;
+		}
+		// osrs_t = 1x, osrs_p = 1x, forced mode
+		self.iface
+			.write_register(REG_CTRL_MEAS, (0b001 << 5) | (0b001 << 2) | 0b01)?;
+		delay.delay_ms(8);
+		while self.is_busy()? {
+			delay.delay_ms(1);
+		}
+
+		let mut data = [0; 8];
+		let len = if self.has_humidity { 8 } else { 6 };
+		self.iface.read_registers(REG_PRESS_MSB, &mut data[..len])?;
+
+		let adc_p = (i32::from(data[0]) << 12)
+			| (i32::from(data[1]) << 4)
+			| i32::from(data[2] >> 4);
+		let adc_t = (i32::from(data[3]) << 12)
+			| (i32::from(data[4]) << 4)
+			| i32::from(data[5] >> 4);
+		let adc_h = (i32::from(data[6]) << 8) | i32::from(data[7]);
+
+		let (t_fine, temperature) = self.calibration.temperature(adc_t);
+		let pressure = self.calibration.pressure(t_fine, adc_p) >> 8;
+		let humidity = self
+			.has_humidity
+			.then(|| (self.calibration.humidity(t_fine, adc_h) * 1000) >> 10);
+
+		Ok(Measurement {
+			temperature,
+			pressure,
+			humidity,
+		})
+	}
+
+	/// Give back the interface.
+	pub fn release(self) -> IFACE {
+		self.iface
+	}
+
+	fn is_busy(&mut self) -> Result<bool, IFACE::Error> {
+		let mut status = [0];
+		self.iface.read_registers(REG_STATUS, &mut status)?;
+		// measuring or im_update
+		Ok(status[0] & 0b1001 != 0)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Calibration words from the BMP280 datasheet's compensation example, with typical humidity
+	/// values of a BME280 (the datasheet has no humidity example).
+	fn calibration() -> Calibration {
+		let words: [u16; 12] = [
+			27504,
+			26435,
+			-1000i16 as u16,
+			36477,
+			-10685i16 as u16,
+			3024,
+			2855,
+			140,
+			-7i16 as u16,
+			15500,
+			-14600i16 as u16,
+			6000,
+		];
+		let mut a = [0; 26];
+		for (chunk, word) in a.chunks_exact_mut(2).zip(words) {
+			chunk.copy_from_slice(&word.to_le_bytes());
+		}
+		a[25] = 75;
+		// h2 = 370, h3 = 0, h4 = 301 (0x12d), h5 = 50 (0x032), h6 = 30
+		let b = [0x72, 0x01, 0, 0x12, 0x2d, 0x03, 30];
+		Calibration::from_bytes(&a, &b)
+	}
+
+	#[test]
+	fn unpack_calibration() {
+		let c = calibration();
+		assert_eq!((c.t1, c.t2, c.t3), (27504, 26435, -1000));
+		assert_eq!((c.p1, c.p2, c.p9), (36477, -10685, 6000));
+		assert_eq!(
+			(c.h1, c.h2, c.h3, c.h4, c.h5, c.h6),
+			(75, 370, 0, 301, 50, 30)
+		);
+	}
+
+	#[test]
+	fn unpack_negative_humidity_nibbles() {
+		// h4 = -2 (0xffe), h5 = -3 (0xffd)
+		let c = Calibration::from_bytes(&[0; 26], &[0, 0, 0, 0xff, 0xde, 0xff, 0]);
+		assert_eq!((c.h4, c.h5), (-2, -3));
+	}
+
+	#[test]
+	fn datasheet_example() {
+		let c = calibration();
+		// 25.08 °C
+		let (t_fine, temperature) = c.temperature(519888);
+		assert_eq!(t_fine, 128422);
+		assert_eq!(temperature, 2508);
+		// 100653.27 Pa
+		assert_eq!(c.pressure(t_fine, 415148), 25767233);
+		assert_eq!(c.pressure(t_fine, 415148) >> 8, 100653);
+		// 59.41 %RH with the floating-point formula
+		assert_eq!(c.humidity(t_fine, 29796), 60832);
+		assert_eq!((c.humidity(t_fine, 29796) * 1000) >> 10, 59406);
+	}
+
+	#[test]
+	fn humidity_is_clamped() {
+		let c = calibration();
+		assert_eq!(c.humidity(128422, 0), 0);
+		assert_eq!(c.humidity(128422, 0xffff), 100 << 10);
+	}
+}
@@ -0,0 +1,221 @@
+//! LED charlieplexing
+//!
+//! Charlieplexing drives `N * (N - 1)` LEDs from `N` pins, e.g. 12 LEDs from 4 pins or 56 from
+//! 8.  There is one LED for every ordered pair of pins, with its anode on the first and its
+//! cathode on the second pin.  To light it, the anode pin is driven high, the cathode pin low,
+//! and all other pins are left floating (high-impedance inputs), so no current flows through the
+//! LEDs connected to them.  Each pin needs a series resistor; the current passes through two of
+//! them.
+//!
+//! Only the LEDs sharing one anode pin can be lit at the same time.  [`Charlieplex`] therefore
+//! multiplexes: each call to [`Charlieplex::tick()`] switches to the next anode pin and lights
+//! those of its LEDs which are on.  `tick()` is usually called from a timer interrupt.
+//!
+//! ```ignore
+//! let mut leds = Charlieplex::new([
+//!     pins.d2.downgrade(),
+//!     pins.d3.downgrade(),
+//!     pins.d4.downgrade(),
+//!     pins.d5.downgrade(),
+//! ]);
+//! leds.set(0, true);
+//! leds.set(Charlieplex::<_, 4>::led_index(3, 1), true);
+//!
+//! // From a timer interrupt at 1 kHz:
+//! leds.tick();
+//! ```
+//!
+//! LED `i` has its anode on pin `i / (N - 1)`, and its cathode on the `i % (N - 1)`-th of the
+//! remaining pins.  [`Charlieplex::led_index()`] computes the index from the two pin numbers.
+//!
+//! # Refresh rate and brightness
+//! A full refresh takes `N` ticks, one per anode pin.  Below a refresh rate of about 100 Hz, the
+//! LEDs visibly flicker, especially in peripheral vision or when moving, so the tick rate should
+//! be at least `N * 100` Hz, e.g. 400 Hz for 4 pins or 800 Hz for 8 pins.  Each LED is only lit
+//! for `1 / N` of the time, so it appears `N` times dimmer than with a static drive; a higher
+//! current makes up for part of it.  The anode pin sources the current of all `N - 1` LEDs of
+//! its row at once, so the current per LED times `N - 1` must stay within the pin limit
+//! (20 mA recommended, 40 mA absolute maximum).
+//!
+//! Missing or irregular ticks show as flicker or brightness differences between the rows, so
+//! keep the interrupt handler calling `tick()` short and its timing steady.
+use crate::port;
+use crate::port::mode;
+use crate::port::PinOps;
+
+/// `N * (N - 1)` LEDs on `N` pins.  See the [module documentation](self).
+pub struct Charlieplex<PIN, const N: usize> {
+	pins: [port::Pin<mode::Input<mode::Floating>, PIN>; N],
+	/// Bit `c` of `rows[a]` is the LED with its anode on pin `a` and its cathode on pin `c`.
+	rows: [u16; N],
+	active_row: usize,
+}
+
+impl<PIN: PinOps, const N: usize> Charlieplex<PIN, N> {
+	/// Number of LEDs.
+	pub const LEDS: usize = N * (N - 1);
+
+	/// Drive the LEDs on `pins`, all off.  The pins are made floating inputs.
+	///
+	/// # Panics
+	/// Panics if there are fewer than 2 or more than 16 pins.
+	pub fn new<MODE: mode::Io>(pins: [port::Pin<MODE, PIN>; N]) -> Self {
+		assert!((2..=16).contains(&N), "charlieplexing needs 2 to 16 pins");
+		Self {
+			pins: pins.map(|pin| pin.into_floating_input()),
+			rows: [0; N],
+			active_row: 0,
+		}
+	}
+
+	/// The index of the LED with its anode on pin `anode` and its cathode on pin `cathode`.
+	///
+	/// # Panics
+	/// Panics if the pins are the same or out of range.
+	pub fn led_index(anode: usize, cathode: usize) -> usize {
+		assert!(anode < N && cathode < N && anode != cathode);
+		let column = if cathode > anode {
+			cathode - 1
+		} else {
+			cathode
+		};
+		anode * (N - 1) + column
+	}
+
+	/// The anode and the cathode pin of LED `led`.
+	///
+	/// # Panics
+	/// Panics if `led` is out of range.
+	pub fn led_pins(led: usize) -> (usize, usize) {
+		assert!(led < Self::LEDS);
+		let anode = led / (N - 1);
+		let column = led % (N - 1);
+		let cathode = if column >= anode { column + 1 } else { column };
+		(anode, cathode)
+	}
+
+	/// Switch LED `led` on or off.  It changes the next time its row is shown by
+	/// [`tick()`][Self::tick].
+	///
+	/// # Panics
+	/// Panics if `led` is out of range.
+	pub fn set(&mut self, led: usize, on: bool) {
+		let (anode, cathode) = Self::led_pins(led);
+		if on {
+			self.rows[anode] |= 1 << cathode;
+		} else {
+			self.rows[anode] &= !(1 << cathode);
+		}
+	}
+
+	/// Whether LED `led` is on.
+	///
+	/// # Panics
+	/// Panics if `led` is out of range.
+	pub fn get(&self, led: usize) -> bool {
+		let (anode, cathode) = Self::led_pins(led);
+		self.rows[anode] & (1 << cathode) != 0
+	}
+
+	/// Switch all LEDs off.
+	pub fn clear(&mut self) {
+		self.rows = [0; N];
+	}
+
+	/// Show the next row: drive its anode pin high and the cathodes of its lit LEDs low, with all
+	/// other pins floating.
+	pub fn tick(&mut self) {
+		self.active_row = (self.active_row + 1) % N;
+		let row = self.rows[self.active_row];
+
+		// Release all pins first, so no LED of the previous row lights up with the new one.
+		for pin in self.pins.iter_mut() {
+			unsafe { pin.pin.make_input(false) };
+		}
+		if row == 0 {
+			return;
+		}
+		for (index, pin) in self.pins.iter_mut().enumerate() {
+			if index == self.active_row {
+				unsafe {
+					pin.pin.out_set();
+					pin.pin.make_output();
+				}
+			} else if row & (1 << index) != 0 {
+				// PORT is already low after make_input(false).
+				unsafe { pin.pin.make_output() };
+			}
+		}
+	}
+
+	/// Switch all LEDs off and return the pins as floating inputs.
+	pub fn release(mut self) -> [port::Pin<mode::Input<mode::Floating>, PIN>; N] {
+		for pin in self.pins.iter_mut() {
+			unsafe { pin.pin.make_input(false) };
+		}
+		self.pins
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	struct MockPin;
+
+	impl PinOps for MockPin {
+		type Dynamic = Self;
+
+		fn into_dynamic(self) -> Self::Dynamic {
+			self
+		}
+
+		unsafe fn out_set(&mut self) {}
+		unsafe fn out_clear(&mut self) {}
+		unsafe fn out_toggle(&mut self) {}
+		unsafe fn out_get(&self) -> bool {
+			false
+		}
+
+		unsafe fn in_get(&self) -> bool {
+			false
+		}
+
+		unsafe fn make_output(&mut self) {}
+		unsafe fn make_input(&mut self, _pull_up: bool) {}
+	}
+
+	fn round_trip<const N: usize>() {
+		for led in 0..Charlieplex::<MockPin, N>::LEDS {
+			let (anode, cathode) = Charlieplex::<MockPin, N>::led_pins(led);
+			assert_ne!(anode, cathode);
+			assert_eq!(Charlieplex::<MockPin, N>::led_index(anode, cathode), led);
+		}
+	}
+
+	#[test]
+	fn led_index_round_trip() {
+		round_trip::<2>();
+		round_trip::<4>();
+		round_trip::<8>();
+		round_trip::<16>();
+	}
+
+	#[test]
+	fn led_pins_of_four() {
+		type Leds = Charlieplex<MockPin, 4>;
+		assert_eq!(Leds::LEDS, 12);
+		assert_eq!(Leds::led_pins(0), (0, 1));
+		assert_eq!(Leds::led_pins(2), (0, 3));
+		assert_eq!(Leds::led_pins(3), (1, 0));
+		assert_eq!(Leds::led_pins(4), (1, 2));
+		assert_eq!(Leds::led_index(3, 1), 10);
+		assert_eq!(Leds::led_pins(11), (3, 2));
+	}
+
+	#[test]
+	#[should_panic]
+	fn led_index_same_pin() {
+		Charlieplex::<MockPin, 4>::led_index(2, 2);
+	}
+}
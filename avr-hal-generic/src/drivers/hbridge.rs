@@ -0,0 +1,304 @@
+//! DC motors on H-bridge driver ICs (DRV8833, L298N, TB6612FNG, DRV8838, ...)
+//!
+//! An H-bridge connects each motor terminal to either supply rail, so the motor can be driven in
+//! both directions, braked (both terminals shorted) or left coasting (both terminals open).  The
+//! driver ICs come with one of two input interfaces, each covered by its own type:
+//!
+//! - [`HBridge`]: two PWM inputs, one per motor terminal (`IN1`/`IN2` on the DRV8833, DRV8871,
+//!   L9110S, or an L298N with `ENA` tied high).  Both low coasts, both high brakes.
+//! - [`DirHBridge`]: a PWM input for the speed and a direction pin (`EN`/`PH` on the DRV8838 and
+//!   DRV8835 in phase/enable mode, `PWM`/`DIR` on Cytron drivers, or an L298N with the PWM on
+//!   `ENA` and `IN2` driven inverted from `IN1`).
+//!
+//! ```ignore
+//! let mut timer = Timer0Pwm::new(dp.TC0, Prescaler::Prescale64);
+//! let mut motor = HBridge::new(
+//!     pins.d5.into_output().into_pwm(&mut timer),
+//!     pins.d6.into_output().into_pwm(&mut timer),
+//! );
+//!
+//! motor.drive(200).unwrap(); // forward at 200/255
+//! motor.drive(-100).unwrap(); // backward at 100/255
+//! motor.brake().unwrap();
+//! ```
+//!
+//! The speed ranges from `-max_speed()` to `max_speed()`, which is the maximum duty cycle of the
+//! PWM pins (255 for the AVR timers).  Larger values are clamped.
+//!
+//! # Shoot-through
+//! If the high-side and the low-side switch of one half of the bridge conduct at the same time,
+//! they short the supply ("shoot-through"), which destroys the bridge within microseconds.  The
+//! driver ICs listed above prevent this themselves: every input combination is safe, and they
+//! insert the necessary dead time when a half-bridge switches over.  A bridge built from discrete
+//! transistors with separate gate inputs does not, and must not be driven with these types.
+//!
+//! Still, neither type reverses the motor in one step.  [`HBridge::drive()`] first turns off the
+//! input which was driving, before it starts the PWM on the other one, and [`DirHBridge::drive()`]
+//! only changes the direction pin while the PWM is off.  Otherwise, the bridge would briefly brake
+//! or drive full speed in the wrong direction during the change.  The PWM pins of an [`HBridge`]
+//! should be on the same timer, so they run at the same frequency.
+use embedded_hal::digital::OutputPin;
+use embedded_hal::pwm::SetDutyCycle;
+use unwrap_infallible::UnwrapInfallible;
+
+/// A motor on an H-bridge with two PWM inputs.  See the [module documentation](self).
+pub struct HBridge<IN1, IN2> {
+	in1: IN1,
+	in2: IN2,
+	speed: i16,
+	braking: bool,
+}
+
+impl<IN1, IN2> HBridge<IN1, IN2>
+where
+	IN1: SetDutyCycle,
+	IN2: SetDutyCycle<Error = IN1::Error>,
+{
+	/// Drive a motor through the `IN1` and `IN2` inputs of the bridge.  Positive speeds drive
+	/// `IN1`.
+	///
+	/// The PWM pins must be enabled.  The motor starts out coasting.
+	pub fn new(in1: IN1, in2: IN2) -> Self {
+		let mut bridge = Self {
+			in1,
+			in2,
+			speed: 0,
+			braking: false,
+		};
+		bridge.in1.set_duty_cycle_fully_off().ok();
+		bridge.in2.set_duty_cycle_fully_off().ok();
+		bridge
+	}
+
+	/// Drive the motor at `speed`: positive is forward (`IN1`), negative is backward (`IN2`), and
+	/// the magnitude sets the duty cycle.  0 coasts.
+	pub fn drive(&mut self, speed: i16) -> Result<(), IN1::Error> {
+		let speed = speed.clamp(-self.max_speed(), self.max_speed());
+		if self.braking {
+			// Both inputs are on, release both before driving one of them.
+			self.in1.set_duty_cycle_fully_off()?;
+			self.in2.set_duty_cycle_fully_off()?;
+			self.braking = false;
+		}
+		// Turn off the active input first, so reversing passes through coasting.
+		if speed >= 0 {
+			self.in2.set_duty_cycle_fully_off()?;
+			self.in1.set_duty_cycle(speed.unsigned_abs())?;
+		} else {
+			self.in1.set_duty_cycle_fully_off()?;
+			self.in2.set_duty_cycle(speed.unsigned_abs())?;
+		}
+		self.speed = speed;
+		Ok(())
+	}
+
+	/// Short the motor terminals (both inputs high), which stops it quickly.
+	pub fn brake(&mut self) -> Result<(), IN1::Error> {
+		self.in1.set_duty_cycle_fully_on()?;
+		self.in2.set_duty_cycle_fully_on()?;
+		self.speed = 0;
+		self.braking = true;
+		Ok(())
+	}
+
+	/// Disconnect the motor (both inputs low), so it runs down freely.
+	pub fn coast(&mut self) -> Result<(), IN1::Error> {
+		self.drive(0)
+	}
+
+	/// The speed set by the last call to [`drive()`][Self::drive], 0 after braking.
+	pub fn speed(&self) -> i16 {
+		self.speed
+	}
+
+	/// The speed for full duty cycle.
+	pub fn max_speed(&self) -> i16 {
+		self.in1.max_duty_cycle().min(i16::MAX as u16) as i16
+	}
+
+	/// Return the PWM pins.  They keep their current duty cycles.
+	pub fn release(self) -> (IN1, IN2) {
+		(self.in1, self.in2)
+	}
+}
+
+/// A motor on an H-bridge with a PWM input for the speed and a direction pin.  See the
+/// [module documentation](self).
+///
+/// There is no coasting: what the bridge does when the PWM is off depends on the IC.  Most of
+/// them, like the DRV8838, brake then.
+pub struct DirHBridge<PWM, DIR> {
+	pwm: PWM,
+	dir: DIR,
+	speed: i16,
+}
+
+impl<PWM, DIR> DirHBridge<PWM, DIR>
+where
+	PWM: SetDutyCycle,
+	DIR: OutputPin<Error = core::convert::Infallible>,
+{
+	/// Drive a motor through the PWM (enable) and direction (phase) inputs of the bridge.
+	/// Positive speeds set the direction pin low.
+	///
+	/// The PWM pin must be enabled.  The motor starts out stopped.
+	pub fn new(pwm: PWM, mut dir: DIR) -> Self {
+		dir.set_low().unwrap_infallible();
+		let mut bridge = Self { pwm, dir, speed: 0 };
+		bridge.pwm.set_duty_cycle_fully_off().ok();
+		bridge
+	}
+
+	/// Drive the motor at `speed`: the sign selects the direction, the magnitude sets the duty
+	/// cycle.  0 stops the motor.
+	pub fn drive(&mut self, speed: i16) -> Result<(), PWM::Error> {
+		let speed = speed.clamp(-self.max_speed(), self.max_speed());
+		if (speed < 0) != (self.speed < 0) {
+			// Only change direction while the bridge is off.
+			self.pwm.set_duty_cycle_fully_off()?;
+			if speed < 0 {
+				self.dir.set_high().unwrap_infallible();
+			} else {
+				self.dir.set_low().unwrap_infallible();
+			}
+		}
+		self.pwm.set_duty_cycle(speed.unsigned_abs())?;
+		self.speed = speed;
+		Ok(())
+	}
+
+	/// Turn off the PWM.  Whether the motor brakes or coasts then depends on the IC.
+	pub fn stop(&mut self) -> Result<(), PWM::Error> {
+		self.drive(0)
+	}
+
+	/// The speed set by the last call to [`drive()`][Self::drive].
+	pub fn speed(&self) -> i16 {
+		self.speed
+	}
+
+	/// The speed for full duty cycle.
+	pub fn max_speed(&self) -> i16 {
+		self.pwm.max_duty_cycle().min(i16::MAX as u16) as i16
+	}
+
+	/// Return the PWM and direction pins.  They keep their current state.
+	pub fn release(self) -> (PWM, DIR) {
+		(self.pwm, self.dir)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::cell::RefCell;
+	use std::rc::Rc;
+	use std::vec::Vec;
+
+	/// Every pin change, in order: the pin name and its new duty cycle or level.
+	type Log = Rc<RefCell<Vec<(&'static str, u16)>>>;
+
+	struct MockPin(&'static str, Log);
+
+	impl embedded_hal::pwm::ErrorType for MockPin {
+		type Error = core::convert::Infallible;
+	}
+
+	impl SetDutyCycle for MockPin {
+		fn max_duty_cycle(&self) -> u16 {
+			255
+		}
+
+		fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+			self.1.borrow_mut().push((self.0, duty));
+			Ok(())
+		}
+	}
+
+	impl embedded_hal::digital::ErrorType for MockPin {
+		type Error = core::convert::Infallible;
+	}
+
+	impl OutputPin for MockPin {
+		fn set_low(&mut self) -> Result<(), Self::Error> {
+			self.1.borrow_mut().push((self.0, 0));
+			Ok(())
+		}
+
+		fn set_high(&mut self) -> Result<(), Self::Error> {
+			self.1.borrow_mut().push((self.0, 1));
+			Ok(())
+		}
+	}
+
+	fn pins(a: &'static str, b: &'static str) -> (MockPin, MockPin, Log) {
+		let log = Log::default();
+		(MockPin(a, log.clone()), MockPin(b, log.clone()), log)
+	}
+
+	#[test]
+	fn hbridge_reverses_through_coasting() {
+		let (in1, in2, log) = pins("in1", "in2");
+		let mut motor = HBridge::new(in1, in2);
+		assert_eq!(log.take(), [("in1", 0), ("in2", 0)]);
+
+		motor.drive(200).unwrap();
+		motor.drive(-100).unwrap();
+		assert_eq!(motor.speed(), -100);
+		assert_eq!(
+			log.take(),
+			[("in2", 0), ("in1", 200), ("in1", 0), ("in2", 100)]
+		);
+
+		motor.coast().unwrap();
+		assert_eq!(motor.speed(), 0);
+		assert_eq!(log.take(), [("in2", 0), ("in1", 0)]);
+	}
+
+	#[test]
+	fn hbridge_brake_and_release() {
+		let (in1, in2, log) = pins("in1", "in2");
+		let mut motor = HBridge::new(in1, in2);
+		motor.drive(50).unwrap();
+		log.take();
+
+		motor.brake().unwrap();
+		assert_eq!(motor.speed(), 0);
+		assert_eq!(log.take(), [("in1", 255), ("in2", 255)]);
+
+		// Both inputs go off before one of them is driven again
+		motor.drive(-30).unwrap();
+		assert_eq!(
+			log.take(),
+			[("in1", 0), ("in2", 0), ("in1", 0), ("in2", 30)]
+		);
+	}
+
+	#[test]
+	fn hbridge_clamps_speed() {
+		let (in1, in2, log) = pins("in1", "in2");
+		let mut motor = HBridge::new(in1, in2);
+		assert_eq!(motor.max_speed(), 255);
+		motor.drive(i16::MIN).unwrap();
+		assert_eq!(motor.speed(), -255);
+		assert_eq!(log.borrow().last(), Some(&("in2", 255)));
+	}
+
+	#[test]
+	fn dir_hbridge_changes_direction_while_off() {
+		let (pwm, dir, log) = pins("pwm", "dir");
+		let mut motor = DirHBridge::new(pwm, dir);
+		assert_eq!(log.take(), [("dir", 0), ("pwm", 0)]);
+
+		motor.drive(100).unwrap();
+		motor.drive(180).unwrap();
+		assert_eq!(log.take(), [("pwm", 100), ("pwm", 180)]);
+
+		motor.drive(-1000).unwrap();
+		assert_eq!(motor.speed(), -255);
+		assert_eq!(log.take(), [("pwm", 0), ("dir", 1), ("pwm", 255)]);
+
+		motor.stop().unwrap();
+		assert_eq!(log.take(), [("pwm", 0), ("dir", 0), ("pwm", 0)]);
+	}
+}
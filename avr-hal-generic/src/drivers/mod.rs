@@ -0,0 +1,18 @@
+//! Drivers for common external devices
+//!
+//! Each driver is gated behind a cargo feature of the same name, so unused drivers don't need to
+//! be compiled.
+#[cfg(feature = "at24")]
+pub mod at24;
+#[cfg(feature = "bme280")]
+pub mod bme280;
+#[cfg(feature = "charlieplex")]
+pub mod charlieplex;
+#[cfg(feature = "hbridge")]
+pub mod hbridge;
+#[cfg(feature = "mpu6050")]
+pub mod mpu6050;
+#[cfg(feature = "mux4051")]
+pub mod mux4051;
+#[cfg(feature = "stepper")]
+pub mod stepper;
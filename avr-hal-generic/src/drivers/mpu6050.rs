@@ -0,0 +1,204 @@
+//! MPU6050 6-axis accelerometer and gyroscope
+//!
+//! # Example
+//! ```ignore
+//! let mut imu = Mpu6050::new(i2c, mpu6050::DEFAULT_ADDRESS);
+//! imu.init(&mut arduino_hal::Delay::new())?;
+//!
+//! let accel = imu.accel_mg()?;
+//! ufmt::uwriteln!(&mut serial, "x: {} mg, y: {} mg, z: {} mg", accel.x, accel.y, accel.z);
+//! ```
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+use crate::util::ByteOrder;
+
+/// I2C address with the `AD0` pin pulled low.
+pub const DEFAULT_ADDRESS: u8 = 0x68;
+/// I2C address with the `AD0` pin pulled high.
+pub const ALTERNATE_ADDRESS: u8 = 0x69;
+
+const REG_SIGNAL_PATH_RESET: u8 = 0x68;
+const REG_GYRO_CONFIG: u8 = 0x1b;
+const REG_ACCEL_CONFIG: u8 = 0x1c;
+const REG_ACCEL_XOUT_H: u8 = 0x3b;
+const REG_GYRO_XOUT_H: u8 = 0x43;
+const REG_PWR_MGMT_1: u8 = 0x6b;
+const REG_WHO_AM_I: u8 = 0x75;
+
+const WHO_AM_I: u8 = 0x68;
+
+/// Full scale range of the accelerometer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AccelRange {
+	G2 = 0,
+	G4 = 1,
+	G8 = 2,
+	G16 = 3,
+}
+
+/// Full scale range of the gyroscope, in degrees per second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum GyroRange {
+	Dps250 = 0,
+	Dps500 = 1,
+	Dps1000 = 2,
+	Dps2000 = 3,
+}
+
+#[derive(ufmt::derive::uDebug, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<E> {
+	/// The I2C transaction failed.
+	I2c(E),
+	/// `WHO_AM_I` returned an unexpected value, so the device is not an MPU6050.
+	WrongDevice(u8),
+}
+
+impl<E> From<E> for Error<E> {
+	fn from(e: E) -> Self {
+		Error::I2c(e)
+	}
+}
+
+/// A value for each of the three axes.
+#[derive(ufmt::derive::uDebug, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Vector3<T> {
+	pub x: T,
+	pub y: T,
+	pub z: T,
+}
+
+/// MPU6050 accelerometer and gyroscope.
+///
+/// The device powers up in sleep mode and only returns zeros until it is woken up, so
+/// [`init()`][Mpu6050::init] has to be called first.
+pub struct Mpu6050<I2C> {
+	i2c: I2C,
+	address: u8,
+	accel_range: AccelRange,
+	gyro_range: GyroRange,
+}
+
+impl<I2C: I2c> Mpu6050<I2C> {
+	pub fn new(i2c: I2C, address: u8) -> Self {
+		Self {
+			i2c,
+			address,
+			accel_range: AccelRange::G2,
+			gyro_range: GyroRange::Dps250,
+		}
+	}
+
+	/// Reset the device, check its identity, and wake it up.
+	///
+	/// This follows the reset sequence from the register map: set `DEVICE_RESET`, reset the
+	/// signal paths, then clear `SLEEP` and select the X gyroscope PLL as clock source, which is
+	/// more stable than the internal oscillator.  Afterwards, the full scale ranges are set to the
+	/// currently selected ones again.  Takes about 200 ms.
+	pub fn init<D: DelayNs>(&mut self, delay: &mut D) -> Result<(), Error<I2C::Error>> {
+		let id = self.read_register(REG_WHO_AM_I)?;
+		if id != WHO_AM_I {
+			return Err(Error::WrongDevice(id));
+		}
+
+		self.write_register(REG_PWR_MGMT_1, 1 << 7)?;
+		delay.delay_ms(100);
+		self.write_register(REG_SIGNAL_PATH_RESET, 0b111)?;
+		delay.delay_ms(100);
+		self.write_register(REG_PWR_MGMT_1, 0x01)?;
+
+		self.set_accel_range(self.accel_range)?;
+		self.set_gyro_range(self.gyro_range)?;
+		Ok(())
+	}
+
+	/// Put the device to sleep or wake it up again.
+	pub fn set_sleep(&mut self, sleep: bool) -> Result<(), I2C::Error> {
+		self.write_register(REG_PWR_MGMT_1, if sleep { 0x41 } else { 0x01 })
+	}
+
+	pub fn set_accel_range(&mut self, range: AccelRange) -> Result<(), I2C::Error> {
+		self.write_register(REG_ACCEL_CONFIG, (range as u8) << 3)?;
+		self.accel_range = range;
+		Ok(())
+	}
+
+	pub fn set_gyro_range(&mut self, range: GyroRange) -> Result<(), I2C::Error> {
+		self.write_register(REG_GYRO_CONFIG, (range as u8) << 3)?;
+		self.gyro_range = range;
+		Ok(())
+	}
+
+	/// Read the raw accelerometer values.
+	pub fn accel_raw(&mut self) -> Result<Vector3<i16>, I2C::Error> {
+		self.read_vector(REG_ACCEL_XOUT_H)
+	}
+
+	/// Read the raw gyroscope values.
+	pub fn gyro_raw(&mut self) -> Result<Vector3<i16>, I2C::Error> {
+		self.read_vector(REG_GYRO_XOUT_H)
+	}
+
+	/// Read the acceleration in milli-g.
+	pub fn accel_mg(&mut self) -> Result<Vector3<i32>, I2C::Error> {
+		// 16384 LSB/g at ±2g, halving with each range step.
+		let lsb_per_g = 16384 >> (self.accel_range as u8);
+		let raw = self.accel_raw()?;
+		let scale = |v: i16| i32::from(v) * 1000 / lsb_per_g;
+		Ok(Vector3 {
+			x: scale(raw.x),
+			y: scale(raw.y),
+			z: scale(raw.z),
+		})
+	}
+
+	/// Read the angular rate in milli-degrees per second.
+	pub fn gyro_mdps(&mut self) -> Result<Vector3<i32>, I2C::Error> {
+		// 131 LSB/(°/s) at ±250°/s, halving with each range step.
+		let lsb_per_10dps: i32 = [1310, 655, 328, 164][self.gyro_range as usize];
+		let raw = self.gyro_raw()?;
+		let scale = |v: i16| i32::from(v) * 10_000 / lsb_per_10dps;
+		Ok(Vector3 {
+			x: scale(raw.x),
+			y: scale(raw.y),
+			z: scale(raw.z),
+		})
+	}
+
+	/// Read the die temperature in hundredths of a degree Celsius.
+	pub fn temperature_centi_celsius(&mut self) -> Result<i16, I2C::Error> {
+		let mut buffer = [0; 2];
+		self.i2c.write_read(self.address, &[REG_ACCEL_XOUT_H + 6], &mut buffer)?;
+		// T = raw / 340 + 36.53 °C
+		let raw = i32::from(ByteOrder::BigEndian.i16_from_bytes(buffer));
+		Ok((raw * 100 / 340 + 3653) as i16)
+	}
+
+	/// Give back the I2C bus.
+	pub fn release(self) -> I2C {
+		self.i2c
+	}
+
+	fn read_vector(&mut self, register: u8) -> Result<Vector3<i16>, I2C::Error> {
+		let mut buffer = [0; 6];
+		self.i2c.write_read(self.address, &[register], &mut buffer)?;
+		let order = ByteOrder::BigEndian;
+		Ok(Vector3 {
+			x: order.i16_from_bytes([buffer[0], buffer[1]]),
+			y: order.i16_from_bytes([buffer[2], buffer[3]]),
+			z: order.i16_from_bytes([buffer[4], buffer[5]]),
+		})
+	}
+
+	fn read_register(&mut self, register: u8) -> Result<u8, I2C::Error> {
+		let mut buffer = [0];
+		self.i2c.write_read(self.address, &[register], &mut buffer)?;
+		Ok(buffer[0])
+	}
+
+	fn write_register(&mut self, register: u8, value: u8) -> Result<(), I2C::Error> {
+		self.i2c.write(self.address, &[register, value])
+	}
+}
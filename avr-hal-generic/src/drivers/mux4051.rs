@@ -0,0 +1,228 @@
+//! 74HC4051 / CD4051 8:1 analog multiplexer
+//!
+//! The 4051 connects one of its eight inputs `Y0`..`Y7` to the common pin `Z`, selected by the
+//! binary number on its three select pins `S0`..`S2`.  With `Z` connected to an ADC pin, one ADC
+//! channel reads eight analog inputs, for the cost of three digital outputs.  Several 4051s can
+//! share the select lines, each on its own ADC channel.
+//!
+//! ```ignore
+//! let mut adc = arduino_hal::Adc::new(dp.ADC, Default::default());
+//! let adc = RefCell::new(adc);
+//! let z = pins.a0.into_analog_input(&mut adc.borrow_mut());
+//!
+//! let mut mux = Mux4051::new(
+//!     pins.d2.into_output(),
+//!     pins.d3.into_output(),
+//!     pins.d4.into_output(),
+//!     AdcInput::new(&adc, z),
+//!     arduino_hal::Delay::new(),
+//! );
+//!
+//! for channel in 0..8 {
+//!     let value = mux.read(channel);
+//! }
+//! ```
+//!
+//! The `INH` (inhibit) pin of the 4051 has to be tied low, and `VEE` to ground for signals
+//! between 0 V and the supply.
+//!
+//! # Settling delay
+//! After the select lines change, the new input needs some time to charge the capacitances
+//! behind the switch: the 4051's own output capacitance and the ADC's sample-and-hold capacitor
+//! (about 14 pF), through the source impedance plus the switch's on-resistance (about 100 Ω at
+//! 5 V).  The switch itself is fast (below 1 µs), so the delay is dominated by the source:
+//!
+//! | Source impedance | Settling to 10 bits |
+//! | --- | --- |
+//! | up to 1 kΩ (e.g. op-amp output) | below 1 µs |
+//! | 10 kΩ (e.g. potentiometer, divider) | about 5 µs |
+//! | 100 kΩ | about 50 µs, or add a capacitor on the input |
+//!
+//! [`Mux4051::new()`] waits [`DEFAULT_SETTLING_US`], which covers the 10 kΩ the AVR's ADC is
+//! specified for anyway.  Without a long enough delay, a reading still partly shows the channel
+//! read before, which looks like crosstalk between the inputs.
+use crate::adc::AnalogInput;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::OutputPin;
+use unwrap_infallible::UnwrapInfallible;
+
+/// Default settling delay after switching channels, in µs.
+pub const DEFAULT_SETTLING_US: u32 = 5;
+
+/// An 8:1 analog multiplexer in front of an analog input.  See the
+/// [module documentation](self).
+pub struct Mux4051<S0, S1, S2, A, D> {
+	s0: S0,
+	s1: S1,
+	s2: S2,
+	input: A,
+	delay: D,
+	settling_us: u32,
+	selected: Option<u8>,
+}
+
+impl<S0, S1, S2, A, D> Mux4051<S0, S1, S2, A, D>
+where
+	S0: OutputPin<Error = core::convert::Infallible>,
+	S1: OutputPin<Error = core::convert::Infallible>,
+	S2: OutputPin<Error = core::convert::Infallible>,
+	A: AnalogInput,
+	D: DelayNs,
+{
+	/// Control a 4051 through its select pins `S0`..`S2` and read its common pin `Z` through
+	/// `input`.
+	pub fn new(s0: S0, s1: S1, s2: S2, input: A, delay: D) -> Self {
+		Self {
+			s0,
+			s1,
+			s2,
+			input,
+			delay,
+			settling_us: DEFAULT_SETTLING_US,
+			selected: None,
+		}
+	}
+
+	/// Change the delay between switching channels and reading the ADC.
+	pub fn set_settling_us(&mut self, settling_us: u32) {
+		self.settling_us = settling_us;
+	}
+
+	/// Connect input `channel` (0 to 7) to the common pin and wait for it to settle.
+	///
+	/// Nothing happens if the channel is already selected.
+	///
+	/// # Panics
+	/// Panics if `channel` is greater than 7.
+	pub fn select(&mut self, channel: u8) {
+		assert!(channel < 8, "the 4051 has 8 channels");
+		if self.selected == Some(channel) {
+			return;
+		}
+		set_pin(&mut self.s0, channel & 0b001 != 0);
+		set_pin(&mut self.s1, channel & 0b010 != 0);
+		set_pin(&mut self.s2, channel & 0b100 != 0);
+		self.selected = Some(channel);
+		self.delay.delay_us(self.settling_us);
+	}
+
+	/// Select input `channel` (0 to 7) and take a reading.
+	///
+	/// # Panics
+	/// Panics if `channel` is greater than 7.
+	pub fn read(&mut self, channel: u8) -> u16 {
+		self.select(channel);
+		self.input.read()
+	}
+
+	/// The currently selected channel, `None` before the first one was selected.
+	pub fn selected(&self) -> Option<u8> {
+		self.selected
+	}
+
+	/// The reading at full scale of the underlying input.
+	pub fn max_value(&self) -> u16 {
+		self.input.max_value()
+	}
+
+	/// Give back the select pins, the input, and the delay.
+	pub fn release(self) -> (S0, S1, S2, A, D) {
+		(self.s0, self.s1, self.s2, self.input, self.delay)
+	}
+}
+
+fn set_pin<P: OutputPin<Error = core::convert::Infallible>>(pin: &mut P, high: bool) {
+	if high {
+		pin.set_high().unwrap_infallible();
+	} else {
+		pin.set_low().unwrap_infallible();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::cell::Cell;
+	use std::rc::Rc;
+	use std::vec::Vec;
+
+	/// One select line, setting its bit of the shared channel number.
+	struct MockSelect(u8, Rc<Cell<u8>>);
+
+	impl embedded_hal::digital::ErrorType for MockSelect {
+		type Error = core::convert::Infallible;
+	}
+
+	impl OutputPin for MockSelect {
+		fn set_low(&mut self) -> Result<(), Self::Error> {
+			self.1.set(self.1.get() & !self.0);
+			Ok(())
+		}
+
+		fn set_high(&mut self) -> Result<(), Self::Error> {
+			self.1.set(self.1.get() | self.0);
+			Ok(())
+		}
+	}
+
+	/// Reads `100 * channel` for the channel on the select lines.
+	struct MockAdc(Rc<Cell<u8>>);
+
+	impl AnalogInput for MockAdc {
+		fn max_value(&self) -> u16 {
+			1023
+		}
+
+		fn read(&mut self) -> u16 {
+			100 * u16::from(self.0.get())
+		}
+	}
+
+	#[derive(Default)]
+	struct MockDelay(Vec<u32>);
+
+	impl DelayNs for MockDelay {
+		fn delay_ns(&mut self, ns: u32) {
+			self.0.push(ns / 1000);
+		}
+	}
+
+	fn mux() -> Mux4051<MockSelect, MockSelect, MockSelect, MockAdc, MockDelay> {
+		let lines = Rc::new(Cell::new(0));
+		Mux4051::new(
+			MockSelect(0b001, lines.clone()),
+			MockSelect(0b010, lines.clone()),
+			MockSelect(0b100, lines.clone()),
+			MockAdc(lines),
+			MockDelay::default(),
+		)
+	}
+
+	#[test]
+	fn reads_selected_channel() {
+		let mut mux = mux();
+		assert_eq!(mux.selected(), None);
+		assert_eq!(mux.max_value(), 1023);
+		for channel in (0..8).rev() {
+			assert_eq!(mux.read(channel), 100 * u16::from(channel));
+		}
+		assert_eq!(mux.selected(), Some(0));
+	}
+
+	#[test]
+	fn settles_only_after_switching() {
+		let mut mux = mux();
+		mux.read(3);
+		mux.read(3);
+		mux.set_settling_us(50);
+		mux.read(5);
+		let (.., delay) = mux.release();
+		assert_eq!(delay.0, [DEFAULT_SETTLING_US, 50]);
+	}
+
+	#[test]
+	#[should_panic(expected = "the 4051 has 8 channels")]
+	fn channel_out_of_range() {
+		mux().select(8);
+	}
+}
@@ -0,0 +1,296 @@
+//! Stepper motors on step/dir drivers (A4988, DRV8825, TMC2208, ...)
+//!
+//! These drivers take one pulse on `STEP` per (micro)step, in the direction selected by `DIR`.
+//! A motor which is started at full speed or stopped abruptly loses steps, so [`Stepper`]
+//! moves with a trapezoidal speed profile: it accelerates at a constant rate up to the maximum
+//! speed, cruises, and decelerates to stand still exactly at the target.  Short moves never
+//! reach the maximum speed and decelerate right after accelerating.
+//!
+//! Steps are timed by a timer: [`Stepper::step()`] emits one step pulse and returns the time
+//! until the next step is due, which is used to schedule the next call, e.g. with a
+//! [one-shot timer](crate::oneshot):
+//!
+//! ```ignore
+//! static STEPPER: Mutex<RefCell<Option<Stepper<Pin<Output, D2>, Pin<Output, D5>>>>> =
+//!     Mutex::new(RefCell::new(None));
+//!
+//! fn next_step() {
+//!     // If the step was due before the timer was armed, take it right away, once the borrows
+//!     // are released again.
+//!     while avr_device::interrupt::free(|cs| {
+//!         let mut stepper = STEPPER.borrow(cs).borrow_mut();
+//!         let Some(delay_us) = stepper.as_mut().unwrap().step() else {
+//!             return false;
+//!         };
+//!         let mut oneshot = ONESHOT.borrow(cs).borrow_mut();
+//!         let result = oneshot.as_mut().unwrap().schedule(delay_us, next_step);
+//!         result == Err(ScheduleError::Missed)
+//!     }) {}
+//! }
+//!
+//! // 2000 steps forward, at most 800 steps/s, accelerating at 1600 steps/s²
+//! avr_device::interrupt::free(|cs| {
+//!     STEPPER.borrow(cs).borrow_mut().as_mut().unwrap().move_to(2000, 800, 1600);
+//! });
+//! next_step();
+//! ```
+//!
+//! # Step timing
+//! The delays follow the approximation by David Austin ("Generate stepper-motor speed profiles
+//! in real time", 2005): the first delay is `c0 = 0.676 * sqrt(2 / accel)`, and each following
+//! one is derived from the previous one with `c(n) = c(n-1) - 2 * c(n-1) / (4n + 1)`, which needs
+//! one division per step instead of a square root.  Deceleration runs the same recurrence
+//! backwards.  The delays are kept in 1/256 µs internally so rounding errors don't accumulate.
+//!
+//! Computing the next delay takes a few hundred CPU cycles, mostly for the 32-bit division, which
+//! limits the step rate to a few thousand steps per second at 16 MHz.
+use embedded_hal::digital::OutputPin;
+use unwrap_infallible::UnwrapInfallible;
+
+/// Fractional bits of the internal step delay.
+const FRACTION_BITS: u32 = 8;
+
+/// A stepper motor on a step/dir driver.  See the [module documentation](self).
+pub struct Stepper<STEP, DIR> {
+	step: STEP,
+	dir: DIR,
+	position: i32,
+	forward: bool,
+	/// Steps of the current move emitted so far.
+	done: u32,
+	total: u32,
+	/// Steps spent accelerating, mirrored by the same number of decelerating steps.
+	ramp_steps: u32,
+	/// Current delay between steps, in µs with `FRACTION_BITS` fractional bits.
+	delay: u32,
+	min_delay: u32,
+}
+
+impl<STEP, DIR> Stepper<STEP, DIR>
+where
+	STEP: OutputPin<Error = core::convert::Infallible>,
+	DIR: OutputPin<Error = core::convert::Infallible>,
+{
+	/// Drive a stepper through its `STEP` and `DIR` pins.  The motor is assumed to be at
+	/// position 0.
+	pub fn new(mut step: STEP, dir: DIR) -> Self {
+		step.set_low().unwrap_infallible();
+		Self {
+			step,
+			dir,
+			position: 0,
+			forward: true,
+			done: 0,
+			total: 0,
+			ramp_steps: 0,
+			delay: 0,
+			min_delay: 0,
+		}
+	}
+
+	/// Start moving to the absolute position `target` (in steps), with a maximum speed of
+	/// `max_speed` steps/s and an acceleration of `accel` steps/s².
+	///
+	/// The motor has to be standing still; a move in progress is replaced without deceleration.
+	/// The first step is emitted by the next call to [`step()`][Stepper::step].
+	pub fn move_to(&mut self, target: i32, max_speed: u32, accel: u32) {
+		let max_speed = max_speed.max(1);
+		let accel = accel.max(1);
+
+		self.forward = target >= self.position;
+		if self.forward {
+			self.dir.set_high().unwrap_infallible();
+		} else {
+			self.dir.set_low().unwrap_infallible();
+		}
+		self.total = target.abs_diff(self.position);
+		self.done = 0;
+
+		// Steps needed to reach the maximum speed: v² / 2a
+		let ramp_to_max =
+			u64::from(max_speed) * u64::from(max_speed) / (2 * u64::from(accel));
+		self.ramp_steps = (ramp_to_max as u32).min(self.total / 2);
+		self.min_delay = (1_000_000 << FRACTION_BITS) / max_speed;
+		self.delay = first_delay(accel).max(self.min_delay);
+	}
+
+	/// Emit one step and return the delay in µs until the next one is due, or `None` if the
+	/// target was reached with this step (or no move is in progress).
+	pub fn step(&mut self) -> Option<u32> {
+		if self.done >= self.total {
+			return None;
+		}
+
+		self.step.set_high().unwrap_infallible();
+		// The drivers need a pulse of at least 1 µs (A4988) to 1.9 µs (DRV8825); this is 2 µs at
+		// up to 20 MHz.
+		avr_device::asm::delay_cycles(40);
+		self.step.set_low().unwrap_infallible();
+
+		self.advance()
+	}
+
+	/// Count the step which was just emitted and compute the delay until the next one.
+	fn advance(&mut self) -> Option<u32> {
+		self.done += 1;
+		if self.forward {
+			self.position += 1;
+		} else {
+			self.position -= 1;
+		}
+
+		let remaining = self.total - self.done;
+		if remaining == 0 {
+			return None;
+		}
+		// Each interval is on the ramp as far as it is from the nearer end of the move, so the
+		// profile is symmetric also for moves which don't reach the maximum speed.
+		let level = self.done.min(remaining);
+		let previous = (self.done - 1).min(remaining + 1);
+		if level > self.ramp_steps {
+			self.delay = self.min_delay;
+		} else if level > previous {
+			// Accelerating: the first delay is used as is, then the recurrence shortens it.
+			if level > 1 {
+				let n = level - 1;
+				self.delay -= 2 * self.delay / (4 * n + 1);
+			}
+			self.delay = self.delay.max(self.min_delay);
+		} else if level < previous {
+			// Decelerating: the recurrence run backwards, ending at the first delay.
+			self.delay += 2 * self.delay / (4 * level - 1);
+		}
+		// Otherwise, this is the middle of a move with an odd number of steps, where the two
+		// middle intervals are equally long.
+		Some(self.delay >> FRACTION_BITS)
+	}
+
+	/// Whether a move is in progress.
+	pub fn is_moving(&self) -> bool {
+		self.done < self.total
+	}
+
+	/// The current position in steps.
+	pub fn position(&self) -> i32 {
+		self.position
+	}
+
+	/// Redefine the current position, e.g. after homing against an end stop.
+	pub fn set_position(&mut self, position: i32) {
+		self.position = position;
+	}
+
+	/// Abort the current move immediately, without deceleration.
+	pub fn stop(&mut self) {
+		self.total = self.done;
+	}
+
+	/// Return the `STEP` and `DIR` pins.
+	pub fn release(self) -> (STEP, DIR) {
+		(self.step, self.dir)
+	}
+}
+
+/// Delay before the first step from standstill, `0.676 * sqrt(2 / accel)` seconds, in µs with
+/// `FRACTION_BITS` fractional bits.
+fn first_delay(accel: u32) -> u32 {
+	// 0.676 * sqrt(2) = 0.956; isqrt(accel << 16) is sqrt(accel) with 8 fractional bits.
+	let numerator = 956_000u64 << (FRACTION_BITS + 8);
+	let delay = numerator / u64::from(isqrt(u64::from(accel) << 16).max(1));
+	delay.min(u64::from(u32::MAX)) as u32
+}
+
+/// Integer square root, rounded down.
+fn isqrt(value: u64) -> u32 {
+	let mut result = 0u64;
+	let mut bit = 1u64 << 62;
+	let mut value = value;
+	while bit > value {
+		bit >>= 2;
+	}
+	while bit != 0 {
+		if value >= result + bit {
+			value -= result + bit;
+			result = (result >> 1) + bit;
+		} else {
+			result >>= 1;
+		}
+		bit >>= 2;
+	}
+	result as u32
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::vec::Vec;
+
+	/// A pin which only remembers its level.
+	#[derive(Default)]
+	struct MockPin(bool);
+
+	impl embedded_hal::digital::ErrorType for MockPin {
+		type Error = core::convert::Infallible;
+	}
+
+	impl OutputPin for MockPin {
+		fn set_low(&mut self) -> Result<(), Self::Error> {
+			self.0 = false;
+			Ok(())
+		}
+
+		fn set_high(&mut self) -> Result<(), Self::Error> {
+			self.0 = true;
+			Ok(())
+		}
+	}
+
+	/// The delays of a whole move, the pulses themselves can't be generated on the host.
+	fn intervals(stepper: &mut Stepper<MockPin, MockPin>) -> Vec<u32> {
+		core::iter::from_fn(|| stepper.is_moving().then(|| stepper.advance()))
+			.flatten()
+			.collect()
+	}
+
+	#[test]
+	fn short_move_is_symmetric() {
+		let mut stepper = Stepper::new(MockPin::default(), MockPin::default());
+		stepper.move_to(4, 800, 1600);
+		assert_eq!(intervals(&mut stepper), [23900, 14340, 23900]);
+		assert_eq!(stepper.position(), 4);
+		assert!(stepper.dir.0);
+	}
+
+	#[test]
+	fn odd_middle_step() {
+		let mut stepper = Stepper::new(MockPin::default(), MockPin::default());
+		stepper.move_to(-5, 800, 1600);
+		assert_eq!(intervals(&mut stepper), [23900, 14340, 14340, 23900]);
+		assert_eq!(stepper.position(), -5);
+		assert!(!stepper.dir.0);
+	}
+
+	#[test]
+	fn long_move_cruises() {
+		let mut stepper = Stepper::new(MockPin::default(), MockPin::default());
+		stepper.move_to(2000, 800, 1600);
+		let intervals = intervals(&mut stepper);
+		assert_eq!(intervals.len(), 1999);
+		assert_eq!(intervals[0], 23900);
+		// 200 steps to reach 800 steps/s
+		assert!(intervals[200..1799].iter().all(|&delay| delay == 1250));
+		assert!(intervals.windows(2).take(199).all(|w| w[0] >= w[1]));
+		assert!(intervals.windows(2).skip(1799).all(|w| w[0] <= w[1]));
+		// The backwards recurrence starts at the cruising speed, so the last interval is
+		// close to the first one but not necessarily equal.
+		assert!(intervals[1998].abs_diff(23900) < 23900 / 20);
+	}
+
+	#[test]
+	fn single_step() {
+		let mut stepper = Stepper::new(MockPin::default(), MockPin::default());
+		stepper.move_to(1, 800, 1600);
+		assert_eq!(intervals(&mut stepper), []);
+		assert_eq!(stepper.position(), 1);
+	}
+}
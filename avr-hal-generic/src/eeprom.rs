@@ -1,10 +1,94 @@
 //!HAL abstraction for EEPROM
 //!
+//! # Programming modes
+//! Most AVRs can program an EEPROM cell in three ways, selected by the `EEPM` bits: an atomic
+//! erase and write (3.4 ms), erase only, and write only (1.8 ms each).  Erasing sets all bits to
+//! `1`, writing can only clear bits to `0`.  [`Eeprom::write()`] reads each cell first and picks
+//! the cheapest mode that yields the new value, skipping cells which already hold it.
+//!
+//! With [`Eeprom::write_with_mode()`], the mode can be chosen explicitly instead, e.g. to write
+//! a region which was erased beforehand with [`WriteMode::WriteOnly`].  This is only available on
+//! devices with `EEPM` (not on the ATmega8, ATmega16, ATmega32A and ATmega128A).
+//!
+//! # Wear leveling
+//! Each EEPROM cell survives about 100 000 erase/write cycles.  A value which is updated every
+//! minute at a fixed address wears out its cells in about ten weeks.  [`EepromRing`] spreads the
+//! updates over a region: every write goes to the next slot of a ring, so each cell is only
+//! written once per lap.  A region of `n` slots lasts `n` times longer.
+//!
+//! # Configuration with integrity check
+//! A configuration which is written while the supply fails is left half-old and half-new.
+//! [`Config`] stores a value together with a CRC-16, so such a corrupted configuration is
+//! detected on the next boot, and [`Config::load_or_default()`] falls back to (and stores) the
+//! defaults instead of running with garbage:
+//!
+//! ```ignore
+//! #[derive(Default)]
+//! struct Settings {
+//!     baudrate: u32,
+//!     address: u8,
+//! }
+//!
+//! impl Persist<5> for Settings {
+//!     fn to_bytes(&self) -> [u8; 5] {
+//!         let b = self.baudrate.to_le_bytes();
+//!         [b[0], b[1], b[2], b[3], self.address]
+//!     }
+//!
+//!     fn from_bytes(bytes: &[u8; 5]) -> Option<Self> {
+//!         let baudrate = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+//!         Some(Settings { baudrate, address: bytes[4] })
+//!     }
+//! }
+//!
+//! const SETTINGS: Config<Settings, 5> = Config::new(0);
+//!
+//! let settings = SETTINGS.load_or_default(&mut eeprom)?;
+//! ```
+//!
+//! An erased EEPROM, as on the first boot after flashing with the EEPROM erased, reads as all
+//! `0xFF`, which is no valid CRC either, so the first boot returns and stores the defaults just
+//! the same.  [`Config::load()`] tells the cases apart, e.g. to log a corruption.
 use core::marker;
 
+use crate::util::crc16_modbus;
+
 #[derive(ufmt::derive::uDebug, Debug)]
 pub struct OutOfBoundsError;
 
+/// Error returned by the writes of a [`GuardedEeprom`].
+#[derive(ufmt::derive::uDebug, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Error {
+	/// The access would go past the end of the EEPROM.
+	OutOfBounds,
+	/// The supply voltage is below the configured threshold; nothing was written.
+	LowVoltage,
+}
+
+impl From<OutOfBoundsError> for Error {
+	fn from(_: OutOfBoundsError) -> Self {
+		Error::OutOfBounds
+	}
+}
+
+/// Source of supply voltage measurements for a [`GuardedEeprom`].
+///
+/// Usually this is implemented by measuring the internal bandgap reference against AVcc with the
+/// ADC (`Vcc = 1.1V * 1024 / reading`).  Any closure returning millivolts works as well.
+pub trait SupplyVoltage {
+	/// Measure the current supply voltage in millivolts.
+	fn supply_millivolts(&mut self) -> u16;
+}
+
+impl<F> SupplyVoltage for F
+where
+	F: FnMut() -> u16,
+{
+	fn supply_millivolts(&mut self) -> u16 {
+		self()
+	}
+}
+
 /// Internal trait for low-level EEPROM peripherals.
 ///
 /// This trait defines the common interface for all EEPROM peripheral variants.
@@ -25,6 +109,28 @@ pub trait EepromOps<H> {
 	fn raw_erase_byte(&mut self, address: u16);
 }
 
+/// Programming mode for [`Eeprom::write_with_mode()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WriteMode {
+	/// Erase and write in one atomic operation (3.4 ms).  Always stores the new value.
+	#[default]
+	EraseWrite,
+	/// Only program bits to `0`, without erasing (1.8 ms).
+	///
+	/// The cell must have been erased (`0xff`) before, otherwise the result is the bitwise AND of
+	/// the old and the new value.
+	WriteOnly,
+}
+
+/// Internal trait for EEPROM peripherals which support selecting the programming mode.
+pub trait EepromWriteModeOps<H>: EepromOps<H> {
+	/// Program a single byte at offset `address` in the given mode, without reading it first.
+	/// Does not do a bounds check.
+	///
+	/// **Warning**: This is a low-level method and should not be called directly from user code.
+	fn raw_program_byte(&mut self, address: u16, data: u8, mode: WriteMode);
+}
+
 pub struct Eeprom<H, EEPROM> {
 	p: EEPROM,
 	_h: marker::PhantomData<H>,
@@ -98,6 +204,447 @@ where
 
 		Ok(())
 	}
+
+	/// Only allow writes while the supply voltage is at or above `threshold_mv`.
+	///
+	/// Writing the EEPROM during a brown-out can leave the affected cells corrupted.  The returned
+	/// [`GuardedEeprom`] measures the supply using `vcc` once before each write or erase and
+	/// refuses it with [`Error::LowVoltage`] if the voltage is too low.  The plain `Eeprom` methods
+	/// stay unchecked, so code which does not opt in pays nothing for the measurement.
+	///
+	/// # Example
+	/// ```
+	/// let mut adc = Adc::new(dp.ADC, Default::default());
+	/// let mut eeprom = Eeprom::new(dp.EEPROM).with_voltage_guard(
+	///     || (1100u32 * 1024 / adc.read_blocking(&channel::Vbg).max(1) as u32) as u16,
+	///     4300,
+	/// );
+	///
+	/// eeprom.write_byte(0, 0x42)?;
+	/// ```
+	pub fn with_voltage_guard<V>(self, vcc: V, threshold_mv: u16) -> GuardedEeprom<H, EEPROM, V>
+	where
+		V: SupplyVoltage,
+	{
+		GuardedEeprom {
+			eeprom: self,
+			vcc,
+			threshold_mv,
+		}
+	}
+}
+
+impl<H, EEPROM> Eeprom<H, EEPROM>
+where
+	EEPROM: EepromWriteModeOps<H>,
+{
+	/// Write `buf` at `offset`, programming every byte in the given `mode`.
+	///
+	/// Unlike [`write()`][Self::write], the old contents are not read and every byte is
+	/// programmed, even if it already holds the new value.  See [`WriteMode`] for the requirement
+	/// of [`WriteMode::WriteOnly`].
+	pub fn write_with_mode(
+		&mut self,
+		offset: u16,
+		buf: &[u8],
+		mode: WriteMode,
+	) -> Result<(), OutOfBoundsError> {
+		if buf.len() as u16 + offset > Self::CAPACITY {
+			return Err(OutOfBoundsError);
+		}
+
+		for (i, byte) in buf.iter().enumerate() {
+			self.p.raw_program_byte(offset + i as u16, *byte, mode)
+		}
+		Ok(())
+	}
+}
+
+/// EEPROM wrapper which refuses to write while the supply voltage is too low.
+///
+/// Created by [`Eeprom::with_voltage_guard`].
+pub struct GuardedEeprom<H, EEPROM, V> {
+	eeprom: Eeprom<H, EEPROM>,
+	vcc: V,
+	threshold_mv: u16,
+}
+
+impl<H, EEPROM, V> GuardedEeprom<H, EEPROM, V>
+where
+	EEPROM: EepromOps<H>,
+	V: SupplyVoltage,
+{
+	/// Currently configured threshold in millivolts.
+	#[inline]
+	pub fn threshold(&self) -> u16 {
+		self.threshold_mv
+	}
+
+	/// Change the threshold below which writes are refused.
+	#[inline]
+	pub fn set_threshold(&mut self, threshold_mv: u16) {
+		self.threshold_mv = threshold_mv;
+	}
+
+	/// Measure the supply voltage and check it against the threshold.
+	pub fn check_supply(&mut self) -> Result<(), Error> {
+		if self.vcc.supply_millivolts() < self.threshold_mv {
+			Err(Error::LowVoltage)
+		} else {
+			Ok(())
+		}
+	}
+
+	#[inline]
+	pub fn capacity(&self) -> u16 {
+		self.eeprom.capacity()
+	}
+
+	#[inline]
+	pub fn read_byte(&self, offset: u16) -> u8 {
+		self.eeprom.read_byte(offset)
+	}
+
+	pub fn read(&self, offset: u16, buf: &mut [u8]) -> Result<(), Error> {
+		Ok(self.eeprom.read(offset, buf)?)
+	}
+
+	pub fn write_byte(&mut self, offset: u16, data: u8) -> Result<(), Error> {
+		if offset >= Eeprom::<H, EEPROM>::CAPACITY {
+			return Err(Error::OutOfBounds);
+		}
+		self.check_supply()?;
+		self.eeprom.write_byte(offset, data);
+		Ok(())
+	}
+
+	pub fn erase_byte(&mut self, offset: u16) -> Result<(), Error> {
+		if offset >= Eeprom::<H, EEPROM>::CAPACITY {
+			return Err(Error::OutOfBounds);
+		}
+		self.check_supply()?;
+		self.eeprom.erase_byte(offset);
+		Ok(())
+	}
+
+	/// Write `buf` starting at `offset`.
+	///
+	/// The supply is only measured once, before the first byte is written.
+	pub fn write(&mut self, offset: u16, buf: &[u8]) -> Result<(), Error> {
+		if buf.len() as u16 + offset > Eeprom::<H, EEPROM>::CAPACITY {
+			return Err(Error::OutOfBounds);
+		}
+		self.check_supply()?;
+		Ok(self.eeprom.write(offset, buf)?)
+	}
+
+	pub fn erase(&mut self, from: u16, to: u16) -> Result<(), Error> {
+		if to > Eeprom::<H, EEPROM>::CAPACITY || from > to {
+			return Err(Error::OutOfBounds);
+		}
+		self.check_supply()?;
+		Ok(self.eeprom.erase(from, to)?)
+	}
+
+	/// Remove the guard and return the plain EEPROM and the voltage source.
+	pub fn release(self) -> (Eeprom<H, EEPROM>, V) {
+		(self.eeprom, self.vcc)
+	}
+}
+
+/// Conversion of a value to and from the `SIZE` bytes stored by a [`Config`].
+pub trait Persist<const SIZE: usize>: Sized {
+	/// Serialize the value.
+	fn to_bytes(&self) -> [u8; SIZE];
+
+	/// Deserialize a value, or return `None` if the bytes don't form a valid one, e.g. an
+	/// out-of-range enum discriminant.
+	fn from_bytes(bytes: &[u8; SIZE]) -> Option<Self>;
+}
+
+/// Reasons a [`Config`] could not be loaded.
+#[derive(ufmt::derive::uDebug, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadError {
+	/// The EEPROM region is erased, nothing was stored yet.
+	Blank,
+	/// The CRC doesn't match, e.g. because a write was interrupted.
+	Corrupted,
+	/// The CRC matches, but [`Persist::from_bytes()`] rejected the contents.
+	Invalid,
+	/// The region goes past the end of the EEPROM.
+	OutOfBounds,
+}
+
+impl From<OutOfBoundsError> for LoadError {
+	fn from(_: OutOfBoundsError) -> Self {
+		LoadError::OutOfBounds
+	}
+}
+
+/// A value of type `T`, stored at a fixed EEPROM offset as `SIZE` bytes followed by a CRC-16.
+/// See the [module documentation](self#configuration-with-integrity-check).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config<T, const SIZE: usize> {
+	offset: u16,
+	_value: marker::PhantomData<T>,
+}
+
+impl<T: Persist<SIZE>, const SIZE: usize> Config<T, SIZE> {
+	/// Number of EEPROM bytes used, i.e. the value and the CRC.
+	pub const LEN: u16 = SIZE as u16 + 2;
+
+	/// Store the configuration at `offset`.  It occupies [`LEN`][Self::LEN] bytes.
+	pub const fn new(offset: u16) -> Self {
+		Self {
+			offset,
+			_value: marker::PhantomData,
+		}
+	}
+
+	/// Read and check the stored value.
+	pub fn load<H, EEPROM: EepromOps<H>>(
+		&self,
+		eeprom: &Eeprom<H, EEPROM>,
+	) -> Result<T, LoadError> {
+		let mut bytes = [0; SIZE];
+		let mut crc = [0; 2];
+		eeprom.read(self.offset, &mut bytes)?;
+		eeprom.read(self.offset + SIZE as u16, &mut crc)?;
+
+		if crc16_modbus(&bytes) != u16::from_le_bytes(crc) {
+			let blank = bytes.iter().chain(&crc).all(|&byte| byte == 0xff);
+			return Err(if blank {
+				LoadError::Blank
+			} else {
+				LoadError::Corrupted
+			});
+		}
+		T::from_bytes(&bytes).ok_or(LoadError::Invalid)
+	}
+
+	/// Store `value` together with its CRC.
+	///
+	/// If the write is interrupted, e.g. by a power failure, the next [`load()`][Self::load]
+	/// reports [`LoadError::Corrupted`] rather than returning a mix of old and new contents.
+	pub fn store<H, EEPROM: EepromOps<H>>(
+		&self,
+		eeprom: &mut Eeprom<H, EEPROM>,
+		value: &T,
+	) -> Result<(), OutOfBoundsError> {
+		if self.offset + Self::LEN > Eeprom::<H, EEPROM>::CAPACITY {
+			return Err(OutOfBoundsError);
+		}
+		let bytes = value.to_bytes();
+		eeprom.write(self.offset, &bytes)?;
+		eeprom.write(
+			self.offset + SIZE as u16,
+			&crc16_modbus(&bytes).to_le_bytes(),
+		)
+	}
+
+	/// Load the stored value, or store and return `T::default()` if there is no valid one.
+	///
+	/// Meant to be called once at boot.  This only fails if the region doesn't fit the EEPROM.
+	pub fn load_or_default<H, EEPROM: EepromOps<H>>(
+		&self,
+		eeprom: &mut Eeprom<H, EEPROM>,
+	) -> Result<T, OutOfBoundsError>
+	where
+		T: Default,
+	{
+		match self.load(eeprom) {
+			Ok(value) => Ok(value),
+			Err(LoadError::OutOfBounds) => Err(OutOfBoundsError),
+			Err(_) => {
+				let value = T::default();
+				self.store(eeprom, &value)?;
+				Ok(value)
+			}
+		}
+	}
+}
+
+/// Marker of a slot which does not hold a value, as left by an erase.
+const RING_EMPTY: u8 = 0xff;
+
+/// A value of `SIZE` bytes, stored wear-leveled in a region of the EEPROM.  See the
+/// [module documentation](self#wear-leveling).
+///
+/// The region is divided into slots of one sequence byte followed by the value.  Every write
+/// goes to the slot after the current one with the next sequence number, and the current slot is
+/// the one whose successor doesn't continue the sequence.  The sequence byte is written last, so
+/// a write interrupted by a reset leaves the previous value in place.
+///
+/// The ring keeps no state in RAM; every access scans the sequence bytes (one byte per slot).
+///
+/// ```ignore
+/// // Bytes 64..320 hold a 4-byte odometer in 51 slots
+/// const ODOMETER: EepromRing<4> = EepromRing::new(64, 256);
+///
+/// let mut distance = ODOMETER.read(&eeprom).map_or(0, u32::from_le_bytes);
+/// distance += 1;
+/// ODOMETER.write(&mut eeprom, &distance.to_le_bytes())?;
+/// ```
+///
+/// # Compaction
+/// After many laps, or after the region was used with a different layout, the ring can be
+/// reset to a single slot holding the current value with [`compact()`][EepromRing::compact].
+/// This is a maintenance operation, e.g. on a maintenance boot or after a firmware update which
+/// changed the layout, not something to do on every start: it erases the sequence byte of every
+/// slot which is not already empty, which takes up to 0.5 s and costs those cells as much wear
+/// as a full lap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EepromRing<const SIZE: usize> {
+	start: u16,
+	slots: u16,
+}
+
+impl<const SIZE: usize> EepromRing<SIZE> {
+	/// Use the `len` bytes starting at `start` for the ring.
+	///
+	/// At most 254 slots are used, as the sequence numbers wrap around at 255; the rest of the
+	/// region is left alone.
+	///
+	/// # Panics
+	/// Panics if the region doesn't fit two slots.
+	pub const fn new(start: u16, len: u16) -> Self {
+		let slots = len as usize / (SIZE + 1);
+		assert!(slots >= 2, "EEPROM ring region too small");
+		Self {
+			start,
+			slots: if slots > 254 { 254 } else { slots as u16 },
+		}
+	}
+
+	/// Number of slots in the ring, i.e. the factor by which it extends the EEPROM's lifetime.
+	pub const fn slots(&self) -> u16 {
+		self.slots
+	}
+
+	/// Read the current value, or `None` if none was written yet (the region is erased).
+	pub fn read<H, EEPROM: EepromOps<H>>(
+		&self,
+		eeprom: &Eeprom<H, EEPROM>,
+	) -> Result<Option<[u8; SIZE]>, OutOfBoundsError> {
+		self.check_bounds(eeprom)?;
+		Ok(self.current(eeprom).map(|(slot, _)| {
+			let mut value = [0; SIZE];
+			eeprom.read(self.slot_offset(slot) + 1, &mut value).ok();
+			value
+		}))
+	}
+
+	/// Write `value` to the next slot.
+	pub fn write<H, EEPROM: EepromOps<H>>(
+		&self,
+		eeprom: &mut Eeprom<H, EEPROM>,
+		value: &[u8; SIZE],
+	) -> Result<(), OutOfBoundsError> {
+		self.check_bounds(eeprom)?;
+		let (slot, sequence) = match self.current(eeprom) {
+			Some((slot, sequence)) => {
+				((slot + 1) % self.slots, next_sequence(sequence))
+			}
+			None => (0, 0),
+		};
+		let offset = self.slot_offset(slot);
+		// Invalidate the slot first: its old contents could continue the sequence, e.g. in a
+		// region which held other data before, and make the half-written value current.
+		eeprom.erase_byte(offset);
+		eeprom.write(offset + 1, value)?;
+		eeprom.write_byte(offset, sequence);
+		Ok(())
+	}
+
+	/// Move the current value to the first slot and invalidate all others, restarting the wear
+	/// cycle.  See [Compaction](EepromRing#compaction).
+	///
+	/// The value survives a reset at any point of the compaction.  Nothing happens if the ring
+	/// is empty.
+	pub fn compact<H, EEPROM: EepromOps<H>>(
+		&self,
+		eeprom: &mut Eeprom<H, EEPROM>,
+	) -> Result<(), OutOfBoundsError> {
+		self.check_bounds(eeprom)?;
+		let (current, sequence) = match self.current(eeprom) {
+			Some(current) => current,
+			None => return Ok(()),
+		};
+
+		// The current slot is the first one (in slot order) whose successor doesn't continue the
+		// sequence.  Invalidating the slots in ascending order, starting with slot 0, never
+		// creates such a break in front of it, so it stays the current one throughout.
+		for slot in 0..self.slots {
+			if slot != current {
+				self.invalidate(eeprom, slot);
+			}
+		}
+		if current != 0 {
+			let mut value = [0; SIZE];
+			let from = self.slot_offset(current);
+			eeprom.read(from + 1, &mut value)?;
+			eeprom.write(self.slot_offset(0) + 1, &value)?;
+			// From here on, both slots hold the value until the old one is invalidated.
+			eeprom.write_byte(self.slot_offset(0), next_sequence(sequence));
+			eeprom.erase_byte(from);
+		}
+		Ok(())
+	}
+
+	/// Invalidate all slots, so [`read()`][EepromRing::read] returns `None`.
+	pub fn clear<H, EEPROM: EepromOps<H>>(
+		&self,
+		eeprom: &mut Eeprom<H, EEPROM>,
+	) -> Result<(), OutOfBoundsError> {
+		self.check_bounds(eeprom)?;
+		for slot in 0..self.slots {
+			self.invalidate(eeprom, slot);
+		}
+		Ok(())
+	}
+
+	/// The current slot and its sequence number.
+	fn current<H, EEPROM: EepromOps<H>>(
+		&self,
+		eeprom: &Eeprom<H, EEPROM>,
+	) -> Option<(u16, u8)> {
+		(0..self.slots).find_map(|slot| {
+			let sequence = eeprom.read_byte(self.slot_offset(slot));
+			let next = eeprom.read_byte(self.slot_offset((slot + 1) % self.slots));
+			(sequence != RING_EMPTY && next != next_sequence(sequence))
+				.then_some((slot, sequence))
+		})
+	}
+
+	fn invalidate<H, EEPROM: EepromOps<H>>(&self, eeprom: &mut Eeprom<H, EEPROM>, slot: u16) {
+		let offset = self.slot_offset(slot);
+		if eeprom.read_byte(offset) != RING_EMPTY {
+			eeprom.erase_byte(offset);
+		}
+	}
+
+	fn slot_offset(&self, slot: u16) -> u16 {
+		self.start + slot * (SIZE as u16 + 1)
+	}
+
+	fn check_bounds<H, EEPROM: EepromOps<H>>(
+		&self,
+		_eeprom: &Eeprom<H, EEPROM>,
+	) -> Result<(), OutOfBoundsError> {
+		if self.slot_offset(self.slots) > Eeprom::<H, EEPROM>::CAPACITY {
+			return Err(OutOfBoundsError);
+		}
+		Ok(())
+	}
+}
+
+/// Sequence number following `sequence`, skipping [`RING_EMPTY`].
+fn next_sequence(sequence: u8) -> u8 {
+	if sequence >= RING_EMPTY - 1 {
+		0
+	} else {
+		sequence + 1
+	}
 }
 
 impl<H, EEPROM> embedded_storage::nor_flash::ReadNorFlash for Eeprom<H, EEPROM>
@@ -236,6 +783,35 @@ macro_rules! impl_eeprom_common {
 				}
 			}
 		}
+
+		impl $crate::eeprom::EepromWriteModeOps<$HAL> for $EEPROM {
+			fn raw_program_byte(
+				&mut self,
+				address: u16,
+				data: u8,
+				mode: $crate::eeprom::WriteMode,
+			) {
+				unsafe {
+					{
+						let $periph_var = &self;
+						let $address = address as $addrwidth;
+						$set_address
+					}
+					self.eedr().write(|w| w.bits(data));
+					match mode {
+						$crate::eeprom::WriteMode::EraseWrite => {
+							let $periph_ewmode_var = &self;
+							$set_erasewrite_mode
+						}
+						$crate::eeprom::WriteMode::WriteOnly => {
+							let $periph_wmode_var = &self;
+							$set_write_mode
+						}
+					}
+					self.eecr().modify(|_, w| w.eepe().set_bit());
+				}
+			}
+		}
 	};
 }
 
@@ -410,3 +986,209 @@ macro_rules! impl_eeprom_attiny {
 		}
 	};
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// An EEPROM of 1 KiB, erased to `0xff`.
+	struct MockEeprom {
+		cells: [u8; 1024],
+		writes: usize,
+	}
+
+	impl EepromOps<()> for MockEeprom {
+		const CAPACITY: u16 = 1024;
+
+		fn raw_read_byte(&self, address: u16) -> u8 {
+			self.cells[usize::from(address)]
+		}
+
+		fn raw_write_byte(&mut self, address: u16, data: u8) {
+			self.cells[usize::from(address)] = data;
+			self.writes += 1;
+		}
+
+		fn raw_erase_byte(&mut self, address: u16) {
+			self.cells[usize::from(address)] = 0xff;
+			self.writes += 1;
+		}
+	}
+
+	fn eeprom() -> Eeprom<(), MockEeprom> {
+		Eeprom::new(MockEeprom {
+			cells: [0xff; 1024],
+			writes: 0,
+		})
+	}
+
+	#[test]
+	fn ring_writes_rotate_through_slots() {
+		let mut eeprom = eeprom();
+		let ring = EepromRing::<2>::new(100, 9);
+		assert_eq!(ring.slots(), 3);
+		assert_eq!(ring.read(&eeprom).unwrap(), None);
+
+		for value in 0..5u8 {
+			ring.write(&mut eeprom, &[value, !value]).unwrap();
+			assert_eq!(ring.read(&eeprom).unwrap(), Some([value, !value]));
+		}
+		// Five writes into three slots: sequence numbers 3, 4, 2
+		assert_eq!(eeprom.p.cells[100..109], [3, 3, !3, 4, 4, !4, 2, 2, !2]);
+		// The rest of the EEPROM is untouched
+		assert_eq!(eeprom.p.cells[109], 0xff);
+	}
+
+	#[test]
+	fn ring_sequence_wraps() {
+		let mut eeprom = eeprom();
+		let ring = EepromRing::<1>::new(0, 6);
+		for value in 0..600u16 {
+			ring.write(&mut eeprom, &[value as u8]).unwrap();
+			assert_eq!(ring.read(&eeprom).unwrap(), Some([value as u8]));
+		}
+
+		// At most 254 slots
+		assert_eq!(EepromRing::<1>::new(0, 1000).slots(), 254);
+	}
+
+	#[test]
+	fn ring_survives_interrupted_write() {
+		let mut eeprom = eeprom();
+		let ring = EepromRing::<2>::new(0, 9);
+		ring.write(&mut eeprom, &[1, 1]).unwrap();
+		ring.write(&mut eeprom, &[2, 2]).unwrap();
+
+		// Reset before the sequence byte of slot 2 was written
+		eeprom.erase_byte(6);
+		eeprom.write(7, &[3, 3]).unwrap();
+		assert_eq!(ring.read(&eeprom).unwrap(), Some([2, 2]));
+
+		// A region which held other data before
+		let mut eeprom = self::eeprom();
+		eeprom.write(0, &[1, 0xaa, 0xaa, 2, 0xbb, 0xbb]).unwrap();
+		ring.write(&mut eeprom, &[3, 3]).unwrap();
+		assert_eq!(ring.read(&eeprom).unwrap(), Some([3, 3]));
+	}
+
+	#[test]
+	fn ring_compact_and_clear() {
+		let mut eeprom = eeprom();
+		let ring = EepromRing::<2>::new(0, 12);
+		// Nothing to do for an empty ring
+		ring.compact(&mut eeprom).unwrap();
+		assert_eq!(eeprom.p.writes, 0);
+
+		for value in 0..6u8 {
+			ring.write(&mut eeprom, &[value, value]).unwrap();
+		}
+		ring.compact(&mut eeprom).unwrap();
+		assert_eq!(ring.read(&eeprom).unwrap(), Some([5, 5]));
+		assert_eq!(eeprom.p.cells[0..3], [6, 5, 5]);
+		assert!([3, 6, 9].iter().all(|&i| eeprom.p.cells[i] == 0xff));
+
+		// Continues after the first slot
+		ring.write(&mut eeprom, &[6, 6]).unwrap();
+		assert_eq!(eeprom.p.cells[3..6], [7, 6, 6]);
+
+		ring.clear(&mut eeprom).unwrap();
+		assert_eq!(ring.read(&eeprom).unwrap(), None);
+	}
+
+	#[test]
+	fn ring_bounds() {
+		let mut eeprom = eeprom();
+		let ring = EepromRing::<3>::new(1020, 8);
+		assert!(ring.read(&eeprom).is_err());
+		assert!(ring.write(&mut eeprom, &[0; 3]).is_err());
+		assert_eq!(eeprom.p.writes, 0);
+	}
+
+	#[test]
+	#[should_panic(expected = "EEPROM ring region too small")]
+	fn ring_needs_two_slots() {
+		EepromRing::<4>::new(0, 9);
+	}
+
+	#[derive(Debug, Default, PartialEq, Eq)]
+	struct Settings {
+		mode: u8,
+		setpoint: u16,
+	}
+
+	impl Persist<3> for Settings {
+		fn to_bytes(&self) -> [u8; 3] {
+			let [a, b] = self.setpoint.to_le_bytes();
+			[self.mode, a, b]
+		}
+
+		fn from_bytes(bytes: &[u8; 3]) -> Option<Self> {
+			(bytes[0] < 4).then_some(Self {
+				mode: bytes[0],
+				setpoint: u16::from_le_bytes([bytes[1], bytes[2]]),
+			})
+		}
+	}
+
+	const SETTINGS: Config<Settings, 3> = Config::new(16);
+
+	#[test]
+	fn config_round_trip() {
+		let mut eeprom = eeprom();
+		assert_eq!(Config::<Settings, 3>::LEN, 5);
+		assert_eq!(SETTINGS.load(&eeprom), Err(LoadError::Blank));
+
+		let settings = Settings {
+			mode: 2,
+			setpoint: 2150,
+		};
+		SETTINGS.store(&mut eeprom, &settings).unwrap();
+		assert_eq!(SETTINGS.load(&eeprom), Ok(settings));
+		// Value, then the CRC low byte first
+		let crc = crate::util::crc16_modbus(&eeprom.p.cells[16..19]);
+		assert_eq!(eeprom.p.cells[19..21], crc.to_le_bytes());
+	}
+
+	#[test]
+	fn config_detects_corruption() {
+		let mut eeprom = eeprom();
+		SETTINGS.store(
+			&mut eeprom,
+			&Settings {
+				mode: 1,
+				setpoint: 100,
+			},
+		)
+		.unwrap();
+		eeprom.write_byte(17, 101);
+		assert_eq!(SETTINGS.load(&eeprom), Err(LoadError::Corrupted));
+
+		// A valid CRC over contents which Persist rejects
+		SETTINGS.store(
+			&mut eeprom,
+			&Settings {
+				mode: 9,
+				setpoint: 0,
+			},
+		)
+		.unwrap();
+		assert_eq!(SETTINGS.load(&eeprom), Err(LoadError::Invalid));
+	}
+
+	#[test]
+	fn config_falls_back_to_default() {
+		let mut eeprom = eeprom();
+		eeprom.write_byte(18, 0);
+		assert_eq!(
+			SETTINGS.load_or_default(&mut eeprom).unwrap(),
+			Settings::default()
+		);
+		// The default was stored
+		assert_eq!(SETTINGS.load(&eeprom), Ok(Settings::default()));
+
+		let config = Config::<Settings, 3>::new(1020);
+		assert_eq!(config.load(&eeprom), Err(LoadError::OutOfBounds));
+		assert!(config.store(&mut eeprom, &Settings::default()).is_err());
+		assert!(config.load_or_default(&mut eeprom).is_err());
+	}
+}
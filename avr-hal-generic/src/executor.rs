@@ -0,0 +1,195 @@
+//! Minimal single-task `async` executor
+//!
+//! [`block_on()`] runs one future to completion.  Whenever the future is pending, the MCU waits
+//! (and sleeps, if a sleep mode was enabled through `SMCR.SE`) until something wakes the task
+//! again.  Wake-ups come from interrupt handlers calling [`wake()`] or [`tick()`], or from
+//! futures which wake themselves because they need to be polled again right away.
+//!
+//! This allows writing linear code instead of hand-written state machines, while several things
+//! can still happen concurrently inside the one task, e.g. by combining futures with a `join` or
+//! `select` from a `no_std` futures crate.
+//!
+//! # Time
+//! Async delays need a periodic interrupt as a time base.  Set up a timer interrupt (like in the
+//! `millis()` examples) and call [`tick()`] from its handler.  [`delay_ticks()`] then counts
+//! these ticks:
+//!
+//! ```ignore
+//! #[avr_device::interrupt(atmega328p)]
+//! fn TIMER0_COMPA() {
+//!     avr_hal_generic::executor::tick();
+//! }
+//!
+//! executor::block_on(async {
+//!     loop {
+//!         led.toggle();
+//!         executor::delay_ticks(500).await;
+//!         let b = serial.read_async().await;
+//!         serial.write_async(b).await;
+//!     }
+//! })
+//! ```
+//!
+//! # Constraints
+//! - There is no heap and no task queue.  The future is pinned on the stack of `block_on()`, so
+//!   the whole state of all nested `async` blocks lives on the stack.  Its size is the size of the
+//!   largest set of locals alive across any `.await`, which can be checked with
+//!   `core::mem::size_of_val(&future)`.  Keep large buffers in `static`s on MCUs with little RAM.
+//! - `block_on()` must not be called from an interrupt handler and not be nested.
+//! - `block_on()` enables interrupts globally, as interrupts are needed to wake the task.
+//! - The USART futures ([`read_async()`][crate::usart::Usart::read_async],
+//!   [`write_async()`][crate::usart::Usart::write_async]) are woken by the `USART_RX` and
+//!   `USART_UDRE` interrupts, whose handlers have to call
+//!   [`Usart::on_async_interrupt()`][crate::usart::Usart::on_async_interrupt]:
+//!
+//! ```ignore
+//! #[avr_device::interrupt(atmega328p)]
+//! fn USART_RX() {
+//!     arduino_hal::hal::usart::Usart0::<arduino_hal::DefaultClock>::on_async_interrupt();
+//! }
+//!
+//! #[avr_device::interrupt(atmega328p)]
+//! fn USART_UDRE() {
+//!     arduino_hal::hal::usart::Usart0::<arduino_hal::DefaultClock>::on_async_interrupt();
+//! }
+//! ```
+use core::cell::Cell;
+use core::future::Future;
+use core::pin::pin;
+use core::pin::Pin;
+use core::task::Context;
+use core::task::Poll;
+use core::task::RawWaker;
+use core::task::RawWakerVTable;
+use core::task::Waker;
+
+use avr_device::interrupt::Mutex;
+
+static WOKEN: Mutex<Cell<bool>> = Mutex::new(Cell::new(true));
+static TICKS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+static VTABLE: RawWakerVTable = RawWakerVTable::new(
+	|_| RawWaker::new(core::ptr::null(), &VTABLE),
+	|_| wake(),
+	|_| wake(),
+	|_| {},
+);
+
+/// Wake the task, so that it is polled again.
+///
+/// Call this from interrupt handlers which signal an event the task might be waiting for.
+pub fn wake() {
+	avr_device::interrupt::free(|cs| WOKEN.borrow(cs).set(true));
+}
+
+/// Advance the tick counter used by [`delay_ticks()`] by one and wake the task.
+///
+/// Call this from a periodic timer interrupt.
+pub fn tick() {
+	avr_device::interrupt::free(|cs| {
+		let ticks = TICKS.borrow(cs);
+		ticks.set(ticks.get().wrapping_add(1));
+		WOKEN.borrow(cs).set(true);
+	});
+}
+
+/// Number of ticks since startup, wrapping around on overflow.
+pub fn now() -> u32 {
+	avr_device::interrupt::free(|cs| TICKS.borrow(cs).get())
+}
+
+/// Run `future` to completion and return its output.
+///
+/// See the [module documentation](self) for details.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+	let mut future = pin!(future);
+	// SAFETY: The vtable functions ignore the data pointer.
+	let waker = unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &VTABLE)) };
+	let mut cx = Context::from_waker(&waker);
+
+	loop {
+		let woken = avr_device::interrupt::free(|cs| WOKEN.borrow(cs).replace(false));
+		if woken {
+			if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+				return output;
+			}
+		}
+
+		avr_device::interrupt::disable();
+		if avr_device::interrupt::free(|cs| WOKEN.borrow(cs).get()) {
+			// SAFETY: Interrupts need to be enabled for the task to ever be woken.
+			unsafe { avr_device::interrupt::enable() };
+		} else {
+			wait_for_interrupt();
+		}
+	}
+}
+
+/// Enable interrupts and sleep until the next one.
+///
+/// The instruction following `sei` is always executed before any pending interrupt is served, so
+/// a wake-up between checking the flag and `sleep` cannot be lost.
+#[cfg(target_arch = "avr")]
+fn wait_for_interrupt() {
+	unsafe { core::arch::asm!("sei", "sleep") };
+}
+
+#[cfg(not(target_arch = "avr"))]
+fn wait_for_interrupt() {
+	unimplemented!("Implementation is only available for avr targets!")
+}
+
+/// Future returned by [`yield_now()`].
+pub struct YieldNow {
+	yielded: bool,
+}
+
+impl Future for YieldNow {
+	type Output = ();
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+		if self.yielded {
+			Poll::Ready(())
+		} else {
+			self.yielded = true;
+			cx.waker().wake_by_ref();
+			Poll::Pending
+		}
+	}
+}
+
+/// Suspend the current task once, giving other futures in the same `join`/`select` a chance to
+/// run.
+pub fn yield_now() -> YieldNow {
+	YieldNow { yielded: false }
+}
+
+/// Future returned by [`delay_ticks()`].
+pub struct DelayTicks {
+	start: u32,
+	ticks: u32,
+}
+
+impl Future for DelayTicks {
+	type Output = ();
+
+	fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+		// No need to store the waker, every tick() wakes the task anyway.
+		if now().wrapping_sub(self.start) >= self.ticks {
+			Poll::Ready(())
+		} else {
+			Poll::Pending
+		}
+	}
+}
+
+/// Wait for `ticks` calls to [`tick()`].
+///
+/// The first tick may come at any time after calling this, so the actual delay lies between
+/// `ticks - 1` and `ticks` tick periods.
+pub fn delay_ticks(ticks: u32) -> DelayTicks {
+	DelayTicks {
+		start: now(),
+		ticks,
+	}
+}
@@ -2,6 +2,7 @@
 //!
 //! Check the documentation of [`I2c`] for details.
 
+pub use embedded_hal::i2c::Operation;
 use embedded_hal::i2c::SevenBitAddress;
 
 use crate::port;
@@ -108,7 +109,7 @@ pub mod twi_status {
 #[derive(ufmt::derive::uDebug, Debug, Clone, Copy, Eq, PartialEq)]
 #[repr(u8)]
 pub enum Error {
-	/// Lost arbitration while trying to acquire bus
+	/// Another master won the bus, see [Multi-master buses](I2c#multi-master-buses)
 	ArbitrationLost,
 	/// No slave answered for this address or a slave replied NACK
 	AddressNack,
@@ -152,6 +153,62 @@ pub enum Direction {
 	Read,
 }
 
+/// Bit rate generator settings for a certain SCL frequency.
+///
+/// The TWI peripheral derives the SCL frequency from the CPU clock, the bit rate register `TWBR`
+/// and the prescaler bits `TWPS` in `TWSR`:
+///
+/// ```text
+/// F_scl = CLK_cpu / (16 + 2 * TWBR * 4^TWPS)
+/// ```
+///
+/// `TWBR` is only 8 bits wide, so without the prescaler the slowest possible bus at 16 MHz is
+/// about 30 kHz.  The smallest prescaler (1, 4, 16, or 64) which fits is chosen, for the finest
+/// resolution.  With a prescaler of 64, the bus can go down to about 500 Hz at 16 MHz, enough for
+/// long or capacitive buses which need 10 kHz.
+///
+/// `TWBR` is rounded up, so the achieved frequency never exceeds the requested one.
+#[derive(ufmt::derive::uDebug, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitRate {
+	/// Value for the `TWBR` register.
+	pub twbr: u8,
+	/// Value for the `TWPS` bits, selecting a prescaler of `4^twps`.
+	pub twps: u8,
+}
+
+impl BitRate {
+	/// Compute the register values for an SCL frequency of `speed` Hz.
+	///
+	/// Frequencies too high for the CPU clock result in `TWBR = 0` (`CLK_cpu / 16`), frequencies
+	/// too low result in the slowest possible setting.
+	pub fn new(clock_hz: u32, speed: u32) -> Self {
+		let cycles = clock_hz.div_ceil(speed.max(1)).saturating_sub(16);
+		for twps in 0..4 {
+			let twbr = cycles.div_ceil(2 << (2 * twps));
+			if twbr <= u8::MAX as u32 {
+				return Self {
+					twbr: twbr as u8,
+					twps,
+				};
+			}
+		}
+		Self {
+			twbr: u8::MAX,
+			twps: 3,
+		}
+	}
+
+	/// The prescaler selected by `TWPS`.
+	pub fn prescaler(&self) -> u8 {
+		1 << (2 * self.twps)
+	}
+
+	/// The SCL frequency in Hz that these settings achieve.
+	pub fn achieved(&self, clock_hz: u32) -> u32 {
+		clock_hz / (16 + 2 * u32::from(self.twbr) * u32::from(self.prescaler()))
+	}
+}
+
 /// Internal trait for low-level I2C peripherals.
 ///
 /// This trait defines the common interface for all I2C peripheral variants.  It is used as an
@@ -213,12 +270,77 @@ pub trait I2cOps<H, SDA, SCL> {
 ///
 /// // i2c implements the embedded-hal traits so it can be used with generic drivers.
 /// ```
+///
+/// # Pull-ups
+/// I2C requires pull-up resistors on SDA and SCL.  The AVR's internal pull-ups can be used for
+/// this, which is often enough for a short bus with few devices at 100 kHz.  They are weak though
+/// (roughly 20-50 kΩ), so the signal edges rise slowly.  For fast mode (400 kHz), long wires or
+/// many devices, external resistors (typically 2.2-4.7 kΩ) are recommended.
+///
+/// - [`I2c::new`] expects pins with the internal pull-ups enabled.
+/// - [`I2c::with_external_pullup`] expects floating pins, for boards with external resistors.
+/// - [`I2c::with_pullups`] takes pins in any input mode and configures them as requested.
+///
+/// # Bus speed
+/// The `speed` argument is the SCL frequency in Hz.  Not every frequency can be hit exactly, the
+/// closest one below it is used and can be checked with [`I2c::speed`].  Slow buses down to a few
+/// hundred Hz are possible, see [`BitRate`] for the details.
+///
+/// # Transactions
+/// [`I2c::transaction`] (also available through the embedded-hal 1.0 [`I2c`][embedded_hal::i2c::I2c]
+/// trait) performs a sequence of reads and writes on one device as a single bus transaction:
+///
+/// - A START and the address are only sent before the first operation and whenever the direction
+///   changes.  Consecutive operations in the same direction are joined without any bus signaling
+///   in between, so two `Write`s look exactly like one longer write to the device.
+/// - A direction change uses a repeated START, so no other master can take the bus in between.
+/// - Only the very last byte of a run of `Read`s is NACKed, to tell the device the read ends.
+/// - A single STOP ends the transaction.
+///
+/// `write_read()` is the common special case of one `Write` followed by one `Read`.  Joining
+/// writes is useful for sending a register address and data from separate buffers:
+///
+/// ```ignore
+/// use arduino_hal::i2c::Operation;
+///
+/// i2c.transaction(0x50, &mut [
+///     Operation::Write(&[0x00, 0x10]), // memory address
+///     Operation::Write(&page),         // data, without a new START
+/// ])?;
+/// ```
+///
+/// # Multi-master buses
+/// Several masters may share one bus.  If two of them start a transfer at the same time, they
+/// notice the collision bit by bit: a master which sends a 1 but sees a 0 on SDA has lost the
+/// arbitration and backs off, while the winner continues undisturbed.  This can happen anywhere
+/// in the address or in data sent by the master.  The operation then fails with
+/// [`Error::ArbitrationLost`] and the bus is released right away, without a STOP.
+///
+/// Nothing went wrong with the bus or the device, the operation just has to be tried again once
+/// the other master is done.  Retry the whole transaction, not only the failed part: the device
+/// has seen the other master's transfer in between.  A START which is requested while the bus is
+/// busy is held back by the hardware until the other master sends its STOP, so no delay is
+/// needed before retrying:
+///
+/// ```ignore
+/// let mut attempts = 0;
+/// let result = loop {
+///     match i2c.write_read(0x48, &[0x00], &mut buffer) {
+///         Err(Error::ArbitrationLost) if attempts < 5 => attempts += 1,
+///         result => break result,
+///     }
+/// };
+/// ```
+///
+/// Limit the number of attempts, in case another master hogs the bus.  This driver only acts as
+/// a master; addressing it from another master is not supported.
 pub struct I2c<H, I2C: I2cOps<H, SDA, SCL>, SDA, SCL, CLOCK> {
 	p: I2C,
 	#[allow(dead_code)]
 	sda: SDA,
 	#[allow(dead_code)]
 	scl: SCL,
+	rate: BitRate,
 	_clock: PhantomData<CLOCK>,
 	_h: PhantomData<H>,
 }
@@ -249,6 +371,7 @@ where
 			p,
 			sda: sda.forget_imode(),
 			scl: scl.forget_imode(),
+			rate: BitRate::new(CLOCK::FREQ, speed),
 			_clock: PhantomData,
 			_h: PhantomData,
 		};
@@ -272,12 +395,143 @@ where
 			p,
 			sda: sda.forget_imode(),
 			scl: scl.forget_imode(),
+			rate: BitRate::new(CLOCK::FREQ, speed),
 			_clock: PhantomData,
 			_h: PhantomData,
 		};
 		i2c.p.raw_setup::<CLOCK>(speed);
 		i2c
 	}
+
+	/// Initialize an I2C peripheral on the given pins, choosing whether the internal pull-ups are
+	/// used.
+	///
+	/// With `pullups` set, the internal pull-ups of SDA and SCL are enabled, otherwise the pins are
+	/// left floating and external resistors are expected.  See [Pull-ups](I2c#pull-ups) for which
+	/// one to pick.
+	///
+	/// Note that the SDA and SCL pins are hardwired for each I2C peripheral and you *must* pass
+	/// the correct ones.  This is enforced at compile time.
+	pub fn with_pullups<SDAMODE, SCLMODE>(
+		p: I2C,
+		sda: port::Pin<port::mode::Input<SDAMODE>, SDAPIN>,
+		scl: port::Pin<port::mode::Input<SCLMODE>, SCLPIN>,
+		speed: u32,
+		pullups: bool,
+	) -> Self
+	where
+		SDAMODE: port::mode::InputMode,
+		SCLMODE: port::mode::InputMode,
+	{
+		let (sda, scl) = if pullups {
+			(
+				sda.into_pull_up_input().forget_imode(),
+				scl.into_pull_up_input().forget_imode(),
+			)
+		} else {
+			(
+				sda.into_floating_input().forget_imode(),
+				scl.into_floating_input().forget_imode(),
+			)
+		};
+		let mut i2c = Self {
+			p,
+			sda,
+			scl,
+			rate: BitRate::new(CLOCK::FREQ, speed),
+			_clock: PhantomData,
+			_h: PhantomData,
+		};
+		i2c.p.raw_setup::<CLOCK>(speed);
+		i2c
+	}
+}
+
+impl<H, I2C: I2cOps<H, SDA, SCL>, SDA, SCL, CLOCK: crate::clock::Clock>
+	I2c<H, I2C, SDA, SCL, CLOCK>
+{
+	/// The SCL frequency in Hz the bus is actually running at.
+	pub fn speed(&self) -> u32 {
+		self.rate.achieved(CLOCK::FREQ)
+	}
+
+	/// The bit rate generator settings, see [`BitRate`].
+	pub fn bit_rate(&self) -> BitRate {
+		self.rate
+	}
+
+	/// Write `bytes` to the device at `address`, retrying the addressing while it does not
+	/// acknowledge.
+	///
+	/// Some devices NACK their address while they are busy, most prominently EEPROMs during their
+	/// internal write cycle.  Instead of waiting for the worst-case time with a fixed delay, the
+	/// start condition and address are sent again until the device acknowledges ("acknowledge
+	/// polling"), which continues as soon as the device is ready.  Each attempt takes about ten
+	/// SCL periods, e.g. 100 µs at 100 kHz, so 50 retries cover a 5 ms write cycle.
+	///
+	/// The address is tried up to `1 + max_retries` times.  If the device never acknowledges,
+	/// [`Error::AddressNack`] is returned.  With empty `bytes`, this just waits until the device
+	/// is ready.
+	pub fn write_with_ack_poll(
+		&mut self,
+		address: u8,
+		bytes: &[u8],
+		max_retries: u16,
+	) -> Result<(), Error> {
+		let mut retries = 0;
+		loop {
+			match self.p.raw_start(address, Direction::Write) {
+				Ok(()) => break,
+				Err(Error::AddressNack) if retries < max_retries => retries += 1,
+				Err(e) => return Err(e),
+			}
+		}
+		self.p.raw_write(bytes)?;
+		self.p.raw_stop()
+	}
+}
+
+impl<H, I2C: I2cOps<H, SDA, SCL>, SDA, SCL, CLOCK> I2c<H, I2C, SDA, SCL, CLOCK> {
+	/// Execute a sequence of reads and writes on the device at `address` as one transaction.
+	///
+	/// See [Transactions](I2c#transactions) for the bus signaling.
+	pub fn transaction(
+		&mut self,
+		address: u8,
+		operations: &mut [Operation<'_>],
+	) -> Result<(), Error> {
+		let mut previous_direction = Direction::Read;
+		let mut ops_iter = operations.iter_mut().enumerate().peekable();
+		while let Some((idx, operation)) = ops_iter.next() {
+			match operation {
+				Operation::Read(buffer) => {
+					if idx == 0 || previous_direction != Direction::Read {
+						self.p.raw_start(address, Direction::Read)?;
+					}
+
+					let next_op_is_read = matches!(
+						ops_iter.peek(),
+						Some((_, Operation::Read(_)))
+					);
+
+					self.p.raw_read(buffer, !next_op_is_read)?;
+					previous_direction = Direction::Read;
+				}
+				Operation::Write(bytes) => {
+					if idx == 0 || previous_direction != Direction::Write {
+						self.p.raw_start(address, Direction::Write)?;
+					}
+					self.p.raw_write(bytes)?;
+					previous_direction = Direction::Write;
+				}
+			}
+		}
+		if operations.len() > 0 {
+			self.p.raw_stop()?;
+		}
+
+		Ok(())
+	}
 }
 
 impl<H, I2C: I2cOps<H, SDA, SCL>, SDA, SCL, CLOCK> I2c<H, I2C, SDA, SCL, CLOCK>
@@ -371,6 +625,140 @@ where
 	}
 }
 
+/// A change reported by [`DevicePresence::poll()`].
+#[derive(ufmt::derive::uDebug, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresenceEvent {
+	/// The device started answering; it has to be initialized again.
+	Connected,
+	/// The device stopped answering.
+	Disconnected,
+}
+
+/// Default number of consecutive probes which have to agree before [`DevicePresence`] reports a
+/// change.
+pub const DEFAULT_PRESENCE_DEBOUNCE: u8 = 3;
+
+/// Watches whether a device on a hot-pluggable I2C connector is present.
+///
+/// Every `interval_ticks` ticks, [`poll()`][Self::poll] probes the address the same way the bus
+/// scan does (see [`I2c::ping_device()`]), and reports when the device appears or disappears.
+/// Time is measured in ticks of a periodic timer: [`tick()`][Self::tick] has to be called
+/// regularly, e.g. every millisecond from a timer interrupt.  `poll()` is then called from the
+/// main loop, and its events take the place of connect/disconnect callbacks:
+///
+/// ```ignore
+/// // Probe the sensor at 0x76 every 100 ms, with tick() called every 1 ms
+/// let mut sensor = DevicePresence::new(0x76, 100);
+///
+/// loop {
+///     match sensor.poll(&mut i2c) {
+///         Some(PresenceEvent::Connected) => init_sensor(&mut i2c),
+///         Some(PresenceEvent::Disconnected) => show_sensor_missing(),
+///         None => (),
+///     }
+/// }
+/// ```
+///
+/// The device is assumed absent at first, so a device which is already plugged in is reported
+/// as [`PresenceEvent::Connected`] by the first probes.
+///
+/// # Debouncing
+/// A single NACK does not mean the device is gone: it may be busy (e.g. an EEPROM during a
+/// write cycle), or a loose contact drops one transfer.  Likewise, the contacts of a connector
+/// bounce while it is being plugged in.  A change is therefore only reported after
+/// [`DEFAULT_PRESENCE_DEBOUNCE`] consecutive probes agree on it, which can be changed with
+/// [`set_debounce()`][Self::set_debounce].  A disconnect is noticed after `debounce *
+/// interval_ticks` ticks at the latest.
+///
+/// A probe which loses the arbitration to another master tells nothing about the device and is
+/// ignored.  Other bus errors count as a missing device.
+pub struct DevicePresence {
+	address: u8,
+	interval_ticks: u16,
+	elapsed_ticks: u16,
+	debounce: u8,
+	/// Consecutive probes which disagree with `present`.
+	streak: u8,
+	present: bool,
+}
+
+impl DevicePresence {
+	/// Watch the device at `address`, probing it every `interval_ticks` ticks.  The first probe
+	/// happens on the first call to [`poll()`][Self::poll].
+	pub fn new(address: u8, interval_ticks: u16) -> Self {
+		Self {
+			address,
+			interval_ticks,
+			elapsed_ticks: interval_ticks,
+			debounce: DEFAULT_PRESENCE_DEBOUNCE,
+			streak: 0,
+			present: false,
+		}
+	}
+
+	/// Change the number of consecutive probes which have to agree before a change is reported.
+	///
+	/// # Panics
+	/// Panics if `probes` is 0.
+	pub fn set_debounce(&mut self, probes: u8) {
+		assert!(probes > 0, "at least one probe is needed");
+		self.debounce = probes;
+		self.streak = 0;
+	}
+
+	/// Advance the timebase by one tick.
+	pub fn tick(&mut self) {
+		self.elapsed_ticks = self.elapsed_ticks.saturating_add(1);
+	}
+
+	/// Probe the device if the interval has passed, and report whether it appeared or
+	/// disappeared.
+	pub fn poll<H, I2C: I2cOps<H, SDA, SCL>, SDA, SCL, CLOCK>(
+		&mut self,
+		i2c: &mut I2c<H, I2C, SDA, SCL, CLOCK>,
+	) -> Option<PresenceEvent>
+	where
+		CLOCK: crate::clock::Clock,
+		crate::delay::Delay<CLOCK>: embedded_hal_v0::blocking::delay::DelayMs<u16>,
+	{
+		if self.elapsed_ticks < self.interval_ticks {
+			return None;
+		}
+		self.elapsed_ticks = 0;
+
+		let answered = match i2c.ping_device(self.address, Direction::Write) {
+			Ok(answered) => answered,
+			Err(Error::ArbitrationLost) => return None,
+			Err(_) => false,
+		};
+		if answered == self.present {
+			self.streak = 0;
+			return None;
+		}
+		self.streak += 1;
+		if self.streak < self.debounce {
+			return None;
+		}
+		self.streak = 0;
+		self.present = answered;
+		Some(if answered {
+			PresenceEvent::Connected
+		} else {
+			PresenceEvent::Disconnected
+		})
+	}
+
+	/// Whether the device was present at the last reported change.
+	pub fn is_present(&self) -> bool {
+		self.present
+	}
+
+	/// The watched address.
+	pub fn address(&self) -> u8 {
+		self.address
+	}
+}
+
 impl<H, I2C: I2cOps<H, SDA, SCL>, SDA, SCL, CLOCK> embedded_hal_v0::blocking::i2c::Write
 	for I2c<H, I2C, SDA, SCL, CLOCK>
 {
@@ -425,37 +813,7 @@ impl<H, I2C: I2cOps<H, SDA, SCL>, SDA, SCL, CLOCK> embedded_hal::i2c::I2c<SevenB
 		address: u8,
 		operations: &mut [embedded_hal::i2c::Operation<'_>],
 	) -> Result<(), Self::Error> {
-		let mut previous_direction = Direction::Read;
-		let mut ops_iter = operations.iter_mut().enumerate().peekable();
-		while let Some((idx, operation)) = ops_iter.next() {
-			match operation {
-				embedded_hal::i2c::Operation::Read(buffer) => {
-					if idx == 0 || previous_direction != Direction::Read {
-						self.p.raw_start(address, Direction::Read)?;
-					}
-
-					let next_op_is_read = matches!(
-						ops_iter.peek(),
-						Some((_, embedded_hal::i2c::Operation::Read(_)))
-					);
-
-					self.p.raw_read(buffer, !next_op_is_read)?;
-					previous_direction = Direction::Read;
-				}
-				embedded_hal::i2c::Operation::Write(bytes) => {
-					if idx == 0 || previous_direction != Direction::Write {
-						self.p.raw_start(address, Direction::Write)?;
-					}
-					self.p.raw_write(bytes)?;
-					previous_direction = Direction::Write;
-				}
-			}
-		}
-		if operations.len() > 0 {
-			self.p.raw_stop()?;
-		}
-
-		Ok(())
+		I2c::transaction(self, address, operations)
 	}
 }
 
@@ -476,13 +834,9 @@ macro_rules! impl_i2c_twi {
 		{
 			#[inline]
 			fn raw_setup<CLOCK: $crate::clock::Clock>(&mut self, speed: u32) {
-				// Calculate TWBR register value
-				let twbr = ((CLOCK::FREQ / speed) - 16) / 2;
-				self.twbr()
-					.write(|w| unsafe { w.bits(twbr.try_into().unwrap()) });
-
-				// Disable prescaler
-				self.twsr().write(|w| w.twps().prescaler_1());
+				let rate = $crate::i2c::BitRate::new(CLOCK::FREQ, speed);
+				self.twbr().write(|w| unsafe { w.bits(rate.twbr) });
+				self.twsr().write(|w| w.twps().set(rate.twps));
 			}
 
 			#[inline]
@@ -504,6 +858,11 @@ macro_rules! impl_i2c_twi {
 					| $crate::i2c::twi_status::TW_REP_START => (),
 					$crate::i2c::twi_status::TW_MT_ARB_LOST
 					| $crate::i2c::twi_status::TW_MR_ARB_LOST => {
+						// Clearing TWINT without START or STOP releases the bus to the
+						// winning master, it is held (SCL low) until then.
+						self.twcr().write(|w| {
+							w.twen().set_bit().twint().set_bit()
+						});
 						return Err($crate::i2c::Error::ArbitrationLost);
 					}
 					$crate::i2c::twi_status::TW_BUS_ERROR => {
@@ -537,7 +896,16 @@ macro_rules! impl_i2c_twi {
 						return Err($crate::i2c::Error::AddressNack);
 					}
 					$crate::i2c::twi_status::TW_MT_ARB_LOST
-					| $crate::i2c::twi_status::TW_MR_ARB_LOST => {
+					| $crate::i2c::twi_status::TW_MR_ARB_LOST
+					| $crate::i2c::twi_status::TW_SR_ARB_LOST_SLA_ACK
+					| $crate::i2c::twi_status::TW_SR_ARB_LOST_GCALL_ACK
+					| $crate::i2c::twi_status::TW_ST_ARB_LOST_SLA_ACK => {
+						// Lost while sending the address, possibly to a master addressing
+						// this device.  There is no slave mode support, so just release the
+						// bus.
+						self.twcr().write(|w| {
+							w.twen().set_bit().twint().set_bit()
+						});
 						return Err($crate::i2c::Error::ArbitrationLost);
 					}
 					$crate::i2c::twi_status::TW_BUS_ERROR => {
@@ -566,6 +934,9 @@ macro_rules! impl_i2c_twi {
 							return Err($crate::i2c::Error::DataNack);
 						}
 						$crate::i2c::twi_status::TW_MT_ARB_LOST => {
+							self.twcr().write(|w| {
+								w.twen().set_bit().twint().set_bit()
+							});
 							return Err(
 								$crate::i2c::Error::ArbitrationLost,
 							);
@@ -612,6 +983,9 @@ macro_rules! impl_i2c_twi {
 						$crate::i2c::twi_status::TW_MR_DATA_ACK
 						| $crate::i2c::twi_status::TW_MR_DATA_NACK => (),
 						$crate::i2c::twi_status::TW_MR_ARB_LOST => {
+							self.twcr().write(|w| {
+								w.twen().set_bit().twint().set_bit()
+							});
 							return Err(
 								$crate::i2c::Error::ArbitrationLost,
 							);
@@ -639,3 +1013,336 @@ macro_rules! impl_i2c_twi {
 		}
 	};
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::VecDeque;
+	use std::vec::Vec;
+
+	#[derive(Debug, PartialEq, Eq)]
+	enum Bus {
+		Start(u8, Direction),
+		Write(Vec<u8>),
+		/// Number of bytes read and whether the last one was NACKed.
+		Read(usize, bool),
+		Stop,
+	}
+
+	/// A TWI which records the bus signaling.
+	#[derive(Default)]
+	struct MockTwi {
+		log: Vec<Bus>,
+		/// Results of the next starts, `Ok` once this is empty.
+		start_errors: VecDeque<Error>,
+		/// Results of the next writes, `Ok` once this is empty.
+		write_errors: VecDeque<Error>,
+		/// Bytes sent by the device.
+		data: VecDeque<u8>,
+	}
+
+	impl I2cOps<(), (), ()> for MockTwi {
+		fn raw_setup<CLOCK: crate::clock::Clock>(&mut self, _speed: u32) {}
+
+		fn raw_start(&mut self, address: u8, direction: Direction) -> Result<(), Error> {
+			self.log.push(Bus::Start(address, direction));
+			self.start_errors.pop_front().map_or(Ok(()), Err)
+		}
+
+		fn raw_write(&mut self, bytes: &[u8]) -> Result<(), Error> {
+			self.log.push(Bus::Write(bytes.to_vec()));
+			self.write_errors.pop_front().map_or(Ok(()), Err)
+		}
+
+		fn raw_read(&mut self, buffer: &mut [u8], last_read: bool) -> Result<(), Error> {
+			for byte in buffer.iter_mut() {
+				*byte = self.data.pop_front().unwrap_or(0xff);
+			}
+			self.log.push(Bus::Read(buffer.len(), last_read));
+			Ok(())
+		}
+
+		fn raw_stop(&mut self) -> Result<(), Error> {
+			self.log.push(Bus::Stop);
+			Ok(())
+		}
+	}
+
+	type TestI2c = I2c<(), MockTwi, (), (), crate::clock::MHz16>;
+
+	fn i2c() -> TestI2c {
+		I2c {
+			p: MockTwi::default(),
+			sda: (),
+			scl: (),
+			rate: BitRate::new(16_000_000, 100_000),
+			_clock: PhantomData,
+			_h: PhantomData,
+		}
+	}
+
+	#[test]
+	fn bit_rate_standard_speeds() {
+		let rate = BitRate::new(16_000_000, 100_000);
+		assert_eq!(rate, BitRate { twbr: 72, twps: 0 });
+		assert_eq!(rate.achieved(16_000_000), 100_000);
+		let rate = BitRate::new(16_000_000, 400_000);
+		assert_eq!(rate, BitRate { twbr: 12, twps: 0 });
+		assert_eq!(rate.achieved(16_000_000), 400_000);
+
+		// Rounded towards the slower bus
+		let rate = BitRate::new(16_000_000, 300_000);
+		assert_eq!(rate.twbr, 19);
+		assert_eq!(rate.achieved(16_000_000), 296_296);
+	}
+
+	#[test]
+	fn bit_rate_uses_prescaler() {
+		let rate = BitRate::new(16_000_000, 10_000);
+		assert_eq!(rate, BitRate { twbr: 198, twps: 1 });
+		assert_eq!(rate.prescaler(), 4);
+		assert_eq!(rate.achieved(16_000_000), 10_000);
+
+		let rate = BitRate::new(16_000_000, 500);
+		assert_eq!(rate, BitRate { twbr: 250, twps: 3 });
+		assert_eq!(rate.prescaler(), 64);
+		assert_eq!(rate.achieved(16_000_000), 499);
+	}
+
+	#[test]
+	fn bit_rate_out_of_range() {
+		// Slowest possible setting
+		let rate = BitRate::new(16_000_000, 100);
+		assert_eq!(rate, BitRate { twbr: 255, twps: 3 });
+		assert_eq!(rate.achieved(16_000_000), 489);
+		// Fastest possible setting
+		let rate = BitRate::new(16_000_000, 2_000_000);
+		assert_eq!(rate, BitRate { twbr: 0, twps: 0 });
+		assert_eq!(rate.achieved(16_000_000), 1_000_000);
+		assert_eq!(BitRate::new(16_000_000, 0), BitRate { twbr: 255, twps: 3 });
+	}
+
+	#[test]
+	fn ack_poll_retries_address() {
+		let mut i2c = i2c();
+		i2c.p.start_errors.extend([Error::AddressNack; 3]);
+		assert_eq!(
+			i2c.write_with_ack_poll(0x50, &[0x00, 0x10, 0xaa], 5),
+			Ok(())
+		);
+		assert_eq!(
+			i2c.p.log,
+			[
+				Bus::Start(0x50, Direction::Write),
+				Bus::Start(0x50, Direction::Write),
+				Bus::Start(0x50, Direction::Write),
+				Bus::Start(0x50, Direction::Write),
+				Bus::Write(std::vec![0x00, 0x10, 0xaa]),
+				Bus::Stop,
+			]
+		);
+	}
+
+	#[test]
+	fn ack_poll_gives_up() {
+		let mut bus = i2c();
+		bus.p.start_errors.extend([Error::AddressNack; 5]);
+		assert_eq!(
+			bus.write_with_ack_poll(0x50, &[], 2),
+			Err(Error::AddressNack)
+		);
+		assert_eq!(bus.p.log.len(), 3);
+
+		// Other errors are not retried
+		let mut bus = i2c();
+		bus.p.start_errors.push_back(Error::BusError);
+		assert_eq!(bus.write_with_ack_poll(0x50, &[], 2), Err(Error::BusError));
+		assert_eq!(bus.p.log, [Bus::Start(0x50, Direction::Write)]);
+
+		// Empty data only waits for the device
+		let mut bus = i2c();
+		assert_eq!(bus.write_with_ack_poll(0x50, &[], 0), Ok(()));
+		assert_eq!(
+			bus.p.log,
+			[
+				Bus::Start(0x50, Direction::Write),
+				Bus::Write(Vec::new()),
+				Bus::Stop,
+			]
+		);
+	}
+
+	#[test]
+	fn transaction_joins_same_direction() {
+		let mut bus = i2c();
+		bus.transaction(
+			0x50,
+			&mut [
+				Operation::Write(&[0x00, 0x10]),
+				Operation::Write(&[1, 2, 3]),
+			],
+		)
+		.unwrap();
+		assert_eq!(
+			bus.p.log,
+			[
+				Bus::Start(0x50, Direction::Write),
+				Bus::Write(std::vec![0x00, 0x10]),
+				Bus::Write(std::vec![1, 2, 3]),
+				Bus::Stop,
+			]
+		);
+	}
+
+	#[test]
+	fn transaction_repeated_start_and_nack() {
+		let mut bus = i2c();
+		bus.p.data.extend([0xde, 0xad, 0xbe]);
+		let mut first = [0; 2];
+		let mut second = [0; 1];
+		bus.transaction(
+			0x48,
+			&mut [
+				Operation::Write(&[0x00]),
+				Operation::Read(&mut first),
+				Operation::Read(&mut second),
+			],
+		)
+		.unwrap();
+		assert_eq!((first, second), ([0xde, 0xad], [0xbe]));
+		// Only the last byte of the reads is NACKed
+		assert_eq!(
+			bus.p.log,
+			[
+				Bus::Start(0x48, Direction::Write),
+				Bus::Write(std::vec![0x00]),
+				Bus::Start(0x48, Direction::Read),
+				Bus::Read(2, false),
+				Bus::Read(1, true),
+				Bus::Stop,
+			]
+		);
+	}
+
+	#[test]
+	fn transaction_edge_cases() {
+		// Nothing at all on the bus
+		let mut bus = i2c();
+		bus.transaction(0x48, &mut []).unwrap();
+		assert!(bus.p.log.is_empty());
+
+		// A read as first operation also needs a start
+		let mut buffer = [0; 1];
+		bus.transaction(0x48, &mut [Operation::Read(&mut buffer)])
+			.unwrap();
+		assert_eq!(
+			bus.p.log,
+			[
+				Bus::Start(0x48, Direction::Read),
+				Bus::Read(1, true),
+				Bus::Stop,
+			]
+		);
+
+		// The embedded-hal trait goes the same way
+		let mut bus = i2c();
+		embedded_hal::i2c::I2c::write_read(&mut bus, 0x48, &[0x01], &mut buffer).unwrap();
+		assert_eq!(bus.p.log.len(), 5);
+	}
+
+	#[test]
+	fn arbitration_loss_aborts_without_stop() {
+		let mut bus = i2c();
+		bus.p.write_errors.push_back(Error::ArbitrationLost);
+		let mut buffer = [0; 1];
+		let mut ops = [Operation::Write(&[0x00]), Operation::Read(&mut buffer)];
+		assert_eq!(bus.transaction(0x48, &mut ops), Err(Error::ArbitrationLost));
+		// The TWI already released the bus, a STOP would disturb the other master
+		assert_eq!(
+			bus.p.log,
+			[
+				Bus::Start(0x48, Direction::Write),
+				Bus::Write(std::vec![0x00])
+			]
+		);
+
+		// Retrying the whole transaction goes through
+		bus.p.log.clear();
+		assert_eq!(bus.transaction(0x48, &mut ops), Ok(()));
+		assert_eq!(bus.p.log.len(), 5);
+		assert_eq!(
+			embedded_hal::i2c::Error::kind(&Error::ArbitrationLost),
+			embedded_hal::i2c::ErrorKind::ArbitrationLoss
+		);
+	}
+
+	/// Let the interval pass and probe once.
+	fn probe(presence: &mut DevicePresence, bus: &mut TestI2c) -> Option<PresenceEvent> {
+		for _ in 0..10 {
+			presence.tick();
+		}
+		presence.poll(bus)
+	}
+
+	#[test]
+	fn presence_debounces_changes() {
+		let mut bus = i2c();
+		let mut presence = DevicePresence::new(0x76, 10);
+		assert_eq!(presence.address(), 0x76);
+
+		// The first probe happens right away, the next only after the interval
+		assert_eq!(presence.poll(&mut bus), None);
+		assert_eq!(presence.poll(&mut bus), None);
+		assert_eq!(bus.p.log.len(), 2);
+		assert_eq!(probe(&mut presence, &mut bus), None);
+		assert_eq!(
+			probe(&mut presence, &mut bus),
+			Some(PresenceEvent::Connected)
+		);
+		assert!(presence.is_present());
+
+		// A single NACK restarts the count
+		bus.p.start_errors
+			.extend([Error::AddressNack, Error::AddressNack]);
+		assert_eq!(probe(&mut presence, &mut bus), None);
+		assert_eq!(probe(&mut presence, &mut bus), None);
+		assert_eq!(probe(&mut presence, &mut bus), None);
+		assert!(presence.is_present());
+
+		// Bus errors count as a missing device
+		bus.p.start_errors.extend([
+			Error::AddressNack,
+			Error::BusError,
+			Error::AddressNack,
+		]);
+		assert_eq!(probe(&mut presence, &mut bus), None);
+		assert_eq!(probe(&mut presence, &mut bus), None);
+		assert_eq!(
+			probe(&mut presence, &mut bus),
+			Some(PresenceEvent::Disconnected)
+		);
+		assert!(!presence.is_present());
+	}
+
+	#[test]
+	fn presence_ignores_lost_arbitration() {
+		let mut bus = i2c();
+		let mut presence = DevicePresence::new(0x76, 10);
+		presence.set_debounce(2);
+		bus.p.start_errors.extend([Error::ArbitrationLost; 3]);
+		assert_eq!(presence.poll(&mut bus), None);
+		assert_eq!(probe(&mut presence, &mut bus), None);
+		assert_eq!(probe(&mut presence, &mut bus), None);
+		assert_eq!(probe(&mut presence, &mut bus), None);
+		assert_eq!(
+			probe(&mut presence, &mut bus),
+			Some(PresenceEvent::Connected)
+		);
+	}
+
+	#[test]
+	#[should_panic(expected = "at least one probe is needed")]
+	fn presence_needs_a_probe() {
+		DevicePresence::new(0x76, 10).set_debounce(0);
+	}
+}
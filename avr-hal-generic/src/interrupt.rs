@@ -0,0 +1,287 @@
+//! Interrupt priorities and nesting
+//!
+//! # Priorities
+//! AVR interrupts have a fixed priority given by their position in the vector table: the lower
+//! the vector address, the higher the priority.  After `RESET`, the external interrupts (`INT0`,
+//! `INT1`, ...) come first, followed by the pin change interrupts, and then the other
+//! peripherals.  The priority only decides which handler runs first when several interrupts are
+//! pending at the same time.  It does not allow a higher-priority interrupt to preempt a running
+//! handler.
+//!
+//! # Nesting
+//! When entering an interrupt handler, the hardware clears the global interrupt flag, so handlers
+//! never nest by default: a long handler delays every other interrupt, regardless of priority.
+//! For a timing-critical source which must not be blocked by a slow, less important handler, the
+//! slow handler can re-enable interrupts for part of its work with [`with_nesting()`].
+//!
+//! This comes with hazards that the compiler cannot check for:
+//!
+//! - **Reentrancy**: The handler itself can be interrupted by *any* interrupt, including its own
+//!   source.  If its interrupt fires again before the nested section ends, the handler is entered
+//!   a second time, and with a steady stream of interrupts the stack overflows.  Disable (or
+//!   clear) the handler's own interrupt source before nesting, and re-enable it afterwards.
+//! - **Shared state**: Everything the nested section touches can be modified by other handlers
+//!   in the middle of it.  State shared with other handlers must be accessed in
+//!   `interrupt::free()` critical sections only, as it would be from the main loop.
+//! - **Stack usage**: Every nesting level needs stack space for another handler's frame.
+//!
+//! # Example
+//! ```ignore
+//! #[avr_device::interrupt(atmega328p)]
+//! fn ADC() {
+//!     // Clear ADIE first, so this handler cannot re-enter itself.
+//!     disable_adc_interrupt();
+//!     let sample = read_adc();
+//!
+//!     // The slow filter may be interrupted by the timer interrupt driving the stepper motor.
+//!     unsafe {
+//!         avr_hal_generic::interrupt::with_nesting(|| update_filter(sample));
+//!     }
+//!
+//!     enable_adc_interrupt();
+//! }
+//! ```
+//!
+//! # Ownership
+//! Every interrupt source has a second-level enable bit next to the global interrupt flag, e.g.
+//! `ADIE` in `ADCSRA`, `UDRIEn` in `UCSRnB`, or the `OCIEnx`/`TOIEn` bits in `TIMSKn`, and every
+//! vector has exactly one handler.  Two features which both use the same source, say a one-shot
+//! timer and a square wave on timer 1, or a background writer and a hand-written `USART_UDRE`
+//! handler, silently break each other: one of them disables the interrupt the other one relies
+//! on, or the handler serves only one of them.  The result is an interrupt which "stopped
+//! firing" for no obvious reason.
+//!
+//! The peripheral singletons already prevent most of this at compile time, as only one owner of
+//! a `TC1` or `USART0` can exist.  What remains are features sharing a vector without sharing a
+//! peripheral object, e.g. a timer split into a PWM and a tick interrupt, or code which pokes the
+//! enable bits through the PAC.  For those, [`claim()`] records at runtime which feature owns
+//! which interrupt vector, and reports the previous owner of a vector which is claimed twice:
+//!
+//! ```ignore
+//! use arduino_hal::pac::interrupt::Interrupt;
+//!
+//! let _tick = interrupt::claim(Interrupt::TIMER0_COMPA as u8, "system tick").unwrap();
+//!
+//! // Later, in some other part of the firmware:
+//! let _tick = interrupt::claim(Interrupt::TIMER0_COMPA as u8, "stepper")
+//!     .expect("TIMER0_COMPA"); // panics: claimed by "system tick"
+//! ```
+//!
+//! A claim lasts until the returned [`InterruptClaim`] is dropped, so keep it next to the
+//! feature using the interrupt, or make it permanent with [`InterruptClaim::forever()`].
+//!
+//! The interrupt-driven helpers of the HAL claim their vectors themselves and keep the claim for
+//! as long as they use the interrupt.  Claiming a vector which is already taken panics there, as
+//! their constructors can't report an error:
+//!
+//! | Helper | Vector | Claimed by |
+//! | --- | --- | --- |
+//! | `Usart::listen()` | `USARTn_RX`, `USARTn_UDRE`, `USARTn_TX` | `listen()` until `unlisten()` |
+//! | `BackgroundWriter` | `USARTn_UDRE` | `into_background_writer()` until `release()` |
+//! | One-shot timers | `TIMERn_COMPA` | `new()` until `release()` |
+//! | `Wdt::start_interrupt()` | `WDT` | the start until `stop()` or `start()` |
+//! | `Wdt::start_with_early_warning()` | `WDT` | the start until `stop()` or `start()` |
+//! | Timer-triggered ADC conversions | `ADC` | `SampledBuffer::start()` until `finish()` |
+//!
+//! So a hand-written handler for one of these vectors should claim it, too, and two helpers
+//! fighting over a vector fail right away instead of breaking each other:
+//!
+//! ```ignore
+//! let mut serial = serial.into_background_writer();
+//! // ...
+//! let _udre = interrupt::claim(Interrupt::USART_UDRE as u8, "debug console").unwrap();
+//! // panics: claimed by "BackgroundWriter"
+//! ```
+
+/// Run `f` with interrupts enabled, so other interrupts can preempt it.
+///
+/// Interrupts are enabled before calling `f` and the previous state of the global interrupt flag
+/// is restored afterwards.  Thus, inside an interrupt handler, interrupts are disabled again
+/// when this returns.  See the [module documentation](self) for the hazards.
+///
+/// # Safety
+/// - This must not be called within a critical section (e.g. from `interrupt::free()`), as that
+///   relies on interrupts staying disabled.
+/// - The caller must make sure the interrupt handler calling this cannot be re-entered while `f`
+///   runs, e.g. by disabling its interrupt source first.
+/// - `f` must not access state shared with other interrupt handlers outside of critical sections.
+#[inline(always)]
+pub unsafe fn with_nesting<F, R>(f: F) -> R
+where
+	F: FnOnce() -> R,
+{
+	let flag = avr_device::interrupt::disable_save();
+	avr_device::interrupt::enable();
+	let r = f();
+	avr_device::interrupt::disable();
+	avr_device::interrupt::restore(flag);
+	r
+}
+
+/// Number of interrupt vectors which can be claimed at the same time.
+pub const MAX_CLAIMS: usize = 8;
+
+type Claims = [Option<(u8, &'static str)>; MAX_CLAIMS];
+
+#[cfg(not(test))]
+static CLAIMS: avr_device::interrupt::Mutex<core::cell::Cell<Claims>> =
+	avr_device::interrupt::Mutex::new(core::cell::Cell::new([None; MAX_CLAIMS]));
+
+/// Access the table of claims in a critical section.
+#[cfg(not(test))]
+fn with_claims<R>(f: impl FnOnce(&mut Claims) -> R) -> R {
+	avr_device::interrupt::free(|cs| {
+		let cell = CLAIMS.borrow(cs);
+		let mut claims = cell.get();
+		let r = f(&mut claims);
+		cell.set(claims);
+		r
+	})
+}
+
+// Critical sections are not available on the host, each test thread gets its own table instead.
+#[cfg(test)]
+std::thread_local! {
+	static CLAIMS: core::cell::Cell<Claims> = const { core::cell::Cell::new([None; MAX_CLAIMS]) };
+}
+
+#[cfg(test)]
+fn with_claims<R>(f: impl FnOnce(&mut Claims) -> R) -> R {
+	CLAIMS.with(|cell| {
+		let mut claims = cell.get();
+		let r = f(&mut claims);
+		cell.set(claims);
+		r
+	})
+}
+
+/// Reasons a [`claim()`] failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClaimError {
+	/// The vector is already owned by the named feature.
+	Claimed { owner: &'static str },
+	/// [`MAX_CLAIMS`] vectors are claimed already.
+	TooManyClaims,
+}
+
+/// Ownership of an interrupt vector, returned by [`claim()`].  The vector is released again when
+/// this is dropped.
+#[must_use = "the claim is released right away if the InterruptClaim is dropped"]
+#[derive(Debug)]
+pub struct InterruptClaim {
+	vector: u8,
+}
+
+impl InterruptClaim {
+	/// The claimed vector number.
+	pub fn vector(&self) -> u8 {
+		self.vector
+	}
+
+	/// The name of the owner given to [`claim()`].
+	pub fn owner(&self) -> &'static str {
+		owner(self.vector).unwrap_or("")
+	}
+
+	/// Keep the vector claimed for the rest of the runtime.
+	pub fn forever(self) {
+		core::mem::forget(self);
+	}
+}
+
+impl Drop for InterruptClaim {
+	fn drop(&mut self) {
+		with_claims(|claims| {
+			for entry in claims.iter_mut() {
+				if matches!(entry, Some((vector, _)) if *vector == self.vector) {
+					*entry = None;
+				}
+			}
+		});
+	}
+}
+
+/// Claim interrupt vector `vector` (its number in the vector table, e.g. `Interrupt::ADC as u8`)
+/// for the feature named `owner`.  See the [module documentation](self#ownership).
+pub fn claim(vector: u8, owner: &'static str) -> Result<InterruptClaim, ClaimError> {
+	with_claims(|claims| {
+		if let Some(owner) = find_owner(claims, vector) {
+			return Err(ClaimError::Claimed { owner });
+		}
+		let free = claims
+			.iter_mut()
+			.find(|entry| entry.is_none())
+			.ok_or(ClaimError::TooManyClaims)?;
+		*free = Some((vector, owner));
+		Ok(InterruptClaim { vector })
+	})
+}
+
+/// Like [`claim()`], but panics if the vector is taken already or too many vectors are claimed.
+///
+/// This is what the interrupt-driven helpers of the HAL use.
+#[track_caller]
+pub fn claim_or_panic(vector: u8, owner: &'static str) -> InterruptClaim {
+	match claim(vector, owner) {
+		Ok(claim) => claim,
+		Err(ClaimError::Claimed { .. }) => panic!("interrupt vector claimed twice"),
+		Err(ClaimError::TooManyClaims) => panic!("too many interrupt vectors claimed"),
+	}
+}
+
+/// The current owner of interrupt vector `vector`, if it is claimed.
+pub fn owner(vector: u8) -> Option<&'static str> {
+	with_claims(|claims| find_owner(claims, vector))
+}
+
+fn find_owner(claims: &Claims, vector: u8) -> Option<&'static str> {
+	claims.iter().find_map(|entry| match entry {
+		Some((claimed, owner)) if *claimed == vector => Some(*owner),
+		_ => None,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// `USART_UDRE` on the ATmega328P.
+	const UDRE: u8 = 19;
+
+	#[test]
+	fn conflicting_features() {
+		let writer = claim(UDRE, "BackgroundWriter").unwrap();
+		assert_eq!(writer.owner(), "BackgroundWriter");
+		assert_eq!(
+			claim(UDRE, "Usart::listen").unwrap_err(),
+			ClaimError::Claimed {
+				owner: "BackgroundWriter"
+			}
+		);
+		drop(writer);
+		assert_eq!(owner(UDRE), None);
+		let listen = claim(UDRE, "Usart::listen").unwrap();
+		assert_eq!(owner(UDRE), Some("Usart::listen"));
+		listen.forever();
+		assert_eq!(owner(UDRE), Some("Usart::listen"));
+	}
+
+	#[test]
+	#[should_panic(expected = "interrupt vector claimed twice")]
+	fn claim_or_panic_on_conflict() {
+		let _oneshot = claim_or_panic(11, "Timer1OneShot");
+		let _other = claim_or_panic(11, "Timer1OneShot");
+	}
+
+	#[test]
+	fn too_many_claims() {
+		let claims: [InterruptClaim; MAX_CLAIMS] =
+			core::array::from_fn(|i| claim(i as u8, "test").unwrap());
+		assert_eq!(
+			claim(MAX_CLAIMS as u8, "test").unwrap_err(),
+			ClaimError::TooManyClaims
+		);
+		drop(claims);
+		assert!(claim(MAX_CLAIMS as u8, "test").is_ok());
+	}
+}
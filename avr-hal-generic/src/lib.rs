@@ -1,6 +1,9 @@
 #![no_std]
 #![feature(asm_experimental_arch)]
 
+#[cfg(test)]
+extern crate std;
+
 pub use embedded_hal as hal;
 pub use embedded_hal_v0 as hal_v0;
 
@@ -13,17 +16,34 @@ pub use paste;
 
 pub mod adc;
 pub mod clock;
+pub mod cobs;
+pub mod counter;
 pub mod delay;
+pub mod drivers;
 pub mod eeprom;
+pub mod executor;
 pub mod i2c;
+pub mod interrupt;
+pub mod log;
+pub mod modbus;
+pub mod noinit;
+pub mod oneshot;
+pub mod pin_change;
 pub mod port;
+pub mod progmem;
+pub mod rate_limit;
+pub mod shift_register;
 pub mod simple_pwm;
+pub mod soft_pwm;
 pub mod spi;
+pub mod square_wave;
 pub mod usart;
+pub mod util;
 pub mod wdt;
 
 /// Prelude containing all HAL traits
 pub mod prelude {
+	pub use crate::counter::IntoCounter as _avr_hal_generic_counter_IntoCounter;
 	pub use crate::hal_v0::prelude::*;
 	pub use ufmt::uWrite as _ufmt_uWrite;
 	pub use unwrap_infallible::UnwrapInfallible as _unwrap_infallible_UnwrapInfallible;
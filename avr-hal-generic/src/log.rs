@@ -0,0 +1,192 @@
+//! Lightweight logging macros on top of `ufmt`
+//!
+//! The [`error!`], [`warn!`], [`info!`] and [`debug!`] macros format their arguments with `ufmt`
+//! and write them, followed by a newline, to a globally registered output.  Any [`ufmt::uWrite`]
+//! with an infallible error type can serve as the output, most commonly a [`Usart`] or a USB
+//! serial port.  Until an output is registered, log messages are silently dropped.
+//!
+//! # Compile-Time Filtering
+//! Each level is enabled by a cargo feature.  Enabling a level also enables all levels above it:
+//!
+//! | Feature | Enabled macros |
+//! | --- | --- |
+//! | `log-error` | `error!` |
+//! | `log-warn` | `error!`, `warn!` |
+//! | `log-info` | `error!`, `warn!`, `info!` |
+//! | `log-debug` | `error!`, `warn!`, `info!`, `debug!` |
+//!
+//! Disabled macros don't format anything, so neither the format strings nor the formatting code
+//! end up in the binary.  Their arguments are still evaluated and then discarded, so side effects
+//! like `info!("{}", counter.next())` happen with and without the feature.
+//!
+//! # Example
+//! ```ignore
+//! static mut SERIAL: Option<arduino_hal::hal::usart::Usart0<arduino_hal::DefaultClock>> = None;
+//!
+//! let serial = arduino_hal::default_serial!(dp, pins, 57600);
+//! let serial = unsafe { (*core::ptr::addr_of_mut!(SERIAL)).insert(serial) };
+//! arduino_hal::log::set_output(serial);
+//!
+//! let x = 42;
+//! arduino_hal::info!("x = {}", x);
+//! ```
+//!
+//! The macros use `ufmt` from the calling crate, which thus needs a dependency on `ufmt`.  Output
+//! happens inside a critical section, so interrupts are delayed while a message is written.
+//!
+//! [`Usart`]: crate::usart::Usart
+use core::cell::RefCell;
+use core::convert::Infallible;
+
+/// Type of output that log messages can be written to.
+pub type Output = dyn ufmt::uWrite<Error = Infallible> + Send;
+
+static OUTPUT: avr_device::interrupt::Mutex<RefCell<Option<&'static mut Output>>> =
+	avr_device::interrupt::Mutex::new(RefCell::new(None));
+
+/// Register `output` as the destination of all log messages.
+///
+/// A previously registered output is replaced and returned.
+pub fn set_output(output: &'static mut Output) -> Option<&'static mut Output> {
+	avr_device::interrupt::free(|cs| OUTPUT.borrow(cs).borrow_mut().replace(output))
+}
+
+/// Unregister the current output and return it.
+pub fn take_output() -> Option<&'static mut Output> {
+	avr_device::interrupt::free(|cs| OUTPUT.borrow(cs).borrow_mut().take())
+}
+
+/// Run `f` with the registered output, if there is one.
+///
+/// This is used by the logging macros and usually does not need to be called directly.
+#[doc(hidden)]
+pub fn with_output<F: FnOnce(&mut Output)>(f: F) {
+	avr_device::interrupt::free(|cs| {
+		if let Some(output) = OUTPUT.borrow(cs).borrow_mut().as_mut() {
+			f(&mut **output);
+		}
+	})
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log {
+	($prefix:literal, $($arg:tt)+) => {
+		$crate::log::with_output(|output| {
+			let _ = ufmt::uwrite!(output, $prefix);
+			let _ = ufmt::uwriteln!(output, $($arg)+);
+		})
+	};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log_discard {
+	($($arg:expr),*) => {{
+		$(let _ = &$arg;)*
+	}};
+}
+
+/// Log a message at the error level.
+///
+/// Only emits code with the `log-error` feature.  See [the module docs](crate::log).
+#[cfg(feature = "log-error")]
+#[macro_export]
+macro_rules! error {
+	($($arg:tt)+) => {
+		$crate::__log!("[ERROR] ", $($arg)+)
+	};
+}
+
+/// Log a message at the error level.
+///
+/// Only emits code with the `log-error` feature.  See [the module docs](crate::log).
+#[cfg(not(feature = "log-error"))]
+#[macro_export]
+macro_rules! error {
+	($fmt:literal $(, $arg:expr)* $(,)?) => {
+		$crate::__log_discard!($($arg),*)
+	};
+}
+
+/// Log a message at the warning level.
+///
+/// Only emits code with the `log-warn` feature.  See [the module docs](crate::log).
+#[cfg(feature = "log-warn")]
+#[macro_export]
+macro_rules! warn {
+	($($arg:tt)+) => {
+		$crate::__log!("[WARN] ", $($arg)+)
+	};
+}
+
+/// Log a message at the warning level.
+///
+/// Only emits code with the `log-warn` feature.  See [the module docs](crate::log).
+#[cfg(not(feature = "log-warn"))]
+#[macro_export]
+macro_rules! warn {
+	($fmt:literal $(, $arg:expr)* $(,)?) => {
+		$crate::__log_discard!($($arg),*)
+	};
+}
+
+/// Log a message at the info level.
+///
+/// Only emits code with the `log-info` feature.  See [the module docs](crate::log).
+#[cfg(feature = "log-info")]
+#[macro_export]
+macro_rules! info {
+	($($arg:tt)+) => {
+		$crate::__log!("[INFO] ", $($arg)+)
+	};
+}
+
+/// Log a message at the info level.
+///
+/// Only emits code with the `log-info` feature.  See [the module docs](crate::log).
+#[cfg(not(feature = "log-info"))]
+#[macro_export]
+macro_rules! info {
+	($fmt:literal $(, $arg:expr)* $(,)?) => {
+		$crate::__log_discard!($($arg),*)
+	};
+}
+
+/// Log a message at the debug level.
+///
+/// Only emits code with the `log-debug` feature.  See [the module docs](crate::log).
+#[cfg(feature = "log-debug")]
+#[macro_export]
+macro_rules! debug {
+	($($arg:tt)+) => {
+		$crate::__log!("[DEBUG] ", $($arg)+)
+	};
+}
+
+/// Log a message at the debug level.
+///
+/// Only emits code with the `log-debug` feature.  See [the module docs](crate::log).
+#[cfg(not(feature = "log-debug"))]
+#[macro_export]
+macro_rules! debug {
+	($fmt:literal $(, $arg:expr)* $(,)?) => {
+		$crate::__log_discard!($($arg),*)
+	};
+}
+
+#[cfg(test)]
+mod tests {
+	#[cfg(not(feature = "log-debug"))]
+	#[test]
+	fn disabled_macro_evaluates_arguments() {
+		let mut calls = 0;
+		let mut next = || {
+			calls += 1;
+			calls
+		};
+		crate::debug!("{} {:?}", next(), next(),);
+		crate::debug!("no arguments");
+		assert_eq!(calls, 2);
+	}
+}
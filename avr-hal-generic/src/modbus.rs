@@ -0,0 +1,300 @@
+//! Modbus RTU slave framing
+//!
+//! Modbus RTU frames ("ADUs") consist of the slave address, a function code, up to 252 bytes of
+//! data, and a CRC-16 transmitted low byte first.  There are no start or end markers: a frame
+//! ends when the line stays silent for 3.5 character times, which [`IdleDetector`] detects.
+//!
+//! [`ModbusRtuSlave`] collects the received bytes, and once the line is idle, checks the address
+//! and the CRC and hands out the function code and data as a [`Request`].  Interpreting the
+//! request (registers, coils, ...) is up to the application.  The response is framed and
+//! checksummed by [`ModbusRtuSlave::send_response()`] or [`ModbusRtuSlave::send_exception()`].
+//!
+//! ```ignore
+//! let mut slave = ModbusRtuSlave::new(17);
+//! let mut idle = IdleDetector::modbus_rtu(9600, 100);
+//!
+//! loop {
+//!     // Usually done in the USART_RX and timer interrupts
+//!     if let Ok(byte) = serial.read() {
+//!         slave.receive(byte);
+//!         idle.byte_received();
+//!     }
+//!
+//!     if idle.idle_since_last_byte() && slave.has_data() {
+//!         match slave.finish_frame() {
+//!             Ok(request) if request.function() == 0x03 => {
+//!                 let response = read_holding_registers(request.data());
+//!                 if !request.is_broadcast() {
+//!                     slave.send_response(&mut serial, 0x03, &response)?;
+//!                 }
+//!             }
+//!             Ok(request) if !request.is_broadcast() => {
+//!                 slave.send_exception(&mut serial, request.function(), 0x01)?;
+//!             }
+//!             // A broadcast, not for us, or corrupted (the master will retry)
+//!             _ => (),
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! # RS-485
+//! Modbus RTU usually runs over a half-duplex RS-485 bus, where the transceiver's driver enable
+//! (`DE`) and receiver enable (`/RE`) pins are connected to one GPIO.  The pin must be driven
+//! high before sending a response and low again only after the last stop bit left the shift
+//! register, i.e. after [`Usart::flush()`][crate::usart::Usart::flush] returned.  Releasing it
+//! earlier cuts off the end of the CRC, releasing it much later collides with the master's next
+//! request.  [`Usart::with_rs485_direction()`][crate::usart::Usart::with_rs485_direction] takes
+//! care of this.
+//!
+//! [`IdleDetector`]: crate::usart::IdleDetector
+use crate::util::crc16_modbus;
+use crate::util::crc16_modbus_update;
+
+/// Maximum size of a Modbus RTU frame, including address and CRC.
+pub const MAX_FRAME_LEN: usize = 256;
+
+/// Address used by the master to send a request to all slaves.  Broadcasts are never answered.
+pub const BROADCAST_ADDRESS: u8 = 0;
+
+/// Reasons for dropping a received frame.
+#[derive(ufmt::derive::uDebug, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameError {
+	/// The frame is shorter than address, function code, and CRC.
+	TooShort,
+	/// The frame is longer than [`MAX_FRAME_LEN`].
+	Overflow,
+	/// The CRC does not match, the frame was corrupted on the bus.
+	Crc,
+	/// The frame is addressed to another slave.
+	OtherAddress,
+}
+
+/// A received request with a valid CRC, see [`ModbusRtuSlave::finish_frame()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Request<'a> {
+	address: u8,
+	function: u8,
+	data: &'a [u8],
+}
+
+impl<'a> Request<'a> {
+	/// The function code, e.g. `0x03` for "read holding registers".
+	pub fn function(&self) -> u8 {
+		self.function
+	}
+
+	/// The data following the function code, without the CRC.
+	pub fn data(&self) -> &'a [u8] {
+		self.data
+	}
+
+	/// Whether the request was sent to all slaves.  Broadcasts must not be answered.
+	pub fn is_broadcast(&self) -> bool {
+		self.address == BROADCAST_ADDRESS
+	}
+}
+
+/// Framing and CRC handling for a Modbus RTU slave.  See the [module documentation](self).
+pub struct ModbusRtuSlave {
+	address: u8,
+	buffer: [u8; MAX_FRAME_LEN],
+	len: usize,
+	overflow: bool,
+}
+
+impl ModbusRtuSlave {
+	/// Create a slave listening on `address` (1 to 247).
+	pub fn new(address: u8) -> Self {
+		Self {
+			address,
+			buffer: [0; MAX_FRAME_LEN],
+			len: 0,
+			overflow: false,
+		}
+	}
+
+	/// The address this slave listens on.
+	pub fn address(&self) -> u8 {
+		self.address
+	}
+
+	/// Append a received byte to the current frame.
+	pub fn receive(&mut self, byte: u8) {
+		if self.len < MAX_FRAME_LEN {
+			self.buffer[self.len] = byte;
+			self.len += 1;
+		} else {
+			self.overflow = true;
+		}
+	}
+
+	/// Whether any bytes were received since the last call to
+	/// [`finish_frame()`][ModbusRtuSlave::finish_frame].
+	pub fn has_data(&self) -> bool {
+		self.len > 0 || self.overflow
+	}
+
+	/// End the current frame after the line became idle, and check it.
+	///
+	/// Returns the request if the frame is complete, addressed to this slave (or a broadcast),
+	/// and its CRC is valid.  In any case, the next received byte starts a new frame.
+	pub fn finish_frame(&mut self) -> Result<Request<'_>, FrameError> {
+		let len = core::mem::replace(&mut self.len, 0);
+		if core::mem::replace(&mut self.overflow, false) {
+			return Err(FrameError::Overflow);
+		}
+		if len < 4 {
+			return Err(FrameError::TooShort);
+		}
+
+		let (frame, crc) = self.buffer[..len].split_at(len - 2);
+		if crc16_modbus(frame).to_le_bytes() != crc {
+			return Err(FrameError::Crc);
+		}
+		let address = frame[0];
+		if address != self.address && address != BROADCAST_ADDRESS {
+			return Err(FrameError::OtherAddress);
+		}
+
+		Ok(Request {
+			address,
+			function: frame[1],
+			data: &frame[2..],
+		})
+	}
+
+	/// Frame a response into `out` and return the used part of it.
+	///
+	/// # Panics
+	/// Panics if `out` is shorter than `data.len() + 4`.
+	pub fn encode_response<'o>(
+		&self,
+		function: u8,
+		data: &[u8],
+		out: &'o mut [u8],
+	) -> &'o [u8] {
+		let len = data.len() + 4;
+		out[0] = self.address;
+		out[1] = function;
+		out[2..len - 2].copy_from_slice(data);
+		let crc = crc16_modbus(&out[..len - 2]);
+		out[len - 2..len].copy_from_slice(&crc.to_le_bytes());
+		&out[..len]
+	}
+
+	/// Send a response with the given function code and data, followed by the CRC.
+	///
+	/// The CRC is computed while sending, so no additional buffer is needed.  This returns as
+	/// soon as the last byte was handed to the USART; flush it before switching an RS-485
+	/// transceiver back to receiving.
+	pub fn send_response<W>(
+		&self,
+		serial: &mut W,
+		function: u8,
+		data: &[u8],
+	) -> Result<(), W::Error>
+	where
+		W: embedded_hal_v0::serial::Write<u8>,
+	{
+		let header = [self.address, function];
+		let crc = crc16_modbus_update(crc16_modbus(&header), data);
+		for &byte in header.iter().chain(data).chain(&crc.to_le_bytes()) {
+			nb::block!(serial.write(byte))?;
+		}
+		Ok(())
+	}
+
+	/// Send an exception response for `function`, e.g. exception code `0x01` for an illegal
+	/// function or `0x02` for an illegal data address.
+	pub fn send_exception<W>(
+		&self,
+		serial: &mut W,
+		function: u8,
+		code: u8,
+	) -> Result<(), W::Error>
+	where
+		W: embedded_hal_v0::serial::Write<u8>,
+	{
+		self.send_response(serial, function | 0x80, &[code])
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::vec::Vec;
+
+	struct MockSerial(Vec<u8>);
+
+	impl embedded_hal_v0::serial::Write<u8> for MockSerial {
+		type Error = core::convert::Infallible;
+
+		fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+			self.0.push(byte);
+			Ok(())
+		}
+
+		fn flush(&mut self) -> nb::Result<(), Self::Error> {
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn frame_round_trip() {
+		let master = ModbusRtuSlave::new(17);
+		let mut out = [0; MAX_FRAME_LEN];
+		let frame = master.encode_response(0x03, &[0x00, 0x6b, 0x00, 0x03], &mut out);
+		assert_eq!(frame, [0x11, 0x03, 0x00, 0x6b, 0x00, 0x03, 0x76, 0x87]);
+
+		let mut serial = MockSerial(Vec::new());
+		master.send_response(&mut serial, 0x03, &[0x00, 0x6b, 0x00, 0x03])
+			.unwrap();
+		assert_eq!(serial.0, frame);
+
+		let mut slave = ModbusRtuSlave::new(17);
+		assert!(!slave.has_data());
+		for &byte in frame {
+			slave.receive(byte);
+		}
+		assert!(slave.has_data());
+		let request = slave.finish_frame().unwrap();
+		assert_eq!(request.function(), 0x03);
+		assert_eq!(request.data(), [0x00, 0x6b, 0x00, 0x03]);
+		assert!(!request.is_broadcast());
+		assert!(!slave.has_data());
+	}
+
+	#[test]
+	fn frame_errors() {
+		let mut out = [0; MAX_FRAME_LEN];
+		let frame = ModbusRtuSlave::new(17).encode_response(0x03, &[0x01], &mut out);
+
+		let mut slave = ModbusRtuSlave::new(18);
+		frame.iter().for_each(|&byte| slave.receive(byte));
+		assert_eq!(slave.finish_frame(), Err(FrameError::OtherAddress));
+
+		let mut slave = ModbusRtuSlave::new(17);
+		frame.iter().for_each(|&byte| slave.receive(byte ^ 0x01));
+		assert_eq!(slave.finish_frame(), Err(FrameError::Crc));
+
+		frame[..3].iter().for_each(|&byte| slave.receive(byte));
+		assert_eq!(slave.finish_frame(), Err(FrameError::TooShort));
+
+		(0..=MAX_FRAME_LEN).for_each(|_| slave.receive(0x11));
+		assert_eq!(slave.finish_frame(), Err(FrameError::Overflow));
+	}
+
+	#[test]
+	fn exception_response() {
+		let mut serial = MockSerial(Vec::new());
+		ModbusRtuSlave::new(1)
+			.send_exception(&mut serial, 0x03, 0x02)
+			.unwrap();
+		let mut slave = ModbusRtuSlave::new(1);
+		serial.0.iter().for_each(|&byte| slave.receive(byte));
+		let request = slave.finish_frame().unwrap();
+		assert_eq!(request.function(), 0x83);
+		assert_eq!(request.data(), [0x02]);
+	}
+}
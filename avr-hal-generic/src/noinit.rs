@@ -0,0 +1,134 @@
+//! Variables which survive a reset
+//!
+//! The startup code zeroes `.bss` and copies the initial values of `.data` from flash on every
+//! reset, so normal `static`s start over each time.  A crash reason or a reboot counter, which the
+//! firmware wants to look at after a watchdog or [software reset][crate::wdt::Wdt::reset], has to
+//! live somewhere else: the [`noinit!`][crate::noinit!] macro places a `static` in the `.noinit`
+//! section, which the linker script reserves in RAM but the startup code leaves alone.
+//!
+//! ```ignore
+//! avr_hal_generic::noinit! {
+//!     static noinit REBOOTS: u16;
+//! }
+//!
+//! let (dp, reset_cause) = Init::new().capture_reset_cause().disable_watchdog().run(dp);
+//! if reset_cause.unwrap().power_on {
+//!     REBOOTS.clear();
+//! }
+//! let reboots = REBOOTS.get().unwrap_or(0);
+//! REBOOTS.set(reboots + 1);
+//! ```
+//!
+//! # Retention
+//! The contents survive every reset which keeps the supply voltage up: watchdog, reset pin,
+//! software reset, and usually a brown-out reset, as long as the voltage didn't drop too low for
+//! the RAM to keep its contents.  They do **not** survive a power cycle; after power-on, the RAM
+//! holds random data.
+//!
+//! [`NoInit`] stores a marker next to the value and only returns values which were
+//! [set][NoInit::set] before.  This reliably detects the state after flashing new firmware or an
+//! unused variable, but the random RAM contents after power-on match the marker with a
+//! probability of 1 in 65536.  Clear the variables when the reset cause (`ResetCause` in the
+//! `init` module of `atmega-hal`) says power-on to be sure.
+//!
+//! The placement of the variables depends on the firmware, so after an update, the previous
+//! firmware's values can end up in different variables.  Store a version along with them if
+//! that matters.
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::ptr;
+
+/// Marker stored next to a value that was set.
+const VALID: u16 = 0x5a3c;
+
+#[repr(C)]
+struct Slot<T> {
+	marker: u16,
+	value: T,
+}
+
+/// A variable in RAM which is not initialized by the startup code.  See the
+/// [module documentation](self).
+///
+/// A `NoInit` is created with the [`noinit!`][crate::noinit!] macro.  All accesses happen with
+/// interrupts disabled, so it can be shared with interrupt handlers.
+pub struct NoInit<T> {
+	slot: UnsafeCell<MaybeUninit<Slot<T>>>,
+}
+
+// SAFETY: All accesses are made in a critical section.
+unsafe impl<T: Send> Sync for NoInit<T> {}
+
+impl<T: Copy> NoInit<T> {
+	/// An uninitialized variable.
+	///
+	/// # Safety
+	/// The `static` holding the result must be placed in the `.noinit` section.  Use the
+	/// [`noinit!`][crate::noinit!] macro instead.
+	#[doc(hidden)]
+	pub const unsafe fn new() -> Self {
+		Self {
+			slot: UnsafeCell::new(MaybeUninit::uninit()),
+		}
+	}
+
+	/// The value, if it was [set][Self::set] before the last reset (or since).
+	pub fn get(&self) -> Option<T> {
+		avr_device::interrupt::free(|_| {
+			let slot = self.slot.get() as *const Slot<T>;
+			// SAFETY: The memory is reserved for the slot, and the value is only read once the
+			// marker shows it was written by set().  Volatile, so the compiler can't assume
+			// anything about memory it never saw written.
+			unsafe {
+				if ptr::read_volatile(ptr::addr_of!((*slot).marker)) == VALID {
+					Some(ptr::read_volatile(ptr::addr_of!((*slot).value)))
+				} else {
+					None
+				}
+			}
+		})
+	}
+
+	/// Store `value`, to be read after the next reset.
+	pub fn set(&self, value: T) {
+		avr_device::interrupt::free(|_| {
+			let slot = self.slot.get() as *mut Slot<T>;
+			// SAFETY: See get().
+			unsafe {
+				ptr::write_volatile(ptr::addr_of_mut!((*slot).value), value);
+				ptr::write_volatile(ptr::addr_of_mut!((*slot).marker), VALID);
+			}
+		})
+	}
+
+	/// Forget the value, so [`get()`][Self::get] returns `None`.
+	pub fn clear(&self) {
+		avr_device::interrupt::free(|_| {
+			let slot = self.slot.get() as *mut Slot<T>;
+			// SAFETY: See get().
+			unsafe { ptr::write_volatile(ptr::addr_of_mut!((*slot).marker), 0) };
+		})
+	}
+}
+
+/// Place `static`s in RAM which is kept across resets, see the [`noinit`][crate::noinit]
+/// module.
+///
+/// ```ignore
+/// avr_hal_generic::noinit! {
+///     /// Address of the last panic, for the crash report after the reset
+///     pub static noinit PANIC_LOCATION: u16;
+/// }
+/// ```
+#[macro_export]
+macro_rules! noinit {
+    ($(
+        $(#[$attr:meta])*
+        $vis:vis static noinit $name:ident: $ty:ty;
+    )*) => {$(
+        $(#[$attr])*
+        #[link_section = ".noinit"]
+        // SAFETY: The static is placed in `.noinit` right above.
+        $vis static $name: $crate::noinit::NoInit<$ty> = unsafe { $crate::noinit::NoInit::new() };
+    )*};
+}
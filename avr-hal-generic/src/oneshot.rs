@@ -0,0 +1,251 @@
+//! One-shot timer callbacks
+//!
+//! A one-shot timer calls a function once, a certain number of microseconds in the future, from
+//! the timer's compare match interrupt.  Unlike a busy-wait delay, the CPU is free to do other
+//! things in the meantime, and unlike a periodic timer interrupt, the timer disarms itself after
+//! firing.  This is useful for precise protocol timing, e.g. ending a pulse or sampling a line
+//! at a fixed offset after an edge.
+//!
+//! The timer runs freely with the prescaler given at construction.  Scheduling sets the compare
+//! register to the current counter value plus the interval and enables the compare match
+//! interrupt.  The interrupt handler is not defined by the HAL, the application has to define it
+//! and call `on_interrupt()` from it:
+//!
+//! ```ignore
+//! #[avr_device::interrupt(atmega328p)]
+//! fn TIMER1_COMPA() {
+//!     Timer1OneShot::on_interrupt();
+//! }
+//!
+//! fn end_pulse() {
+//!     // ...
+//! }
+//!
+//! let mut oneshot = Timer1OneShot::new::<CoreClock>(dp.TC1, Prescaler::Prescale8);
+//! unsafe { avr_device::interrupt::enable() };
+//!
+//! start_pulse();
+//! oneshot.schedule(500, end_pulse).unwrap();
+//! ```
+//!
+//! The one-shot timer takes over the timer's compare match A interrupt, so the vector cannot be
+//! shared with other features.  It claims the vector from `new()` until `release()`; see
+//! [interrupt ownership](crate::interrupt#ownership).
+//!
+//! # Schedulable intervals
+//! The interval is converted to timer ticks, which must fit the 16-bit compare register:
+//!
+//! | Prescaler | Resolution at 16 MHz | Maximum interval at 16 MHz |
+//! | --- | --- | --- |
+//! | `Direct` | 0.0625 µs | 4.1 ms |
+//! | `Prescale8` | 0.5 µs | 32.7 ms |
+//! | `Prescale64` | 4 µs | 262 ms |
+//! | `Prescale256` | 16 µs | 1.05 s |
+//! | `Prescale1024` | 64 µs | 4.19 s |
+//!
+//! The shortest interval is one tick.  The callback runs after the interval has passed, plus the
+//! interrupt latency (a few microseconds at 16 MHz, more if another handler is running).  So
+//! intervals below about 10 µs are dominated by the latency rather than the timer.
+//!
+//! With short intervals and a fast-running timer, the counter can pass the compare value while
+//! it is still being written.  The compare match then would only happen after the counter
+//! wrapped around, so in this case `schedule()` disarms the timer again and returns
+//! [`ScheduleError::Missed`].  The callback is not called, the caller decides what to do, e.g.
+//! call it right away.  Doing so from `schedule()` itself would run the callback while the
+//! caller may still hold borrows the callback needs, like the `RefCell` of a driver in the
+//! [stepper](crate::drivers) example.
+
+/// Error returned by `schedule()`.
+#[derive(ufmt::derive::uDebug, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleError {
+	/// The requested interval is zero ticks long or does not fit the 16-bit timer at the chosen
+	/// prescaler.
+	IntervalOutOfRange,
+	/// The interval passed while arming the timer, so the callback will not be called.
+	Missed,
+}
+
+/// Convert an interval in microseconds to timer ticks.
+#[doc(hidden)]
+pub fn ticks_for(micros: u32, tick_hz: u32) -> Result<u16, ScheduleError> {
+	let ticks = (u64::from(micros) * u64::from(tick_hz) + 500_000) / 1_000_000;
+	match u16::try_from(ticks) {
+		Ok(0) | Err(_) => Err(ScheduleError::IntervalOutOfRange),
+		Ok(ticks) => Ok(ticks),
+	}
+}
+
+/// Whether a counter which read `armed_at` right before the compare register was set to
+/// `armed_at + ticks` has already passed the compare value when it reads `counter`.
+#[doc(hidden)]
+pub fn compare_passed(armed_at: u16, ticks: u16, counter: u16) -> bool {
+	counter.wrapping_sub(armed_at) >= ticks
+}
+
+#[macro_export]
+macro_rules! impl_oneshot {
+    (
+        $(#[$oneshot_attr:meta])*
+        pub struct $OneShot:ident {
+            timer: $TIMER:ty,
+            vector: $vector:expr,
+            init: |$init_timer:ident, $prescaler:ident| $init_block:block,
+            arm: |$arm_timer:ident, $ticks:ident| $arm_block:block,
+            disarm: |$disarm_timer:ident| $disarm_block:block,
+        }
+    ) => {
+        $crate::paste::paste! {
+            static [<$OneShot:upper _CALLBACK>]: $crate::avr_device::interrupt::Mutex<
+                core::cell::Cell<Option<fn()>>,
+            > = $crate::avr_device::interrupt::Mutex::new(core::cell::Cell::new(None));
+
+            $(#[$oneshot_attr])*
+            pub struct $OneShot {
+                timer: $TIMER,
+                tick_hz: u32,
+                _claim: $crate::interrupt::InterruptClaim,
+            }
+
+            impl $OneShot {
+                /// Start the timer running freely with the given prescaler.
+                ///
+                /// # Panics
+                /// Panics if another feature claimed the compare match vector already.
+                pub fn new<CLOCK: $crate::clock::Clock>(
+                    timer: $TIMER,
+                    prescaler: $crate::simple_pwm::Prescaler,
+                ) -> Self {
+                    let claim = $crate::interrupt::claim_or_panic($vector, stringify!($OneShot));
+                    {
+                        let $init_timer = &timer;
+                        let $prescaler = prescaler;
+                        $init_block
+                    }
+                    Self {
+                        timer,
+                        tick_hz: CLOCK::FREQ / prescaler.divider(),
+                        _claim: claim,
+                    }
+                }
+
+                /// Call `callback` once, `micros` microseconds from now.
+                ///
+                /// A callback which is still pending is replaced.  The callback runs in the
+                /// interrupt handler, so it should be short.  If the interval already passed
+                /// while arming the timer, nothing is scheduled and
+                /// [`ScheduleError::Missed`]($crate::oneshot::ScheduleError::Missed) is returned,
+                /// see [Schedulable intervals]($crate::oneshot#schedulable-intervals).
+                pub fn schedule(
+                    &mut self,
+                    micros: u32,
+                    callback: fn(),
+                ) -> Result<(), $crate::oneshot::ScheduleError> {
+                    let ticks = $crate::oneshot::ticks_for(micros, self.tick_hz)?;
+                    $crate::avr_device::interrupt::free(|cs| {
+                        [<$OneShot:upper _CALLBACK>].borrow(cs).set(Some(callback));
+                        // The arm block evaluates to whether the compare match was missed.
+                        let missed: bool = {
+                            let $arm_timer = &self.timer;
+                            let $ticks = ticks;
+                            $arm_block
+                        };
+                        if missed {
+                            let $disarm_timer = &self.timer;
+                            $disarm_block
+                            [<$OneShot:upper _CALLBACK>].borrow(cs).set(None);
+                            Err($crate::oneshot::ScheduleError::Missed)
+                        } else {
+                            Ok(())
+                        }
+                    })
+                }
+
+                /// Cancel a pending callback.
+                pub fn cancel(&mut self) {
+                    $crate::avr_device::interrupt::free(|cs| {
+                        let $disarm_timer = &self.timer;
+                        $disarm_block
+                        [<$OneShot:upper _CALLBACK>].borrow(cs).set(None);
+                    });
+                }
+
+                /// Whether a callback is scheduled and has not run yet.
+                pub fn is_pending(&self) -> bool {
+                    $crate::avr_device::interrupt::free(|cs| {
+                        [<$OneShot:upper _CALLBACK>].borrow(cs).get().is_some()
+                    })
+                }
+
+                /// Disarm the timer and run the pending callback.
+                ///
+                /// This must be called from the compare match interrupt handler.
+                pub fn on_interrupt() {
+                    let callback = $crate::avr_device::interrupt::free(|cs| {
+                        // SAFETY: Only the interrupt enable bit is modified, inside a critical
+                        // section.
+                        let $disarm_timer = unsafe { &*<$TIMER>::ptr() };
+                        $disarm_block
+                        [<$OneShot:upper _CALLBACK>].borrow(cs).take()
+                    });
+                    if let Some(callback) = callback {
+                        callback();
+                    }
+                }
+
+                /// Cancel a pending callback, release the vector, and return the timer peripheral.
+                pub fn release(mut self) -> $TIMER {
+                    self.cancel();
+                    self.timer
+                }
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn ticks_for_rounds() {
+		// 16 MHz / 8: 2 ticks per µs
+		assert_eq!(ticks_for(500, 2_000_000), Ok(1000));
+		// 16 MHz / 64: 4 µs per tick, 6 µs rounds to 2 ticks
+		assert_eq!(ticks_for(6, 250_000), Ok(2));
+		assert_eq!(ticks_for(5, 250_000), Ok(1));
+	}
+
+	#[test]
+	fn ticks_for_range() {
+		assert_eq!(
+			ticks_for(0, 2_000_000),
+			Err(ScheduleError::IntervalOutOfRange)
+		);
+		// Rounds to zero ticks
+		assert_eq!(
+			ticks_for(1, 250_000),
+			Err(ScheduleError::IntervalOutOfRange)
+		);
+		assert_eq!(ticks_for(32_767, 2_000_000), Ok(65_534));
+		assert_eq!(
+			ticks_for(32_768, 2_000_000),
+			Err(ScheduleError::IntervalOutOfRange)
+		);
+		// Doesn't overflow for long intervals at high tick rates
+		assert_eq!(
+			ticks_for(u32::MAX, 16_000_000),
+			Err(ScheduleError::IntervalOutOfRange)
+		);
+	}
+
+	#[test]
+	fn compare_passed_wraps() {
+		assert!(!compare_passed(100, 5, 100));
+		assert!(!compare_passed(100, 5, 104));
+		assert!(compare_passed(100, 5, 105));
+		assert!(compare_passed(100, 5, 200));
+		// Across the wrap-around of the counter
+		assert!(!compare_passed(65_534, 5, 2));
+		assert!(compare_passed(65_534, 5, 3));
+	}
+}
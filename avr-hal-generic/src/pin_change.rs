@@ -0,0 +1,300 @@
+//! Pin change interrupt dispatch
+//!
+//! Pin change interrupts fire one interrupt per group of pins (usually one port), for any edge
+//! on any enabled pin of the group.  The handler then has to find out which pins changed, and in
+//! which direction, by comparing the current state of the port with the one seen by the previous
+//! interrupt.  [`PinChangeGroup`] keeps that snapshot and does the comparison.
+//!
+//! The interrupts are enabled through the `PCICR` and `PCMSK#` registers, and the handler reads
+//! the input register of the port (`PIN#`):
+//!
+//! ```ignore
+//! static GROUP: Mutex<RefCell<PinChangeGroup>> =
+//!     Mutex::new(RefCell::new(PinChangeGroup::new(0b0000_0011)));
+//!
+//! // Enable PCINT0 and PCINT1 (PB0 and PB1 on the ATmega328P)
+//! dp.EXINT.pcicr().write(|w| unsafe { w.bits(0b001) });
+//! dp.EXINT.pcmsk0().write(|w| unsafe { w.bits(0b0000_0011) });
+//!
+//! #[avr_device::interrupt(atmega328p)]
+//! fn PCINT0() {
+//!     let state = unsafe { &*PORTB::ptr() }.pinb().read().bits();
+//!     avr_device::interrupt::free(|cs| {
+//!         let mut group = GROUP.borrow(cs).borrow_mut();
+//!         group.update(state);
+//!         for (pin, edge) in group.changed_pins() {
+//!             // pin is the bit number within the port
+//!         }
+//!     });
+//! }
+//! ```
+//!
+//! A pin that toggles twice before the handler reads the port (a pulse shorter than the
+//! interrupt latency) shows no change at all.  Initialize the snapshot with
+//! [`PinChangeGroup::with_state()`] to avoid reporting spurious changes on the first interrupt.
+//!
+//! # Counting pulses
+//! Flow meters, energy meters and rain gauges emit one pulse per unit, which have to be counted
+//! over months.  A [`PulseCounter`] counts them in a `static` shared between the interrupt
+//! handler and the main program:
+//!
+//! ```ignore
+//! static FLOW: PulseCounter = PulseCounter::new();
+//!
+//! #[avr_device::interrupt(atmega328p)]
+//! fn PCINT0() {
+//!     let state = unsafe { &*PORTB::ptr() }.pinb().read().bits();
+//!     avr_device::interrupt::free(|cs| {
+//!         let mut group = GROUP.borrow(cs).borrow_mut();
+//!         group.update(state);
+//!         FLOW.on_pin_change(&group, 0, Edge::Falling);
+//!     });
+//! }
+//!
+//! let liters = FLOW.count() / PULSES_PER_LITER;
+//! ```
+//!
+//! Reading a `u32` takes four separate loads on the 8-bit AVR.  If the interrupt handler
+//! increments the counter in between, e.g. from `0x0000_ffff` to `0x0001_0000`, the main program
+//! can get a torn value made of old and new bytes, here `0x0001_ffff` or `0x0000_0000`.
+//! `PulseCounter` therefore disables interrupts for the few cycles it takes to read or modify
+//! the count.  No pulse is lost by this, an edge during that time is handled right after.
+//!
+//! The count wraps around after 2³² pulses, which takes 50 days even at 1000 pulses per second.
+//! Compute differences between two readings with `wrapping_sub()` and they stay correct across
+//! the wrap-around.
+use core::cell::Cell;
+
+pub use crate::counter::Edge;
+
+/// Snapshot of a pin change interrupt group.  See the [module documentation](self).
+#[derive(Debug, Clone, Copy)]
+pub struct PinChangeGroup {
+	mask: u8,
+	previous: u8,
+	current: u8,
+}
+
+impl PinChangeGroup {
+	/// Track the pins in `mask`, assuming all of them start out low.
+	pub const fn new(mask: u8) -> Self {
+		Self::with_state(mask, 0)
+	}
+
+	/// Track the pins in `mask`, starting from the port state `state`.
+	pub const fn with_state(mask: u8, state: u8) -> Self {
+		Self {
+			mask,
+			previous: state,
+			current: state,
+		}
+	}
+
+	/// Record a new port state, e.g. read in the pin change interrupt handler.
+	pub fn update(&mut self, state: u8) {
+		self.previous = self.current;
+		self.current = state;
+	}
+
+	/// Bit mask of the tracked pins which changed with the last [`update()`][Self::update].
+	pub fn changed_mask(&self) -> u8 {
+		(self.previous ^ self.current) & self.mask
+	}
+
+	/// The pins which changed with the last [`update()`][Self::update], as bit numbers within
+	/// the port, with the direction of the change.
+	pub fn changed_pins(&self) -> impl Iterator<Item = (u8, Edge)> {
+		let changed = self.changed_mask();
+		let current = self.current;
+		(0..8).filter(move |pin| changed & (1 << pin) != 0)
+			.map(move |pin| {
+				if current & (1 << pin) != 0 {
+					(pin, Edge::Rising)
+				} else {
+					(pin, Edge::Falling)
+				}
+			})
+	}
+
+	/// The most recently recorded port state.
+	pub fn state(&self) -> u8 {
+		self.current
+	}
+
+	/// The tracked pins.
+	pub fn mask(&self) -> u8 {
+		self.mask
+	}
+
+	/// Whether `pin` changed in the direction of `edge` with the last update.
+	fn changed_to(&self, pin: u8, edge: Edge) -> bool {
+		assert!(pin < 8, "pin {} is not within the port", pin);
+		let high = self.current & (1 << pin) != 0;
+		self.changed_mask() & (1 << pin) != 0 && high == (edge == Edge::Rising)
+	}
+}
+
+/// A 32-bit pulse count shared between an interrupt handler and the main program.  See
+/// [Counting pulses](self#counting-pulses).
+pub struct PulseCounter {
+	count: avr_device::interrupt::Mutex<Cell<u32>>,
+}
+
+impl PulseCounter {
+	/// A counter starting at zero.
+	pub const fn new() -> Self {
+		Self {
+			count: avr_device::interrupt::Mutex::new(Cell::new(0)),
+		}
+	}
+
+	/// Count one pulse.
+	pub fn increment(&self) {
+		self.with_count(|count| count.set(count.get().wrapping_add(1)))
+	}
+
+	/// Count a pulse if `pin` of `group` changed in the direction of `edge` with the group's
+	/// last [`update()`][PinChangeGroup::update].
+	///
+	/// # Panics
+	/// Panics if `pin` is not a bit number within the port (0 to 7).
+	pub fn on_pin_change(&self, group: &PinChangeGroup, pin: u8, edge: Edge) {
+		if group.changed_to(pin, edge) {
+			self.increment();
+		}
+	}
+
+	/// The number of pulses counted so far, read atomically.
+	pub fn count(&self) -> u32 {
+		self.with_count(|count| count.get())
+	}
+
+	/// Set the count back to zero.
+	pub fn reset(&self) {
+		self.with_count(|count| count.set(0))
+	}
+
+	/// Read the count and set it back to zero in one step, so no pulse in between gets lost.
+	pub fn take(&self) -> u32 {
+		self.with_count(|count| count.replace(0))
+	}
+
+	/// Access the count in a critical section.
+	#[cfg(not(test))]
+	fn with_count<R>(&self, f: impl FnOnce(&Cell<u32>) -> R) -> R {
+		avr_device::interrupt::free(|cs| f(self.count.borrow(cs)))
+	}
+
+	// Critical sections are not available on the host, and each test uses its own counter.
+	#[cfg(test)]
+	fn with_count<R>(&self, f: impl FnOnce(&Cell<u32>) -> R) -> R {
+		// SAFETY: Tests don't share counters between threads.
+		f(self.count
+			.borrow(unsafe { avr_device::interrupt::CriticalSection::new() }))
+	}
+}
+
+impl Default for PulseCounter {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::vec::Vec;
+
+	#[test]
+	fn changed_pins_with_edges() {
+		let mut group = PinChangeGroup::new(0b0000_1111);
+		group.update(0b0000_0101);
+		assert_eq!(group.changed_mask(), 0b0000_0101);
+		assert_eq!(
+			group.changed_pins().collect::<Vec<_>>(),
+			[(0, Edge::Rising), (2, Edge::Rising)]
+		);
+
+		group.update(0b0000_0110);
+		assert_eq!(
+			group.changed_pins().collect::<Vec<_>>(),
+			[(0, Edge::Falling), (1, Edge::Rising)]
+		);
+		assert_eq!(group.state(), 0b0000_0110);
+	}
+
+	#[test]
+	fn untracked_pins_are_ignored() {
+		let mut group = PinChangeGroup::with_state(0b0000_0011, 0b1111_0000);
+		assert_eq!(group.mask(), 0b0000_0011);
+		assert_eq!(group.changed_mask(), 0);
+
+		// Only untracked pins changed
+		group.update(0b0000_1100);
+		assert_eq!(group.changed_pins().count(), 0);
+
+		// The first update compares against the initial state
+		let mut group = PinChangeGroup::with_state(0xff, 0b0000_0001);
+		group.update(0b0000_0001);
+		assert_eq!(group.changed_mask(), 0);
+	}
+
+	#[test]
+	fn pulse_edges() {
+		let mut group = PinChangeGroup::with_state(0b0000_0011, 0b0000_0001);
+		group.update(0b0000_0010);
+		assert!(group.changed_to(0, Edge::Falling));
+		assert!(!group.changed_to(0, Edge::Rising));
+		assert!(group.changed_to(1, Edge::Rising));
+		assert!(!group.changed_to(1, Edge::Falling));
+
+		// Unchanged and untracked pins never count
+		group.update(0b1000_0010);
+		assert!(!group.changed_to(1, Edge::Rising));
+		assert!(!group.changed_to(1, Edge::Falling));
+		assert!(!group.changed_to(7, Edge::Rising));
+	}
+
+	#[test]
+	#[should_panic(expected = "pin 8 is not within the port")]
+	fn pulse_edge_pin_out_of_range() {
+		let counter = PulseCounter::new();
+		counter.on_pin_change(&PinChangeGroup::new(0xff), 8, Edge::Rising);
+	}
+
+	#[test]
+	fn pulse_counter_counts_edges() {
+		let counter = PulseCounter::default();
+		let mut group = PinChangeGroup::new(0b0000_0001);
+		for state in [1, 0, 1, 0, 0, 1] {
+			group.update(state);
+			counter.on_pin_change(&group, 0, Edge::Falling);
+		}
+		assert_eq!(counter.count(), 2);
+
+		counter.increment();
+		assert_eq!(counter.take(), 3);
+		assert_eq!(counter.count(), 0);
+		counter.increment();
+		counter.reset();
+		assert_eq!(counter.count(), 0);
+	}
+
+	#[test]
+	fn pulse_counter_byte_boundaries() {
+		// Carries into the upper bytes must show up in full, never as old and new bytes mixed
+		for (start, end) in [
+			(0x0000_00ff, 0x0000_0100),
+			(0x0000_ffff, 0x0001_0000),
+			(0x00ff_ffff, 0x0100_0000),
+			(u32::MAX, 0),
+		] {
+			let counter = PulseCounter {
+				count: avr_device::interrupt::Mutex::new(Cell::new(start)),
+			};
+			assert_eq!(counter.count(), start);
+			counter.increment();
+			assert_eq!(counter.count(), end);
+		}
+	}
+}
@@ -3,6 +3,7 @@
 //! Please take a look at the documentation for [`Pin`] for a detailed explanation.
 
 use core::marker::PhantomData;
+use embedded_hal::delay::DelayNs;
 use embedded_hal::digital::ErrorType;
 use embedded_hal::digital::InputPin;
 use embedded_hal::digital::OutputPin;
@@ -80,6 +81,17 @@ pub trait PinOps {
 	unsafe fn make_input(&mut self, pull_up: bool);
 }
 
+/// Port and bit mask of a pin.
+///
+/// Implemented for all pin types of a HAL crate.  This is what allows the `PortTransaction` of a
+/// HAL to figure out which `PORT` register and bit a pin belongs to.
+pub trait PinLocation {
+	type Port;
+
+	/// Return the port this pin belongs to and the bit mask of the pin within that port.
+	fn location(&self) -> (Self::Port, u8);
+}
+
 /// Representation of an MCU pin.
 ///
 /// # Design Rationale
@@ -111,6 +123,33 @@ pub trait PinOps {
 ///
 /// let output: Pin<mode::Output, port::PD3> = pins.pd3.into_output();
 /// ```
+///
+/// # Ownership and Peripherals
+/// Each pin exists exactly once and every mode conversion consumes the pin it is called on.  The
+/// peripheral drivers build on this: constructors like `Spi::new()`, `I2c::new()` or
+/// `Usart::new()` take their pins by value and keep them for as long as the peripheral is in
+/// use.  A pin which was handed to a peripheral is therefore gone from the `Pins` struct and any
+/// attempt to also use it as GPIO is rejected by the compiler:
+///
+/// ```ignore
+/// let sclk = pins.pb5.into_output();
+/// let (spi, cs) = atmega_hal::Spi::new(dp.SPI, sclk, mosi, miso, cs, Default::default());
+///
+/// sclk.set_high();
+/// // error[E0382]: borrow of moved value: `sclk`
+/// ```
+///
+/// Since the pin types also encode which pins a peripheral is hard-wired to, passing the wrong
+/// pin to a constructor is a compile error as well.  To use the pins for something else again,
+/// the peripheral has to be given up, which returns the pins:
+///
+/// ```ignore
+/// let (spi_periph, sclk, mosi, miso, cs) = spi.release(cs);
+/// sclk.set_high(); // fine now
+/// ```
+///
+/// This only covers the HAL's own drivers.  Pins used through `unsafe` register access or via
+/// `Peripherals::steal()` are outside of this tracking.
 pub struct Pin<MODE, PIN> {
 	pub(crate) pin: PIN,
 	pub(crate) _mode: PhantomData<MODE>,
@@ -202,6 +241,9 @@ impl<PIN: PinOps, MODE: mode::Io> Pin<MODE, PIN> {
 	///
 	/// Some pins can be repurposed as ADC channels.  For those pins, the `into_analog_input()`
 	/// method is available.
+	///
+	/// On MCUs which have a `DIDR` (Digital Input Disable Register), the digital input buffer of
+	/// the pin is switched off as well.  [`into_digital()`](Pin::into_digital) turns it back on.
 	pub fn into_analog_input<H, ADC, CLOCK>(
 		self,
 		adc: &mut crate::adc::Adc<H, ADC, CLOCK>,
@@ -324,6 +366,25 @@ impl<PIN: PinOps> Pin<mode::Output, PIN> {
 	}
 }
 
+impl<MODE, PIN: PinLocation> Pin<MODE, PIN> {
+	/// Return the port this pin belongs to and the bit mask of the pin within that port.
+	///
+	/// Unlike the [`PinLocation`] trait, this is available in every pin mode.
+	#[inline]
+	pub fn location(&self) -> (PIN::Port, u8) {
+		self.pin.location()
+	}
+}
+
+impl<PIN: PinLocation> PinLocation for Pin<mode::Output, PIN> {
+	type Port = PIN::Port;
+
+	#[inline]
+	fn location(&self) -> (Self::Port, u8) {
+		self.pin.location()
+	}
+}
+
 // Implements OutputPinV0 from embedded-hal to make sure external libraries work
 impl<PIN: PinOps> OutputPinV0 for Pin<mode::Output, PIN> {
 	type Error = core::convert::Infallible;
@@ -488,6 +549,16 @@ impl<PIN: PinOps, IMODE: mode::InputMode> InputPin for Pin<mode::Input<IMODE>, P
 }
 
 /// # Digital Input
+/// Inputs can be floating or use the internal pull-up.  The devices supported by this crate have
+/// no internal pull-down resistors, so there is no `into_pull_down_input()`: use
+/// [`into_floating_input()`][Pin::into_floating_input] together with an external pull-down
+/// resistor (typically 10 kΩ to GND) instead, or invert the logic and use the internal pull-up.
+///
+/// | Family | Pull-up | Pull-down |
+/// | --- | --- | --- |
+/// | ATmega, ATtiny (classic) | yes | no, use an external resistor |
+/// | megaAVR-0/1, tinyAVR-0/1/2, AVR-Dx | yes | no, use an external resistor |
+/// | XMEGA | yes | yes (not supported by this crate) |
 impl<PIN: PinOps, IMODE: mode::InputMode> Pin<mode::Input<IMODE>, PIN> {
 	/// Check whether the pin is driven high.
 	#[inline]
@@ -500,6 +571,76 @@ impl<PIN: PinOps, IMODE: mode::InputMode> Pin<mode::Input<IMODE>, PIN> {
 	pub fn is_low(&self) -> bool {
 		!unsafe { self.pin.in_get() }
 	}
+
+	/// Read the level of a noisy input by majority vote.
+	///
+	/// Samples the pin `samples` times, waiting `interval_us` microseconds between two samples,
+	/// and returns `true` if more than half of the samples were high.  Use an odd number of
+	/// samples to avoid ties, which count as low.
+	///
+	/// This **blocks** for `(samples - 1) * interval_us` microseconds.  It is meant for one-off
+	/// reads, e.g. of a jumper at boot, where keeping debounce state around is not worth it.
+	///
+	/// ```
+	/// let mut delay = arduino_hal::Delay::new();
+	/// let jumper = pins.d2.into_pull_up_input();
+	/// let config_mode = jumper.read_debounced(&mut delay, 5, 1000);
+	/// ```
+	pub fn read_debounced<D: DelayNs>(
+		&self,
+		delay: &mut D,
+		samples: u8,
+		interval_us: u32,
+	) -> bool {
+		let samples = samples.max(1);
+		let mut high = 0u8;
+		for i in 0..samples {
+			if i != 0 {
+				delay.delay_us(interval_us);
+			}
+			if self.is_high() {
+				high += 1;
+			}
+		}
+		high > samples / 2
+	}
+
+	/// Read the level once it was the same for `samples` consecutive reads.
+	///
+	/// The pin is read back to back, and any read which differs from the previous one restarts
+	/// the count, so a glitch shorter than `samples` reads is never reported.  Unlike
+	/// [`read_debounced()`][Self::read_debounced], there is no delay between the reads: each one
+	/// takes only a few CPU cycles, which makes this cheap enough for interrupt handlers and fast
+	/// polling loops.
+	///
+	/// # Latency and noise immunity
+	/// A clean input returns after `samples` reads.  More samples filter longer glitches, but the
+	/// call takes correspondingly longer, and a line which keeps toggling faster than `samples`
+	/// reads blocks the call until it settles.  As a rule of thumb, one read takes about 4 cycles
+	/// (0.25 µs at 16 MHz), so `samples` should cover the longest glitch to be filtered, e.g. 8
+	/// reads for spikes of up to 2 µs.  The bouncing of mechanical contacts lasts milliseconds and
+	/// needs the time-based [`read_debounced()`][Self::read_debounced] instead.
+	///
+	/// ```
+	/// let line = pins.d2.into_floating_input();
+	/// // Ignore spikes of up to a few reads
+	/// let level = line.read_filtered(8);
+	/// ```
+	#[inline]
+	pub fn read_filtered(&self, samples: u8) -> bool {
+		let mut level = self.is_high();
+		let mut stable = 1;
+		while stable < samples {
+			let next = self.is_high();
+			if next == level {
+				stable += 1;
+			} else {
+				level = next;
+				stable = 1;
+			}
+		}
+		level
+	}
 }
 
 /// # Analog Input
@@ -518,6 +659,16 @@ impl<PIN: PinOps, IMODE: mode::InputMode> Pin<mode::Input<IMODE>, PIN> {
 /// // ^- this is equivalent to -v
 /// let voltage = adc.read_blocking(&a0);
 /// ```
+///
+/// ## Digital Input Buffer
+/// While a pin is in [`mode::Analog`], its digital input buffer is disabled through the
+/// corresponding bit in the `DIDR#` register (on MCUs that have one).  An analog voltage
+/// somewhere between the logic levels makes the digital input buffer draw a considerable
+/// cross-current and its switching couples noise into the ADC.  The datasheets therefore
+/// recommend disabling the buffer on every pin used as an analog input.  This happens
+/// automatically in [`into_analog_input()`](Pin::into_analog_input) and is undone in
+/// [`into_digital()`](Pin::into_digital), so the pin can be reused as a regular digital pin
+/// afterwards.
 impl<PIN: PinOps> Pin<mode::Analog, PIN> {
 	pub fn analog_read<H, ADC, CLOCK>(&self, adc: &mut crate::adc::Adc<H, ADC, CLOCK>) -> u16
 	where
@@ -603,6 +754,7 @@ macro_rules! impl_port_traditional_base {
         }
 
         $crate::paste::paste! {
+            #[derive(Clone, Copy, PartialEq, Eq)]
             #[repr(u8)]
             pub enum DynamicPort {
                 $([<PORT $name>]),+
@@ -625,6 +777,205 @@ macro_rules! impl_port_traditional_base {
             }
         }
 
+        impl $crate::port::PinLocation for Dynamic {
+            type Port = DynamicPort;
+
+            #[inline]
+            fn location(&self) -> (DynamicPort, u8) {
+                (self.port, self.mask)
+            }
+        }
+
+        $crate::paste::paste! {
+            /// Stage writes to several output pins and apply them together.
+            ///
+            /// [`set_high()`][PortTransaction::set_high] and [`set_low()`][PortTransaction::set_low]
+            /// only record the change.  [`commit()`][PortTransaction::commit] then applies all
+            /// staged changes with a single write to the `PORT` register of each port involved.
+            /// All pins of one port thus change on the same clock edge, which is useful for
+            /// parallel buses.  Pins on different ports change a few cycles apart, in port order.
+            ///
+            /// # Example
+            /// ```ignore
+            /// let mut tx = PortTransaction::new();
+            /// tx.set_high(&d0).set_low(&d1).set_high(&d2);
+            /// tx.commit();
+            /// ```
+            #[derive(Default)]
+            pub struct PortTransaction {
+                $(
+                    [<set_ $name:lower>]: u8,
+                    [<clear_ $name:lower>]: u8,
+                )+
+            }
+
+            impl PortTransaction {
+                pub fn new() -> Self {
+                    Self::default()
+                }
+
+                /// Stage driving `pin` high.
+                pub fn set_high<PIN>(&mut self, pin: &PIN) -> &mut Self
+                where
+                    PIN: $crate::port::PinLocation<Port = DynamicPort>,
+                {
+                    self.set(pin, true)
+                }
+
+                /// Stage driving `pin` low.
+                pub fn set_low<PIN>(&mut self, pin: &PIN) -> &mut Self
+                where
+                    PIN: $crate::port::PinLocation<Port = DynamicPort>,
+                {
+                    self.set(pin, false)
+                }
+
+                /// Stage driving `pin` to the given level.
+                ///
+                /// A later call for the same pin overrides an earlier one.
+                pub fn set<PIN>(&mut self, pin: &PIN, high: bool) -> &mut Self
+                where
+                    PIN: $crate::port::PinLocation<Port = DynamicPort>,
+                {
+                    let (port, mask) = pin.location();
+                    match port {
+                        $(DynamicPort::[<PORT $name>] => {
+                            if high {
+                                self.[<set_ $name:lower>] |= mask;
+                                self.[<clear_ $name:lower>] &= !mask;
+                            } else {
+                                self.[<clear_ $name:lower>] |= mask;
+                                self.[<set_ $name:lower>] &= !mask;
+                            }
+                        })+
+                    }
+                    self
+                }
+
+                /// Apply all staged changes and start over with an empty transaction.
+                ///
+                /// Each port with staged changes gets exactly one read-modify-write of its `PORT`
+                /// register.  All writes happen inside a single critical section.
+                pub fn commit(&mut self) {
+                    $crate::avr_device::interrupt::free(|_| {
+                        $(
+                            let set = self.[<set_ $name:lower>];
+                            let clear = self.[<clear_ $name:lower>];
+                            if set | clear != 0 {
+                                unsafe {
+                                    (*<$port>::ptr()).[<port $name:lower>]().modify(|r, w| {
+                                        w.bits((r.bits() & !clear) | set)
+                                    });
+                                }
+                            }
+                        )+
+                    });
+                    *self = Self::default();
+                }
+            }
+        }
+
+        $crate::paste::paste! {
+            /// Saved `DDR` and `PORT` register contents of all ports.
+            ///
+            /// This is meant for entering a deep sleep mode: Floating inputs draw current whenever
+            /// their level drifts into the undefined region, and forgotten outputs may drive loads.
+            /// Take a snapshot, put all pins into their [lowest power
+            /// configuration][PortSnapshot::lowest_power], sleep, and [restore][PortSnapshot::restore]
+            /// the old configuration on wake-up.
+            ///
+            /// # Excluded pins
+            /// Some pins must keep their configuration while sleeping and have to be passed as
+            /// `keep` to [`lowest_power()`][PortSnapshot::lowest_power]:
+            ///
+            /// - The pin which is supposed to wake the MCU through an external or pin-change
+            ///   interrupt.  Its pull-up setting is part of the wake-up logic.
+            /// - Outputs which hold external circuitry in a defined state, e.g. the enable line of a
+            ///   voltage regulator or the chip-select of an SPI device.
+            /// - Pins with external pull-downs, where the internal pull-up would form a voltage
+            ///   divider and draw current instead of saving it.
+            ///
+            /// Between `lowest_power()` and `restore()` the pin types no longer match the hardware
+            /// state, so no pins should be used in between.
+            ///
+            /// # Example
+            /// ```ignore
+            /// let snapshot = PortSnapshot::capture();
+            /// PortSnapshot::lowest_power(&[wake_button.location()]);
+            /// // ... enter power-down sleep ...
+            /// snapshot.restore();
+            /// ```
+            #[derive(Clone, Copy, PartialEq, Eq)]
+            pub struct PortSnapshot {
+                $(
+                    [<ddr $name:lower>]: u8,
+                    [<port $name:lower>]: u8,
+                )+
+            }
+
+            impl PortSnapshot {
+                /// Record the current `DDR` and `PORT` registers of all ports.
+                pub fn capture() -> Self {
+                    $crate::avr_device::interrupt::free(|_| unsafe {
+                        Self {
+                            $(
+                                [<ddr $name:lower>]: (*<$port>::ptr()).[<ddr $name:lower>]().read().bits(),
+                                [<port $name:lower>]: (*<$port>::ptr()).[<port $name:lower>]().read().bits(),
+                            )+
+                        }
+                    })
+                }
+
+                /// Turn all pins except those in `keep` into inputs with the pull-up enabled.
+                ///
+                /// An input held at a defined level by its pull-up draws no current, neither does
+                /// a pin which is not driving anything.  The direction is switched first so that
+                /// no output is briefly driven high while the pull-ups get enabled.
+                ///
+                /// Pins in `keep` are given by their [`location()`][Pin::location], for example
+                /// `&[pins.pd2.location()]`.
+                pub fn lowest_power(keep: &[(DynamicPort, u8)]) {
+                    $(
+                        let mut [<keep_ $name:lower>] = 0u8;
+                    )+
+                    for &(port, mask) in keep {
+                        match port {
+                            $(DynamicPort::[<PORT $name>] => [<keep_ $name:lower>] |= mask,)+
+                        }
+                    }
+
+                    $crate::avr_device::interrupt::free(|_| unsafe {
+                        $(
+                            let keep = [<keep_ $name:lower>];
+                            (*<$port>::ptr()).[<ddr $name:lower>]().modify(|r, w| {
+                                w.bits(r.bits() & keep)
+                            });
+                            (*<$port>::ptr()).[<port $name:lower>]().modify(|r, w| {
+                                w.bits((r.bits() & keep) | !keep)
+                            });
+                        )+
+                    });
+                }
+
+                /// Write the recorded configuration back to all ports.
+                ///
+                /// The `PORT` register is written before `DDR` so that pins which become outputs
+                /// immediately drive their recorded level.
+                pub fn restore(&self) {
+                    $crate::avr_device::interrupt::free(|_| unsafe {
+                        $(
+                            (*<$port>::ptr()).[<port $name:lower>]().write(|w| {
+                                w.bits(self.[<port $name:lower>])
+                            });
+                            (*<$port>::ptr()).[<ddr $name:lower>]().write(|w| {
+                                w.bits(self.[<ddr $name:lower>])
+                            });
+                        )+
+                    });
+                }
+            }
+        }
+
         $crate::paste::paste! {
             impl $crate::port::PinOps for Dynamic {
                 type Dynamic = Self;
@@ -730,6 +1081,15 @@ macro_rules! impl_port_traditional_base {
                     _private: ()
                 }
 
+                impl $crate::port::PinLocation for [<P $name $pin>] {
+                    type Port = DynamicPort;
+
+                    #[inline]
+                    fn location(&self) -> (DynamicPort, u8) {
+                        (DynamicPort::[<PORT $name>], 1u8 << $pin)
+                    }
+                }
+
                 impl $crate::port::PinOps for [<P $name $pin>] {
                     type Dynamic = Dynamic;
 
@@ -856,3 +1216,102 @@ macro_rules! renamed_pins {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use core::cell::Cell;
+
+	/// An input pin which reads a given sequence of levels, repeating the last one.
+	struct ScriptedPin {
+		levels: &'static [bool],
+		reads: Cell<usize>,
+	}
+
+	impl ScriptedPin {
+		fn new(levels: &'static [bool]) -> Pin<mode::Input<mode::Floating>, Self> {
+			Pin::new(Self {
+				levels,
+				reads: Cell::new(0),
+			})
+		}
+	}
+
+	impl PinOps for ScriptedPin {
+		type Dynamic = Self;
+
+		fn into_dynamic(self) -> Self::Dynamic {
+			self
+		}
+
+		unsafe fn out_set(&mut self) {}
+		unsafe fn out_clear(&mut self) {}
+		unsafe fn out_toggle(&mut self) {}
+		unsafe fn out_get(&self) -> bool {
+			false
+		}
+
+		unsafe fn in_get(&self) -> bool {
+			let reads = self.reads.get();
+			self.reads.set(reads + 1);
+			self.levels[reads.min(self.levels.len() - 1)]
+		}
+
+		unsafe fn make_output(&mut self) {}
+		unsafe fn make_input(&mut self, _pull_up: bool) {}
+	}
+
+	/// A delay which only adds up the requested time.
+	#[derive(Default)]
+	struct MockDelay {
+		ns: u64,
+	}
+
+	impl DelayNs for MockDelay {
+		fn delay_ns(&mut self, ns: u32) {
+			self.ns += u64::from(ns);
+		}
+	}
+
+	#[test]
+	fn read_debounced_majority() {
+		let mut delay = MockDelay::default();
+		let pin = ScriptedPin::new(&[true, false, true, true, false]);
+		assert!(pin.read_debounced(&mut delay, 5, 1000));
+		assert_eq!(pin.pin.reads.get(), 5);
+		assert_eq!(delay.ns, 4_000_000);
+
+		// Ties count as low
+		let pin = ScriptedPin::new(&[true, false, true, false]);
+		assert!(!pin.read_debounced(&mut MockDelay::default(), 4, 10));
+	}
+
+	#[test]
+	fn read_debounced_zero_samples() {
+		let mut delay = MockDelay::default();
+		let pin = ScriptedPin::new(&[true]);
+		assert!(pin.read_debounced(&mut delay, 0, 1000));
+		assert_eq!(pin.pin.reads.get(), 1);
+		assert_eq!(delay.ns, 0);
+	}
+
+	#[test]
+	fn read_filtered_ignores_glitches() {
+		let pin = ScriptedPin::new(&[true, false, false, true, true, true, false]);
+		assert!(pin.read_filtered(3));
+		assert_eq!(pin.pin.reads.get(), 6);
+
+		let pin = ScriptedPin::new(&[false, true, false, false]);
+		assert!(!pin.read_filtered(2));
+		assert_eq!(pin.pin.reads.get(), 4);
+	}
+
+	#[test]
+	fn read_filtered_single_read() {
+		for samples in [0, 1] {
+			let pin = ScriptedPin::new(&[true, false]);
+			assert!(pin.read_filtered(samples));
+			assert_eq!(pin.pin.reads.get(), 1);
+		}
+	}
+}
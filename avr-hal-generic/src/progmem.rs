@@ -0,0 +1,192 @@
+//! Constant data in program memory (flash)
+//!
+//! AVRs have separate address spaces for program memory (flash) and data memory (RAM).  Normal
+//! pointers, and thus all Rust references, point into data memory.  Every `static` or constant
+//! which is accessed through a reference is therefore copied from flash to RAM by the startup
+//! code and occupies RAM for the whole runtime, even if it is never modified.  With only 2 KiB of
+//! RAM on an ATmega328P, a font or a large lookup table quickly uses up all of it.
+//!
+//! [`ProgMem`] keeps data in flash only.  The [`progmem!`][crate::progmem!] macro places a
+//! `static` in the `.progmem.data` section, which the linker puts into flash without a copy in
+//! RAM, and wraps it so it can only be read through the `LPM` instruction, never through a normal
+//! reference.  Only the pointer to it remains in RAM:
+//!
+//! ```ignore
+//! avr_hal_generic::progmem! {
+//!     static progmem SINE: [u8; 8] = [128, 218, 255, 218, 128, 37, 0, 37];
+//!     static progmem GREETING: [u8; 5] = *b"hello";
+//! }
+//!
+//! let third = SINE.load_at(2);
+//! for byte in GREETING.iter() {
+//!     serial.write_byte(byte);
+//! }
+//! let whole_table: [u8; 8] = SINE.load();
+//! ```
+//!
+//! # Overhead
+//! `LPM` takes 3 cycles per byte, one more than a load from RAM.  In addition, the address has
+//! to be in the `Z` register, so loads are not combined or hoisted by the compiler as well as
+//! normal loads.  [`ProgMem::load()`] copies the whole value to the stack, so for large arrays
+//! prefer [`ProgMem::load_at()`] or [`ProgMem::iter()`], which only load one element at a time.
+//!
+//! # Limitations
+//! `LPM` can only address the lower 64 KiB of flash.  On devices with more flash (ATmega128A,
+//! ATmega1280, ATmega1284P, ATmega2560), the linker places `.progmem.data` at the start of flash,
+//! right after the vector table, so this only matters if there are more than about 64 KiB of
+//! progmem data in total.
+use core::mem::MaybeUninit;
+
+/// A value stored in program memory.  See the [module documentation](self).
+///
+/// A `ProgMem` can only be created with the [`progmem!`][crate::progmem!] macro.  It holds a
+/// pointer to the value instead of the value itself, so the compiler never gets a reference to
+/// data it would load from the wrong address space.
+pub struct ProgMem<T> {
+	target: *const T,
+}
+
+// SAFETY: The value is never modified and only read through `LPM`.
+unsafe impl<T> Sync for ProgMem<T> {}
+
+impl<T: Copy> ProgMem<T> {
+	/// Wrap a pointer to a value in program memory.
+	///
+	/// # Safety
+	/// `target` must point to a `static` in the `.progmem.data` section.  Use the
+	/// [`progmem!`][crate::progmem!] macro instead.
+	#[doc(hidden)]
+	pub const unsafe fn new(target: *const T) -> Self {
+		Self { target }
+	}
+
+	/// Copy the whole value from program memory.
+	pub fn load(&self) -> T {
+		// SAFETY: `target` points to a valid `T` in program memory.
+		unsafe { load_raw(self.target) }
+	}
+}
+
+impl<T: Copy, const N: usize> ProgMem<[T; N]> {
+	/// Number of elements in the array.
+	pub const fn len(&self) -> usize {
+		N
+	}
+
+	/// Whether the array is empty.
+	pub const fn is_empty(&self) -> bool {
+		N == 0
+	}
+
+	/// Copy the element at `index` from program memory.
+	///
+	/// # Panics
+	/// Panics if `index` is out of bounds.
+	pub fn load_at(&self, index: usize) -> T {
+		assert!(index < N);
+		// SAFETY: The element is in bounds and lives in program memory.
+		unsafe { load_raw((self.target as *const T).add(index)) }
+	}
+
+	/// Iterate over copies of the elements, loading one element at a time.
+	pub fn iter(&self) -> ProgMemIter<'_, T, N> {
+		ProgMemIter {
+			array: self,
+			index: 0,
+		}
+	}
+}
+
+impl<'a, T: Copy, const N: usize> IntoIterator for &'a ProgMem<[T; N]> {
+	type Item = T;
+	type IntoIter = ProgMemIter<'a, T, N>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.iter()
+	}
+}
+
+/// Iterator over the elements of a [`ProgMem`] array, created by [`ProgMem::iter()`].
+pub struct ProgMemIter<'a, T, const N: usize> {
+	array: &'a ProgMem<[T; N]>,
+	index: usize,
+}
+
+impl<T: Copy, const N: usize> Iterator for ProgMemIter<'_, T, N> {
+	type Item = T;
+
+	fn next(&mut self) -> Option<T> {
+		if self.index < N {
+			let element = self.array.load_at(self.index);
+			self.index += 1;
+			Some(element)
+		} else {
+			None
+		}
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		let remaining = N - self.index;
+		(remaining, Some(remaining))
+	}
+}
+
+impl<T: Copy, const N: usize> ExactSizeIterator for ProgMemIter<'_, T, N> {}
+
+/// Copy a `T` from program memory byte by byte.
+///
+/// # Safety
+/// `source` must point to a valid `T` in program memory.
+unsafe fn load_raw<T: Copy>(source: *const T) -> T {
+	let mut value = MaybeUninit::<T>::uninit();
+	let source = source as *const u8;
+	let destination = value.as_mut_ptr() as *mut u8;
+	for i in 0..core::mem::size_of::<T>() {
+		*destination.add(i) = read_byte(source.add(i));
+	}
+	value.assume_init()
+}
+
+#[cfg(target_arch = "avr")]
+#[inline(always)]
+unsafe fn read_byte(address: *const u8) -> u8 {
+	let value: u8;
+	core::arch::asm!(
+		"lpm {value}, Z",
+		value = out(reg) value,
+		in("Z") address,
+		options(pure, readonly, nostack, preserves_flags),
+	);
+	value
+}
+
+#[cfg(not(target_arch = "avr"))]
+#[inline(always)]
+unsafe fn read_byte(address: *const u8) -> u8 {
+	// There is only one address space on other targets.
+	core::ptr::read(address)
+}
+
+/// Place `static`s in program memory, see the [`progmem`][crate::progmem] module.
+///
+/// ```ignore
+/// avr_hal_generic::progmem! {
+///     /// Lookup table for a gamma of 2.2
+///     pub static progmem GAMMA: [u8; 256] = [0, 0, 0, /* ... */ 255];
+/// }
+/// ```
+#[macro_export]
+macro_rules! progmem {
+    ($(
+        $(#[$attr:meta])*
+        $vis:vis static progmem $name:ident: $ty:ty = $value:expr;
+    )*) => {$(
+        $(#[$attr])*
+        $vis static $name: $crate::progmem::ProgMem<$ty> = {
+            #[link_section = ".progmem.data"]
+            static VALUE: $ty = $value;
+            // SAFETY: `VALUE` is placed in `.progmem.data` right above.
+            unsafe { $crate::progmem::ProgMem::new(core::ptr::addr_of!(VALUE)) }
+        };
+    )*};
+}
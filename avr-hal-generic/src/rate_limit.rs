@@ -0,0 +1,255 @@
+//! Outputs with a minimum time between state changes
+//!
+//! Relays must not be switched too often: the contacts arc on every switching operation, and
+//! rapid toggling (e.g. a thermostat oscillating around its set point) wears them out or welds
+//! them shut.  Compressors, pumps and some motor drivers have similar restrictions.
+//! [`RateLimitedOutput`] enforces a minimum interval between two changes of an output pin.
+//!
+//! Time is measured in ticks of a periodic timer: [`RateLimitedOutput::tick()`] has to be called
+//! regularly, e.g. every millisecond from a timer interrupt.
+//!
+//! # Reject or defer
+//! A change which comes too soon can be handled in two ways, and the right one depends on the
+//! application, so both are offered:
+//!
+//! - [`try_set()`][RateLimitedOutput::try_set] **rejects** it with [`TooSoon`] and leaves the
+//!   output as it is.  The caller decides what to do, e.g. retry on the next control loop
+//!   iteration with a fresh decision.  This is the better choice when the desired state is
+//!   recomputed regularly anyway.
+//! - [`set_deferred()`][RateLimitedOutput::set_deferred] **defers** it: the change is applied by
+//!   `tick()` as soon as the interval has passed.  Only the latest request is kept, and a request
+//!   for the current state cancels a pending change.  This suits one-off commands (e.g. "switch
+//!   on" from a button), which would otherwise get lost.
+//!
+//! ```ignore
+//! // At most one change every 5 s, with tick() called every 1 ms
+//! let mut heater = RateLimitedOutput::new(pins.d7.into_output(), 5000);
+//!
+//! loop {
+//!     let want_heat = temperature < setpoint;
+//!     match heater.try_set(want_heat) {
+//!         Ok(()) => (),
+//!         // Keep the current state for now, decide again next time
+//!         Err(TooSoon { .. }) => (),
+//!     }
+//! }
+//! ```
+use crate::port;
+use crate::port::mode;
+
+/// A change of a [`RateLimitedOutput`] was rejected because the minimum interval since the last
+/// change has not passed yet.
+#[derive(ufmt::derive::uDebug, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TooSoon {
+	/// Ticks until the output may change again.
+	pub remaining_ticks: u16,
+}
+
+/// An output pin which changes its state at most once per interval.  See the
+/// [module documentation](self).
+pub struct RateLimitedOutput<PIN> {
+	pin: port::Pin<mode::Output, PIN>,
+	min_interval_ticks: u16,
+	elapsed_ticks: u16,
+	pending: Option<bool>,
+}
+
+impl<PIN: port::PinOps> RateLimitedOutput<PIN> {
+	/// Limit `pin` to one change every `min_interval_ticks` ticks.
+	///
+	/// The pin keeps its current state, and the first change is allowed right away.
+	pub fn new(pin: port::Pin<mode::Output, PIN>, min_interval_ticks: u16) -> Self {
+		Self {
+			pin,
+			min_interval_ticks,
+			elapsed_ticks: min_interval_ticks,
+			pending: None,
+		}
+	}
+
+	/// Set the output to `state` (`true` is high), unless it changed less than the minimum
+	/// interval ago.
+	///
+	/// Setting the state the output already has always succeeds and does not restart the
+	/// interval.  A pending deferred change is cancelled either way.
+	pub fn try_set(&mut self, state: bool) -> Result<(), TooSoon> {
+		self.pending = None;
+		if state == self.is_set_high() {
+			return Ok(());
+		}
+		if !self.may_change() {
+			return Err(TooSoon {
+				remaining_ticks: self.min_interval_ticks - self.elapsed_ticks,
+			});
+		}
+		self.apply(state);
+		Ok(())
+	}
+
+	/// Set the output to `state` now if allowed, otherwise as soon as the minimum interval has
+	/// passed.
+	pub fn set_deferred(&mut self, state: bool) {
+		if self.try_set(state).is_err() {
+			self.pending = Some(state);
+		}
+	}
+
+	/// Advance the time by one tick and apply a deferred change once it is allowed.
+	pub fn tick(&mut self) {
+		self.elapsed_ticks = self.elapsed_ticks.saturating_add(1);
+		if self.may_change() {
+			if let Some(state) = self.pending.take() {
+				self.apply(state);
+			}
+		}
+	}
+
+	/// Whether the output is currently set high.
+	pub fn is_set_high(&self) -> bool {
+		self.pin.is_set_high()
+	}
+
+	/// The state of a deferred change which is still waiting for the interval to pass.
+	pub fn pending(&self) -> Option<bool> {
+		self.pending
+	}
+
+	/// Change the minimum interval.  It also applies to the time since the last change.
+	pub fn set_min_interval(&mut self, min_interval_ticks: u16) {
+		self.min_interval_ticks = min_interval_ticks;
+	}
+
+	/// Drop a pending change and return the pin in its current state.
+	pub fn release(self) -> port::Pin<mode::Output, PIN> {
+		self.pin
+	}
+
+	fn may_change(&self) -> bool {
+		self.elapsed_ticks >= self.min_interval_ticks
+	}
+
+	fn apply(&mut self, state: bool) {
+		if state {
+			self.pin.set_high();
+		} else {
+			self.pin.set_low();
+		}
+		self.elapsed_ticks = 0;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[derive(Default)]
+	struct MockPin {
+		level: bool,
+		changes: usize,
+	}
+
+	impl MockPin {
+		fn drive(&mut self, level: bool) {
+			if level != self.level {
+				self.changes += 1;
+			}
+			self.level = level;
+		}
+	}
+
+	impl port::PinOps for MockPin {
+		type Dynamic = Self;
+
+		fn into_dynamic(self) -> Self::Dynamic {
+			self
+		}
+
+		unsafe fn out_set(&mut self) {
+			self.drive(true);
+		}
+		unsafe fn out_clear(&mut self) {
+			self.drive(false);
+		}
+		unsafe fn out_toggle(&mut self) {
+			self.drive(!self.level);
+		}
+		unsafe fn out_get(&self) -> bool {
+			self.level
+		}
+		unsafe fn in_get(&self) -> bool {
+			self.level
+		}
+		unsafe fn make_output(&mut self) {}
+		unsafe fn make_input(&mut self, _pull_up: bool) {}
+	}
+
+	fn output(min_interval_ticks: u16) -> RateLimitedOutput<MockPin> {
+		RateLimitedOutput::new(
+			port::Pin::new(MockPin::default()).into_output(),
+			min_interval_ticks,
+		)
+	}
+
+	fn ticks(output: &mut RateLimitedOutput<MockPin>, n: u16) {
+		for _ in 0..n {
+			output.tick();
+		}
+	}
+
+	#[test]
+	fn try_set_rejects_early_changes() {
+		let mut relay = output(100);
+		// The first change is allowed right away
+		assert_eq!(relay.try_set(true), Ok(()));
+		ticks(&mut relay, 40);
+		assert_eq!(
+			relay.try_set(false),
+			Err(TooSoon {
+				remaining_ticks: 60
+			})
+		);
+		assert!(relay.is_set_high());
+		// Keeping the state is always fine
+		assert_eq!(relay.try_set(true), Ok(()));
+
+		ticks(&mut relay, 60);
+		assert_eq!(relay.try_set(false), Ok(()));
+		assert!(!relay.is_set_high());
+		assert_eq!(relay.release().pin.changes, 2);
+	}
+
+	#[test]
+	fn deferred_change_applied_by_tick() {
+		let mut relay = output(100);
+		relay.set_deferred(true);
+		assert!(relay.is_set_high());
+
+		relay.set_deferred(false);
+		assert_eq!(relay.pending(), Some(false));
+		ticks(&mut relay, 99);
+		assert!(relay.is_set_high());
+		relay.tick();
+		assert!(!relay.is_set_high());
+		assert_eq!(relay.pending(), None);
+	}
+
+	#[test]
+	fn deferred_change_latest_wins() {
+		let mut relay = output(100);
+		relay.set_deferred(true);
+		relay.set_deferred(false);
+		// A request for the current state cancels the pending change
+		relay.set_deferred(true);
+		assert_eq!(relay.pending(), None);
+		ticks(&mut relay, 100);
+		assert!(relay.is_set_high());
+
+		// A changed interval also applies to the time already passed
+		relay.set_min_interval(200);
+		relay.set_deferred(false);
+		assert_eq!(relay.pending(), Some(false));
+		relay.set_min_interval(50);
+		relay.tick();
+		assert!(!relay.is_set_high());
+	}
+}
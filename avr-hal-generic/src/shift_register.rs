@@ -0,0 +1,236 @@
+//! Shift register helpers
+//!
+//! [`ShiftRegister165`] reads parallel inputs through one or more chained 74HC165
+//! parallel-in/serial-out shift registers, e.g. to read lots of buttons and switches with only
+//! three pins.
+use crate::port;
+use crate::port::mode;
+
+/// One or more chained 74HC165 parallel-in/serial-out shift registers.
+///
+/// `N` is the number of chained registers.  Wiring:
+///
+/// - `SH/LD` (pin 1) of all registers to the `load` pin.
+/// - `CLK` (pin 2) of all registers to the `clock` pin.
+/// - `CLK INH` (pin 15) of all registers to GND.
+/// - `QH` (pin 9) of the first register to the `data` pin.  `QH` of each further register goes to
+///   `SER` (pin 10) of the one before it.
+///
+/// # Read sequence
+/// 1. `SH/LD` is pulsed low, which latches all parallel inputs into the registers at once.
+/// 2. With `SH/LD` high again, the register output `QH` shows input `H` of the first register.
+///    It is sampled, then a rising edge on `CLK` shifts the next bit in.  This repeats for all
+///    `8 * N` bits.
+///
+/// # Bit order
+/// Byte `i` of the result comes from the `i`-th register counted from the MCU.  Within each byte,
+/// input `A` is bit 0 and input `H` is bit 7.
+///
+/// # Example
+/// ```ignore
+/// let mut buttons: ShiftRegister165<_, _, _, 2> = ShiftRegister165::new(
+///     pins.d4.into_output_high(),
+///     pins.d5.into_output(),
+///     pins.d6.into_floating_input(),
+/// );
+/// let state: u32 = buttons.read_u32();
+/// ```
+pub struct ShiftRegister165<LOADPIN, CLKPIN, DATAPIN, const N: usize> {
+	load: port::Pin<mode::Output, LOADPIN>,
+	clock: port::Pin<mode::Output, CLKPIN>,
+	data: port::Pin<mode::Input, DATAPIN>,
+}
+
+impl<LOADPIN, CLKPIN, DATAPIN, const N: usize> ShiftRegister165<LOADPIN, CLKPIN, DATAPIN, N>
+where
+	LOADPIN: port::PinOps,
+	CLKPIN: port::PinOps,
+	DATAPIN: port::PinOps,
+{
+	pub fn new<IMODE: mode::InputMode>(
+		mut load: port::Pin<mode::Output, LOADPIN>,
+		mut clock: port::Pin<mode::Output, CLKPIN>,
+		data: port::Pin<mode::Input<IMODE>, DATAPIN>,
+	) -> Self {
+		load.set_high();
+		clock.set_low();
+		Self {
+			load,
+			clock,
+			data: data.forget_imode(),
+		}
+	}
+
+	/// Latch all inputs and shift them in.  See [Bit order](Self#bit-order).
+	pub fn read(&mut self) -> [u8; N] {
+		// The minimum pulse widths of the 74HC165 are well below one instruction cycle, so no
+		// explicit delays are needed.
+		self.load.set_low();
+		self.load.set_high();
+
+		let mut result = [0u8; N];
+		for byte in result.iter_mut() {
+			for _ in 0..8 {
+				*byte = (*byte << 1) | u8::from(self.data.is_high());
+				self.clock.set_high();
+				self.clock.set_low();
+			}
+		}
+		result
+	}
+
+	/// Latch and shift in all inputs, packed into a `u32`.
+	///
+	/// Byte `0` of [`read()`][ShiftRegister165::read] ends up in the lowest 8 bits, byte 1 in
+	/// the next 8 bits, and so on.  Only up to 4 chained registers fit, further ones are
+	/// ignored.
+	pub fn read_u32(&mut self) -> u32 {
+		self.read()
+			.iter()
+			.take(4)
+			.enumerate()
+			.fold(0, |acc, (i, byte)| acc | (u32::from(*byte) << (8 * i)))
+	}
+
+	/// Give back the pins.
+	pub fn release(
+		self,
+	) -> (
+		port::Pin<mode::Output, LOADPIN>,
+		port::Pin<mode::Output, CLKPIN>,
+		port::Pin<mode::Input, DATAPIN>,
+	) {
+		(self.load, self.clock, self.data)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::cell::RefCell;
+	use std::rc::Rc;
+	use std::vec::Vec;
+
+	/// A chain of 74HC165, with `SER` of the last register tied low.
+	struct Chain {
+		inputs: Vec<u8>,
+		regs: Vec<u8>,
+		load: bool,
+		clock: bool,
+	}
+
+	impl Chain {
+		fn new(inputs: &[u8]) -> Rc<RefCell<Self>> {
+			Rc::new(RefCell::new(Self {
+				inputs: inputs.to_vec(),
+				regs: std::vec![0; inputs.len()],
+				load: true,
+				clock: false,
+			}))
+		}
+
+		fn set_load(&mut self, level: bool) {
+			self.load = level;
+			if !level {
+				self.regs = self.inputs.clone();
+			}
+		}
+
+		fn set_clock(&mut self, level: bool) {
+			if level && !self.clock && self.load {
+				for i in 0..self.regs.len() {
+					let next = self.regs.get(i + 1).map_or(0, |r| r >> 7);
+					self.regs[i] = (self.regs[i] << 1) | next;
+				}
+			}
+			self.clock = level;
+		}
+	}
+
+	enum Wire {
+		Load,
+		Clock,
+		Data,
+	}
+
+	struct MockPin(Wire, Rc<RefCell<Chain>>);
+
+	impl port::PinOps for MockPin {
+		type Dynamic = Self;
+
+		fn into_dynamic(self) -> Self::Dynamic {
+			self
+		}
+
+		unsafe fn out_set(&mut self) {
+			self.drive(true);
+		}
+		unsafe fn out_clear(&mut self) {
+			self.drive(false);
+		}
+		unsafe fn out_toggle(&mut self) {
+			unreachable!();
+		}
+		unsafe fn out_get(&self) -> bool {
+			false
+		}
+
+		unsafe fn in_get(&self) -> bool {
+			assert!(matches!(self.0, Wire::Data));
+			self.1.borrow().regs[0] & 0x80 != 0
+		}
+
+		unsafe fn make_output(&mut self) {}
+		unsafe fn make_input(&mut self, _pull_up: bool) {}
+	}
+
+	impl MockPin {
+		fn drive(&mut self, level: bool) {
+			match self.0 {
+				Wire::Load => self.1.borrow_mut().set_load(level),
+				Wire::Clock => self.1.borrow_mut().set_clock(level),
+				Wire::Data => panic!("data pin driven"),
+			}
+		}
+	}
+
+	fn chain<const N: usize>(
+		inputs: &[u8],
+	) -> (
+		ShiftRegister165<MockPin, MockPin, MockPin, N>,
+		Rc<RefCell<Chain>>,
+	) {
+		let chain = Chain::new(inputs);
+		let sr = ShiftRegister165::new(
+			port::Pin::new(MockPin(Wire::Load, chain.clone())).into_output(),
+			port::Pin::new(MockPin(Wire::Clock, chain.clone())).into_output(),
+			port::Pin::new(MockPin(Wire::Data, chain.clone())),
+		);
+		(sr, chain)
+	}
+
+	#[test]
+	fn read_single_register() {
+		let (mut sr, _) = chain::<1>(&[0b1000_0001]);
+		assert_eq!(sr.read(), [0b1000_0001]);
+		let (mut sr, _) = chain::<1>(&[0x5a]);
+		assert_eq!(sr.read(), [0x5a]);
+	}
+
+	#[test]
+	fn read_chain_order() {
+		let (mut sr, chain) = chain::<3>(&[0x12, 0x34, 0x56]);
+		assert_eq!(sr.read(), [0x12, 0x34, 0x56]);
+		assert_eq!(sr.read_u32(), 0x0056_3412);
+
+		// Every read latches the inputs again
+		chain.borrow_mut().inputs[1] = 0xff;
+		assert_eq!(sr.read(), [0x12, 0xff, 0x56]);
+	}
+
+	#[test]
+	fn read_u32_ignores_further_registers() {
+		let (mut sr, _) = chain::<5>(&[1, 2, 3, 4, 5]);
+		assert_eq!(sr.read_u32(), 0x0403_0201);
+	}
+}
@@ -1,4 +1,47 @@
 //! PWM Implementation
+//!
+//! # Glitch-free duty cycle updates
+//! Most timers are run in fast PWM mode; `TC2` on ATmega1280/ATmega2560 and `TC1` on
+//! ATmega16/ATmega164PA run in phase correct PWM mode instead.  In the PWM modes, the output
+//! compare registers (`OCRnx`) are double-buffered by the hardware: a new duty cycle is written to
+//! a buffer and only copied into the actual compare register at the end of the current period.
+//! Thus, changing the duty cycle never produces a shortened or doubled pulse, which matters for
+//! smooth LED fades and motor control.
+//!
+//! | Timer mode | `OCRnx` update |
+//! | --- | --- |
+//! | Normal, CTC | immediately (not double-buffered) |
+//! | Fast PWM | at `BOTTOM`, i.e. at the start of the next period |
+//! | Phase correct PWM | at `TOP` |
+//!
+//! The 16-bit compare registers of 16-bit timers are written through a temporary register shared
+//! by all 16-bit registers of the timer, so [`set_duty()`][Pin::set_duty] writes them inside a
+//! critical section to stay consistent when interrupt handlers access the same timer.
+//!
+//! # Multiple channels per timer
+//! Every timer has two or three output compare units (`OCnA`, `OCnB`, and on the larger 16-bit
+//! timers `OCnC`), each driving its own pin.  All pins of a timer can be converted with
+//! `into_pwm()` at the same time and each gets its own duty cycle, which is enough for an RGB
+//! LED from a single timer:
+//!
+//! ```ignore
+//! let timer1 = Timer1Pwm::new(dp.TC1, Prescaler::Prescale64);
+//! let mut red = pins.d11.into_output().into_pwm(&timer1);
+//! let mut green = pins.d12.into_output().into_pwm(&timer1);
+//! let mut blue = pins.d13.into_output().into_pwm(&timer1);
+//! red.set_duty(255);
+//! green.set_duty(64);
+//! blue.set_duty(0);
+//! ```
+//!
+//! The channels share the timer's counter, and with it the prescaler, `TOP`, and thus the PWM
+//! frequency; only the duty cycles are independent.  All channels of a timer also start their
+//! periods at the same time.  Signals which need different frequencies must come from different
+//! timers.
+//!
+//! **Note**: With a duty cycle of `0`, the timers running in fast PWM mode still output a spike of
+//! one timer tick per period.  Use [`disable()`][Pin::disable] to hold the output low completely.
+//! The phase correct timers listed above output a constant low level at duty `0`.
 
 use core::marker::PhantomData;
 use embedded_hal::pwm;
@@ -8,6 +51,7 @@ use embedded_hal::pwm::SetDutyCycle;
 
 use crate::port::mode;
 use crate::port::Pin;
+use crate::progmem::ProgMem;
 
 /// Clock prescaler for PWM
 ///
@@ -18,6 +62,10 @@ use crate::port::Pin;
 /// F_pwm = CLK_io / (Prescaler * 256);
 /// ```
 ///
+/// The table below is for fast PWM.  Timers running in phase correct mode (`TC2` on
+/// ATmega1280/2560, `TC1` on ATmega164PA/16) take 510 ticks per period, about half these
+/// frequencies.  The `frequency()` method of each timer type accounts for its mode.
+///
 /// | Prescaler | 16 MHz Clock | 8 MHz Clock |
 /// | --- | --- | ---|
 /// | `Direct` | 62.5 kHz | 31.3 kHz |
@@ -39,6 +87,42 @@ pub enum Prescaler {
 	Prescale1024,
 }
 
+impl Prescaler {
+	/// The factor by which the IO clock is divided.
+	pub fn divider(self) -> u32 {
+		match self {
+			Prescaler::Direct => 1,
+			Prescaler::Prescale8 => 8,
+			Prescaler::Prescale64 => 64,
+			Prescaler::Prescale256 => 256,
+			Prescaler::Prescale1024 => 1024,
+		}
+	}
+
+	/// The fast PWM frequency in Hz resulting from this prescaler with the given IO clock.
+	///
+	/// In 8-bit fast PWM mode, one period is [`FAST_PWM_PERIOD`] (256) timer ticks.  The timers
+	/// with a separate `TOP` register (`TC4` on ATmega32U4, `TC1` on ATtiny85) leave it at its
+	/// reset value of 255 as well.  For timers in phase correct mode, use
+	/// [`pwm_frequency_for_period()`][Prescaler::pwm_frequency_for_period] or the timer's
+	/// `frequency()` method.
+	pub fn pwm_frequency(self, clock_hz: u32) -> u32 {
+		self.pwm_frequency_for_period(clock_hz, FAST_PWM_PERIOD)
+	}
+
+	/// The PWM frequency in Hz resulting from this prescaler with the given IO clock, for a
+	/// period of `period_ticks` timer ticks.
+	pub fn pwm_frequency_for_period(self, clock_hz: u32, period_ticks: u32) -> u32 {
+		clock_hz / (self.divider() * period_ticks)
+	}
+}
+
+/// Timer ticks per period in 8-bit fast PWM mode, counting from 0 to 255.
+pub const FAST_PWM_PERIOD: u32 = 256;
+
+/// Timer ticks per period in 8-bit phase correct PWM mode, counting from 0 to 255 and back down.
+pub const PHASE_CORRECT_PWM_PERIOD: u32 = 510;
+
 /// Implement traits and types for PWM timers
 pub trait PwmPinOps<TC> {
 	type Duty;
@@ -73,6 +157,7 @@ impl<TC, PIN: PwmPinOps<TC>> Pin<mode::PwmOutput<TC>, PIN> {
 		self.pin.disable();
 	}
 
+	/// The currently configured duty cycle, read back from the compare register.
 	pub fn get_duty(&self) -> <PIN as PwmPinOps<TC>>::Duty {
 		self.pin.get_duty()
 	}
@@ -117,12 +202,271 @@ impl<TC, PIN: PwmPinOps<TC, Duty = u8>> SetDutyCycle for Pin<mode::PwmOutput<TC>
 	}
 }
 
+/// A digital-to-analog converter made of a PWM output and an external RC low-pass filter.
+///
+/// The filter averages the PWM signal to a DC voltage proportional to the duty cycle, so
+/// [`set_voltage_mv()`][PwmDac::set_voltage_mv] just computes the matching duty cycle from the
+/// supply voltage.  With 8-bit duty cycles, the resolution is `VCC / 256`, about 20 mV at 5 V.
+///
+/// ```text
+///            R
+/// PWM ----[=====]----+----> analog output (high impedance load or op-amp buffer)
+///                    |
+///                   === C
+///                    |
+///                   GND
+/// ```
+///
+/// # Ripple and bandwidth
+/// The filter cannot remove the PWM frequency completely.  The remaining ripple is largest at
+/// 50 % duty cycle, about `VCC / (4 * f_pwm * R * C)` peak to peak.  A larger `R * C` means less
+/// ripple, but the output also follows changes more slowly: it settles within about `5 * R * C`.
+/// So pick the fastest PWM prescaler available and then the smallest `R * C` with acceptable
+/// ripple.  For example, at 62.5 kHz (`Prescaler::Direct` at 16 MHz) with 10 kΩ and 1 µF, the
+/// ripple is 2 mV at 5 V and the output settles in 50 ms.  A second RC stage reduces the ripple
+/// much further for the same settling time.
+///
+/// The output voltage follows the supply voltage, so an unstable supply directly shows up on the
+/// output.  Also, the filter's output impedance is `R`, so anything but a high-impedance input
+/// needs a buffer.
+pub struct PwmDac<TC, PIN> {
+	pin: Pin<mode::PwmOutput<TC>, PIN>,
+	supply_mv: u16,
+	duty: Option<u8>,
+}
+
+impl<TC, PIN: PwmPinOps<TC, Duty = u8>> PwmDac<TC, PIN> {
+	/// Use `pin` as a DAC, with the PWM high level being `supply_mv` millivolts.
+	///
+	/// The output starts at 0 V.
+	pub fn new(mut pin: Pin<mode::PwmOutput<TC>, PIN>, supply_mv: u16) -> Self {
+		pin.disable();
+		Self {
+			pin,
+			supply_mv,
+			duty: None,
+		}
+	}
+
+	/// The duty cycle producing `mv` millivolts, or `None` for 0 V.
+	///
+	/// In fast PWM mode, a compare value of `duty` keeps the output high for `duty + 1` out of
+	/// 256 ticks, so the average voltage is `VCC * (duty + 1) / 256`.  A true 0 V is only
+	/// possible by disabling the output.
+	pub fn duty_for_mv(&self, mv: u16) -> Option<u8> {
+		let mv = u32::from(mv.min(self.supply_mv));
+		let supply = u32::from(self.supply_mv).max(1);
+		let ticks = (mv * 256 + supply / 2) / supply;
+		ticks.checked_sub(1).map(|duty| duty.min(255) as u8)
+	}
+
+	/// Set the output to `mv` millivolts, or as close as the resolution allows.
+	///
+	/// Voltages above the supply voltage are clamped to it.
+	pub fn set_voltage_mv(&mut self, mv: u16) {
+		self.duty = self.duty_for_mv(mv);
+		match self.duty {
+			Some(duty) => {
+				self.pin.set_duty(duty);
+				self.pin.enable();
+			}
+			None => self.pin.disable(),
+		}
+	}
+
+	/// The voltage currently being output, in millivolts, after rounding to the resolution.
+	pub fn voltage_mv(&self) -> u16 {
+		match self.duty {
+			Some(duty) => {
+				((u32::from(self.supply_mv) * (u32::from(duty) + 1) + 128) / 256)
+					as u16
+			}
+			None => 0,
+		}
+	}
+
+	/// Update the supply voltage, e.g. after measuring it, and recompute the output.
+	pub fn set_supply_mv(&mut self, supply_mv: u16) {
+		let mv = self.voltage_mv();
+		self.supply_mv = supply_mv;
+		self.set_voltage_mv(mv);
+	}
+
+	/// Switch the output to 0 V and give back the pin.
+	pub fn release(mut self) -> Pin<mode::PwmOutput<TC>, PIN> {
+		self.pin.disable();
+		self.pin
+	}
+}
+
+/// Number of samples in one period of a [`WaveGen`] waveform table.
+pub const WAVE_TABLE_LEN: usize = 256;
+
+crate::progmem! {
+	/// One period of a sine wave, centered on 128, for [`WaveGen::sine()`].
+	pub static progmem SINE_TABLE: [u8; WAVE_TABLE_LEN] = [
+		128, 131, 134, 137, 140, 143, 146, 149, 152, 155, 158, 162, 165, 167, 170, 173,
+		176, 179, 182, 185, 188, 190, 193, 196, 198, 201, 203, 206, 208, 211, 213, 215,
+		218, 220, 222, 224, 226, 228, 230, 232, 234, 235, 237, 238, 240, 241, 243, 244,
+		245, 246, 248, 249, 250, 250, 251, 252, 253, 253, 254, 254, 254, 255, 255, 255,
+		255, 255, 255, 255, 254, 254, 254, 253, 253, 252, 251, 250, 250, 249, 248, 246,
+		245, 244, 243, 241, 240, 238, 237, 235, 234, 232, 230, 228, 226, 224, 222, 220,
+		218, 215, 213, 211, 208, 206, 203, 201, 198, 196, 193, 190, 188, 185, 182, 179,
+		176, 173, 170, 167, 165, 162, 158, 155, 152, 149, 146, 143, 140, 137, 134, 131,
+		128, 124, 121, 118, 115, 112, 109, 106, 103, 100, 97, 93, 90, 88, 85, 82,
+		79, 76, 73, 70, 67, 65, 62, 59, 57, 54, 52, 49, 47, 44, 42, 40,
+		37, 35, 33, 31, 29, 27, 25, 23, 21, 20, 18, 17, 15, 14, 12, 11,
+		10, 9, 7, 6, 5, 5, 4, 3, 2, 2, 1, 1, 1, 0, 0, 0,
+		0, 0, 0, 0, 1, 1, 1, 2, 2, 3, 4, 5, 5, 6, 7, 9,
+		10, 11, 12, 14, 15, 17, 18, 20, 21, 23, 25, 27, 29, 31, 33, 35,
+		37, 40, 42, 44, 47, 49, 52, 54, 57, 59, 62, 65, 67, 70, 73, 76,
+		79, 82, 85, 88, 90, 93, 97, 100, 103, 106, 109, 112, 115, 118, 121, 124,
+	];
+}
+
+/// A low-frequency waveform generator built from a PWM output, a waveform table in flash, and
+/// an external RC low-pass filter.
+///
+/// Like [`PwmDac`], the filter turns the duty cycle into a voltage.  [`tick()`][WaveGen::tick]
+/// has to be called at a fixed sample rate from a timer interrupt and sets the duty cycle to the
+/// next sample of one period of the waveform, a table of [`WAVE_TABLE_LEN`] duty cycles in
+/// [program memory][crate::progmem].  The position in the table is a 32-bit phase accumulator
+/// which advances by `frequency * 2^32 / sample_rate` per tick, so any frequency up to half the
+/// sample rate can be set with a resolution of `sample_rate / 2^32`, and the table steps by
+/// fractional amounts instead of being played at a fixed speed.
+///
+/// ```ignore
+/// let timer0 = Timer0Pwm::new(dp.TC0, Prescaler::Direct);
+/// let mut wave = WaveGen::sine(pins.d6.into_output().into_pwm(&timer0), 62_500, 440);
+///
+/// // Timer 0 overflows at the sample rate of 62.5 kHz (16 MHz / 256); enable TOIE0 and tick
+/// // from its interrupt, so every PWM period gets a new sample.
+/// #[avr_device::interrupt(atmega328p)]
+/// fn TIMER0_OVF() {
+///     // ... wave.tick() on the WaveGen kept in a Mutex<RefCell<_>> ...
+/// }
+/// ```
+///
+/// Custom waveforms are tables of their own:
+///
+/// ```ignore
+/// avr_hal_generic::progmem! {
+///     static progmem SAWTOOTH: [u8; WAVE_TABLE_LEN] = { /* 0, 1, 2, ..., 255 */ };
+/// }
+///
+/// let mut wave = WaveGen::new(pin, &SAWTOOTH, 10_000, 50);
+/// ```
+///
+/// # Frequency and resolution
+/// The sample rate is limited by the CPU time of the interrupt, about 100 cycles per tick, so at
+/// 16 MHz a sample rate of 62.5 kHz (one sample per 8-bit PWM period) already takes 40 % of the
+/// CPU; 10 kHz to 20 kHz is a more reasonable choice.  The output is a staircase with
+/// `sample_rate / frequency` steps per period: at 10 kHz, a 100 Hz sine has 100 steps and is
+/// smooth, while a 2 kHz sine only has 5 and is mostly a filtered square wave.  Above about a
+/// tenth of the sample rate, the table position also jumps unevenly, adding harmonics.
+///
+/// The amplitude resolution is 8 bits, like the duty cycle.
+///
+/// # Output filter
+/// Without a filter, the output is the raw PWM signal.  The RC low-pass filter has to pass the
+/// generated frequency and suppress both the PWM frequency and the sample rate steps, so its
+/// cutoff frequency `1 / (2 * pi * R * C)` belongs between the highest generated frequency and
+/// the sample rate.  For example, 1 kΩ and 100 nF give a cutoff at 1.6 kHz; a second RC stage
+/// or an active filter improves the suppression considerably.  As with [`PwmDac`], the output
+/// needs a buffer for anything but a high-impedance load.
+pub struct WaveGen<TC, PIN> {
+	pin: Pin<mode::PwmOutput<TC>, PIN>,
+	table: &'static ProgMem<[u8; WAVE_TABLE_LEN]>,
+	sample_rate_hz: u32,
+	phase: u32,
+	increment: u32,
+}
+
+impl<TC, PIN: PwmPinOps<TC, Duty = u8>> WaveGen<TC, PIN> {
+	/// Generate the waveform in `table` with `frequency_hz` on `pin`, with [`tick()`] called
+	/// `sample_rate_hz` times per second.
+	///
+	/// The output starts at the first sample of the table.
+	///
+	/// [`tick()`]: WaveGen::tick
+	pub fn new(
+		mut pin: Pin<mode::PwmOutput<TC>, PIN>,
+		table: &'static ProgMem<[u8; WAVE_TABLE_LEN]>,
+		sample_rate_hz: u32,
+		frequency_hz: u32,
+	) -> Self {
+		pin.set_duty(table.load_at(0));
+		pin.enable();
+		let mut wave = Self {
+			pin,
+			table,
+			sample_rate_hz: sample_rate_hz.max(1),
+			phase: 0,
+			increment: 0,
+		};
+		wave.set_frequency(frequency_hz);
+		wave
+	}
+
+	/// Generate a sine wave with `frequency_hz` from [`SINE_TABLE`].  See [`new()`].
+	///
+	/// [`new()`]: WaveGen::new
+	pub fn sine(
+		pin: Pin<mode::PwmOutput<TC>, PIN>,
+		sample_rate_hz: u32,
+		frequency_hz: u32,
+	) -> Self {
+		Self::new(pin, &SINE_TABLE, sample_rate_hz, frequency_hz)
+	}
+
+	/// Output the next sample.  Has to be called at the sample rate given to [`new()`].
+	///
+	/// [`new()`]: WaveGen::new
+	pub fn tick(&mut self) {
+		self.phase = self.phase.wrapping_add(self.increment);
+		let index = (self.phase >> 24) as usize;
+		self.pin.set_duty(self.table.load_at(index));
+	}
+
+	/// Change the frequency without a jump in the waveform.
+	///
+	/// Frequencies above half the sample rate are clamped to it.
+	pub fn set_frequency(&mut self, frequency_hz: u32) {
+		let frequency_hz = frequency_hz.min(self.sample_rate_hz / 2);
+		self.increment =
+			((u64::from(frequency_hz) << 32) / u64::from(self.sample_rate_hz)) as u32;
+	}
+
+	/// The generated frequency, rounded to whole Hz.
+	pub fn frequency(&self) -> u32 {
+		((u64::from(self.increment) * u64::from(self.sample_rate_hz) + (1 << 31)) >> 32)
+			as u32
+	}
+
+	/// Amount by which the phase advances per tick, where `2^32` is one period.
+	pub fn phase_increment(&self) -> u32 {
+		self.increment
+	}
+
+	/// Switch to another waveform table, keeping frequency and phase.
+	pub fn set_table(&mut self, table: &'static ProgMem<[u8; WAVE_TABLE_LEN]>) {
+		self.table = table;
+	}
+
+	/// Stop the output and give back the pin.
+	pub fn release(mut self) -> Pin<mode::PwmOutput<TC>, PIN> {
+		self.pin.disable();
+		self.pin
+	}
+}
+
 #[macro_export]
 macro_rules! impl_simple_pwm {
     (
         $(#[$timer_pwm_attr:meta])*
         pub struct $TimerPwm:ident {
             timer: $TIMER:ty,
+            period: $period:expr,
             init: |$init_timer:ident, $prescaler:ident| $init_block:block,
             pins: {$(
                 $PXi:ident: {
@@ -136,11 +480,15 @@ macro_rules! impl_simple_pwm {
         $(#[$timer_pwm_attr])*
         pub struct $TimerPwm {
             timer: $TIMER,
+            prescaler: $crate::simple_pwm::Prescaler,
         }
 
         impl $TimerPwm {
+            /// Timer ticks per PWM period, depending on the PWM mode this timer runs in.
+            pub const PERIOD_TICKS: u32 = $period;
+
             pub fn new(timer: $TIMER, prescaler: $crate::simple_pwm::Prescaler) -> $TimerPwm {
-                let mut t = $TimerPwm { timer };
+                let mut t = $TimerPwm { timer, prescaler };
 
                 {
                     let $init_timer = &mut t.timer;
@@ -150,6 +498,18 @@ macro_rules! impl_simple_pwm {
 
                 t
             }
+
+            /// The prescaler this timer was configured with.
+            pub fn prescaler(&self) -> $crate::simple_pwm::Prescaler {
+                self.prescaler
+            }
+
+            /// The PWM frequency in Hz, computed from the prescaler, the IO clock `CLOCK`, and
+            /// [`PERIOD_TICKS`][Self::PERIOD_TICKS].
+            pub fn frequency<CLOCK: $crate::clock::Clock>(&self) -> u32 {
+                self.prescaler
+                    .pwm_frequency_for_period(CLOCK::FREQ, Self::PERIOD_TICKS)
+            }
         }
 
         $(
@@ -185,11 +545,262 @@ macro_rules! impl_simple_pwm {
                 }
 
                 fn set_duty(&mut self, duty: Self::Duty) {
-                    // SAFETY: This register is exclusively used here so there are no concurrency
-                    // issues.
-                    unsafe { (&*<$TIMER>::ptr()).$ocr().write(|w| w.bits(duty.into())); };
+                    // SAFETY: This register is exclusively used here.  The critical section keeps
+                    // the write of 16-bit registers, which goes through the timer's shared TEMP
+                    // register, from being interleaved with interrupt handlers using the same
+                    // timer.  The hardware double-buffers the value until the end of the period.
+                    $crate::avr_device::interrupt::free(|_| unsafe {
+                        (&*<$TIMER>::ptr()).$ocr().write(|w| w.bits(duty.into()));
+                    });
                 }
             }
         )+
     }
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::port;
+	use std::cell::RefCell;
+	use std::rc::Rc;
+
+	struct MockTc;
+
+	/// Compare registers and output enables of a timer with three channels.
+	#[derive(Default)]
+	struct Timer {
+		ocr: [u8; 3],
+		enabled: [bool; 3],
+		writes: usize,
+	}
+
+	struct MockChannel(usize, Rc<RefCell<Timer>>);
+
+	impl port::PinOps for MockChannel {
+		type Dynamic = Self;
+
+		fn into_dynamic(self) -> Self::Dynamic {
+			self
+		}
+
+		unsafe fn out_set(&mut self) {}
+		unsafe fn out_clear(&mut self) {}
+		unsafe fn out_toggle(&mut self) {}
+		unsafe fn out_get(&self) -> bool {
+			false
+		}
+		unsafe fn in_get(&self) -> bool {
+			false
+		}
+		unsafe fn make_output(&mut self) {}
+		unsafe fn make_input(&mut self, _pull_up: bool) {}
+	}
+
+	impl PwmPinOps<MockTc> for MockChannel {
+		type Duty = u8;
+
+		fn enable(&mut self) {
+			self.1.borrow_mut().enabled[self.0] = true;
+		}
+
+		fn disable(&mut self) {
+			self.1.borrow_mut().enabled[self.0] = false;
+		}
+
+		fn get_duty(&self) -> u8 {
+			self.1.borrow().ocr[self.0]
+		}
+
+		fn get_max_duty(&self) -> u8 {
+			u8::MAX
+		}
+
+		fn set_duty(&mut self, value: u8) {
+			let mut timer = self.1.borrow_mut();
+			timer.ocr[self.0] = value;
+			timer.writes += 1;
+		}
+	}
+
+	fn channel(
+		timer: &Rc<RefCell<Timer>>,
+		index: usize,
+	) -> Pin<mode::PwmOutput<MockTc>, MockChannel> {
+		Pin::new(MockChannel(index, timer.clone()))
+			.into_output()
+			.into_pwm(&MockTc)
+	}
+
+	#[test]
+	fn pwm_frequency() {
+		assert_eq!(Prescaler::Direct.pwm_frequency(16_000_000), 62_500);
+		assert_eq!(Prescaler::Prescale64.pwm_frequency(16_000_000), 976);
+		assert_eq!(Prescaler::Prescale1024.pwm_frequency(8_000_000), 30);
+	}
+
+	#[test]
+	fn phase_correct_frequency() {
+		// The well-known 490 Hz of `analogWrite()` on the timers in phase correct mode
+		assert_eq!(
+			Prescaler::Prescale64
+				.pwm_frequency_for_period(16_000_000, PHASE_CORRECT_PWM_PERIOD),
+			490
+		);
+		assert_eq!(
+			Prescaler::Prescale64.pwm_frequency_for_period(16_000_000, FAST_PWM_PERIOD),
+			Prescaler::Prescale64.pwm_frequency(16_000_000)
+		);
+	}
+
+	#[test]
+	fn set_duty_cycle_writes_once() {
+		let timer = Rc::new(RefCell::new(Timer::default()));
+		let mut pwm = channel(&timer, 0);
+
+		pwm.set_duty_cycle(200).unwrap();
+		assert_eq!(pwm.get_duty(), 200);
+		assert_eq!(timer.borrow().writes, 1);
+
+		// Rejected duty cycles leave the compare register alone
+		assert_eq!(pwm.set_duty_cycle(256), Err(PwmError::DutyCycleTooLarge));
+		assert_eq!(pwm.get_duty(), 200);
+		assert_eq!(timer.borrow().writes, 1);
+
+		pwm.set_duty_cycle_percent(50).unwrap();
+		assert_eq!(pwm.get_duty(), 127);
+		pwm.set_duty_cycle_fully_off().unwrap();
+		assert_eq!(pwm.get_duty(), 0);
+		assert_eq!(pwm.max_duty_cycle(), 255);
+	}
+
+	#[test]
+	fn channels_of_one_timer_are_independent() {
+		let timer = Rc::new(RefCell::new(Timer::default()));
+		let mut red = channel(&timer, 0);
+		let mut green = channel(&timer, 1);
+		let mut blue = channel(&timer, 2);
+
+		red.set_duty(255);
+		green.set_duty(64);
+		blue.set_duty(0);
+		red.enable();
+		green.enable();
+		assert_eq!(timer.borrow().ocr, [255, 64, 0]);
+		assert_eq!(timer.borrow().enabled, [true, true, false]);
+
+		green.disable();
+		green.set_duty(128);
+		assert_eq!(timer.borrow().ocr, [255, 128, 0]);
+		assert_eq!(timer.borrow().enabled, [true, false, false]);
+		assert_eq!(
+			(red.get_duty(), green.get_duty(), blue.get_duty()),
+			(255, 128, 0)
+		);
+	}
+
+	#[test]
+	fn dac_duty_for_mv() {
+		let timer = Rc::new(RefCell::new(Timer::default()));
+		let dac = PwmDac::new(channel(&timer, 0), 5000);
+		assert_eq!(dac.duty_for_mv(0), None);
+		// Below half a step
+		assert_eq!(dac.duty_for_mv(9), None);
+		assert_eq!(dac.duty_for_mv(10), Some(0));
+		assert_eq!(dac.duty_for_mv(2500), Some(127));
+		assert_eq!(dac.duty_for_mv(5000), Some(255));
+		// Clamped to the supply
+		assert_eq!(dac.duty_for_mv(6000), Some(255));
+	}
+
+	#[test]
+	fn dac_output() {
+		let timer = Rc::new(RefCell::new(Timer::default()));
+		timer.borrow_mut().enabled[0] = true;
+		let mut dac = PwmDac::new(channel(&timer, 0), 5000);
+		assert!(!timer.borrow().enabled[0]);
+		assert_eq!(dac.voltage_mv(), 0);
+
+		dac.set_voltage_mv(2500);
+		assert_eq!(
+			(timer.borrow().ocr[0], timer.borrow().enabled[0]),
+			(127, true)
+		);
+		assert_eq!(dac.voltage_mv(), 2500);
+
+		// The output voltage is kept when the supply changes
+		dac.set_supply_mv(3300);
+		assert_eq!(timer.borrow().ocr[0], 193);
+		assert_eq!(dac.voltage_mv(), 2501);
+
+		dac.set_voltage_mv(0);
+		assert!(!timer.borrow().enabled[0]);
+		assert_eq!(dac.voltage_mv(), 0);
+
+		dac.set_voltage_mv(1000);
+		let pin = dac.release();
+		assert!(!timer.borrow().enabled[0]);
+		assert_eq!(pin.get_duty(), 77);
+	}
+
+	const fn ramp() -> [u8; WAVE_TABLE_LEN] {
+		let mut table = [0; WAVE_TABLE_LEN];
+		let mut i = 0;
+		while i < WAVE_TABLE_LEN {
+			table[i] = i as u8;
+			i += 1;
+		}
+		table
+	}
+
+	crate::progmem! {
+		static progmem RAMP: [u8; WAVE_TABLE_LEN] = ramp();
+	}
+
+	#[test]
+	fn wave_frequency() {
+		let timer = Rc::new(RefCell::new(Timer::default()));
+		let mut wave = WaveGen::sine(channel(&timer, 0), 62_500, 440);
+		assert_eq!(wave.phase_increment(), 30_236_569);
+		assert_eq!(wave.frequency(), 440);
+
+		// Clamped to half the sample rate
+		wave.set_frequency(40_000);
+		assert_eq!(wave.phase_increment(), 1 << 31);
+		assert_eq!(wave.frequency(), 31_250);
+	}
+
+	#[test]
+	fn wave_steps_through_table() {
+		let timer = Rc::new(RefCell::new(Timer::default()));
+		let mut wave = WaveGen::sine(channel(&timer, 0), 256, 1);
+		assert_eq!(
+			(timer.borrow().ocr[0], timer.borrow().enabled[0]),
+			(128, true)
+		);
+		wave.tick();
+		assert_eq!(timer.borrow().ocr[0], 131);
+		for _ in 1..64 {
+			wave.tick();
+		}
+		assert_eq!(timer.borrow().ocr[0], 255);
+
+		// Same phase, other table
+		wave.set_table(&RAMP);
+		wave.tick();
+		assert_eq!(timer.borrow().ocr[0], 65);
+
+		// Fractional steps: 1.5 samples per tick
+		let mut wave = WaveGen::new(wave.release(), &RAMP, 512, 3);
+		assert_eq!(timer.borrow().ocr[0], 0);
+		let mut samples = [0; 4];
+		for sample in samples.iter_mut() {
+			wave.tick();
+			*sample = timer.borrow().ocr[0];
+		}
+		assert_eq!(samples, [1, 3, 4, 6]);
+
+		let _pin = wave.release();
+		assert!(!timer.borrow().enabled[0]);
+	}
+}
@@ -0,0 +1,222 @@
+//! Software PWM on arbitrary pins
+//!
+//! [`SoftPwm`] drives PWM on any output pins, not just the few timer output compare pins.  A
+//! counter runs from 0 to 255 and wraps around; every call to [`SoftPwm::tick()`] advances it by
+//! one and updates all pins.  `tick()` is usually called from a timer interrupt, so the PWM
+//! frequency is the interrupt rate divided by 256.
+//!
+//! # Phase offsets
+//! Without offsets, all channels switch on at the same time at the start of every period.  With
+//! several LEDs (e.g. an RGB LED) this draws the peak current of all of them at once, which can
+//! cause supply dips and brown-outs on battery-powered boards, and makes the channels interact
+//! visibly.  [`SoftPwm::set_phase()`] shifts the on-time of a channel within the period: a
+//! channel with phase `p` switches on when the counter reaches `p` instead of `0`.
+//! [`SoftPwm::stagger()`] spreads all channels evenly across the period.
+//!
+//! ```text
+//! counter  0        64       128      192      255
+//! ch 0     ████████████                          phase 0,   duty 96
+//! ch 1              ████████████                 phase 64,  duty 96
+//! ch 2                       ████████████        phase 128, duty 96
+//! ```
+//!
+//! # CPU cost
+//! Each `tick()` compares and writes every pin, which takes roughly 10-20 cycles per channel
+//! (more for [downgraded][crate::port::Pin::downgrade] pins), plus the interrupt entry and exit.
+//! For a flicker-free 100 Hz PWM, `tick()` must run at 25.6 kHz, which leaves 625 cycles per tick
+//! at 16 MHz, so three channels cost roughly a fifth of the CPU time.  Lower PWM frequencies are
+//! proportionally cheaper.
+//!
+//! # Example
+//! ```ignore
+//! let mut rgb = SoftPwm::new([
+//!     pins.d2.into_output().downgrade(),
+//!     pins.d4.into_output().downgrade(),
+//!     pins.d7.into_output().downgrade(),
+//! ]);
+//! rgb.stagger();
+//! rgb.set_duty(0, 200);
+//!
+//! // From a timer interrupt at 25.6 kHz:
+//! rgb.tick();
+//! ```
+use crate::port;
+use crate::port::mode;
+
+/// Software PWM on `N` pins.  See the [module documentation](self).
+pub struct SoftPwm<PIN, const N: usize> {
+	pins: [port::Pin<mode::Output, PIN>; N],
+	duty: [u8; N],
+	phase: [u8; N],
+	counter: u8,
+}
+
+impl<PIN: port::PinOps, const N: usize> SoftPwm<PIN, N> {
+	/// Create a software PWM with all channels off and no phase offsets.
+	pub fn new(mut pins: [port::Pin<mode::Output, PIN>; N]) -> Self {
+		for pin in pins.iter_mut() {
+			pin.set_low();
+		}
+		Self {
+			pins,
+			duty: [0; N],
+			phase: [0; N],
+			counter: 0,
+		}
+	}
+
+	/// Set the duty cycle of `channel`: the pin is high for `duty` out of 256 ticks.
+	///
+	/// # Panics
+	/// Panics if `channel` is out of range.
+	pub fn set_duty(&mut self, channel: usize, duty: u8) {
+		self.duty[channel] = duty;
+	}
+
+	pub fn duty(&self, channel: usize) -> u8 {
+		self.duty[channel]
+	}
+
+	/// Shift the on-time of `channel` by `offset` ticks within the period.
+	///
+	/// # Panics
+	/// Panics if `channel` is out of range.
+	pub fn set_phase(&mut self, channel: usize, offset: u8) {
+		self.phase[channel] = offset;
+	}
+
+	pub fn phase(&self, channel: usize) -> u8 {
+		self.phase[channel]
+	}
+
+	/// Spread the phases of all channels evenly across the period.
+	pub fn stagger(&mut self) {
+		for (i, phase) in self.phase.iter_mut().enumerate() {
+			*phase = (i * 256 / N) as u8;
+		}
+	}
+
+	/// Advance the PWM by one step and update all pins.
+	pub fn tick(&mut self) {
+		self.counter = self.counter.wrapping_add(1);
+		for (i, pin) in self.pins.iter_mut().enumerate() {
+			if self.counter.wrapping_sub(self.phase[i]) < self.duty[i] {
+				pin.set_high();
+			} else {
+				pin.set_low();
+			}
+		}
+	}
+
+	/// Switch all pins off and return them.
+	pub fn release(mut self) -> [port::Pin<mode::Output, PIN>; N] {
+		for pin in self.pins.iter_mut() {
+			pin.set_low();
+		}
+		self.pins
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::cell::Cell;
+	use std::rc::Rc;
+	use std::vec::Vec;
+
+	struct MockPin(Rc<Cell<bool>>);
+
+	impl port::PinOps for MockPin {
+		type Dynamic = Self;
+
+		fn into_dynamic(self) -> Self::Dynamic {
+			self
+		}
+
+		unsafe fn out_set(&mut self) {
+			self.0.set(true);
+		}
+		unsafe fn out_clear(&mut self) {
+			self.0.set(false);
+		}
+		unsafe fn out_toggle(&mut self) {
+			self.0.set(!self.0.get());
+		}
+		unsafe fn out_get(&self) -> bool {
+			self.0.get()
+		}
+		unsafe fn in_get(&self) -> bool {
+			self.0.get()
+		}
+		unsafe fn make_output(&mut self) {}
+		unsafe fn make_input(&mut self, _pull_up: bool) {}
+	}
+
+	fn soft_pwm<const N: usize>() -> (SoftPwm<MockPin, N>, [Rc<Cell<bool>>; N]) {
+		let levels: [Rc<Cell<bool>>; N] =
+			core::array::from_fn(|_| Rc::new(Cell::new(true)));
+		let pins = core::array::from_fn(|i| {
+			port::Pin::new(MockPin(levels[i].clone())).into_output_high()
+		});
+		(SoftPwm::new(pins), levels)
+	}
+
+	/// Run one full period and collect the counter values at which each channel was high, in
+	/// ascending order.
+	fn period<const N: usize>(
+		pwm: &mut SoftPwm<MockPin, N>,
+		levels: &[Rc<Cell<bool>>; N],
+	) -> [Vec<u8>; N] {
+		let mut high: [Vec<u8>; N] = core::array::from_fn(|_| Vec::new());
+		for _ in 0..256 {
+			pwm.tick();
+			for (channel, level) in levels.iter().enumerate() {
+				if level.get() {
+					high[channel].push(pwm.counter);
+				}
+			}
+		}
+		for ticks in high.iter_mut() {
+			ticks.sort();
+		}
+		high
+	}
+
+	#[test]
+	fn duty_cycles() {
+		let (mut pwm, levels) = soft_pwm::<3>();
+		assert!(levels.iter().all(|l| !l.get()));
+		pwm.set_duty(1, 96);
+		pwm.set_duty(2, 255);
+		assert_eq!(pwm.duty(1), 96);
+
+		let high = period(&mut pwm, &levels);
+		assert!(high[0].is_empty());
+		assert_eq!(high[1], (0..96).collect::<Vec<u8>>());
+		assert_eq!(high[2].len(), 255);
+
+		let _pins = pwm.release();
+		assert!(levels.iter().all(|l| !l.get()));
+	}
+
+	#[test]
+	fn phase_offsets() {
+		let (mut pwm, levels) = soft_pwm::<3>();
+		pwm.stagger();
+		assert_eq!([pwm.phase(0), pwm.phase(1), pwm.phase(2)], [0, 85, 170]);
+		for channel in 0..3 {
+			pwm.set_duty(channel, 96);
+		}
+
+		let high = period(&mut pwm, &levels);
+		assert_eq!(high[0], (0..96).collect::<Vec<u8>>());
+		assert_eq!(high[1], (85..181).collect::<Vec<u8>>());
+		// Wraps around into the next period
+		let mut expected: Vec<u8> = (0..10).collect();
+		expected.extend(170..=255);
+		assert_eq!(high[2], expected);
+
+		pwm.set_phase(0, 200);
+		assert_eq!(period(&mut pwm, &levels)[0].len(), 96);
+	}
+}
@@ -3,6 +3,7 @@ use crate::port;
 use core::marker::PhantomData;
 use embedded_hal::spi::SpiBus;
 use embedded_hal::spi::{self};
+use unwrap_infallible::UnwrapInfallible;
 
 /// Oscillator Clock Frequency division options.
 ///
@@ -80,6 +81,8 @@ pub trait SpiOps<H, SCLK, MOSI, MISO, CS> {
 	fn raw_setup(&mut self, settings: &Settings);
 	/// Disable the peripheral
 	fn raw_release(&mut self);
+	/// Enable or disable the peripheral without touching the rest of its configuration
+	fn raw_set_enabled(&mut self, enabled: bool);
 
 	/// Check the interrupt flag to see if the write has completed
 	///
@@ -292,6 +295,37 @@ where
 		(self.p, self.sclk, self.mosi, self.miso, cs.0)
 	}
 
+	/// Temporarily disable the SPI peripheral and hand out SCLK and MOSI as GPIO pins.
+	///
+	/// Waits for an in-flight write to complete first.  While the peripheral is disabled, SCLK
+	/// and MOSI are controlled by their port registers again, so they can be used e.g. to drive
+	/// LEDs or a shift register between SPI transfers.  Call [`ParkedSpi::resume()`] with the
+	/// pins to get the `Spi` back.
+	///
+	/// Keep all chip-selects deasserted while parked, otherwise the connected devices interpret
+	/// the GPIO activity on SCLK and MOSI as an SPI transfer.
+	pub fn park(
+		mut self,
+	) -> (
+		ParkedSpi<H, SPI, SCLKPIN, MOSIPIN, MISOPIN, CSPIN>,
+		port::Pin<port::mode::Output, SCLKPIN>,
+		port::Pin<port::mode::Output, MOSIPIN>,
+	) {
+		nb::block!(self.flush()).unwrap_infallible();
+		self.p.raw_set_enabled(false);
+		(
+			ParkedSpi {
+				p: self.p,
+				miso: self.miso,
+				_pins: PhantomData,
+				_cs: PhantomData,
+				_h: PhantomData,
+			},
+			self.sclk,
+			self.mosi,
+		)
+	}
+
 	fn flush(&mut self) -> nb::Result<(), core::convert::Infallible> {
 		if self.write_in_progress {
 			if self.p.raw_check_iflag() {
@@ -313,6 +347,51 @@ where
 	}
 }
 
+/// A disabled SPI peripheral whose SCLK and MOSI pins are in use as GPIOs.
+///
+/// Created by [`Spi::park()`].  Only the SPI enable bit is cleared while parked, so the whole
+/// configuration survives a park/resume cycle: the data order, the clock polarity and phase, and
+/// the clock rate are the same after [`ParkedSpi::resume()`] as before parking.  The MISO pin
+/// stays owned by the parked SPI, and the [`ChipSelectPin`] stays with the caller, so CS remains
+/// an output as the peripheral requires.
+pub struct ParkedSpi<H, SPI, SCLKPIN, MOSIPIN, MISOPIN, CSPIN> {
+	p: SPI,
+	miso: port::Pin<port::mode::Input, MISOPIN>,
+	_pins: PhantomData<(SCLKPIN, MOSIPIN)>,
+	_cs: PhantomData<CSPIN>,
+	_h: PhantomData<H>,
+}
+
+impl<H, SPI, SCLKPIN, MOSIPIN, MISOPIN, CSPIN> ParkedSpi<H, SPI, SCLKPIN, MOSIPIN, MISOPIN, CSPIN>
+where
+	SPI: SpiOps<H, SCLKPIN, MOSIPIN, MISOPIN, CSPIN>,
+	SCLKPIN: port::PinOps,
+	MOSIPIN: port::PinOps,
+	MISOPIN: port::PinOps,
+	CSPIN: port::PinOps,
+{
+	/// Re-enable the SPI peripheral with its previous configuration.
+	///
+	/// The pins must be outputs again.  SCLK should be set to the idle level of the configured
+	/// clock polarity before resuming, to avoid a spurious clock edge.
+	pub fn resume(
+		mut self,
+		sclk: port::Pin<port::mode::Output, SCLKPIN>,
+		mosi: port::Pin<port::mode::Output, MOSIPIN>,
+	) -> Spi<H, SPI, SCLKPIN, MOSIPIN, MISOPIN, CSPIN> {
+		self.p.raw_set_enabled(true);
+		Spi {
+			p: self.p,
+			sclk,
+			mosi,
+			miso: self.miso,
+			write_in_progress: false,
+			_cs: PhantomData,
+			_h: PhantomData,
+		}
+	}
+}
+
 /// FullDuplex trait implementation, allowing this struct to be provided to
 /// drivers that require it for operation.  Only 8-bit word size is supported
 /// for now.
@@ -448,6 +527,90 @@ where
 {
 }
 
+/// A single device on a shared SPI bus, re-exported from [`embedded_hal_bus`].
+///
+/// Pairs a reference to the shared bus with the chip-select pin of one device and implements the
+/// embedded-hal 1.0 [`SpiDevice`][embedded_hal::spi::SpiDevice] trait on top of it.  Each
+/// transaction asserts CS, runs all operations, waits for the bus to become idle and releases CS
+/// again.  Device drivers can then simply take a `RefCellDevice` and need not care about other
+/// devices on the same bus.
+///
+/// # Sharing the bus
+/// The bus is stored in a [`RefCell`][core::cell::RefCell] and each `RefCellDevice` only holds a
+/// shared reference to it.  On the single-core AVR, this is all that is needed as long as the
+/// devices are only used from the main program: Transactions cannot overlap and the `RefCell`
+/// catches any accidental nesting with a panic.  If a device is also accessed from an interrupt
+/// handler, the whole transaction must run inside [`avr_device::interrupt::free`] so that it
+/// cannot be interrupted halfway.
+///
+/// `RefCellDevice::new()` does not touch the CS pin, so it has to be passed in high
+/// (deselected), e.g. with `into_output_high()`.  The `delay` is used for
+/// [`Operation::DelayNs`][embedded_hal::spi::Operation::DelayNs] and can be e.g.
+/// `arduino_hal::Delay::new()`.
+///
+/// # Example
+/// ```ignore
+/// use core::cell::RefCell;
+/// use arduino_hal::spi::RefCellDevice;
+///
+/// let (spi, _) = arduino_hal::Spi::new(/* ... */);
+/// let bus = RefCell::new(spi);
+///
+/// let flash = RefCellDevice::new(&bus, pins.d9.into_output_high(), arduino_hal::Delay::new());
+/// let display = RefCellDevice::new(&bus, pins.d8.into_output_high(), arduino_hal::Delay::new());
+/// ```
+pub use embedded_hal_bus::spi::RefCellDevice;
+
+/// Clock `chain` through a daisy chain of devices sharing one CS, in a single transaction.
+///
+/// In a daisy chain, MOSI is connected to the data input of the first device, the data output of
+/// each device to the input of the next one, and the output of the last device to MISO.  The
+/// devices are one long shift register: while CS is asserted, every byte clocked in pushes the
+/// contents of the whole chain along by one byte, and releasing CS latches what each device
+/// holds at that point.  So one transfer of the combined length of all frames updates every
+/// device at once, and `chain` is replaced with what the devices shifted out.
+///
+/// ```ignore
+/// // Three 74HC595 shift registers with a shared latch (CS)
+/// let mut leds = RefCellDevice::new(&bus, pins.d10.into_output_high(), Delay::new());
+///
+/// let mut chain = [0u8; 3];
+/// daisy_frame(&mut chain, 0, 1)[0] = 0b0000_0001; // the register connected to MOSI
+/// daisy_frame(&mut chain, 2, 1)[0] = 0b1000_0000; // the last one in the chain
+/// daisy_transfer(&mut leds, &mut chain)?;
+/// ```
+///
+/// # Order across the chain
+/// The byte clocked in first travels furthest, so `chain` starts with the frame of the **last**
+/// device (the one connected to MISO) and ends with the frame of the first device (the one
+/// connected to MOSI).  The data read back is in the same order: the last device's register is
+/// the first to appear on MISO.  [`daisy_frame()`] finds the frame of a device by its position in
+/// the chain, counted from MOSI.
+///
+/// Within each frame, bytes and bits go out in the order the devices expect them on their own,
+/// e.g. most significant byte first for 16-bit MAX7219 commands, with
+/// [`DataOrder::MostSignificantFirst`].  Frames which are not a whole number of bytes (say, a
+/// chain of three 12-bit DACs) have to be packed into one bit stream and padded at the
+/// **start**: the padding bits are pushed out of the far end and never latched.
+pub fn daisy_transfer<D: spi::SpiDevice>(device: &mut D, chain: &mut [u8]) -> Result<(), D::Error> {
+	device.transaction(&mut [spi::Operation::TransferInPlace(chain)])
+}
+
+/// The frame of the device at `position` (0 is the device connected to MOSI) in a buffer for
+/// [`daisy_transfer()`], where each device takes `frame_len` bytes.
+///
+/// # Panics
+/// Panics if the chain is too short for a device at `position`.
+pub fn daisy_frame(chain: &mut [u8], position: usize, frame_len: usize) -> &mut [u8] {
+	let devices = chain.len() / frame_len;
+	assert!(
+		position < devices,
+		"position is beyond the end of the chain"
+	);
+	let start = (devices - 1 - position) * frame_len;
+	&mut chain[start..start + frame_len]
+}
+
 /// Implement traits for a SPI interface
 #[macro_export]
 macro_rules! impl_spi {
@@ -521,6 +684,10 @@ macro_rules! impl_spi {
 				self.spcr().write(|w| w.spe().clear_bit());
 			}
 
+			fn raw_set_enabled(&mut self, enabled: bool) {
+				self.spcr().modify(|_, w| w.spe().bit(enabled));
+			}
+
 			fn raw_check_iflag(&self) -> bool {
 				self.spsr().read().spif().bit_is_set()
 			}
@@ -541,3 +708,189 @@ macro_rules! impl_spi {
 		}
 	};
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use core::cell::Cell;
+	use std::vec::Vec;
+
+	struct MockPin(bool);
+
+	impl port::PinOps for MockPin {
+		type Dynamic = Self;
+
+		fn into_dynamic(self) -> Self::Dynamic {
+			self
+		}
+
+		unsafe fn out_set(&mut self) {
+			self.0 = true;
+		}
+		unsafe fn out_clear(&mut self) {
+			self.0 = false;
+		}
+		unsafe fn out_toggle(&mut self) {
+			self.0 = !self.0;
+		}
+		unsafe fn out_get(&self) -> bool {
+			self.0
+		}
+		unsafe fn in_get(&self) -> bool {
+			self.0
+		}
+		unsafe fn make_output(&mut self) {}
+		unsafe fn make_input(&mut self, _pull_up: bool) {}
+	}
+
+	/// An SPI peripheral whose writes take a few polls of the interrupt flag to complete.
+	#[derive(Default)]
+	struct MockSpi {
+		setups: usize,
+		enabled: bool,
+		busy_polls: Cell<u8>,
+		written: u8,
+	}
+
+	impl SpiOps<(), MockPin, MockPin, MockPin, MockPin> for MockSpi {
+		fn raw_setup(&mut self, _settings: &Settings) {
+			self.setups += 1;
+			self.enabled = true;
+		}
+
+		fn raw_release(&mut self) {
+			self.enabled = false;
+		}
+
+		fn raw_set_enabled(&mut self, enabled: bool) {
+			self.enabled = enabled;
+		}
+
+		fn raw_check_iflag(&self) -> bool {
+			let polls = self.busy_polls.get();
+			self.busy_polls.set(polls.saturating_sub(1));
+			polls == 0
+		}
+
+		fn raw_read(&self) -> u8 {
+			self.written
+		}
+
+		fn raw_write(&mut self, byte: u8) {
+			assert!(self.enabled);
+			self.written = byte;
+			self.busy_polls.set(3);
+		}
+
+		fn raw_transaction(&mut self, byte: u8) -> u8 {
+			self.raw_write(byte);
+			self.busy_polls.set(0);
+			byte
+		}
+	}
+
+	type TestSpi = Spi<(), MockSpi, MockPin, MockPin, MockPin, MockPin>;
+
+	fn spi() -> (TestSpi, ChipSelectPin<MockPin>) {
+		Spi::new(
+			MockSpi::default(),
+			port::Pin::new(MockPin(false)).into_output(),
+			port::Pin::new(MockPin(false)).into_output(),
+			port::Pin::new(MockPin(false)).into_pull_up_input(),
+			port::Pin::new(MockPin(true)).into_output(),
+			Settings::default(),
+		)
+	}
+
+	#[test]
+	fn park_and_resume() {
+		let (mut spi, _cs) = spi();
+		spi.write(0x55);
+
+		// Parking waits for the write to finish
+		let (parked, mut sclk, mut mosi) = spi.park();
+		assert_eq!(parked.p.busy_polls.get(), 0);
+		assert!(!parked.p.enabled);
+
+		sclk.set_high();
+		mosi.toggle();
+		assert!(sclk.is_set_high() && mosi.is_set_high());
+		sclk.set_low();
+
+		let mut spi = parked.resume(sclk, mosi);
+		assert!(spi.p.enabled);
+		// The configuration was kept, not set up again
+		assert_eq!(spi.p.setups, 1);
+		assert!(!spi.write_in_progress);
+		assert_eq!(SpiBus::write(&mut spi, &[1, 2]), Ok(()));
+		assert_eq!(spi.p.written, 2);
+	}
+
+	/// A daisy chain of 8-bit shift registers with a shared latch.
+	struct Chain {
+		/// Shift registers, starting at the one connected to MOSI.
+		regs: Vec<u8>,
+		latched: Vec<u8>,
+		transactions: usize,
+	}
+
+	impl spi::ErrorType for Chain {
+		type Error = core::convert::Infallible;
+	}
+
+	impl spi::SpiDevice for Chain {
+		fn transaction(
+			&mut self,
+			operations: &mut [spi::Operation<'_, u8>],
+		) -> Result<(), Self::Error> {
+			self.transactions += 1;
+			for op in operations {
+				let spi::Operation::TransferInPlace(buffer) = op else {
+					unreachable!();
+				};
+				for byte in buffer.iter_mut() {
+					let out = self.regs.pop().unwrap();
+					self.regs.insert(0, *byte);
+					*byte = out;
+				}
+			}
+			self.latched = self.regs.clone();
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn daisy_frames() {
+		let mut chain = [0, 1, 2, 3, 4, 5];
+		assert_eq!(daisy_frame(&mut chain, 0, 2), [4, 5]);
+		assert_eq!(daisy_frame(&mut chain, 2, 2), [0, 1]);
+		assert_eq!(daisy_frame(&mut chain, 5, 1), [0]);
+		// Leftover bytes of partial frames are padding at the start
+		assert_eq!(daisy_frame(&mut chain[1..], 1, 2), [1, 2]);
+	}
+
+	#[test]
+	#[should_panic(expected = "position is beyond the end of the chain")]
+	fn daisy_frame_out_of_range() {
+		daisy_frame(&mut [0; 6], 3, 2);
+	}
+
+	#[test]
+	fn daisy_transfer_updates_all_devices() {
+		let mut device = Chain {
+			regs: std::vec![0xa0, 0xa1, 0xa2],
+			latched: Vec::new(),
+			transactions: 0,
+		};
+		let mut chain = [0u8; 3];
+		daisy_frame(&mut chain, 0, 1)[0] = 0b0000_0001;
+		daisy_frame(&mut chain, 2, 1)[0] = 0b1000_0000;
+		daisy_transfer(&mut device, &mut chain).unwrap();
+
+		assert_eq!(device.transactions, 1);
+		assert_eq!(device.latched, [0b0000_0001, 0, 0b1000_0000]);
+		// Read back in the same order
+		assert_eq!(*daisy_frame(&mut chain, 0, 1), [0xa0]);
+		assert_eq!(*daisy_frame(&mut chain, 2, 1), [0xa2]);
+	}
+}
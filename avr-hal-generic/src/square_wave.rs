@@ -0,0 +1,368 @@
+//! Square wave generation
+//!
+//! A 16-bit timer in CTC mode counts up to `OCRnA` and then starts over.  When its output compare
+//! unit is set to toggle the `OCnA` pin on every compare match, the pin outputs a square wave
+//! with a fixed 50% duty cycle and a frequency of
+//!
+//! ```text
+//! f = CLK_io / (2 * Prescaler * (1 + OCRnA))
+//! ```
+//!
+//! This is different from PWM, where the frequency is fixed by the prescaler and the duty cycle
+//! varies.  Here the frequency is the point, e.g. to clock an external chip or to output a
+//! reference tone.  The wave is generated entirely in hardware without any CPU involvement.
+//!
+//! The prescaler and `OCRnA` are selected automatically.  Not every frequency can be hit exactly
+//! because both are integers, so the achieved frequency and its deviation are reported as a
+//! [`Frequency`].  The smallest possible prescaler is used for the best resolution.  At 16 MHz,
+//! the range is about 0.12 Hz to 8 MHz; requests outside the range are clamped.
+//!
+//! # Melodies
+//! [`Melody`] plays a sequence of `(frequency in Hz, duration in ms)` notes on a square wave in
+//! the background.  Its [`tick()`][Melody::tick] has to be called once per millisecond, usually
+//! from a timer interrupt, and reconfigures the square wave whenever a note ends.  A frequency of
+//! 0 is a rest, during which the output is silent:
+//!
+//! ```ignore
+//! const ALARM: [(u32, u16); 4] = [(880, 150), (0, 50), (880, 150), (0, 650)];
+//!
+//! let wave = Timer1SquareWave::<CoreClock>::new(dp.TC1, pins.d9.into_output(), 880);
+//! let mut melody = Melody::new(wave);
+//! melody.play(&ALARM);
+//!
+//! // From a 1 kHz timer interrupt:
+//! melody.tick();
+//! ```
+//!
+//! Two consecutive notes of the same frequency sound like one long note; put a short rest between
+//! them to separate them.
+
+use crate::simple_pwm::Prescaler;
+
+/// Frequency of a square wave, as it was achieved.
+#[derive(ufmt::derive::uDebug, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frequency {
+	/// Frequency actually generated, in Hz (rounded).
+	pub achieved: u32,
+	/// Deviation from the requested frequency in Hz, `achieved - requested`, saturated to the
+	/// range of an `i32`.
+	pub error: i32,
+}
+
+/// Select prescaler and compare value for a 16-bit timer.
+///
+/// Returns the prescaler, the value for `OCRnA`, and the resulting frequency.
+#[doc(hidden)]
+pub fn compute_16bit(clock_hz: u32, frequency_hz: u32) -> (Prescaler, u16, Frequency) {
+	const PRESCALERS: [(Prescaler, u64); 5] = [
+		(Prescaler::Direct, 1),
+		(Prescaler::Prescale8, 8),
+		(Prescaler::Prescale64, 64),
+		(Prescaler::Prescale256, 256),
+		(Prescaler::Prescale1024, 1024),
+	];
+
+	let f = u64::from(frequency_hz.max(1));
+	let clock = u64::from(clock_hz);
+	let (prescaler, divider, ticks) = PRESCALERS
+		.iter()
+		.map(|&(p, n)| (p, n, (clock + n * f) / (2 * n * f)))
+		.find(|&(_, _, ticks)| ticks <= 0x1_0000)
+		.unwrap_or((Prescaler::Prescale1024, 1024, 0x1_0000));
+	let ticks = ticks.max(1);
+
+	let achieved = ((clock + divider * ticks) / (2 * divider * ticks)) as u32;
+	let frequency = Frequency {
+		achieved,
+		error: (i64::from(achieved) - i64::from(frequency_hz))
+			.clamp(i32::MIN.into(), i32::MAX.into()) as i32,
+	};
+	(prescaler, (ticks - 1) as u16, frequency)
+}
+
+/// An output which can play a tone, implemented by the square wave generators.
+pub trait ToneOutput {
+	/// Start outputting a tone of (roughly) `frequency_hz`.
+	fn set_tone(&mut self, frequency_hz: u32);
+	/// Stop the tone.
+	fn silence(&mut self);
+}
+
+impl<T: ToneOutput> ToneOutput for &mut T {
+	fn set_tone(&mut self, frequency_hz: u32) {
+		T::set_tone(self, frequency_hz)
+	}
+
+	fn silence(&mut self) {
+		T::silence(self)
+	}
+}
+
+/// Non-blocking player for a sequence of notes.  See the [module documentation](self#melodies).
+pub struct Melody<'a, T> {
+	output: T,
+	notes: &'a [(u32, u16)],
+	index: usize,
+	remaining_ms: u16,
+}
+
+impl<'a, T: ToneOutput> Melody<'a, T> {
+	/// Create a player for `output`, which is silenced until a melody is played.
+	pub fn new(mut output: T) -> Self {
+		output.silence();
+		Self {
+			output,
+			notes: &[],
+			index: 0,
+			remaining_ms: 0,
+		}
+	}
+
+	/// Start playing `notes`, given as `(frequency in Hz, duration in ms)`, from the beginning.
+	///
+	/// A melody which is still playing is replaced.  A frequency of 0 is a rest.
+	pub fn play(&mut self, notes: &'a [(u32, u16)]) {
+		self.notes = notes;
+		self.start_note(0);
+	}
+
+	/// Advance the melody by one millisecond, switching to the next note when the current one
+	/// ends.
+	///
+	/// Returns whether the melody is still playing.
+	pub fn tick(&mut self) -> bool {
+		if !self.is_playing() {
+			return false;
+		}
+		self.remaining_ms = self.remaining_ms.saturating_sub(1);
+		if self.remaining_ms == 0 {
+			self.start_note(self.index + 1);
+		}
+		self.is_playing()
+	}
+
+	/// Whether a melody is playing.
+	pub fn is_playing(&self) -> bool {
+		self.index < self.notes.len()
+	}
+
+	/// Index of the note currently playing, `None` if the melody has ended.
+	pub fn current_note(&self) -> Option<usize> {
+		self.is_playing().then_some(self.index)
+	}
+
+	/// Stop the melody and silence the output.
+	pub fn stop(&mut self) {
+		self.index = self.notes.len();
+		self.output.silence();
+	}
+
+	/// Stop the melody and return the output.
+	pub fn release(mut self) -> T {
+		self.stop();
+		self.output
+	}
+
+	fn start_note(&mut self, index: usize) {
+		self.index = index;
+		while let Some(&(frequency_hz, duration_ms)) = self.notes.get(self.index) {
+			if duration_ms > 0 {
+				if frequency_hz == 0 {
+					self.output.silence();
+				} else {
+					self.output.set_tone(frequency_hz);
+				}
+				self.remaining_ms = duration_ms;
+				return;
+			}
+			// Notes of zero length are skipped.
+			self.index += 1;
+		}
+		self.output.silence();
+	}
+}
+
+#[macro_export]
+macro_rules! impl_square_wave {
+    (
+        $(#[$wave_attr:meta])*
+        pub struct $SquareWave:ident {
+            timer: $TIMER:ty,
+            pin: $PIN:ty,
+            start: |$start_timer:ident, $prescaler:ident, $ocr:ident| $start_block:block,
+            stop: |$stop_timer:ident| $stop_block:block,
+        }
+    ) => {
+        $(#[$wave_attr])*
+        pub struct $SquareWave<CLOCK> {
+            timer: $TIMER,
+            pin: $crate::port::Pin<$crate::port::mode::Output, $PIN>,
+            frequency: $crate::square_wave::Frequency,
+            _clock: core::marker::PhantomData<CLOCK>,
+        }
+
+        impl<CLOCK: $crate::clock::Clock> $SquareWave<CLOCK> {
+            /// Start outputting a square wave of (roughly) `frequency_hz` on the pin.
+            ///
+            /// Use [`frequency()`][Self::frequency] to check the achieved frequency.
+            pub fn new(
+                timer: $TIMER,
+                pin: $crate::port::Pin<$crate::port::mode::Output, $PIN>,
+                frequency_hz: u32,
+            ) -> Self {
+                let mut w = $SquareWave {
+                    timer,
+                    pin,
+                    frequency: $crate::square_wave::Frequency { achieved: 0, error: 0 },
+                    _clock: core::marker::PhantomData,
+                };
+                w.set_frequency(frequency_hz);
+                w
+            }
+
+            /// Change the frequency and return the achieved one.
+            ///
+            /// The timer is restarted, so the current half-period may be cut short.
+            pub fn set_frequency(&mut self, frequency_hz: u32) -> $crate::square_wave::Frequency {
+                let (prescaler, ocr, frequency) =
+                    $crate::square_wave::compute_16bit(CLOCK::FREQ, frequency_hz);
+                $crate::avr_device::interrupt::free(|_| {
+                    let $start_timer = &mut self.timer;
+                    let $prescaler = prescaler;
+                    let $ocr = ocr;
+                    $start_block
+                });
+                self.frequency = frequency;
+                frequency
+            }
+
+            /// The achieved frequency and its deviation from the requested one.
+            pub fn frequency(&self) -> $crate::square_wave::Frequency {
+                self.frequency
+            }
+
+            /// Stop the square wave, until the next call to
+            /// [`set_frequency()`][Self::set_frequency].
+            pub fn silence(&mut self) {
+                let $stop_timer = &mut self.timer;
+                $stop_block
+            }
+
+            /// Stop the square wave and return the timer peripheral and the pin.
+            pub fn release(
+                mut self,
+            ) -> ($TIMER, $crate::port::Pin<$crate::port::mode::Output, $PIN>) {
+                {
+                    let $stop_timer = &mut self.timer;
+                    $stop_block
+                }
+                (self.timer, self.pin)
+            }
+        }
+
+        impl<CLOCK: $crate::clock::Clock> $crate::square_wave::ToneOutput for $SquareWave<CLOCK> {
+            fn set_tone(&mut self, frequency_hz: u32) {
+                self.set_frequency(frequency_hz);
+            }
+
+            fn silence(&mut self) {
+                $SquareWave::silence(self)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::vec::Vec;
+
+	/// Records every tone, `None` for silence.
+	#[derive(Default)]
+	struct Recorder(Vec<Option<u32>>);
+
+	impl ToneOutput for Recorder {
+		fn set_tone(&mut self, frequency_hz: u32) {
+			self.0.push(Some(frequency_hz));
+		}
+
+		fn silence(&mut self) {
+			self.0.push(None);
+		}
+	}
+
+	#[test]
+	fn compute_16bit_table() {
+		let table = [
+			(32768, Prescaler::Direct, 243, 32787, 19),
+			(1, Prescaler::Prescale256, 31249, 1, 0),
+			(440, Prescaler::Direct, 18181, 440, 0),
+			(8_000_000, Prescaler::Direct, 0, 8_000_000, 0),
+			// Out of range: clamped to the fastest and the slowest wave
+			(10_000_000, Prescaler::Direct, 0, 8_000_000, -2_000_000),
+			(0, Prescaler::Prescale256, 31249, 1, 1),
+		];
+		for (frequency, prescaler, ocr, achieved, error) in table {
+			assert_eq!(
+				compute_16bit(16_000_000, frequency),
+				(prescaler, ocr, Frequency { achieved, error }),
+				"{} Hz",
+				frequency
+			);
+		}
+	}
+
+	#[test]
+	fn compute_16bit_error_saturates() {
+		let (_, _, frequency) = compute_16bit(16_000_000, u32::MAX);
+		assert_eq!(frequency.achieved, 8_000_000);
+		assert_eq!(frequency.error, i32::MIN);
+	}
+
+	#[test]
+	fn melody_sequencing() {
+		let notes = [(440, 3), (0, 2), (660, 0), (880, 1)];
+		let mut recorder = Recorder::default();
+		let mut melody = Melody::new(&mut recorder);
+		assert!(!melody.is_playing());
+		assert!(!melody.tick());
+
+		melody.play(&notes);
+		assert_eq!(melody.current_note(), Some(0));
+		assert!(melody.tick());
+		assert!(melody.tick());
+		assert_eq!(melody.current_note(), Some(0));
+		// A rest
+		assert!(melody.tick());
+		assert_eq!(melody.current_note(), Some(1));
+		assert!(melody.tick());
+		// Notes of zero length are skipped
+		assert!(melody.tick());
+		assert_eq!(melody.current_note(), Some(3));
+		assert!(!melody.tick());
+		assert_eq!(melody.current_note(), None);
+		assert!(!melody.tick());
+
+		melody.release();
+		assert_eq!(recorder.0, [None, Some(440), None, Some(880), None, None]);
+	}
+
+	#[test]
+	fn melody_stop_and_replace() {
+		let mut melody = Melody::new(Recorder::default());
+		melody.play(&[(440, 10)]);
+		melody.tick();
+		// Playing another melody starts it from the beginning
+		melody.play(&[(0, 0), (523, 5)]);
+		assert_eq!(melody.current_note(), Some(1));
+		melody.stop();
+		assert!(!melody.is_playing());
+
+		melody.play(&[(440, 10)]);
+		let recorder = melody.release();
+		assert_eq!(
+			recorder.0,
+			[None, Some(440), Some(523), None, Some(440), None]
+		);
+	}
+}
@@ -86,6 +86,14 @@ impl<CLOCK: crate::clock::Clock> Baudrate<CLOCK> {
 	}
 }
 
+impl<CLOCK> Baudrate<CLOCK> {
+	/// CPU cycles for the longest frame: start bit, 9 data bits, parity, and 2 stop bits.
+	fn frame_cycles(&self) -> u32 {
+		let bit = if self.u2x { 8 } else { 16 } * (u32::from(self.ubrr) + 1);
+		13 * bit
+	}
+}
+
 /// Provide a `into_baudrate()` method for integers.
 ///
 /// This extension trait allows conveniently initializing a baudrate by using
@@ -142,6 +150,7 @@ impl BaudrateArduinoExt for u32 {
 }
 
 /// Events/Interrupts for USART peripherals
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Event {
 	/// A complete byte was received.
@@ -163,6 +172,86 @@ pub enum Event {
 	DataRegisterEmpty,
 }
 
+/// A 9-bit frame in multi-processor communication mode.
+///
+/// See [`Usart::set_multiprocessor_mode`].
+#[derive(ufmt::derive::uDebug, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Frame {
+	/// Frame with the ninth bit set, selecting a receiver.
+	Address(u8),
+	/// Frame with the ninth bit cleared.
+	Data(u8),
+}
+
+/// Error returned by [`Usart::read_timeout`] when no byte arrived in time.
+#[derive(ufmt::derive::uDebug, Debug, Clone, Copy, Eq, PartialEq)]
+pub struct TimeoutError;
+
+/// Number of CPU cycles spent busy-waiting between two polls in [`Usart::read_timeout`] and
+/// [`Usart::flush`].
+const READ_POLL_CYCLES: u32 = 32;
+
+fn read_timeout<H, USART: UsartOps<H, RX, TX>, RX, TX>(
+	p: &mut USART,
+	cycles: u32,
+) -> Result<u8, TimeoutError> {
+	let mut remaining = cycles;
+	loop {
+		match p.raw_read() {
+			Ok(byte) => return Ok(byte),
+			Err(nb::Error::WouldBlock) => (),
+			Err(nb::Error::Other(e)) => match e {},
+		}
+		remaining = remaining
+			.checked_sub(READ_POLL_CYCLES)
+			.ok_or(TimeoutError)?;
+		avr_device::asm::delay_cycles(READ_POLL_CYCLES);
+	}
+}
+
+/// Wait for the last frame to be shifted out while the [`Event::TxComplete`] interrupt is enabled.
+///
+/// The interrupt handler clears `TXC#`, possibly before it could be seen here, so polling it alone
+/// could wait forever.  Instead, the interrupt is masked, and once the data register is empty,
+/// `TXC#` is polled for at most `frame_cycles`, the time the last frame takes.  The interrupt is
+/// enabled again afterwards, and if `TXC#` got set in the meantime, the handler runs right away.
+fn flush_listened<H, USART: UsartOps<H, RX, TX>, RX, TX>(p: &mut USART, frame_cycles: u32) {
+	p.raw_interrupt(Event::TxComplete, false);
+	nb::block!(p.raw_flush()).unwrap_infallible();
+	let mut remaining = frame_cycles;
+	while !p.raw_tx_complete() {
+		remaining = match remaining.checked_sub(READ_POLL_CYCLES) {
+			Some(remaining) => remaining,
+			None => break,
+		};
+		avr_device::asm::delay_cycles(READ_POLL_CYCLES);
+	}
+	p.raw_interrupt(Event::TxComplete, true);
+}
+
+/// Turn a non-blocking result into a [`Poll`][core::task::Poll] for the `*_async()` methods.
+///
+/// While pending, the interrupt for `event` is enabled, so it wakes the task through
+/// [`Usart::on_async_interrupt()`] once the USART is ready.  Enabling it when the USART became
+/// ready in the meantime fires the interrupt right away, so no wake-up is lost.
+fn poll_nb<H, USART: UsartOps<H, RX, TX>, RX, TX, T>(
+	p: &mut USART,
+	event: Event,
+	result: nb::Result<T, core::convert::Infallible>,
+) -> core::task::Poll<T> {
+	match result {
+		Ok(value) => {
+			p.raw_interrupt(event, false);
+			core::task::Poll::Ready(value)
+		}
+		Err(nb::Error::WouldBlock) => {
+			p.raw_interrupt(event, true);
+			core::task::Poll::Pending
+		}
+		Err(nb::Error::Other(e)) => match e {},
+	}
+}
+
 /// Internal trait for low-level USART peripherals.
 ///
 /// This trait defines the common interface for all USART peripheral variants.  It is used as an
@@ -181,7 +270,8 @@ pub trait UsartOps<H, RX, TX> {
 	/// Flush all remaining data in the TX buffer.
 	///
 	/// This operation must be non-blocking and return [`nb::Error::WouldBlock`] if not all data
-	/// was flushed yet.
+	/// was flushed yet.  Note that this only waits for the data register to be empty (`UDRE#`),
+	/// the last byte might still be in the process of being shifted out.
 	///
 	/// **Warning**: This is a low-level method and should not be called directly from user code.
 	fn raw_flush(&mut self) -> nb::Result<(), core::convert::Infallible>;
@@ -190,8 +280,19 @@ pub trait UsartOps<H, RX, TX> {
 	/// This operation must be non-blocking and return [`nb::Error::WouldBlock`] until the byte is
 	/// enqueued.  The operation should not wait for the byte to have actually been sent.
 	///
+	/// The transmit complete flag (`TXC#`) must be cleared atomically with enqueueing the byte so
+	/// [`raw_tx_complete`](UsartOps::raw_tx_complete) only reports completion once this byte is
+	/// out.
+	///
 	/// **Warning**: This is a low-level method and should not be called directly from user code.
 	fn raw_write(&mut self, byte: u8) -> nb::Result<(), core::convert::Infallible>;
+	/// Check whether the transmitter has finished shifting out the last frame (`TXC#`).
+	///
+	/// This flag is only set once a transmission completes, it stays cleared after
+	/// initialization until the first byte was sent.
+	///
+	/// **Warning**: This is a low-level method and should not be called directly from user code.
+	fn raw_tx_complete(&mut self) -> bool;
 	/// Read a byte from the RX buffer.
 	///
 	/// This operation must be non-blocking and return [`nb::Error::WouldBlock`] if no incoming
@@ -204,6 +305,99 @@ pub trait UsartOps<H, RX, TX> {
 	///
 	/// **Warning**: This is a low-level method and should not be called directly from user code.
 	fn raw_interrupt(&mut self, event: Event, state: bool);
+	/// Disable the interrupt for `event` from its interrupt handler, which doesn't own the
+	/// peripheral.
+	///
+	/// # Safety
+	/// The peripheral is accessed without owning it, so this must only be used to mask an
+	/// interrupt from its own handler.
+	///
+	/// **Warning**: This is a low-level method and should not be called directly from user code.
+	unsafe fn raw_mask_interrupt(event: Event);
+	/// Number of the interrupt vector for `event`, for [`interrupt::claim()`].
+	///
+	/// [`interrupt::claim()`]: crate::interrupt::claim
+	fn raw_vector(event: Event) -> u8;
+
+	/// Enable/Disable the receiver (`RXEN#`).
+	///
+	/// While the receiver is disabled, the RX pin is an ordinary GPIO.
+	///
+	/// **Warning**: This is a low-level method and should not be called directly from user code.
+	fn raw_set_receiver(&mut self, enable: bool);
+	/// Enable/Disable the transmitter (`TXEN#`).
+	///
+	/// While the transmitter is disabled, the TX pin is an ordinary GPIO.
+	///
+	/// **Warning**: This is a low-level method and should not be called directly from user code.
+	fn raw_set_transmitter(&mut self, enable: bool);
+
+	/// Switch between 8-bit and 9-bit frames (`UCSZ#2`).
+	///
+	/// **Warning**: This is a low-level method and should not be called directly from user code.
+	fn raw_set_nine_bit(&mut self, enable: bool);
+	/// Enable/Disable multi-processor communication mode (`MPCM#`).
+	///
+	/// While enabled, the receiver ignores all frames whose ninth bit is cleared.
+	///
+	/// **Warning**: This is a low-level method and should not be called directly from user code.
+	fn raw_set_mpcm(&mut self, enable: bool);
+	/// Set the ninth bit of frames written afterwards (`TXB8#`).
+	///
+	/// **Warning**: This is a low-level method and should not be called directly from user code.
+	fn raw_set_tx_bit8(&mut self, bit: bool);
+	/// Read a 9-bit frame from the RX buffer.
+	///
+	/// Like [`raw_read`](UsartOps::raw_read), but with the ninth bit (`RXB8#`) in bit 8 of the
+	/// result.
+	///
+	/// **Warning**: This is a low-level method and should not be called directly from user code.
+	fn raw_read_9bit(&mut self) -> nb::Result<u16, core::convert::Infallible>;
+}
+
+/// Interrupt vectors claimed by a USART, one slot per [`Event`].
+struct EventClaims([Option<crate::interrupt::InterruptClaim>; 3]);
+
+impl EventClaims {
+	const fn new() -> Self {
+		Self([None, None, None])
+	}
+
+	fn index(event: Event) -> usize {
+		match event {
+			Event::RxComplete => 0,
+			Event::DataRegisterEmpty => 1,
+			Event::TxComplete => 2,
+		}
+	}
+
+	/// Claim `vector` for `event`, unless this USART holds it already.
+	fn claim(&mut self, event: Event, vector: u8, owner: &'static str) {
+		let slot = &mut self.0[Self::index(event)];
+		if slot.is_none() {
+			*slot = Some(crate::interrupt::claim_or_panic(vector, owner));
+		}
+	}
+
+	fn release(&mut self, event: Event) {
+		self.0[Self::index(event)] = None;
+	}
+
+	fn holds(&self, event: Event) -> bool {
+		self.0[Self::index(event)].is_some()
+	}
+
+	/// Split into the claims of the receiver and those of the transmitter.
+	fn split(self) -> (Self, Self) {
+		let [rx, udre, tx] = self.0;
+		(Self([rx, None, None]), Self([None, udre, tx]))
+	}
+
+	fn merge(self, other: Self) -> Self {
+		let [a0, a1, a2] = self.0;
+		let [b0, b1, b2] = other.0;
+		Self([a0.or(b0), a1.or(b1), a2.or(b2)])
+	}
 }
 
 /// USART/Serial driver
@@ -231,6 +425,9 @@ pub struct Usart<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> {
 	p: USART,
 	rx: RX,
 	tx: TX,
+	tx_pending: bool,
+	frame_cycles: u32,
+	claims: EventClaims,
 	_clock: marker::PhantomData<CLOCK>,
 	_h: marker::PhantomData<H>,
 }
@@ -260,24 +457,101 @@ where
 			p,
 			rx: rx.forget_imode(),
 			tx,
+			tx_pending: false,
+			frame_cycles: baudrate.frame_cycles(),
+			claims: EventClaims::new(),
 			_clock: marker::PhantomData,
 			_h: marker::PhantomData,
 		};
 		usart.p.raw_init(baudrate);
 		usart
 	}
+
+	/// Initialize a USART peripheral for transmitting only.
+	///
+	/// The receiver stays disabled, so the RX pin is not needed and remains free for other uses.
+	/// This returns just the [`UsartWriter`] half, which has no methods for reading.  Use
+	/// [`UsartWriter::release`] to get the peripheral and the TX pin back.
+	///
+	/// # Example
+	/// ```
+	/// let mut log = atmega_hal::usart::Usart0::<CoreClock>::new_tx_only(
+	///     dp.USART0,
+	///     pins.pd1.into_output(),
+	///     57600.into_baudrate(),
+	/// );
+	/// let button = pins.pd0.into_pull_up_input();
+	/// ```
+	pub fn new_tx_only(
+		mut p: USART,
+		tx: port::Pin<port::mode::Output, TXPIN>,
+		baudrate: Baudrate<CLOCK>,
+	) -> UsartWriter<
+		H,
+		USART,
+		port::Pin<port::mode::Input, RXPIN>,
+		port::Pin<port::mode::Output, TXPIN>,
+		CLOCK,
+	> {
+		let frame_cycles = baudrate.frame_cycles();
+		p.raw_init(baudrate);
+		p.raw_set_receiver(false);
+		UsartWriter {
+			p,
+			tx,
+			tx_pending: false,
+			frame_cycles,
+			claims: EventClaims::new(),
+			_rx: marker::PhantomData,
+			_clock: marker::PhantomData,
+			_h: marker::PhantomData,
+		}
+	}
 }
 
 impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> Usart<H, USART, RX, TX, CLOCK> {
 	/// Deinitialize/disable this peripheral and release the pins.
+	///
+	/// Any byte still being transmitted is allowed to finish first.
 	pub fn release(mut self) -> (USART, RX, TX) {
+		self.flush();
 		self.p.raw_deinit();
 		(self.p, self.rx, self.tx)
 	}
 
 	/// Block until all remaining data has been transmitted.
+	///
+	/// See [`Usart::tx_complete`] for details.  While the [`Event::TxComplete`] interrupt is
+	/// [listened][Usart::listen] for, its handler clears `TXC#`, so this masks the interrupt and
+	/// waits at most one frame time after the data register is empty instead.
 	pub fn flush(&mut self) {
-		nb::block!(self.p.raw_flush()).unwrap_infallible()
+		if self.tx_pending && self.claims.holds(Event::TxComplete) {
+			flush_listened(&mut self.p, self.frame_cycles);
+			self.tx_pending = false;
+		}
+		while !self.tx_complete() {}
+	}
+
+	/// Check whether all written data has physically left the transmitter.
+	///
+	/// There are two different notions of "done" for a USART transmitter:
+	///
+	/// - **Data register empty** (`UDRE#`): The data register can accept the next byte.  The
+	///   previous byte may still be in the shift register and only partially on the wire.
+	/// - **Transmission complete** (`TXC#`): The shift register has shifted out the last stop bit
+	///   and no further data is waiting.
+	///
+	/// This method reports the latter, which is what you need before changing the baudrate,
+	/// disabling the transmitter, or turning around a half-duplex line.
+	///
+	/// The `TXC#` flag is cleared by hardware when the [`Event::TxComplete`] interrupt is
+	/// serviced.  Don't rely on this method while that interrupt is enabled, [`Usart::flush`]
+	/// still works then.
+	pub fn tx_complete(&mut self) -> bool {
+		if self.tx_pending && self.p.raw_tx_complete() {
+			self.tx_pending = false;
+		}
+		!self.tx_pending
 	}
 
 	/// Transmit a byte.
@@ -285,7 +559,8 @@ impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> Usart<H, USART, RX, TX, CLOCK
 	/// This method will block until the byte has been enqueued for transmission but **not** until
 	/// it was entirely sent.
 	pub fn write_byte(&mut self, byte: u8) {
-		nb::block!(self.p.raw_write(byte)).unwrap_infallible()
+		nb::block!(self.p.raw_write(byte)).unwrap_infallible();
+		self.tx_pending = true;
 	}
 
 	/// Receive a byte.
@@ -295,14 +570,166 @@ impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> Usart<H, USART, RX, TX, CLOCK
 		nb::block!(self.p.raw_read()).unwrap_infallible()
 	}
 
+	/// Receive a byte, giving up after roughly `cycles` CPU cycles.
+	///
+	/// The timeout is measured by counting busy-wait cycles between polls of the receiver, so no
+	/// timer is needed.  It is a lower bound: interrupts firing while waiting make the actual time
+	/// longer.  Divide by `CLOCK::FREQ` to convert to seconds, e.g. `16_000` cycles are 1ms at
+	/// 16MHz.
+	///
+	/// # Example
+	/// ```
+	/// match serial.read_timeout(16_000 * 50) {
+	///     Ok(b) => handle_response(b),
+	///     Err(TimeoutError) => retry_request(),
+	/// }
+	/// ```
+	pub fn read_timeout(&mut self, cycles: u32) -> Result<u8, TimeoutError> {
+		read_timeout(&mut self.p, cycles)
+	}
+
+	/// Transmit a byte from an `async` context.
+	///
+	/// Completes once the byte has been enqueued for transmission.  While the data register is
+	/// full, the task sleeps with the `USART_UDRE` interrupt enabled, whose handler has to call
+	/// [`on_async_interrupt()`][Usart::on_async_interrupt].  See [`executor`][crate::executor]
+	/// for how to run this.
+	pub async fn write_async(&mut self, byte: u8) {
+		core::future::poll_fn(|_| {
+			let result = self.p.raw_write(byte);
+			poll_nb(&mut self.p, Event::DataRegisterEmpty, result)
+		})
+		.await;
+		self.tx_pending = true;
+	}
+
+	/// Receive a byte from an `async` context.
+	///
+	/// While no byte is available, the task sleeps with the `USART_RX` interrupt enabled, whose
+	/// handler has to call [`on_async_interrupt()`][Usart::on_async_interrupt].  See
+	/// [`executor`][crate::executor] for how to run this.
+	pub async fn read_async(&mut self) -> u8 {
+		core::future::poll_fn(|_| {
+			let result = self.p.raw_read();
+			poll_nb(&mut self.p, Event::RxComplete, result)
+		})
+		.await
+	}
+
+	/// Wake the task waiting in [`read_async()`][Usart::read_async] or
+	/// [`write_async()`][Usart::write_async].  Call this from the `USART_RX` and `USART_UDRE`
+	/// interrupt handlers:
+	///
+	/// ```ignore
+	/// type Serial = arduino_hal::hal::usart::Usart0<arduino_hal::DefaultClock>;
+	///
+	/// #[avr_device::interrupt(atmega328p)]
+	/// fn USART_RX() {
+	///     Serial::on_async_interrupt();
+	/// }
+	///
+	/// #[avr_device::interrupt(atmega328p)]
+	/// fn USART_UDRE() {
+	///     Serial::on_async_interrupt();
+	/// }
+	/// ```
+	///
+	/// Both interrupts stay active until the byte is read or written, so they are disabled here
+	/// and enabled again by the future if it still has to wait.  This does not go along with
+	/// [`listen()`][Usart::listen] for the same events or a [`BackgroundWriter`].
+	pub fn on_async_interrupt() {
+		// SAFETY: Called from the interrupt handlers, only masking their own interrupts.
+		unsafe {
+			USART::raw_mask_interrupt(Event::RxComplete);
+			USART::raw_mask_interrupt(Event::DataRegisterEmpty);
+		}
+		crate::executor::wake();
+	}
+
+	/// Enable/Disable multi-processor communication mode.
+	///
+	/// This mode allows several MCUs to share a bus (e.g. RS-485), where receivers don't have to
+	/// look at frames meant for others.  Frames get a ninth bit, which is set for address frames
+	/// and cleared for data frames:
+	///
+	/// 1. The sender transmits the address of the receiver with
+	///    [`write_address()`][Usart::write_address], followed by the data as normal bytes.
+	/// 2. Each receiver has its address filter (`MPCM#`) enabled, so its hardware drops all data
+	///    frames and only address frames arrive.  Data frames do not even raise the RX interrupt.
+	/// 3. The addressed receiver disables its filter and receives the data frames, while all other
+	///    receivers keep ignoring them.
+	/// 4. When the next address frame arrives, the receiver enables its filter again, unless it
+	///    is addressed once more.
+	///
+	/// [`read_addressed()`][Usart::read_addressed] does steps 2 to 4 automatically.  Enabling
+	/// this mode switches to 9-bit frames and enables the address filter, so all nodes on the bus
+	/// need to use this mode.
+	pub fn set_multiprocessor_mode(&mut self, enable: bool) {
+		self.p.raw_set_nine_bit(enable);
+		self.p.raw_set_tx_bit8(false);
+		self.p.raw_set_mpcm(enable);
+	}
+
+	/// Enable/Disable the address filter (`MPCM#`) in multi-processor communication mode.
+	///
+	/// While enabled, data frames are dropped by the hardware.
+	pub fn set_address_filter(&mut self, enable: bool) {
+		self.p.raw_set_mpcm(enable);
+	}
+
+	/// Transmit an address frame in multi-processor communication mode.
+	///
+	/// Blocks until the frame was moved into the shift register, so the bytes written afterwards
+	/// are sent as data frames.
+	pub fn write_address(&mut self, address: u8) {
+		self.p.raw_set_tx_bit8(true);
+		self.write_byte(address);
+		nb::block!(self.p.raw_flush()).unwrap_infallible();
+		self.p.raw_set_tx_bit8(false);
+	}
+
+	/// Receive a frame in multi-processor communication mode.
+	pub fn read_frame(&mut self) -> nb::Result<Frame, core::convert::Infallible> {
+		let frame = self.p.raw_read_9bit()?;
+		if frame & 0x100 != 0 {
+			Ok(Frame::Address(frame as u8))
+		} else {
+			Ok(Frame::Data(frame as u8))
+		}
+	}
+
+	/// Receive a data byte addressed to `address` in multi-processor communication mode.
+	///
+	/// Address frames are consumed and update the address filter: it gets disabled when `address`
+	/// is selected and enabled otherwise.  Only data frames following a matching address frame are
+	/// returned.
+	pub fn read_addressed(&mut self, address: u8) -> nb::Result<u8, core::convert::Infallible> {
+		match self.read_frame()? {
+			Frame::Address(a) => {
+				self.p.raw_set_mpcm(a != address);
+				Err(nb::Error::WouldBlock)
+			}
+			Frame::Data(byte) => Ok(byte),
+		}
+	}
+
 	/// Enable the interrupt for [`Event`].
+	///
+	/// The interrupt vector is [claimed](crate::interrupt#ownership) until
+	/// [`unlisten()`][Usart::unlisten] is called or the USART is released.
+	///
+	/// # Panics
+	/// Panics if another feature claimed the vector already.
 	pub fn listen(&mut self, event: Event) {
+		self.claims
+			.claim(event, USART::raw_vector(event), "Usart::listen");
 		self.p.raw_interrupt(event, true);
 	}
 
 	/// Disable the interrupt for [`Event`].
 	pub fn unlisten(&mut self, event: Event) {
 		self.p.raw_interrupt(event, false);
+		self.claims.release(event);
 	}
 
 	/// Split this USART into a [`UsartReader`] and a [`UsartWriter`].
@@ -314,10 +741,12 @@ impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> Usart<H, USART, RX, TX, CLOCK
 		UsartReader<H, USART, RX, TX, CLOCK>,
 		UsartWriter<H, USART, RX, TX, CLOCK>,
 	) {
+		let (rx_claims, tx_claims) = self.claims.split();
 		(
 			UsartReader {
 				p: unsafe { core::ptr::read(&self.p) },
 				rx: self.rx,
+				claims: rx_claims,
 				_tx: marker::PhantomData,
 				_clock: marker::PhantomData,
 				_h: marker::PhantomData,
@@ -325,6 +754,9 @@ impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> Usart<H, USART, RX, TX, CLOCK
 			UsartWriter {
 				p: self.p,
 				tx: self.tx,
+				tx_pending: self.tx_pending,
+				frame_cycles: self.frame_cycles,
+				claims: tx_claims,
 				_rx: marker::PhantomData,
 				_clock: marker::PhantomData,
 				_h: marker::PhantomData,
@@ -350,11 +782,21 @@ impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> embedded_hal_v0::serial::Writ
 	type Error = core::convert::Infallible;
 
 	fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
-		self.p.raw_write(byte)
+		self.p.raw_write(byte)?;
+		self.tx_pending = true;
+		Ok(())
 	}
 
 	fn flush(&mut self) -> nb::Result<(), Self::Error> {
-		self.p.raw_flush()
+		if self.claims.holds(Event::TxComplete) {
+			// TXC# can't be polled, this blocks for at most one frame.
+			self.flush();
+		}
+		if self.tx_complete() {
+			Ok(())
+		} else {
+			Err(nb::Error::WouldBlock)
+		}
 	}
 }
 
@@ -378,6 +820,9 @@ impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> embedded_hal_v0::serial::Read
 pub struct UsartWriter<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> {
 	p: USART,
 	tx: TX,
+	tx_pending: bool,
+	frame_cycles: u32,
+	claims: EventClaims,
 	_rx: marker::PhantomData<RX>,
 	_clock: marker::PhantomData<CLOCK>,
 	_h: marker::PhantomData<H>,
@@ -392,12 +837,58 @@ pub struct UsartWriter<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> {
 pub struct UsartReader<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> {
 	p: USART,
 	rx: RX,
+	claims: EventClaims,
 	_tx: marker::PhantomData<TX>,
 	_clock: marker::PhantomData<CLOCK>,
 	_h: marker::PhantomData<H>,
 }
 
 impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> UsartWriter<H, USART, RX, TX, CLOCK> {
+	/// Block until all remaining data has been transmitted.
+	///
+	/// See [`Usart::flush`] for details.
+	pub fn flush(&mut self) {
+		if self.tx_pending && self.claims.holds(Event::TxComplete) {
+			flush_listened(&mut self.p, self.frame_cycles);
+			self.tx_pending = false;
+		}
+		while !self.tx_complete() {}
+	}
+
+	/// Check whether all written data has physically left the transmitter.
+	///
+	/// See [`Usart::tx_complete`] for details.
+	pub fn tx_complete(&mut self) -> bool {
+		if self.tx_pending && self.p.raw_tx_complete() {
+			self.tx_pending = false;
+		}
+		!self.tx_pending
+	}
+
+	/// Transmit a byte from an `async` context.
+	///
+	/// See [`Usart::write_async`] for details.
+	pub async fn write_async(&mut self, byte: u8) {
+		core::future::poll_fn(|_| {
+			let result = self.p.raw_write(byte);
+			poll_nb(&mut self.p, Event::DataRegisterEmpty, result)
+		})
+		.await;
+		self.tx_pending = true;
+	}
+
+	/// Disable the transmitter and release the peripheral and the TX pin.
+	///
+	/// This is the counterpart to [`Usart::new_tx_only`].  Any data still being transmitted is
+	/// allowed to finish first.  A writer from [`Usart::split`] should be
+	/// [reunited][UsartWriter::reunite] with its reader instead, as the reader keeps using the
+	/// peripheral.
+	pub fn release(mut self) -> (USART, TX) {
+		self.flush();
+		self.p.raw_set_transmitter(false);
+		(self.p, self.tx)
+	}
+
 	/// Merge this `UsartWriter` with a [`UsartReader`] back into a single [`Usart`] peripheral.
 	pub fn reunite(
 		self,
@@ -407,6 +898,9 @@ impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> UsartWriter<H, USART, RX, TX,
 			p: self.p,
 			rx: other.rx,
 			tx: self.tx,
+			tx_pending: self.tx_pending,
+			frame_cycles: self.frame_cycles,
+			claims: self.claims.merge(other.claims),
 			_clock: marker::PhantomData,
 			_h: marker::PhantomData,
 		}
@@ -414,6 +908,24 @@ impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> UsartWriter<H, USART, RX, TX,
 }
 
 impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> UsartReader<H, USART, RX, TX, CLOCK> {
+	/// Receive a byte, giving up after roughly `cycles` CPU cycles.
+	///
+	/// See [`Usart::read_timeout`] for details.
+	pub fn read_timeout(&mut self, cycles: u32) -> Result<u8, TimeoutError> {
+		read_timeout(&mut self.p, cycles)
+	}
+
+	/// Receive a byte from an `async` context.
+	///
+	/// See [`Usart::read_async`] for details.
+	pub async fn read_async(&mut self) -> u8 {
+		core::future::poll_fn(|_| {
+			let result = self.p.raw_read();
+			poll_nb(&mut self.p, Event::RxComplete, result)
+		})
+		.await
+	}
+
 	/// Merge this `UsartReader` with a [`UsartWriter`] back into a single [`Usart`] peripheral.
 	pub fn reunite(
 		self,
@@ -423,6 +935,9 @@ impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> UsartReader<H, USART, RX, TX,
 			p: self.p,
 			rx: self.rx,
 			tx: other.tx,
+			tx_pending: other.tx_pending,
+			frame_cycles: other.frame_cycles,
+			claims: self.claims.merge(other.claims),
 			_clock: marker::PhantomData,
 			_h: marker::PhantomData,
 		}
@@ -436,7 +951,8 @@ impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> ufmt::uWrite
 
 	fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
 		for b in s.as_bytes().iter() {
-			nb::block!(self.p.raw_write(*b)).unwrap_infallible()
+			nb::block!(self.p.raw_write(*b)).unwrap_infallible();
+			self.tx_pending = true;
 		}
 		Ok(())
 	}
@@ -448,11 +964,21 @@ impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> embedded_hal_v0::serial::Writ
 	type Error = core::convert::Infallible;
 
 	fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
-		self.p.raw_write(byte)
+		self.p.raw_write(byte)?;
+		self.tx_pending = true;
+		Ok(())
 	}
 
 	fn flush(&mut self) -> nb::Result<(), Self::Error> {
-		self.p.raw_flush()
+		if self.claims.holds(Event::TxComplete) {
+			// TXC# can't be polled, this blocks for at most one frame.
+			self.flush();
+		}
+		if self.tx_complete() {
+			Ok(())
+		} else {
+			Err(nb::Error::WouldBlock)
+		}
 	}
 }
 
@@ -466,6 +992,530 @@ impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> embedded_hal_v0::serial::Read
 	}
 }
 
+/// A [`Usart`] driving the direction pin of an RS-485 transceiver.
+///
+/// Created by [`Usart::with_rs485_direction()`].  Half-duplex RS-485 transceivers have a driver
+/// enable (`DE`) and a receiver enable (`/RE`) pin, usually tied together to one GPIO which must
+/// be high while transmitting and low while receiving.  This wrapper raises the pin before the
+/// first byte of a transmission and lowers it again once the transmission is complete.
+///
+/// "Complete" means the `TXC#` flag is set, i.e. the stop bit of the last byte has left the
+/// shift register.  Lowering the pin when the data register is empty (`UDRE#`) would cut off the
+/// last byte, which then ends in garbage on the bus.  The pin is lowered by
+/// [`Rs485Usart::flush()`] and, automatically, before reading, as nothing can be received while
+/// the transceiver is driving the bus.  As with [`Usart::tx_complete()`], this does not work
+/// while the [`Event::TxComplete`] interrupt is enabled, because servicing it clears `TXC#`.
+///
+/// # Example
+/// ```
+/// let mut bus = Usart::new(dp.USART0, pins.pd0, pins.pd1.into_output(), 9600.into_baudrate())
+///     .with_rs485_direction(pins.pd2.into_output());
+///
+/// ufmt::uwrite!(&mut bus, "PING").unwrap_infallible();
+/// // Waits for the last byte to leave, then switches the transceiver to receiving
+/// let reply = bus.read_byte();
+/// ```
+pub struct Rs485Usart<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK, DIR> {
+	usart: Usart<H, USART, RX, TX, CLOCK>,
+	direction: port::Pin<port::mode::Output, DIR>,
+	transmitting: bool,
+}
+
+impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> Usart<H, USART, RX, TX, CLOCK> {
+	/// Drive the direction pin of an RS-485 transceiver around all transmissions.
+	///
+	/// Any pending transmission is completed first and the pin starts out low (receiving).  See
+	/// [`Rs485Usart`] for details.
+	pub fn with_rs485_direction<DIR: port::PinOps>(
+		mut self,
+		mut direction: port::Pin<port::mode::Output, DIR>,
+	) -> Rs485Usart<H, USART, RX, TX, CLOCK, DIR> {
+		self.flush();
+		direction.set_low();
+		Rs485Usart {
+			usart: self,
+			direction,
+			transmitting: false,
+		}
+	}
+}
+
+impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK, DIR: port::PinOps>
+	Rs485Usart<H, USART, RX, TX, CLOCK, DIR>
+{
+	/// Complete any transmission and return the USART and the direction pin.
+	pub fn release(
+		mut self,
+	) -> (
+		Usart<H, USART, RX, TX, CLOCK>,
+		port::Pin<port::mode::Output, DIR>,
+	) {
+		self.flush();
+		(self.usart, self.direction)
+	}
+
+	/// Transmit a byte, switching the transceiver to transmitting first if necessary.
+	///
+	/// Like [`Usart::write_byte()`], this does not wait for the byte to be sent.
+	pub fn write_byte(&mut self, byte: u8) {
+		self.start_transmission();
+		self.usart.write_byte(byte);
+	}
+
+	/// Block until the transmission is complete and switch the transceiver back to receiving.
+	pub fn flush(&mut self) {
+		if self.transmitting {
+			self.usart.flush();
+			self.end_transmission();
+		}
+	}
+
+	/// Whether the transceiver is currently driving the bus.
+	pub fn is_transmitting(&self) -> bool {
+		self.transmitting
+	}
+
+	/// Receive a byte, completing any transmission first.
+	pub fn read_byte(&mut self) -> u8 {
+		self.flush();
+		self.usart.read_byte()
+	}
+
+	fn start_transmission(&mut self) {
+		if !self.transmitting {
+			self.direction.set_high();
+			self.transmitting = true;
+		}
+	}
+
+	fn end_transmission(&mut self) {
+		self.direction.set_low();
+		self.transmitting = false;
+	}
+
+	/// Lower the direction pin once the transmission is complete, without blocking.
+	fn poll_end_transmission(&mut self) -> nb::Result<(), core::convert::Infallible> {
+		if self.transmitting {
+			if self.usart.claims.holds(Event::TxComplete) {
+				// TXC# can't be polled, this blocks for at most one frame.
+				self.usart.flush();
+			}
+			if !self.usart.tx_complete() {
+				return Err(nb::Error::WouldBlock);
+			}
+			self.end_transmission();
+		}
+		Ok(())
+	}
+}
+
+impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK, DIR: port::PinOps> ufmt::uWrite
+	for Rs485Usart<H, USART, RX, TX, CLOCK, DIR>
+{
+	type Error = core::convert::Infallible;
+
+	fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+		for b in s.as_bytes().iter() {
+			self.write_byte(*b);
+		}
+		Ok(())
+	}
+}
+
+impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK, DIR: port::PinOps>
+	embedded_hal_v0::serial::Write<u8> for Rs485Usart<H, USART, RX, TX, CLOCK, DIR>
+{
+	type Error = core::convert::Infallible;
+
+	fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+		self.start_transmission();
+		embedded_hal_v0::serial::Write::write(&mut self.usart, byte)
+	}
+
+	fn flush(&mut self) -> nb::Result<(), Self::Error> {
+		self.poll_end_transmission()
+	}
+}
+
+impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK, DIR: port::PinOps>
+	embedded_hal_v0::serial::Read<u8> for Rs485Usart<H, USART, RX, TX, CLOCK, DIR>
+{
+	type Error = core::convert::Infallible;
+
+	fn read(&mut self) -> nb::Result<u8, Self::Error> {
+		self.poll_end_transmission()?;
+		self.usart.p.raw_read()
+	}
+}
+
+/// Interrupt-driven transmission of `'static` buffers, created by
+/// [`Usart::into_background_writer()`].
+///
+/// [`write_async()`][BackgroundWriter::write_async] only records the buffer and enables the
+/// [`Event::DataRegisterEmpty`] interrupt.  The handler of that interrupt calls
+/// [`on_data_register_empty()`][BackgroundWriter::on_data_register_empty], which moves the next
+/// byte into the data register, so the main program is free while a long message is sent.  As
+/// both share the writer, it lives in a `Mutex<RefCell<...>>`:
+///
+/// ```ignore
+/// type Writer = arduino_hal::usart::BackgroundWriter<USART0, Pin<Input, D0>, Pin<Output, D1>>;
+/// static WRITER: Mutex<RefCell<Option<Writer>>> = Mutex::new(RefCell::new(None));
+///
+/// #[avr_device::interrupt(atmega328p)]
+/// fn USART_UDRE() {
+///     avr_device::interrupt::free(|cs| {
+///         if let Some(writer) = WRITER.borrow(cs).borrow_mut().as_mut() {
+///             writer.on_data_register_empty();
+///         }
+///     });
+/// }
+///
+/// let writer = serial.into_background_writer();
+/// avr_device::interrupt::free(|cs| WRITER.borrow(cs).replace(Some(writer)));
+/// unsafe { avr_device::interrupt::enable() };
+///
+/// avr_device::interrupt::free(|cs| {
+///     let mut writer = WRITER.borrow(cs).borrow_mut();
+///     writer.as_mut().unwrap().write_async(b"A long status report ...\r\n")
+/// })
+/// .unwrap();
+/// // Do other work while the report is sent
+/// ```
+///
+/// The writer enables and disables the `DataRegisterEmpty` interrupt on its own, so nothing else
+/// may use the `USARTn_UDRE` vector at the same time.  It [claims](crate::interrupt#ownership)
+/// the vector until it is released, and panics if the vector is taken already.
+///
+/// # Buffer lifetime
+/// The interrupt handler reads the buffer long after `write_async()` returned, so it must not be
+/// modified or freed during the transmission.  Requiring `&'static [u8]` guarantees this at
+/// compile time: string literals and `static`s qualify, a buffer on the stack does not.  To send
+/// data assembled at runtime, format it into a `static` buffer and only touch that buffer again
+/// once [`done()`][BackgroundWriter::done] returns `true`.
+pub struct BackgroundWriter<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> {
+	usart: Usart<H, USART, RX, TX, CLOCK>,
+	buffer: &'static [u8],
+	position: usize,
+}
+
+impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> Usart<H, USART, RX, TX, CLOCK> {
+	/// Transmit whole buffers from the `USART_UDRE` interrupt.  See [`BackgroundWriter`].
+	///
+	/// # Panics
+	/// Panics if another feature claimed the `USARTn_UDRE` vector already.
+	pub fn into_background_writer(mut self) -> BackgroundWriter<H, USART, RX, TX, CLOCK> {
+		let event = Event::DataRegisterEmpty;
+		self.claims
+			.claim(event, USART::raw_vector(event), "BackgroundWriter");
+		BackgroundWriter {
+			usart: self,
+			buffer: &[],
+			position: 0,
+		}
+	}
+}
+
+impl<H, USART: UsartOps<H, RX, TX>, RX, TX, CLOCK> BackgroundWriter<H, USART, RX, TX, CLOCK> {
+	/// Start transmitting `buffer` in the background.
+	///
+	/// Returns `WouldBlock` if the previous buffer was not handed to the USART completely yet.
+	pub fn write_async(
+		&mut self,
+		buffer: &'static [u8],
+	) -> nb::Result<(), core::convert::Infallible> {
+		if !self.done() {
+			return Err(nb::Error::WouldBlock);
+		}
+		self.buffer = buffer;
+		self.position = 0;
+		if !buffer.is_empty() {
+			// Fires right away if the data register is already empty.
+			self.usart.p.raw_interrupt(Event::DataRegisterEmpty, true);
+		}
+		Ok(())
+	}
+
+	/// Whether all bytes of the last buffer were handed to the USART.
+	///
+	/// The last byte may still be shifting out.  Poll this in a loop, each time inside a short
+	/// critical section, so the interrupt handler can run in between.
+	pub fn done(&self) -> bool {
+		self.position >= self.buffer.len()
+	}
+
+	/// Move the next byte into the data register.
+	///
+	/// This must be called from the `USART_UDRE` interrupt handler.  The interrupt is disabled
+	/// once the last byte was written.
+	pub fn on_data_register_empty(&mut self) {
+		if let Some(&byte) = self.buffer.get(self.position) {
+			// The data register is empty, so this does not block.
+			self.usart.write_byte(byte);
+			self.position += 1;
+		}
+		if self.done() {
+			self.usart.p.raw_interrupt(Event::DataRegisterEmpty, false);
+		}
+	}
+
+	/// Send the rest of the current buffer without interrupts and return the USART.
+	///
+	/// This releases the claim on the `USARTn_UDRE` vector.
+	pub fn release(mut self) -> Usart<H, USART, RX, TX, CLOCK> {
+		self.usart.unlisten(Event::DataRegisterEmpty);
+		while let Some(&byte) = self.buffer.get(self.position) {
+			self.usart.write_byte(byte);
+			self.position += 1;
+		}
+		self.usart
+	}
+}
+
+/// Software idle-line detection for delimiting variable-length frames.
+///
+/// The USART has no hardware idle detection, so this counts ticks of a periodic timer since the
+/// last received byte: call [`IdleDetector::byte_received()`] for every byte (e.g. from the
+/// `USART_RX` interrupt) and [`IdleDetector::tick()`] from a timer interrupt.  Once no byte
+/// arrived for the configured number of ticks, [`IdleDetector::idle_since_last_byte()`] returns
+/// `true` and the frame is complete.  The timeout resolution is one tick, so the timer should run
+/// several times faster than the timeout.
+///
+/// # Modbus RTU
+/// Modbus RTU frames have no start or end marker; a frame ends when the line is silent for at
+/// least 3.5 character times.  A character is 11 bits (start bit, 8 data bits, parity or a second
+/// stop bit, and a stop bit).  Above 19200 baud the specification fixes the gap at 1750 µs, to
+/// keep the timing feasible for software implementations.  [`IdleDetector::modbus_rtu()`]
+/// computes this timeout:
+///
+/// ```ignore
+/// // Timer interrupt every 100 µs, 9600 baud: 3.5 * 11 / 9600 s = 4.01 ms = 41 ticks
+/// let mut idle = IdleDetector::modbus_rtu(9600, 100);
+///
+/// // USART_RX interrupt:
+/// buffer.push(serial.read_byte());
+/// idle.byte_received();
+///
+/// // Timer interrupt:
+/// idle.tick();
+///
+/// // Main loop:
+/// if idle.idle_since_last_byte() && !buffer.is_empty() {
+///     handle_frame(&buffer);
+///     buffer.clear();
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct IdleDetector {
+	timeout_ticks: u16,
+	elapsed_ticks: u16,
+}
+
+impl IdleDetector {
+	/// Create a detector which reports an idle line after `timeout_ticks` ticks without a byte.
+	///
+	/// The line starts out idle.
+	pub const fn new(timeout_ticks: u16) -> Self {
+		Self {
+			timeout_ticks,
+			elapsed_ticks: timeout_ticks,
+		}
+	}
+
+	/// Create a detector for a timeout in microseconds, with [`IdleDetector::tick()`] being
+	/// called every `tick_us` microseconds.
+	///
+	/// The timeout is rounded up to whole ticks.
+	pub fn from_micros(timeout_us: u32, tick_us: u32) -> Self {
+		let ticks = timeout_us.div_ceil(tick_us);
+		Self::new(ticks.min(u16::MAX as u32) as u16)
+	}
+
+	/// Create a detector for the Modbus RTU inter-frame gap of 3.5 character times at `baudrate`.
+	pub fn modbus_rtu(baudrate: u32, tick_us: u32) -> Self {
+		let timeout_us = if baudrate > 19200 {
+			1750
+		} else {
+			// 3.5 characters of 11 bits each
+			(77 * 1_000_000 / 2u32).div_ceil(baudrate)
+		};
+		Self::from_micros(timeout_us, tick_us)
+	}
+
+	/// Restart the timeout after a byte was received.
+	pub fn byte_received(&mut self) {
+		self.elapsed_ticks = 0;
+	}
+
+	/// Advance the time since the last byte by one tick.
+	pub fn tick(&mut self) {
+		self.elapsed_ticks = self.elapsed_ticks.saturating_add(1);
+	}
+
+	/// Whether the line was idle for at least the timeout since the last received byte.
+	pub fn idle_since_last_byte(&self) -> bool {
+		self.elapsed_ticks >= self.timeout_ticks
+	}
+
+	/// Change the timeout, e.g. after switching the baudrate.
+	pub fn set_timeout(&mut self, timeout_ticks: u16) {
+		self.timeout_ticks = timeout_ticks;
+	}
+
+	/// The timeout in ticks.
+	pub fn timeout(&self) -> u16 {
+		self.timeout_ticks
+	}
+}
+
+/// Assemble lines of text from a serial port, for simple command interfaces.
+///
+/// [`LineReader::poll_line()`] reads all bytes available from the serial port without blocking
+/// and returns a complete line once one has arrived, without its line ending:
+///
+/// ```ignore
+/// let mut lines = LineReader::<32>::new();
+///
+/// loop {
+///     match lines.poll_line(&mut serial) {
+///         Some("LED ON") => led.set_high(),
+///         Some("LED OFF") => led.set_low(),
+///         Some(other) => ufmt::uwriteln!(&mut serial, "unknown: {}\r", other).unwrap_infallible(),
+///         None => (),
+///     }
+///     // Do other work ...
+/// }
+/// ```
+///
+/// - Lines end with `\n`, `\r`, or `\r\n`, so terminals sending any of them work.  Empty lines
+///   are skipped.
+/// - Backspace (`0x08`) and DEL (`0x7F`) remove the previous character, for typing commands in a
+///   terminal.
+/// - A line longer than `N` bytes is dropped entirely instead of being cut off, since a
+///   truncated command could mean something else.  The same happens to lines which are not valid
+///   UTF-8.  [`take_dropped()`][LineReader::take_dropped] reports whether this happened.
+#[derive(Debug)]
+pub struct LineReader<const N: usize> {
+	buffer: [u8; N],
+	len: usize,
+	discarding: bool,
+	dropped: bool,
+	after_cr: bool,
+	line_ready: bool,
+}
+
+impl<const N: usize> Default for LineReader<N> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<const N: usize> LineReader<N> {
+	pub const fn new() -> Self {
+		Self {
+			buffer: [0; N],
+			len: 0,
+			discarding: false,
+			dropped: false,
+			after_cr: false,
+			line_ready: false,
+		}
+	}
+
+	/// Feed a single received byte, e.g. from the RX interrupt.
+	///
+	/// Returns `true` when this byte completed a line, which can then be fetched with
+	/// [`line()`][LineReader::line].  The line stays available until the next call to `push()`.
+	pub fn push(&mut self, byte: u8) -> bool {
+		if self.line_ready {
+			self.line_ready = false;
+			self.len = 0;
+		}
+		let after_cr = core::mem::replace(&mut self.after_cr, byte == b'\r');
+
+		match byte {
+			// Second half of a CRLF
+			b'\n' if after_cr => false,
+			b'\r' | b'\n' => self.end_line(),
+			0x08 | 0x7f => {
+				self.len = self.len.saturating_sub(1);
+				false
+			}
+			_ if self.discarding => false,
+			_ if self.len == N => {
+				self.discarding = true;
+				false
+			}
+			_ => {
+				self.buffer[self.len] = byte;
+				self.len += 1;
+				false
+			}
+		}
+	}
+
+	fn end_line(&mut self) -> bool {
+		if core::mem::replace(&mut self.discarding, false) {
+			self.dropped = true;
+			self.len = 0;
+			return false;
+		}
+		if self.len == 0 {
+			return false;
+		}
+		if core::str::from_utf8(&self.buffer[..self.len]).is_err() {
+			self.dropped = true;
+			self.len = 0;
+			return false;
+		}
+		self.line_ready = true;
+		true
+	}
+
+	/// The line completed by the last call to [`push()`][LineReader::push], if any.
+	pub fn line(&self) -> Option<&str> {
+		if self.line_ready {
+			// The line was checked to be valid UTF-8 when it was completed.
+			core::str::from_utf8(&self.buffer[..self.len]).ok()
+		} else {
+			None
+		}
+	}
+
+	/// Read the available bytes from `serial` until a line is complete, without blocking.
+	///
+	/// Returns `None` if no complete line has arrived yet; the partial line is kept for the next
+	/// call.
+	pub fn poll_line<R>(&mut self, serial: &mut R) -> Option<&str>
+	where
+		R: embedded_hal_v0::serial::Read<u8>,
+	{
+		loop {
+			match serial.read() {
+				Ok(byte) => {
+					if self.push(byte) {
+						return self.line();
+					}
+				}
+				Err(_) => return None,
+			}
+		}
+	}
+
+	/// Whether a line was dropped because it was too long or not valid UTF-8, since the last
+	/// call to this method.
+	pub fn take_dropped(&mut self) -> bool {
+		core::mem::replace(&mut self.dropped, false)
+	}
+
+	/// Discard the partial line.
+	pub fn clear(&mut self) {
+		self.len = 0;
+		self.discarding = false;
+		self.after_cr = false;
+		self.line_ready = false;
+	}
+}
+
 #[macro_export]
 macro_rules! impl_usart_traditional {
 	(
@@ -474,6 +1524,7 @@ macro_rules! impl_usart_traditional {
         register_suffix: $n:expr,
         rx: $rxpin:ty,
         tx: $txpin:ty,
+        vectors: $vectors:expr,
     ) => {
 		$crate::paste::paste! {
 		    impl $crate::usart::UsartOps<
@@ -519,10 +1570,24 @@ macro_rules! impl_usart_traditional {
 			    // Call flush to make sure the data-register is empty
 			    self.raw_flush()?;
 
-			    self.[<udr $n>]().write(|w| unsafe { w.bits(byte) });
+			    $crate::avr_device::interrupt::free(|_| {
+				self.[<udr $n>]().write(|w| unsafe { w.bits(byte) });
+				// Clear TXC so it only gets set again once this byte was sent.  FE, DOR and UPE
+				// must be written as zero, so only U2X and MPCM are kept.
+				let a = self.[<ucsr $n a>]().read();
+				self.[<ucsr $n a>]().write(|w| w
+				    .[<u2x $n>]().bit(a.[<u2x $n>]().bit())
+				    .[<mpcm $n>]().bit(a.[<mpcm $n>]().bit())
+				    .[<txc $n>]().set_bit()
+				);
+			    });
 			    Ok(())
 			}
 
+			fn raw_tx_complete(&mut self) -> bool {
+			    self.[<ucsr $n a>]().read().[<txc $n>]().bit_is_set()
+			}
+
 			fn raw_read(&mut self) -> $crate::nb::Result<u8, core::convert::Infallible> {
 			    if self.[<ucsr $n a>]().read().[<rxc $n>]().bit_is_clear() {
 				return Err($crate::nb::Error::WouldBlock);
@@ -544,7 +1609,465 @@ macro_rules! impl_usart_traditional {
 				}
 			    }
 			}
+
+			unsafe fn raw_mask_interrupt(event: $crate::usart::Event) {
+			    <Self as $crate::usart::UsartOps<
+				$HAL,
+				$crate::port::Pin<$crate::port::mode::Input, $rxpin>,
+				$crate::port::Pin<$crate::port::mode::Output, $txpin>,
+			    >>::raw_interrupt(&mut Self::steal(), event, false);
+			}
+
+			fn raw_vector(event: $crate::usart::Event) -> u8 {
+			    let vectors: [u8; 3] = $vectors;
+			    match event {
+				$crate::usart::Event::RxComplete => vectors[0],
+				$crate::usart::Event::DataRegisterEmpty => vectors[1],
+				$crate::usart::Event::TxComplete => vectors[2],
+			    }
+			}
+
+			fn raw_set_receiver(&mut self, enable: bool) {
+			    self.[<ucsr $n b>]().modify(|_, w| w.[<rxen $n>]().bit(enable));
+			}
+
+			fn raw_set_transmitter(&mut self, enable: bool) {
+			    self.[<ucsr $n b>]().modify(|_, w| w.[<txen $n>]().bit(enable));
+			}
+
+			fn raw_set_nine_bit(&mut self, enable: bool) {
+			    self.[<ucsr $n b>]().modify(|_, w| w.[<ucsz $n 2>]().bit(enable));
+			}
+
+			fn raw_set_mpcm(&mut self, enable: bool) {
+			    // TXC is cleared by writing a one, and FE, DOR and UPE must be written as zero,
+			    // so only U2X is kept.
+			    let a = self.[<ucsr $n a>]().read();
+			    self.[<ucsr $n a>]().write(|w| w
+				.[<u2x $n>]().bit(a.[<u2x $n>]().bit())
+				.[<mpcm $n>]().bit(enable)
+			    );
+			}
+
+			fn raw_set_tx_bit8(&mut self, bit: bool) {
+			    self.[<ucsr $n b>]().modify(|_, w| w.[<txb8 $n>]().bit(bit));
+			}
+
+			fn raw_read_9bit(&mut self) -> $crate::nb::Result<u16, core::convert::Infallible> {
+			    if self.[<ucsr $n a>]().read().[<rxc $n>]().bit_is_clear() {
+				return Err($crate::nb::Error::WouldBlock);
+			    }
+
+			    // RXB8 must be read before UDR, which pops the frame from the buffer.
+			    let bit8 = self.[<ucsr $n b>]().read().[<rxb8 $n>]().bit();
+			    Ok((u16::from(bit8) << 8) | u16::from(self.[<udr $n>]().read().bits()))
+			}
 		    }
 		}
 	};
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::VecDeque;
+	use std::vec::Vec;
+
+	/// A USART which records what the driver does with it.
+	#[derive(Default)]
+	struct MockUsart {
+		/// Frames to be received, with the ninth bit in bit 8.
+		rx: VecDeque<u16>,
+		/// Written frames, with the ninth bit in bit 8.
+		tx: Vec<u16>,
+		/// `TXC`, cleared by every write.
+		tx_complete: bool,
+		/// Interrupt enable bits, indexed like `EventClaims`.
+		interrupts: [bool; 3],
+		nine_bit: bool,
+		mpcm: bool,
+		tx_bit8: bool,
+	}
+
+	impl UsartOps<(), (), ()> for MockUsart {
+		fn raw_init<CLOCK>(&mut self, _baudrate: Baudrate<CLOCK>) {}
+
+		fn raw_deinit(&mut self) {}
+
+		fn raw_flush(&mut self) -> nb::Result<(), core::convert::Infallible> {
+			Ok(())
+		}
+
+		fn raw_write(&mut self, byte: u8) -> nb::Result<(), core::convert::Infallible> {
+			self.tx.push(u16::from(self.tx_bit8) << 8 | u16::from(byte));
+			self.tx_complete = false;
+			Ok(())
+		}
+
+		fn raw_tx_complete(&mut self) -> bool {
+			self.tx_complete
+		}
+
+		fn raw_read(&mut self) -> nb::Result<u8, core::convert::Infallible> {
+			self.raw_read_9bit().map(|frame| frame as u8)
+		}
+
+		fn raw_interrupt(&mut self, event: Event, state: bool) {
+			self.interrupts[EventClaims::index(event)] = state;
+		}
+
+		unsafe fn raw_mask_interrupt(_event: Event) {}
+
+		fn raw_vector(event: Event) -> u8 {
+			20 + EventClaims::index(event) as u8
+		}
+
+		fn raw_set_receiver(&mut self, _enable: bool) {}
+
+		fn raw_set_transmitter(&mut self, _enable: bool) {}
+
+		fn raw_set_nine_bit(&mut self, enable: bool) {
+			self.nine_bit = enable;
+		}
+
+		fn raw_set_mpcm(&mut self, enable: bool) {
+			self.mpcm = enable;
+		}
+
+		fn raw_set_tx_bit8(&mut self, bit: bool) {
+			self.tx_bit8 = bit;
+		}
+
+		fn raw_read_9bit(&mut self) -> nb::Result<u16, core::convert::Infallible> {
+			self.rx.pop_front().ok_or(nb::Error::WouldBlock)
+		}
+	}
+
+	/// An output pin which only remembers its level.
+	#[derive(Default)]
+	struct MockPin(bool);
+
+	impl port::PinOps for MockPin {
+		type Dynamic = Self;
+
+		fn into_dynamic(self) -> Self::Dynamic {
+			self
+		}
+
+		unsafe fn out_set(&mut self) {
+			self.0 = true;
+		}
+		unsafe fn out_clear(&mut self) {
+			self.0 = false;
+		}
+		unsafe fn out_toggle(&mut self) {
+			self.0 = !self.0;
+		}
+		unsafe fn out_get(&self) -> bool {
+			self.0
+		}
+
+		unsafe fn in_get(&self) -> bool {
+			self.0
+		}
+
+		unsafe fn make_output(&mut self) {}
+		unsafe fn make_input(&mut self, _pull_up: bool) {}
+	}
+
+	type TestUsart = Usart<(), MockUsart, (), (), crate::clock::MHz16>;
+
+	fn usart() -> TestUsart {
+		Usart {
+			p: MockUsart::default(),
+			rx: (),
+			tx: (),
+			tx_pending: false,
+			frame_cycles: 0,
+			claims: EventClaims::new(),
+			_clock: marker::PhantomData,
+			_h: marker::PhantomData,
+		}
+	}
+
+	#[test]
+	fn read_timeout() {
+		let mut serial = usart();
+		// With less than one poll interval left, the first empty poll gives up without
+		// busy-waiting, which is not available on the host.
+		assert_eq!(serial.read_timeout(0), Err(TimeoutError));
+		assert_eq!(serial.read_timeout(READ_POLL_CYCLES - 1), Err(TimeoutError));
+		serial.p.rx.push_back(0x42);
+		assert_eq!(serial.read_timeout(0), Ok(0x42));
+	}
+
+	#[test]
+	fn event_claims_follow_split_and_reunite() {
+		let mut claims = EventClaims::new();
+		claims.claim(Event::RxComplete, 18, "Usart::listen");
+		claims.claim(Event::DataRegisterEmpty, 19, "BackgroundWriter");
+		// Claiming an event this USART holds already is not a conflict.
+		claims.claim(Event::DataRegisterEmpty, 19, "BackgroundWriter");
+
+		let (rx, tx) = claims.split();
+		assert_eq!(crate::interrupt::owner(18), Some("Usart::listen"));
+		drop(rx);
+		assert_eq!(crate::interrupt::owner(18), None);
+		assert_eq!(crate::interrupt::owner(19), Some("BackgroundWriter"));
+
+		let mut claims = EventClaims::new().merge(tx);
+		claims.release(Event::DataRegisterEmpty);
+		assert_eq!(crate::interrupt::owner(19), None);
+	}
+
+	#[test]
+	fn idle_detector_modbus_timeout() {
+		// 3.5 * 11 bits at 9600 baud = 4010.4 µs, rounded up to 41 ticks of 100 µs
+		assert_eq!(IdleDetector::modbus_rtu(9600, 100).timeout(), 41);
+		assert_eq!(IdleDetector::modbus_rtu(19200, 100).timeout(), 21);
+		// Fixed 1750 µs above 19200 baud
+		assert_eq!(IdleDetector::modbus_rtu(115200, 100).timeout(), 18);
+	}
+
+	#[test]
+	fn idle_detector_counts_ticks() {
+		let mut idle = IdleDetector::new(3);
+		assert!(idle.idle_since_last_byte());
+		idle.byte_received();
+		idle.tick();
+		idle.tick();
+		assert!(!idle.idle_since_last_byte());
+		idle.tick();
+		assert!(idle.idle_since_last_byte());
+	}
+
+	#[test]
+	fn multiprocessor_send() {
+		let mut serial = usart();
+		serial.set_multiprocessor_mode(true);
+		assert!(serial.p.nine_bit && serial.p.mpcm);
+
+		serial.write_address(0x05);
+		serial.write_byte(0x42);
+		assert_eq!(serial.p.tx, [0x105, 0x042]);
+
+		serial.set_multiprocessor_mode(false);
+		assert!(!serial.p.nine_bit && !serial.p.mpcm);
+	}
+
+	#[test]
+	fn multiprocessor_receive() {
+		let mut serial = usart();
+		serial.set_multiprocessor_mode(true);
+		// The hardware filter drops data frames while MPCM is set, the mock does not.
+		serial.p.rx.extend([0x103, 0x105, 0x042, 0x043, 0x107]);
+
+		assert_eq!(serial.read_addressed(5), Err(nb::Error::WouldBlock));
+		assert!(serial.p.mpcm);
+		assert_eq!(serial.read_addressed(5), Err(nb::Error::WouldBlock));
+		assert!(!serial.p.mpcm);
+		assert_eq!(serial.read_addressed(5), Ok(0x42));
+		assert_eq!(serial.read_addressed(5), Ok(0x43));
+		assert_eq!(serial.read_addressed(5), Err(nb::Error::WouldBlock));
+		assert!(serial.p.mpcm);
+
+		serial.p.rx.push_back(0x107);
+		assert_eq!(serial.read_frame(), Ok(Frame::Address(7)));
+	}
+
+	#[test]
+	fn rs485_direction_follows_transmission() {
+		use embedded_hal_v0::serial::Read;
+		use embedded_hal_v0::serial::Write;
+
+		let direction = port::Pin::new(MockPin(true)).into_output_high();
+		let mut serial = usart().with_rs485_direction(direction);
+		assert!(!serial.is_transmitting());
+		assert!(!serial.direction.pin.0);
+
+		serial.write_byte(0x01);
+		Write::write(&mut serial, 0x02).unwrap();
+		assert!(serial.is_transmitting());
+		assert!(serial.direction.pin.0);
+		assert_eq!(serial.usart.p.tx, [0x01, 0x02]);
+
+		// The last byte is still shifting out
+		assert_eq!(Write::flush(&mut serial), Err(nb::Error::WouldBlock));
+		serial.usart.p.rx.push_back(0x42);
+		assert_eq!(Read::read(&mut serial), Err(nb::Error::WouldBlock));
+		assert!(serial.direction.pin.0);
+
+		serial.usart.p.tx_complete = true;
+		assert_eq!(Read::read(&mut serial), Ok(0x42));
+		assert!(!serial.is_transmitting());
+		assert!(!serial.direction.pin.0);
+
+		serial.write_byte(0x03);
+		serial.usart.p.tx_complete = true;
+		serial.flush();
+		assert!(!serial.direction.pin.0);
+		let (_usart, direction) = serial.release();
+		assert!(!direction.pin.0);
+	}
+
+	fn push_all<const N: usize>(
+		lines: &mut LineReader<N>,
+		bytes: &[u8],
+	) -> Option<std::string::String> {
+		let mut complete = None;
+		for &byte in bytes {
+			if lines.push(byte) {
+				complete = lines.line().map(std::string::String::from);
+			}
+		}
+		complete
+	}
+
+	#[test]
+	fn line_reader_line_endings() {
+		let mut lines = LineReader::<16>::new();
+		assert_eq!(
+			push_all(&mut lines, b"LED ON\r\n").as_deref(),
+			Some("LED ON")
+		);
+		// The line is only available until the next byte, the LF of the CRLF here
+		assert_eq!(lines.line(), None);
+		assert!(!lines.push(b'\n'));
+		assert_eq!(push_all(&mut lines, b"a\nb\r").as_deref(), Some("b"));
+		// Empty lines are ignored
+		assert_eq!(push_all(&mut lines, b"\r\n\n"), None);
+		assert_eq!(
+			push_all(&mut lines, b"ab\x08c\x7fd\r").as_deref(),
+			Some("ad")
+		);
+		assert!(!lines.take_dropped());
+	}
+
+	#[test]
+	fn line_reader_drops_bad_lines() {
+		let mut lines = LineReader::<4>::new();
+		assert_eq!(push_all(&mut lines, b"abcd\r").as_deref(), Some("abcd"));
+		assert_eq!(push_all(&mut lines, b"abcde\r"), None);
+		assert!(lines.take_dropped());
+		assert!(!lines.take_dropped());
+		assert_eq!(push_all(&mut lines, b"\xff\xfe\r"), None);
+		assert!(lines.take_dropped());
+		assert_eq!(push_all(&mut lines, b"ok\r").as_deref(), Some("ok"));
+	}
+
+	#[test]
+	fn line_reader_polls_serial() {
+		let mut serial = usart();
+		let mut lines = LineReader::<16>::new();
+		serial.p.rx.extend(b"HEL".map(u16::from));
+		assert_eq!(lines.poll_line(&mut serial), None);
+		serial.p.rx.extend(b"LO\r\nX".map(u16::from));
+		assert_eq!(lines.poll_line(&mut serial), Some("HELLO"));
+		assert_eq!(serial.p.rx, [u16::from(b'\n'), u16::from(b'X')]);
+	}
+
+	#[test]
+	fn background_writer_sends_from_interrupt() {
+		let udre = EventClaims::index(Event::DataRegisterEmpty);
+		let mut writer = usart().into_background_writer();
+		assert_eq!(
+			crate::interrupt::owner(MockUsart::raw_vector(Event::DataRegisterEmpty)),
+			Some("BackgroundWriter")
+		);
+		assert!(writer.done());
+
+		writer.write_async(b"abc").unwrap();
+		assert!(writer.usart.p.interrupts[udre]);
+		assert_eq!(writer.write_async(b"x"), Err(nb::Error::WouldBlock));
+
+		writer.on_data_register_empty();
+		writer.on_data_register_empty();
+		assert!(!writer.done());
+		writer.on_data_register_empty();
+		assert!(writer.done());
+		assert!(!writer.usart.p.interrupts[udre]);
+		assert_eq!(writer.usart.p.tx, b"abc".map(u16::from));
+
+		// Empty buffers don't enable the interrupt
+		writer.write_async(b"").unwrap();
+		assert!(!writer.usart.p.interrupts[udre]);
+	}
+
+	#[test]
+	fn background_writer_release_sends_rest() {
+		let mut writer = usart().into_background_writer();
+		writer.write_async(b"hello").unwrap();
+		writer.on_data_register_empty();
+		let serial = writer.release();
+		assert_eq!(serial.p.tx, b"hello".map(u16::from));
+		assert!(!serial.p.interrupts[EventClaims::index(Event::DataRegisterEmpty)]);
+		assert_eq!(
+			crate::interrupt::owner(MockUsart::raw_vector(Event::DataRegisterEmpty)),
+			None
+		);
+	}
+
+	#[test]
+	#[should_panic(expected = "interrupt vector claimed twice")]
+	fn background_writer_conflicts_with_other_owner() {
+		let _other = crate::interrupt::claim(
+			MockUsart::raw_vector(Event::DataRegisterEmpty),
+			"debug console",
+		)
+		.unwrap();
+		let _writer = usart().into_background_writer();
+	}
+
+	#[test]
+	fn tx_complete_tracks_last_write() {
+		let mut serial = usart();
+		// TXC stays cleared until the first transmission, nothing is pending though
+		assert!(serial.tx_complete());
+		serial.flush();
+
+		serial.write_byte(0x42);
+		assert!(!serial.tx_complete());
+		assert_eq!(
+			embedded_hal_v0::serial::Write::flush(&mut serial),
+			Err(nb::Error::WouldBlock)
+		);
+		serial.p.tx_complete = true;
+		assert!(serial.tx_complete());
+		serial.flush();
+
+		// A stale TXC from an earlier byte does not count for the next one
+		serial.write_byte(0x43);
+		assert!(!serial.tx_complete());
+	}
+
+	#[test]
+	fn tx_pending_survives_split() {
+		let mut serial = usart();
+		serial.write_byte(0x42);
+		let (reader, mut writer) = serial.split();
+		// Both halves share the peripheral, which owns heap memory only in this mock.
+		core::mem::forget(reader);
+		assert!(!writer.tx_complete());
+		writer.p.tx_complete = true;
+		assert!(writer.tx_complete());
+	}
+
+	#[test]
+	fn flush_with_tx_complete_listened() {
+		let mut serial = usart();
+		serial.listen(Event::TxComplete);
+		serial.write_byte(0x42);
+		// The interrupt handler took TXC before it could be polled
+		serial.flush();
+		assert!(serial.tx_complete());
+		assert!(serial.p.interrupts[EventClaims::index(Event::TxComplete)]);
+		assert_eq!(embedded_hal_v0::serial::Write::flush(&mut serial), Ok(()));
+
+		// TXC still set after masking the interrupt ends the wait right away
+		serial.frame_cycles = 13 * 1667;
+		serial.write_byte(0x43);
+		serial.p.tx_complete = true;
+		let (p, _, _) = serial.release();
+		assert_eq!(p.tx, [0x42, 0x43]);
+	}
+}
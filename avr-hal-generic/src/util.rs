@@ -0,0 +1,516 @@
+//! Helpers for drivers
+//!
+//! # Byte Order
+//! Sensors and other I2C devices usually store multi-byte values in consecutive registers, some
+//! with the most significant byte first (big endian), some the other way around (little endian).
+//! [`ByteOrder`] converts between bytes and values in either order, and [`read_u16_be()`] and
+//! friends read or write such a value in a single I2C transaction:
+//!
+//! ```ignore
+//! // Read the 16-bit temperature value from registers 0x41 (high byte) and 0x42 (low byte)
+//! let raw = util::read_i16_be(&mut i2c, 0x68, 0x41)?;
+//! ```
+//!
+//! # Retries
+//! Some peripherals fail now and then, e.g. a sensor which is busy with a conversion or an SD
+//! card which is not ready yet.  [`retry()`] runs an operation again after a short delay until it
+//! succeeds or the attempts are used up:
+//!
+//! ```ignore
+//! let raw = util::retry(&mut delay, 3, 500, || util::read_i16_be(&mut i2c, 0x68, 0x41))?;
+//! ```
+//!
+//! # Register devices
+//! Most I2C chips expose their configuration and data as numbered 8-bit registers: a write of the
+//! register number, optionally followed by data, writes registers, and a write of the register
+//! number followed by a read (with a repeated start) reads them.  [`RegDevice`] binds an I2C bus
+//! to a device address and provides these accesses, including read-modify-write of single bits:
+//!
+//! ```ignore
+//! let mut imu = RegDevice::new(i2c, 0x68);
+//!
+//! // Wake up: clear the SLEEP bit in PWR_MGMT_1
+//! imu.clear_bits(0x6b, 1 << 6)?;
+//! let who_am_i = imu.read_reg(0x75)?;
+//! let accel_x = imu.read_u16(0x3b, ByteOrder::BigEndian)? as i16;
+//! ```
+//!
+//! # Checksums
+//! [`crc16_modbus()`] computes the CRC-16 used by Modbus RTU (polynomial `0xA001` reflected,
+//! initial value `0xFFFF`).  It works bitwise without a lookup table, which saves 512 bytes of
+//! flash at the cost of about 8 shifts per byte.
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+use embedded_hal::i2c::Operation;
+
+/// Order of bytes in a multi-byte value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+	/// Most significant byte first.
+	BigEndian,
+	/// Least significant byte first.
+	LittleEndian,
+}
+
+impl ByteOrder {
+	pub fn u16_from_bytes(self, bytes: [u8; 2]) -> u16 {
+		match self {
+			ByteOrder::BigEndian => u16::from_be_bytes(bytes),
+			ByteOrder::LittleEndian => u16::from_le_bytes(bytes),
+		}
+	}
+
+	pub fn u16_to_bytes(self, value: u16) -> [u8; 2] {
+		match self {
+			ByteOrder::BigEndian => value.to_be_bytes(),
+			ByteOrder::LittleEndian => value.to_le_bytes(),
+		}
+	}
+
+	pub fn i16_from_bytes(self, bytes: [u8; 2]) -> i16 {
+		self.u16_from_bytes(bytes) as i16
+	}
+
+	pub fn i16_to_bytes(self, value: i16) -> [u8; 2] {
+		self.u16_to_bytes(value as u16)
+	}
+
+	pub fn u32_from_bytes(self, bytes: [u8; 4]) -> u32 {
+		match self {
+			ByteOrder::BigEndian => u32::from_be_bytes(bytes),
+			ByteOrder::LittleEndian => u32::from_le_bytes(bytes),
+		}
+	}
+
+	pub fn u32_to_bytes(self, value: u32) -> [u8; 4] {
+		match self {
+			ByteOrder::BigEndian => value.to_be_bytes(),
+			ByteOrder::LittleEndian => value.to_le_bytes(),
+		}
+	}
+
+	pub fn i32_from_bytes(self, bytes: [u8; 4]) -> i32 {
+		self.u32_from_bytes(bytes) as i32
+	}
+
+	pub fn i32_to_bytes(self, value: i32) -> [u8; 4] {
+		self.u32_to_bytes(value as u32)
+	}
+}
+
+/// Read a 16-bit value from the registers `register` and `register + 1`.
+///
+/// Sends `register`, then reads two bytes with a repeated start in between.  This relies on the
+/// device auto-incrementing its register pointer, which nearly all devices do.
+pub fn read_u16<I2C: I2c>(
+	i2c: &mut I2C,
+	address: u8,
+	register: u8,
+	order: ByteOrder,
+) -> Result<u16, I2C::Error> {
+	let mut buffer = [0; 2];
+	i2c.write_read(address, &[register], &mut buffer)?;
+	Ok(order.u16_from_bytes(buffer))
+}
+
+/// Write a 16-bit value to the registers `register` and `register + 1`.
+pub fn write_u16<I2C: I2c>(
+	i2c: &mut I2C,
+	address: u8,
+	register: u8,
+	value: u16,
+	order: ByteOrder,
+) -> Result<(), I2C::Error> {
+	let [a, b] = order.u16_to_bytes(value);
+	i2c.write(address, &[register, a, b])
+}
+
+/// Read a big endian `u16`, see [`read_u16()`].
+pub fn read_u16_be<I2C: I2c>(i2c: &mut I2C, address: u8, register: u8) -> Result<u16, I2C::Error> {
+	read_u16(i2c, address, register, ByteOrder::BigEndian)
+}
+
+/// Read a little endian `u16`, see [`read_u16()`].
+pub fn read_u16_le<I2C: I2c>(i2c: &mut I2C, address: u8, register: u8) -> Result<u16, I2C::Error> {
+	read_u16(i2c, address, register, ByteOrder::LittleEndian)
+}
+
+/// Read a big endian `i16`, see [`read_u16()`].
+pub fn read_i16_be<I2C: I2c>(i2c: &mut I2C, address: u8, register: u8) -> Result<i16, I2C::Error> {
+	read_u16_be(i2c, address, register).map(|v| v as i16)
+}
+
+/// Read a little endian `i16`, see [`read_u16()`].
+pub fn read_i16_le<I2C: I2c>(i2c: &mut I2C, address: u8, register: u8) -> Result<i16, I2C::Error> {
+	read_u16_le(i2c, address, register).map(|v| v as i16)
+}
+
+/// Write a big endian `u16`, see [`write_u16()`].
+pub fn write_u16_be<I2C: I2c>(
+	i2c: &mut I2C,
+	address: u8,
+	register: u8,
+	value: u16,
+) -> Result<(), I2C::Error> {
+	write_u16(i2c, address, register, value, ByteOrder::BigEndian)
+}
+
+/// Write a little endian `u16`, see [`write_u16()`].
+pub fn write_u16_le<I2C: I2c>(
+	i2c: &mut I2C,
+	address: u8,
+	register: u8,
+	value: u16,
+) -> Result<(), I2C::Error> {
+	write_u16(i2c, address, register, value, ByteOrder::LittleEndian)
+}
+
+/// An I2C device with 8-bit registers.  See the [module documentation](self#register-devices).
+///
+/// Multi-register accesses rely on the device auto-incrementing its register pointer, which
+/// nearly all devices do.
+pub struct RegDevice<I2C> {
+	i2c: I2C,
+	address: u8,
+}
+
+impl<I2C: I2c> RegDevice<I2C> {
+	/// Access the device at the 7-bit `address` on `i2c`.
+	///
+	/// `i2c` can also be a `&mut` reference to share the bus with other devices.
+	pub fn new(i2c: I2C, address: u8) -> Self {
+		Self { i2c, address }
+	}
+
+	/// The 7-bit address of the device.
+	pub fn address(&self) -> u8 {
+		self.address
+	}
+
+	/// Read a single register.
+	pub fn read_reg(&mut self, register: u8) -> Result<u8, I2C::Error> {
+		let mut buffer = [0];
+		self.read_regs(register, &mut buffer)?;
+		Ok(buffer[0])
+	}
+
+	/// Write a single register.
+	pub fn write_reg(&mut self, register: u8, value: u8) -> Result<(), I2C::Error> {
+		self.i2c.write(self.address, &[register, value])
+	}
+
+	/// Read consecutive registers, starting at `register`, into `buffer`.
+	pub fn read_regs(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), I2C::Error> {
+		self.i2c.write_read(self.address, &[register], buffer)
+	}
+
+	/// Write `data` to consecutive registers, starting at `register`, in one transaction.
+	pub fn write_regs(&mut self, register: u8, data: &[u8]) -> Result<(), I2C::Error> {
+		// Adjacent write operations are sent without a repeated start in between.
+		self.i2c.transaction(
+			self.address,
+			&mut [Operation::Write(&[register]), Operation::Write(data)],
+		)
+	}
+
+	/// Read a register, pass its value through `f`, and write the result back.
+	///
+	/// Returns the written value.  The register is written even if `f` did not change it.
+	pub fn modify_reg<F>(&mut self, register: u8, f: F) -> Result<u8, I2C::Error>
+	where
+		F: FnOnce(u8) -> u8,
+	{
+		let value = f(self.read_reg(register)?);
+		self.write_reg(register, value)?;
+		Ok(value)
+	}
+
+	/// Set the bits in `mask`, leaving the other bits of the register unchanged.
+	pub fn set_bits(&mut self, register: u8, mask: u8) -> Result<(), I2C::Error> {
+		self.modify_reg(register, |v| v | mask).map(|_| ())
+	}
+
+	/// Clear the bits in `mask`, leaving the other bits of the register unchanged.
+	pub fn clear_bits(&mut self, register: u8, mask: u8) -> Result<(), I2C::Error> {
+		self.modify_reg(register, |v| v & !mask).map(|_| ())
+	}
+
+	/// Read a 16-bit value from `register` and `register + 1`.
+	pub fn read_u16(&mut self, register: u8, order: ByteOrder) -> Result<u16, I2C::Error> {
+		read_u16(&mut self.i2c, self.address, register, order)
+	}
+
+	/// Write a 16-bit value to `register` and `register + 1`.
+	pub fn write_u16(
+		&mut self,
+		register: u8,
+		value: u16,
+		order: ByteOrder,
+	) -> Result<(), I2C::Error> {
+		write_u16(&mut self.i2c, self.address, register, value, order)
+	}
+
+	/// Return the I2C bus.
+	pub fn release(self) -> I2C {
+		self.i2c
+	}
+}
+
+/// Run `op` up to `attempts` times until it returns `Ok`, waiting `backoff_us` microseconds
+/// between attempts.
+///
+/// Returns the first success, or the error of the last attempt.  `op` is always run at least
+/// once, even with `attempts` set to `0`.
+pub fn retry<D, T, E, F>(delay: &mut D, attempts: u8, backoff_us: u32, mut op: F) -> Result<T, E>
+where
+	D: DelayNs,
+	F: FnMut() -> Result<T, E>,
+{
+	let mut remaining = attempts.max(1);
+	loop {
+		remaining -= 1;
+		match op() {
+			Ok(value) => return Ok(value),
+			Err(e) if remaining == 0 => return Err(e),
+			Err(_) => delay.delay_us(backoff_us),
+		}
+	}
+}
+
+/// Compute the CRC-16/MODBUS of `data`.
+///
+/// Modbus transmits the result low byte first, i.e. as [`u16::to_le_bytes()`].
+pub fn crc16_modbus(data: &[u8]) -> u16 {
+	crc16_modbus_update(0xffff, data)
+}
+
+/// Continue a CRC-16/MODBUS computation over `data`, starting from a previous result `crc`.
+///
+/// Computing the CRC of a message in parts gives the same result as computing it at once, when
+/// starting the first part at `0xFFFF`.
+pub fn crc16_modbus_update(mut crc: u16, data: &[u8]) -> u16 {
+	for &byte in data {
+		crc ^= u16::from(byte);
+		for _ in 0..8 {
+			if crc & 1 != 0 {
+				crc = (crc >> 1) ^ 0xa001;
+			} else {
+				crc >>= 1;
+			}
+		}
+	}
+	crc
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::vec::Vec;
+
+	#[test]
+	fn crc16_modbus_reference_frame() {
+		let frame = [0x01, 0x03, 0x00, 0x00, 0x00, 0x0a];
+		assert_eq!(crc16_modbus(&frame), 0xcdc5);
+		// Sent low byte first
+		assert_eq!(crc16_modbus(&frame).to_le_bytes(), [0xc5, 0xcd]);
+		assert_eq!(
+			crc16_modbus_update(crc16_modbus(&frame[..2]), &frame[2..]),
+			0xcdc5
+		);
+		assert_eq!(crc16_modbus(&[]), 0xffff);
+	}
+
+	/// A device with 256 auto-incrementing 8-bit registers.
+	struct MockI2c {
+		address: u8,
+		regs: [u8; 256],
+		pointer: u8,
+		transactions: usize,
+	}
+
+	impl MockI2c {
+		fn new(address: u8) -> Self {
+			Self {
+				address,
+				regs: [0; 256],
+				pointer: 0,
+				transactions: 0,
+			}
+		}
+	}
+
+	impl embedded_hal::i2c::ErrorType for MockI2c {
+		type Error = embedded_hal::i2c::ErrorKind;
+	}
+
+	impl I2c for MockI2c {
+		fn transaction(
+			&mut self,
+			address: u8,
+			operations: &mut [Operation<'_>],
+		) -> Result<(), Self::Error> {
+			if address != self.address {
+				return Err(embedded_hal::i2c::ErrorKind::NoAcknowledge(
+					embedded_hal::i2c::NoAcknowledgeSource::Address,
+				));
+			}
+			self.transactions += 1;
+			let mut pointer_set = false;
+			for op in operations {
+				match op {
+					Operation::Write(data) => {
+						for &byte in data.iter() {
+							if pointer_set {
+								self.regs[usize::from(
+									self.pointer,
+								)] = byte;
+								self.pointer = self
+									.pointer
+									.wrapping_add(1);
+							} else {
+								self.pointer = byte;
+								pointer_set = true;
+							}
+						}
+					}
+					Operation::Read(buffer) => {
+						for byte in buffer.iter_mut() {
+							*byte = self.regs
+								[usize::from(self.pointer)];
+							self.pointer = self.pointer.wrapping_add(1);
+						}
+					}
+				}
+			}
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn byte_order_conversions() {
+		let be = ByteOrder::BigEndian;
+		let le = ByteOrder::LittleEndian;
+		assert_eq!(be.u16_from_bytes([0x12, 0x34]), 0x1234);
+		assert_eq!(le.u16_from_bytes([0x12, 0x34]), 0x3412);
+		assert_eq!(be.u16_to_bytes(0x1234), [0x12, 0x34]);
+		assert_eq!(le.u16_to_bytes(0x1234), [0x34, 0x12]);
+		assert_eq!(be.i16_from_bytes([0xff, 0x38]), -200);
+		assert_eq!(le.i16_to_bytes(-200), [0x38, 0xff]);
+		assert_eq!(be.u32_from_bytes([1, 2, 3, 4]), 0x0102_0304);
+		assert_eq!(le.u32_to_bytes(0x0102_0304), [4, 3, 2, 1]);
+		assert_eq!(le.i32_from_bytes([0xfe, 0xff, 0xff, 0xff]), -2);
+		assert_eq!(be.i32_to_bytes(-2), [0xff, 0xff, 0xff, 0xfe]);
+	}
+
+	#[test]
+	fn register_pair_access() {
+		let mut i2c = MockI2c::new(0x68);
+		i2c.regs[0x41] = 0xfe;
+		i2c.regs[0x42] = 0x0c;
+		assert_eq!(read_u16_be(&mut i2c, 0x68, 0x41), Ok(0xfe0c));
+		assert_eq!(read_u16_le(&mut i2c, 0x68, 0x41), Ok(0x0cfe));
+		assert_eq!(read_i16_be(&mut i2c, 0x68, 0x41), Ok(-500));
+		assert_eq!(read_i16_le(&mut i2c, 0x68, 0x41), Ok(0x0cfe));
+		assert_eq!(i2c.transactions, 4);
+
+		write_u16_be(&mut i2c, 0x68, 0x10, 0xabcd).unwrap();
+		assert_eq!(i2c.regs[0x10..0x12], [0xab, 0xcd]);
+		write_u16_le(&mut i2c, 0x68, 0x10, 0xabcd).unwrap();
+		assert_eq!(i2c.regs[0x10..0x12], [0xcd, 0xab]);
+		assert_eq!(i2c.transactions, 6);
+
+		assert!(read_u16_be(&mut i2c, 0x69, 0x41).is_err());
+	}
+
+	#[test]
+	fn reg_device_access() {
+		let mut dev = RegDevice::new(MockI2c::new(0x68), 0x68);
+		assert_eq!(dev.address(), 0x68);
+
+		dev.write_reg(0x6b, 0x40).unwrap();
+		assert_eq!(dev.read_reg(0x6b), Ok(0x40));
+
+		dev.write_regs(0x20, &[1, 2, 3]).unwrap();
+		let mut buffer = [0; 3];
+		dev.read_regs(0x20, &mut buffer).unwrap();
+		assert_eq!(buffer, [1, 2, 3]);
+
+		dev.write_u16(0x3b, 0x1234, ByteOrder::BigEndian).unwrap();
+		assert_eq!(dev.read_u16(0x3b, ByteOrder::LittleEndian), Ok(0x3412));
+
+		let i2c = dev.release();
+		assert_eq!(i2c.regs[0x20..0x23], [1, 2, 3]);
+		assert_eq!(i2c.transactions, 6);
+	}
+
+	#[test]
+	fn reg_device_read_modify_write() {
+		let mut dev = RegDevice::new(MockI2c::new(0x68), 0x68);
+		dev.write_reg(0x6b, 0b0100_0001).unwrap();
+
+		dev.clear_bits(0x6b, 1 << 6).unwrap();
+		assert_eq!(dev.read_reg(0x6b), Ok(0b0000_0001));
+		dev.set_bits(0x6b, 0b1000_0000).unwrap();
+		assert_eq!(dev.read_reg(0x6b), Ok(0b1000_0001));
+		assert_eq!(dev.modify_reg(0x6b, |v| v >> 4), Ok(0b0000_1000));
+
+		// A shared bus works through a reference
+		let mut i2c = dev.release();
+		let mut other = RegDevice::new(&mut i2c, 0x69);
+		assert!(other.read_reg(0x6b).is_err());
+	}
+
+	#[derive(Default)]
+	struct MockDelay {
+		us: Vec<u32>,
+	}
+
+	impl DelayNs for MockDelay {
+		fn delay_ns(&mut self, _ns: u32) {
+			unreachable!();
+		}
+
+		fn delay_us(&mut self, us: u32) {
+			self.us.push(us);
+		}
+	}
+
+	#[test]
+	fn retry_until_success() {
+		let mut delay = MockDelay::default();
+		let mut calls = 0;
+		let result: Result<u8, u8> = retry(&mut delay, 5, 500, || {
+			calls += 1;
+			if calls < 3 {
+				Err(calls)
+			} else {
+				Ok(42)
+			}
+		});
+		assert_eq!(result, Ok(42));
+		assert_eq!(calls, 3);
+		assert_eq!(delay.us, [500, 500]);
+	}
+
+	#[test]
+	fn retry_returns_last_error() {
+		let mut delay = MockDelay::default();
+		let mut calls = 0;
+		let result: Result<(), u8> = retry(&mut delay, 3, 100, || {
+			calls += 1;
+			Err(calls)
+		});
+		assert_eq!(result, Err(3));
+		// No delay after the last attempt
+		assert_eq!(delay.us, [100, 100]);
+
+		// At least one attempt
+		let mut calls = 0;
+		let result: Result<(), ()> = retry(&mut delay, 0, 100, || {
+			calls += 1;
+			Err(())
+		});
+		assert_eq!(result, Err(()));
+		assert_eq!(calls, 1);
+	}
+}
@@ -1,6 +1,11 @@
 //! WDT Implementation
+use core::cell::Cell;
 use core::marker::PhantomData;
 
+/// Handler installed by [`Wdt::start_with_early_warning()`].
+static EARLY_WARNING: avr_device::interrupt::Mutex<Cell<Option<fn()>>> =
+	avr_device::interrupt::Mutex::new(Cell::new(None));
+
 /// Watchdog Timeout
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Timeout {
@@ -44,6 +49,32 @@ pub trait WdtOps<H> {
 	/// **Warning**: This is a low-level method and should not be called directly from user code.
 	fn raw_start(&mut self, timeout: Timeout) -> Result<(), ()>;
 
+	/// Start the watchdog timer in interrupt mode with the specified timeout.
+	///
+	/// Instead of resetting the MCU, the watchdog fires its interrupt when the timeout expires,
+	/// and keeps running.  If interrupt mode or the timeout value is not supported, `Err(())`
+	/// should be returned.
+	///
+	/// **Warning**: This is a low-level method and should not be called directly from user code.
+	fn raw_start_interrupt(&mut self, _timeout: Timeout) -> Result<(), ()> {
+		Err(())
+	}
+
+	/// Start the watchdog timer in interrupt and system reset mode with the specified timeout.
+	///
+	/// The first timeout fires the watchdog interrupt, the next one resets the MCU.  If this mode
+	/// or the timeout value is not supported, `Err(())` should be returned.
+	///
+	/// **Warning**: This is a low-level method and should not be called directly from user code.
+	fn raw_start_interrupt_and_reset(&mut self, _timeout: Timeout) -> Result<(), ()> {
+		Err(())
+	}
+
+	/// The number of the watchdog interrupt vector, if there is one.
+	fn raw_vector() -> Option<u8> {
+		None
+	}
+
 	/// Feed this watchdog, to reset its period.
 	///
 	/// **Warning**: This is a low-level method and should not be called directly from user code.
@@ -57,25 +88,171 @@ pub trait WdtOps<H> {
 
 pub struct Wdt<H, WDT> {
 	p: WDT,
+	claim: Option<crate::interrupt::InterruptClaim>,
 	_h: PhantomData<H>,
 }
 
 impl<H, WDT: WdtOps<H>> Wdt<H, WDT> {
 	pub fn new(mut p: WDT, m: &WDT::MCUSR) -> Self {
 		p.raw_init(m);
-		Self { p, _h: PhantomData }
+		Self {
+			p,
+			claim: None,
+			_h: PhantomData,
+		}
 	}
 
 	pub fn start(&mut self, timeout: Timeout) -> Result<(), ()> {
+		self.claim = None;
 		self.p.raw_start(timeout)
 	}
 
+	/// Claim the `WDT` vector for `owner`, replacing the previous claim of this watchdog.
+	fn claim_vector(&mut self, owner: &'static str) {
+		self.claim = None;
+		self.claim = WDT::raw_vector()
+			.map(|vector| crate::interrupt::claim_or_panic(vector, owner));
+	}
+
+	/// Start the watchdog as a periodic interrupt source instead of a reset source.
+	///
+	/// The watchdog interrupt fires every `timeout`, until the watchdog is [stopped][Self::stop].
+	/// It keeps running in all sleep modes, so this is the usual way to wake up from power-down
+	/// periodically.  The application has to define the `WDT` interrupt handler, otherwise the
+	/// interrupt jumps to the reset vector.
+	///
+	/// The `WDT` vector is [claimed](crate::interrupt#ownership) until the watchdog is stopped
+	/// or started in reset mode.  Returns `Err(())` on MCUs without a watchdog interrupt.
+	///
+	/// # Panics
+	/// Panics if another feature claimed the `WDT` vector already.
+	pub fn start_interrupt(&mut self, timeout: Timeout) -> Result<(), ()> {
+		self.claim_vector("Wdt::start_interrupt");
+		let result = self.p.raw_start_interrupt(timeout);
+		if result.is_err() {
+			self.claim = None;
+		}
+		result
+	}
+
+	/// Start the watchdog with an early warning before it resets the MCU.
+	///
+	/// If the watchdog is not fed within `timeout`, it first fires its interrupt, which runs
+	/// `warning`, e.g. to save critical state to the EEPROM.  If it is still not fed, the MCU is
+	/// reset after another `timeout`.  The application has to define the `WDT` interrupt handler
+	/// and call [`Wdt::on_interrupt()`] from it:
+	///
+	/// ```ignore
+	/// #[avr_device::interrupt(atmega328p)]
+	/// fn WDT() {
+	///     Wdt::on_interrupt();
+	/// }
+	///
+	/// fn save_state() {
+	///     // Write the odometer (4 bytes, about 14 ms) to the EEPROM
+	/// }
+	///
+	/// watchdog.start_with_early_warning(Timeout::Ms250, save_state).unwrap();
+	/// ```
+	///
+	/// # Time budget
+	/// The reset follows one `timeout` after the warning, regardless of what the handler does, so
+	/// `warning` has to finish well within that.  Writing one EEPROM byte takes about 3.4 ms, so
+	/// with the shortest timeout of 16 ms, only a handful of bytes can be saved.  Choose a
+	/// timeout which covers the data to be saved.  Feeding the watchdog from the handler averts
+	/// the reset, but then the next timeout resets without a warning, as the hardware disarms the
+	/// warning when the interrupt runs.
+	///
+	/// The `WDT` vector is [claimed](crate::interrupt#ownership) until the watchdog is stopped
+	/// or started in reset mode.  Returns `Err(())` on MCUs without a watchdog interrupt.
+	///
+	/// # Panics
+	/// Panics if another feature claimed the `WDT` vector already.
+	pub fn start_with_early_warning(
+		&mut self,
+		timeout: Timeout,
+		warning: fn(),
+	) -> Result<(), ()> {
+		self.claim_vector("Wdt::start_with_early_warning");
+		avr_device::interrupt::free(|cs| EARLY_WARNING.borrow(cs).set(Some(warning)));
+		let result = self.p.raw_start_interrupt_and_reset(timeout);
+		if result.is_err() {
+			self.claim = None;
+		}
+		result
+	}
+
+	/// Run the handler installed by [`start_with_early_warning()`][Self::start_with_early_warning].
+	///
+	/// This must be called from the `WDT` interrupt handler.
+	pub fn on_interrupt() {
+		let warning = avr_device::interrupt::free(|cs| EARLY_WARNING.borrow(cs).get());
+		if let Some(warning) = warning {
+			warning();
+		}
+	}
+
+	/// Reset the MCU.
+	///
+	/// The watchdog is started with its shortest timeout and left to expire, which resets the
+	/// whole chip like a real watchdog timeout would, so the reset cause reads "watchdog".
+	/// Unlike jumping to address 0, this also resets all peripherals.  Variables placed with
+	/// [`noinit!`][crate::noinit!] keep their values.
+	///
+	/// The watchdog stays enabled after the reset, so it has to be disabled early in `main()`,
+	/// e.g. with `Init::disable_watchdog()`.
+	pub fn reset(&mut self) -> ! {
+		avr_device::interrupt::disable();
+		self.p.raw_start(Timeout::Ms16)
+			.expect("watchdog not supported by this MCU");
+		loop {
+			avr_device::asm::nop();
+		}
+	}
+
 	pub fn feed(&mut self) {
 		self.p.raw_feed()
 	}
 
 	pub fn stop(&mut self) {
-		self.p.raw_stop()
+		self.p.raw_stop();
+		self.claim = None;
+	}
+}
+
+/// Run `iteration` forever, resetting the MCU if a single iteration takes longer than `timeout`.
+///
+/// The watchdog is started with `timeout` and fed once after every completed iteration.  If an
+/// iteration hangs (e.g. waiting for a peripheral that never answers), the watchdog is not fed in
+/// time and resets the chip.
+///
+/// Feeding is deliberately tied to the main loop making progress.  Feeding from a timer interrupt
+/// instead is a common mistake: the interrupt keeps firing while the main loop is stuck, so the
+/// watchdog never triggers and the device hangs forever.  For the same reason, do not feed the
+/// watchdog from inside `iteration`.
+///
+/// # Panics
+/// Panics if `timeout` is not supported by this MCU.
+///
+/// # Example
+/// ```
+/// let mut watchdog = Wdt::new(dp.WDT, &dp.CPU.mcusr);
+///
+/// wdt::guarded_loop(&mut watchdog, Timeout::Ms500, || {
+///     let reading = sensor.read().unwrap();
+///     ufmt::uwriteln!(&mut serial, "{}", reading).unwrap_infallible();
+/// });
+/// ```
+pub fn guarded_loop<H, WDT, F>(wdt: &mut Wdt<H, WDT>, timeout: Timeout, mut iteration: F) -> !
+where
+	WDT: WdtOps<H>,
+	F: FnMut(),
+{
+	wdt.start(timeout)
+		.expect("watchdog timeout not supported by this MCU");
+	loop {
+		iteration();
+		wdt.feed();
 	}
 }
 
@@ -87,6 +264,7 @@ macro_rules! impl_wdt {
         mcusr: $MCUSR:ty,
         wdtcsr_name: $wdtcsr:ident,
         timeout: |$to:ident, $w:ident| $to_match:expr,
+        $(interrupt_enable: $wdie:ident, vector: $vector:expr,)?
     ) => {
 		impl $crate::wdt::WdtOps<$HAL> for $WDT {
 			type MCUSR = $MCUSR;
@@ -125,6 +303,47 @@ macro_rules! impl_wdt {
 				})
 			}
 
+			$(
+				#[inline]
+				fn raw_vector() -> Option<u8> {
+					Some($vector)
+				}
+
+				#[inline]
+				fn raw_start_interrupt_and_reset(&mut self, timeout: Timeout) -> Result<(), ()> {
+					$crate::avr_device::interrupt::free(|_| {
+						self.raw_feed();
+						self.$wdtcsr()
+							.modify(|_, w| w.wdce().set_bit().wde().set_bit());
+						self.$wdtcsr().write(|w| {
+							let $to = timeout;
+							let $w = w;
+							($to_match).$wdie().set_bit().wde().set_bit().wdce().clear_bit()
+						});
+
+						Ok(())
+					})
+				}
+
+				#[inline]
+				fn raw_start_interrupt(&mut self, timeout: Timeout) -> Result<(), ()> {
+					// Same timed sequence as above, but with WDE cleared and the interrupt
+					// enabled instead.
+					$crate::avr_device::interrupt::free(|_| {
+						self.raw_feed();
+						self.$wdtcsr()
+							.modify(|_, w| w.wdce().set_bit().wde().set_bit());
+						self.$wdtcsr().write(|w| {
+							let $to = timeout;
+							let $w = w;
+							($to_match).$wdie().set_bit().wde().clear_bit().wdce().clear_bit()
+						});
+
+						Ok(())
+					})
+				}
+			)?
+
 			#[inline]
 			fn raw_feed(&mut self) {
 				avr_device::asm::wdr();
@@ -152,3 +371,42 @@ macro_rules! impl_wdt {
 		}
 	};
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A watchdog with its interrupt on vector 6.
+	struct MockWdt;
+
+	impl WdtOps<()> for MockWdt {
+		type MCUSR = ();
+
+		fn raw_init(&mut self, _m: &()) {}
+
+		fn raw_start(&mut self, _timeout: Timeout) -> Result<(), ()> {
+			Ok(())
+		}
+
+		fn raw_start_interrupt_and_reset(&mut self, _timeout: Timeout) -> Result<(), ()> {
+			Ok(())
+		}
+
+		fn raw_vector() -> Option<u8> {
+			Some(6)
+		}
+
+		fn raw_feed(&mut self) {}
+		fn raw_stop(&mut self) {}
+	}
+
+	fn warning() {}
+
+	#[test]
+	#[should_panic(expected = "interrupt vector claimed twice")]
+	fn early_warning_needs_the_vector() {
+		let _other = crate::interrupt::claim(6, "other").unwrap();
+		let mut wdt = Wdt::new(MockWdt, &());
+		let _ = wdt.start_with_early_warning(Timeout::Ms250, warning);
+	}
+}
@@ -0,0 +1,57 @@
+/*!
+ * Use the USB frame number as a millisecond timebase and blink the LED on every 1000th frame.
+ *
+ * While a host is connected, it sends a start-of-frame packet every millisecond and the USB
+ * controller counts them in `UDFNUM`.  This example only enables the controller and attaches to
+ * the bus, it does not contain a USB stack.  The host will therefore fail to enumerate the
+ * device and, after a few retries, stop sending frames (Linux logs "unable to enumerate USB
+ * device").  Until then, the LED blinks once per second; afterwards it stops.  Real firmware
+ * polls its USB stack in the loop instead, which keeps the frames coming.
+ *
+ * The frame number does not advance while no cable is connected or the bus is suspended, so it
+ * is no replacement for a timer in code which must run without a host.
+ *
+ * Connections
+ * -----------
+ *  - USB: connect to a host.
+ */
+#![no_std]
+#![no_main]
+
+use arduino_hal::hal::usb;
+use panic_halt as _;
+
+#[arduino_hal::entry]
+fn main() -> ! {
+	let dp = arduino_hal::Peripherals::take().unwrap();
+	let pins = arduino_hal::pins!(dp);
+
+	let mut led = pins.d13.into_output();
+
+	// The USB controller needs a 48 MHz clock from the PLL, which takes the 16 MHz crystal
+	// divided by 2 as its input.
+	dp.PLL.pllcsr()
+		.write(|w| w.pindiv().set_bit().plle().set_bit());
+	while dp.PLL.pllcsr().read().plock().bit_is_clear() {}
+
+	dp.USB_DEVICE.uhwcon().write(|w| w.uvrege().set_bit());
+	dp.USB_DEVICE
+		.usbcon()
+		.write(|w| w.usbe().set_bit().otgpade().set_bit().frzclk().clear_bit());
+	// Attach to the bus by enabling the pull-up on D+.
+	dp.USB_DEVICE.udcon().modify(|_, w| w.detach().clear_bit());
+
+	let mut last = usb::frame_number();
+	loop {
+		// Only look at the frame number once a new frame started.
+		if !usb::take_sof() {
+			continue;
+		}
+
+		let frame = usb::frame_number();
+		if usb::frames_since(last, frame) >= 1000 {
+			last = frame;
+			led.toggle();
+		}
+	}
+}
@@ -0,0 +1,37 @@
+/*!
+ * Drive all three PWM outputs of TC1 at once, with different duty cycles.
+ *
+ * Connect LEDs with resistors to pins D11 (OC1A), D12 (OC1B) and D13 (OC1C, also the on-board
+ * LED).  All three run at the same PWM frequency, because they share the timer, but each fades
+ * at its own pace.
+ */
+#![no_std]
+#![no_main]
+
+use arduino_hal::simple_pwm::*;
+use panic_halt as _;
+
+#[arduino_hal::entry]
+fn main() -> ! {
+	let dp = arduino_hal::Peripherals::take().unwrap();
+	let pins = arduino_hal::pins!(dp);
+
+	let timer1 = Timer1Pwm::new(dp.TC1, Prescaler::Prescale64);
+
+	let mut d11 = pins.d11.into_output().into_pwm(&timer1);
+	let mut d12 = pins.d12.into_output().into_pwm(&timer1);
+	let mut d13 = pins.d13.into_output().into_pwm(&timer1);
+	d11.enable();
+	d12.enable();
+	d13.enable();
+
+	let mut step: u8 = 0;
+	loop {
+		d11.set_duty(step);
+		d12.set_duty(step.wrapping_mul(2));
+		d13.set_duty(255 - step);
+
+		step = step.wrapping_add(1);
+		arduino_hal::delay_ms(10);
+	}
+}
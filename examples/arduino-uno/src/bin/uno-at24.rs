@@ -0,0 +1,80 @@
+/*!
+ * Keep a boot counter and settings in an external AT24C256 I2C EEPROM.
+ *
+ * The settings are stored at an address which is not page-aligned on purpose, so the write is
+ * split across two pages by the driver.  Press reset a few times and watch the counter go up.
+ *
+ * Connections
+ * -----------
+ *  - `A4`: I2C SDA signal
+ *  - `A5`: I2C SCL signal
+ *  - EEPROM `A0`-`A2` to GND (address 0x50)
+ */
+#![no_std]
+#![no_main]
+
+use arduino_hal::drivers::at24;
+use arduino_hal::prelude::*;
+use panic_halt as _;
+
+const SETTINGS_ADDRESS: u16 = 0x003c;
+
+struct Settings {
+	boot_count: u32,
+	brightness: u8,
+}
+
+impl Settings {
+	fn from_bytes(bytes: [u8; 5]) -> Self {
+		Self {
+			boot_count: u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+			brightness: bytes[4],
+		}
+	}
+
+	fn to_bytes(&self) -> [u8; 5] {
+		let c = self.boot_count.to_le_bytes();
+		[c[0], c[1], c[2], c[3], self.brightness]
+	}
+}
+
+#[arduino_hal::entry]
+fn main() -> ! {
+	let dp = arduino_hal::Peripherals::take().unwrap();
+	let pins = arduino_hal::pins!(dp);
+	let mut serial = arduino_hal::default_serial!(dp, pins, 57600);
+	let mut delay = arduino_hal::Delay::new();
+
+	let i2c = arduino_hal::I2c::new(
+		dp.TWI,
+		pins.a4.into_pull_up_input(),
+		pins.a5.into_pull_up_input(),
+		100000,
+	);
+	let mut eeprom = at24::At24::new(i2c, at24::DEFAULT_ADDRESS, at24::AT24C256);
+
+	let mut bytes = [0; 5];
+	eeprom.read(SETTINGS_ADDRESS, &mut bytes).unwrap();
+	let mut settings = Settings::from_bytes(bytes);
+	if settings.boot_count == u32::MAX {
+		// Erased EEPROM reads as 0xff
+		settings = Settings {
+			boot_count: 0,
+			brightness: 128,
+		};
+	}
+
+	settings.boot_count += 1;
+	eeprom.write(SETTINGS_ADDRESS, &settings.to_bytes(), &mut delay)
+		.unwrap();
+
+	ufmt::uwriteln!(
+		&mut serial,
+		"Boot #{}, brightness {}\r",
+		settings.boot_count,
+		settings.brightness
+	)
+	.unwrap_infallible();
+
+	loop {}
+}
@@ -0,0 +1,39 @@
+/*!
+ * Demonstrates the logging macros of `arduino-hal`.
+ *
+ * The serial port is registered as the log output once and afterwards `info!()` & co. can be
+ * called from anywhere.  Which levels are compiled in is selected through the `log-*` features of
+ * `arduino-hal`.  This example needs the `log-info` feature of the example crate, which enables
+ * `log-info` in `arduino-hal`, so the `debug!()` below produces no code:
+ *
+ *     cargo run --bin uno-log --features log-info
+ */
+#![no_std]
+#![no_main]
+
+use panic_halt as _;
+
+type Console = arduino_hal::hal::usart::Usart0<arduino_hal::DefaultClock>;
+static mut CONSOLE: Option<Console> = None;
+
+#[arduino_hal::entry]
+fn main() -> ! {
+	let dp = arduino_hal::Peripherals::take().unwrap();
+	let pins = arduino_hal::pins!(dp);
+	let serial = arduino_hal::default_serial!(dp, pins, 57600);
+
+	// SAFETY: This is the only place where `CONSOLE` is ever accessed.
+	let console = unsafe { (*core::ptr::addr_of_mut!(CONSOLE)).insert(serial) };
+	arduino_hal::log::set_output(console);
+
+	let mut x: u16 = 0;
+	loop {
+		arduino_hal::info!("x = {}", x);
+		arduino_hal::debug!("this is compiled out");
+		if x % 10 == 9 {
+			arduino_hal::warn!("x is about to reach a multiple of ten");
+		}
+		x = x.wrapping_add(1);
+		arduino_hal::delay_ms(500);
+	}
+}
@@ -0,0 +1,55 @@
+/*!
+ * Stream the tilt measured by an MPU6050 accelerometer/gyroscope over serial.
+ *
+ * While the board rests, the acceleration vector only contains gravity, so its direction shows
+ * how the sensor is tilted: lying flat, z reads about 1000 mg and x/y about 0 mg.
+ *
+ * Connections
+ * -----------
+ *  - `A4`: I2C SDA signal
+ *  - `A5`: I2C SCL signal
+ *  - MPU6050 `AD0` to GND (address 0x68)
+ */
+#![no_std]
+#![no_main]
+
+use arduino_hal::drivers::mpu6050;
+use arduino_hal::prelude::*;
+use panic_halt as _;
+
+#[arduino_hal::entry]
+fn main() -> ! {
+	let dp = arduino_hal::Peripherals::take().unwrap();
+	let pins = arduino_hal::pins!(dp);
+	let mut serial = arduino_hal::default_serial!(dp, pins, 57600);
+
+	let i2c = arduino_hal::I2c::new(
+		dp.TWI,
+		pins.a4.into_pull_up_input(),
+		pins.a5.into_pull_up_input(),
+		50000,
+	);
+
+	let mut imu = mpu6050::Mpu6050::new(i2c, mpu6050::DEFAULT_ADDRESS);
+	if let Err(e) = imu.init(&mut arduino_hal::Delay::new()) {
+		ufmt::uwriteln!(&mut serial, "MPU6050 init failed: {:?}\r", e).unwrap_infallible();
+		loop {}
+	}
+
+	loop {
+		let accel = imu.accel_mg().unwrap();
+		let gyro = imu.gyro_mdps().unwrap();
+		ufmt::uwriteln!(
+			&mut serial,
+			"tilt x: {} mg, y: {} mg, z: {} mg; rate x: {}, y: {}, z: {} mdps\r",
+			accel.x,
+			accel.y,
+			accel.z,
+			gyro.x,
+			gyro.y,
+			gyro.z,
+		)
+		.unwrap_infallible();
+		arduino_hal::delay_ms(200);
+	}
+}
@@ -0,0 +1,51 @@
+/*!
+ * Count resets in a variable which is not cleared by the startup code.
+ *
+ * Every five seconds, the firmware resets itself through the watchdog.  The reboot counter lives
+ * in the `.noinit` section, so it keeps counting across these resets.  Pressing the reset button
+ * keeps it as well, only unplugging the board (a power-on reset) starts it over at 0.
+ */
+#![no_std]
+#![no_main]
+
+use arduino_hal::hal::wdt;
+use arduino_hal::init::Init;
+use arduino_hal::prelude::*;
+use panic_halt as _;
+
+arduino_hal::noinit! {
+	static noinit REBOOTS: u16;
+}
+
+#[arduino_hal::entry]
+fn main() -> ! {
+	let dp = arduino_hal::Peripherals::take().unwrap();
+	// The watchdog is still running after the software reset, so disable it first thing.
+	let (dp, reset_cause) = Init::new().capture_reset_cause().disable_watchdog().run(dp);
+	let reset_cause = reset_cause.unwrap();
+	let pins = arduino_hal::pins!(dp);
+	let mut serial = arduino_hal::default_serial!(dp, pins, 57600);
+
+	// After power-on, the RAM holds random data.
+	if reset_cause.power_on {
+		REBOOTS.clear();
+	}
+	let reboots = REBOOTS.get().unwrap_or(0);
+	REBOOTS.set(reboots.wrapping_add(1));
+
+	ufmt::uwriteln!(
+		&mut serial,
+		"Reboot #{} (power-on: {}, watchdog: {})\r",
+		reboots,
+		reset_cause.power_on,
+		reset_cause.watchdog
+	)
+	.unwrap_infallible();
+
+	arduino_hal::delay_ms(5000);
+	ufmt::uwriteln!(&mut serial, "Resetting...\r").unwrap_infallible();
+	arduino_hal::delay_ms(10);
+
+	let mut watchdog = wdt::Wdt::new(dp.WDT, &dp.CPU.mcusr());
+	watchdog.reset();
+}
@@ -0,0 +1,38 @@
+/*!
+ * Output a 1 kHz square wave on pin D9 using Timer1, without any CPU involvement.
+ *
+ * Connect a scope or frequency counter to D9 to verify.  The achieved frequency is printed over
+ * serial; the wave keeps running while the CPU sits in the loop below.
+ */
+#![no_std]
+#![no_main]
+
+use arduino_hal::prelude::*;
+use arduino_hal::square_wave::Timer1SquareWave;
+use panic_halt as _;
+
+#[arduino_hal::entry]
+fn main() -> ! {
+	let dp = arduino_hal::Peripherals::take().unwrap();
+	let pins = arduino_hal::pins!(dp);
+	let mut serial = arduino_hal::default_serial!(dp, pins, 57600);
+
+	let wave = Timer1SquareWave::<arduino_hal::DefaultClock>::new(
+		dp.TC1,
+		pins.d9.into_output(),
+		1_000,
+	);
+
+	let f = wave.frequency();
+	ufmt::uwriteln!(
+		&mut serial,
+		"Generating {} Hz (error {} Hz) on D9\r",
+		f.achieved,
+		f.error
+	)
+	.unwrap_infallible();
+
+	loop {
+		arduino_hal::delay_ms(1000);
+	}
+}
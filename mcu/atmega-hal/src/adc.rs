@@ -25,9 +25,16 @@
 //! ```
 
 use crate::port;
+pub use avr_hal_generic::adc::trimmed_mean;
 pub use avr_hal_generic::adc::AdcChannel;
 pub use avr_hal_generic::adc::AdcOps;
+pub use avr_hal_generic::adc::AnalogInput;
 pub use avr_hal_generic::adc::ClockDivider;
+pub use avr_hal_generic::adc::DiffCoding;
+pub use avr_hal_generic::adc::SchmittInput;
+pub use avr_hal_generic::adc::TwoPointCalibration;
+pub use avr_hal_generic::adc::Window;
+pub use avr_hal_generic::adc::WindowEvent;
 
 /// Select the voltage reference for the ADC peripheral
 ///
@@ -83,6 +90,14 @@ pub type Adc<CLOCK> = avr_hal_generic::adc::Adc<crate::Atmega, crate::pac::ADC,
 /// Check the [`avr_hal_generic::adc::Channel`] documentation.
 pub type Channel = avr_hal_generic::adc::Channel<crate::Atmega, crate::pac::ADC>;
 
+/// Check the [`avr_hal_generic::adc::CalibratedDiffAdc`] documentation.
+pub type CalibratedDiffAdc =
+	avr_hal_generic::adc::CalibratedDiffAdc<crate::Atmega, crate::pac::ADC>;
+
+/// Check the [`avr_hal_generic::adc::AdcInput`] documentation.
+pub type AdcInput<'a, CLOCK> =
+	avr_hal_generic::adc::AdcInput<'a, crate::Atmega, crate::pac::ADC, CLOCK>;
+
 /// Additional channels
 ///
 /// Some channels are not directly connected to pins.  This module provides types which can be used
@@ -99,6 +114,7 @@ pub mod channel {
 	#[cfg(all(
 		any(
 			feature = "atmega168",
+			feature = "atmega168pb",
 			feature = "atmega32a",
 			feature = "atmega328p",
 			feature = "atmega328pb",
@@ -114,6 +130,7 @@ pub mod channel {
 	#[cfg(all(
 		any(
 			feature = "atmega168",
+			feature = "atmega168pb",
 			feature = "atmega32a",
 			feature = "atmega328p",
 			feature = "atmega328pb",
@@ -130,6 +147,7 @@ pub mod channel {
 		feature = "atmega1280",
 		feature = "atmega16",
 		feature = "atmega168",
+		feature = "atmega168pb",
 		feature = "atmega2560",
 		feature = "atmega32a",
 		feature = "atmega328p",
@@ -147,6 +165,7 @@ pub mod channel {
 	#[cfg(any(
 		feature = "atmega1280",
 		feature = "atmega168",
+		feature = "atmega168pb",
 		feature = "atmega2560",
 		feature = "atmega32a",
 		feature = "atmega328p",
@@ -162,6 +181,7 @@ pub mod channel {
 	))]
 	pub struct Gnd;
 	#[cfg(any(
+		feature = "atmega168pb",
 		feature = "atmega328p",
 		feature = "atmega328pb",
 		feature = "atmega32u4",
@@ -173,6 +193,7 @@ pub mod channel {
 
 #[cfg(any(
 	feature = "atmega168",
+	feature = "atmega168pb",
 	feature = "atmega328p",
 	feature = "atmega328pb",
 	feature = "atmega48p",
@@ -202,7 +223,12 @@ avr_hal_generic::impl_adc! {
 	channel::ADC7: crate::pac::adc::admux::MUX_A::ADC7,
 	channel::Vbg: crate::pac::adc::admux::MUX_A::ADC_VBG,
 	channel::Gnd: crate::pac::adc::admux::MUX_A::ADC_GND,
-	#[cfg(any(feature = "atmega328p", feature = "atmega328pb", feature = "atmega48p"))]
+	#[cfg(any(
+	    feature = "atmega168pb",
+	    feature = "atmega328p",
+	    feature = "atmega328pb",
+	    feature = "atmega48p"
+	))]
 	channel::Temperature: crate::pac::adc::admux::MUX_A::TEMPSENS,
     },
 }
@@ -407,3 +433,310 @@ avr_hal_generic::impl_adc! {
 	channel::Gnd: crate::pac::adc::admux::MUX_A::ADC_GND,
     },
 }
+
+/// The requested sample rate cannot be generated by `TC1`, see [`SampledBuffer::start()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateOutOfRange;
+
+/// Timer-triggered sampling of one or more ADC channels into a buffer.
+///
+/// For signal processing (filters, an FFT, ...), the samples have to be taken at a precise and
+/// uniform rate, which a loop calling [`read_blocking()`][Adc::read_blocking] can't guarantee.
+/// `SampledBuffer` runs `TC1` in CTC mode at the sample rate and lets its compare match B event
+/// start every conversion through the ADC's auto trigger, so the timing is exact and doesn't
+/// depend on interrupt latency.  The `ADC` interrupt handler only stores the results and has to
+/// call [`on_conversion()`][SampledBuffer::on_conversion]:
+///
+/// ```ignore
+/// static SAMPLER: Mutex<RefCell<Option<SampledBuffer<CoreClock, 1>>>> =
+///     Mutex::new(RefCell::new(None));
+/// static mut SAMPLES: [u16; 128] = [0; 128];
+///
+/// #[avr_device::interrupt(atmega328p)]
+/// fn ADC() {
+///     avr_device::interrupt::free(|cs| {
+///         if let Some(sampler) = SAMPLER.borrow(cs).borrow_mut().as_mut() {
+///             sampler.on_conversion();
+///         }
+///     });
+/// }
+///
+/// let a0 = pins.pc0.into_analog_input(&mut adc).into_channel();
+/// let samples = unsafe { &mut *core::ptr::addr_of_mut!(SAMPLES) };
+/// let sampler = SampledBuffer::start(adc, dp.TC1, [a0], 8000, samples).unwrap();
+/// avr_device::interrupt::free(|cs| SAMPLER.borrow(cs).replace(Some(sampler)));
+/// unsafe { avr_device::interrupt::enable() };
+///
+/// // Wait for the buffer to fill, then process it
+/// while !avr_device::interrupt::free(|cs| {
+///     SAMPLER.borrow(cs).borrow().as_ref().unwrap().is_full()
+/// }) {}
+/// ```
+///
+/// With several channels, they are sampled in turn: `buffer[i]` holds a sample of
+/// `channels[i % CHANNELS]`.  The channels are not sampled at the same instant but one or more
+/// sample periods apart.
+///
+/// # Discarded conversions
+/// The first conversions after sampling started and after every channel switch are inaccurate
+/// and dropped, as many as set with
+/// [`set_discard_after_switch()`][avr_hal_generic::adc::Adc::set_discard_after_switch] (one by
+/// default).  Each of them takes one timer period.  With a single channel,
+/// this only delays the first sample.  With several channels, each sample takes
+/// `1 + discard` periods, so each channel is sampled at `rate_hz / (CHANNELS * (1 + discard))`;
+/// set the discard count to 0 if the sources are low-impedance and the full rate is needed.
+///
+/// # Maximum sample rate
+/// An auto-triggered conversion takes 13.5 ADC clock cycles, and the ADC clock should stay
+/// between 50 kHz and 200 kHz for the full 10-bit resolution.  At 16 MHz, this means
+/// `ClockDivider::Factor128` (125 kHz) and at most about 9 kHz, or `ClockDivider::Factor64`
+/// (250 kHz) and about 18 kHz with slightly reduced accuracy.  Faster ADC clocks of up to 1 MHz
+/// work with roughly 8 bits of resolution.  Beyond that, the interrupt handler has to finish
+/// before the next conversion ends; it takes about 100 cycles including the `Mutex`, so at
+/// 16 MHz the CPU is fully busy at about 150 kHz, and anything over 20 kHz leaves little time
+/// for the main program.  A conversion which is triggered while the previous one is still
+/// running is skipped, so a too high rate silently drops samples.
+///
+/// The `TC1` compare match B interrupt flag is the trigger source, so the `TIMER1_COMPB`
+/// interrupt must stay disabled.  The `ADC` vector is
+/// [claimed](avr_hal_generic::interrupt#ownership) from `start()` until `finish()`.
+///
+/// Not available on the ATmega8, ATmega16, ATmega32A, and ATmega128A, whose ADC has no (or a
+/// differently configured) auto trigger.
+#[cfg(not(any(
+	feature = "atmega8",
+	feature = "atmega16",
+	feature = "atmega32a",
+	feature = "atmega128a"
+)))]
+pub struct SampledBuffer<CLOCK, const CHANNELS: usize> {
+	adc: Adc<CLOCK>,
+	tc1: crate::pac::TC1,
+	channels: [Channel; CHANNELS],
+	buffer: &'static mut [u16],
+	position: usize,
+	rate_hz: u32,
+	clock_select: u8,
+	discard: u8,
+	pending_discard: u8,
+	_claim: avr_hal_generic::interrupt::InterruptClaim,
+}
+
+#[cfg(not(any(
+	feature = "atmega8",
+	feature = "atmega16",
+	feature = "atmega32a",
+	feature = "atmega128a"
+)))]
+impl<CLOCK: avr_hal_generic::clock::Clock, const CHANNELS: usize> SampledBuffer<CLOCK, CHANNELS> {
+	/// Start sampling `channels` in turn at `rate_hz` samples per second, until `buffer` is full.
+	///
+	/// The sample rate is rounded to the nearest one `TC1` can generate, see
+	/// [`rate_hz()`][Self::rate_hz].  Fails if it is 0 or above the timer's clock.
+	///
+	/// # Panics
+	/// Panics if `CHANNELS` is 0, or if another feature claimed the `ADC` vector already.
+	pub fn start(
+		adc: Adc<CLOCK>,
+		tc1: crate::pac::TC1,
+		channels: [Channel; CHANNELS],
+		rate_hz: u32,
+		buffer: &'static mut [u16],
+	) -> Result<Self, RateOutOfRange> {
+		assert!(CHANNELS > 0);
+		let (clock_select, top, rate_hz) =
+			sample_timer(CLOCK::FREQ, rate_hz).ok_or(RateOutOfRange)?;
+		let claim = avr_hal_generic::interrupt::claim_or_panic(
+			crate::pac::Interrupt::ADC as u8,
+			"SampledBuffer",
+		);
+
+		tc1.tccr1b().reset();
+		tc1.tccr1a().reset();
+		tc1.tcnt1().reset();
+		tc1.ocr1a().write(|w| w.set(top));
+		tc1.ocr1b().write(|w| w.set(top));
+		tc1.timsk1().modify(|_, w| w.ocie1b().clear_bit());
+
+		let discard = adc.discard_after_switch();
+		let mut sampler = Self {
+			adc,
+			tc1,
+			channels,
+			buffer,
+			position: 0,
+			rate_hz,
+			clock_select,
+			discard,
+			pending_discard: discard,
+			_claim: claim,
+		};
+		sampler.restart();
+		Ok(sampler)
+	}
+
+	/// Store the result of a finished conversion.  Call this from the `ADC` interrupt handler.
+	///
+	/// Returns `true` once the buffer is full; sampling stops then.
+	pub fn on_conversion(&mut self) -> bool {
+		// SAFETY: The ADC is owned by `self.adc`, which is not used while sampling.
+		let mut adc = unsafe { crate::pac::ADC::steal() };
+		// Re-arm the trigger: the ADC only starts a conversion on a rising edge of OCF1B.
+		self.tc1.tifr1().write(|w| w.ocf1b().set_bit());
+
+		let reading = adc.raw_read_adc();
+		if self.pending_discard > 0 {
+			self.pending_discard -= 1;
+			return false;
+		}
+		if let Some(sample) = self.buffer.get_mut(self.position) {
+			*sample = reading;
+			self.position += 1;
+		}
+		if self.is_full() {
+			self.stop(&adc);
+			return true;
+		}
+		if CHANNELS > 1 {
+			// The multiplexer is latched when a conversion starts, which is the next timer
+			// event.
+			adc.raw_set_channel(self.channels[self.position % CHANNELS].channel());
+			self.pending_discard = self.discard;
+		}
+		false
+	}
+
+	/// Whether the buffer is full.
+	pub fn is_full(&self) -> bool {
+		self.position >= self.buffer.len()
+	}
+
+	/// The actual sample rate in Hz, after rounding to the timer resolution.
+	pub fn rate_hz(&self) -> u32 {
+		self.rate_hz
+	}
+
+	/// The samples taken so far.
+	pub fn samples(&self) -> &[u16] {
+		&self.buffer[..self.position]
+	}
+
+	/// Start over at the beginning of the buffer, e.g. after processing a full one.
+	pub fn restart(&mut self) {
+		// SAFETY: See on_conversion().
+		let mut adc = unsafe { crate::pac::ADC::steal() };
+		self.position = 0;
+		self.pending_discard = self.discard;
+		self.tc1.tccr1b().reset();
+		self.tc1.tcnt1().reset();
+		self.tc1.tifr1().write(|w| w.ocf1b().set_bit());
+
+		adc.raw_set_channel(self.channels[0].channel());
+		// Trigger source 0b0101 is Timer/Counter1 Compare Match B on all supported devices.
+		// The enum variant names differ, so the bits are written directly, keeping the other
+		// bits (e.g. `MUX5`) as they are.
+		adc.adcsrb()
+			.modify(|r, w| unsafe { w.bits((r.bits() & !ADTS_MASK) | 0x05) });
+		adc.adcsra()
+			.modify(|_, w| w.adif().set_bit().adate().set_bit().adie().set_bit());
+
+		// CTC mode with OCR1A as TOP (WGM12), started with the selected clock.  The CS1
+		// variant names differ between devices, but the values are the same.
+		self.tc1.tccr1b()
+			.write(|w| unsafe { w.bits((1 << 3) | self.clock_select) });
+	}
+
+	/// Stop sampling and give back the ADC, the timer, the channels, and the buffer.
+	pub fn finish(
+		self,
+	) -> (
+		Adc<CLOCK>,
+		crate::pac::TC1,
+		[Channel; CHANNELS],
+		&'static mut [u16],
+	) {
+		// SAFETY: See on_conversion().
+		self.stop(&unsafe { crate::pac::ADC::steal() });
+		let mut adc = self.adc;
+		// The multiplexer was switched behind the Adc's back.
+		adc.forget_selected_channel();
+		(adc, self.tc1, self.channels, self.buffer)
+	}
+
+	fn stop(&self, adc: &crate::pac::ADC) {
+		self.tc1.tccr1b().reset();
+		adc.adcsra()
+			.modify(|_, w| w.adate().clear_bit().adie().clear_bit());
+	}
+}
+
+/// The auto trigger source bits (`ADTS`) in `ADCSRB`.  On the ATmega1280/2560, bit 3 is `MUX5`.
+#[cfg(not(any(
+	feature = "atmega8",
+	feature = "atmega16",
+	feature = "atmega32a",
+	feature = "atmega128a",
+	feature = "atmega32u4"
+)))]
+const ADTS_MASK: u8 = 0x07;
+#[cfg(feature = "atmega32u4")]
+const ADTS_MASK: u8 = 0x0f;
+
+/// Prescaler selection, compare value and actual rate for running `TC1` at `rate_hz` in CTC mode.
+#[cfg(not(any(
+	feature = "atmega8",
+	feature = "atmega16",
+	feature = "atmega32a",
+	feature = "atmega128a"
+)))]
+fn sample_timer(clock_hz: u32, rate_hz: u32) -> Option<(u8, u16, u32)> {
+	if rate_hz == 0 || rate_hz > clock_hz {
+		return None;
+	}
+	[(1u8, 1u32), (2, 8), (3, 64), (4, 256), (5, 1024)]
+		.iter()
+		.find_map(|&(cs, prescaler)| {
+			let timer_hz = clock_hz / prescaler;
+			let ticks = (timer_hz + rate_hz / 2) / rate_hz;
+			if (1..=0x1_0000).contains(&ticks) {
+				Some((cs, (ticks - 1) as u16, (timer_hz + ticks / 2) / ticks))
+			} else {
+				None
+			}
+		})
+}
+
+#[cfg(test)]
+mod tests {
+	#[allow(unused_imports)]
+	use super::*;
+
+	#[cfg(not(any(
+		feature = "atmega8",
+		feature = "atmega16",
+		feature = "atmega32a",
+		feature = "atmega128a"
+	)))]
+	#[test]
+	fn sample_timer_rates() {
+		// No prescaler needed
+		assert_eq!(sample_timer(16_000_000, 8000), Some((1, 1999, 8000)));
+		// 64 is the first prescaler which fits 16 bits
+		assert_eq!(sample_timer(16_000_000, 10), Some((3, 24999, 10)));
+		assert_eq!(sample_timer(16_000_000, 1), Some((4, 62499, 1)));
+		// Rounded to the nearest possible rate
+		assert_eq!(sample_timer(16_000_000, 7000), Some((1, 2285, 6999)));
+		assert_eq!(
+			sample_timer(16_000_000, 16_000_000),
+			Some((1, 0, 16_000_000))
+		);
+		assert_eq!(sample_timer(16_000_000, 0), None);
+		assert_eq!(sample_timer(16_000_000, 16_000_001), None);
+	}
+
+	#[cfg(any(feature = "atmega1280", feature = "atmega2560"))]
+	#[test]
+	fn trigger_source_keeps_mux5() {
+		assert_eq!(ADTS_MASK & (1 << 3), 0);
+	}
+}
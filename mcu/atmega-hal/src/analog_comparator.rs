@@ -0,0 +1,180 @@
+//! Analog comparator
+//!
+//! The analog comparator compares the voltages on `AIN0` (positive input) and `AIN1` (negative
+//! input).  Its output is high while `AIN0` is above `AIN1`.
+//!
+//! # Input capture from the comparator
+//! On most ATmegas, the comparator output can be routed to the input capture unit of `TC1` by
+//! setting `ACIC` in `ACSR`.  Every change of the comparator output then latches the free-running
+//! timer value into `ICR1` in hardware, so the period between two crossings is measured with
+//! timer resolution and without any interrupt latency.  [`ComparatorCapture`] sets this up and
+//! reports the period between consecutive captures.
+//!
+//! A typical application is measuring the mains frequency from its zero crossings:
+//!
+//! ```text
+//!                      VCC                 VCC
+//!                       |                   |
+//!                     [10k]               [10k]
+//!  AC ----[100k]--------+----> AIN0         +----> AIN1
+//!                       |                   |
+//!                     [10k]               [10k]
+//!                       |                   |
+//!                      GND                 GND
+//! ```
+//!
+//! `AC` is a low voltage AC signal, e.g. from the secondary of a 9 V transformer.  It is
+//! attenuated and biased around `VCC / 2`, and `AIN1` sits at exactly `VCC / 2`, so the
+//! comparator output toggles at each zero crossing.  **Never connect mains voltage directly**,
+//! always use an isolating transformer.  The comparator has no hysteresis, so noise around the
+//! crossing can cause extra toggles: the input capture noise canceler is always enabled (it
+//! requires 4 equal samples), and a small capacitor from `AIN0` to ground helps further.
+//!
+//! With [`Prescaler::Prescale8`] at 16 MHz, the timer ticks at 2 MHz, so a 50 Hz period is 40000
+//! ticks and a 60 Hz period 33333 ticks.  The timer is 16 bits wide, so periods longer than 65535
+//! ticks (about 30.5 Hz with this prescaler) wrap around and must be avoided by choosing a larger
+//! prescaler.
+//!
+//! On the ATmega328P, `AIN0` is `PD6` (Arduino `D6`) and `AIN1` is `PD7` (Arduino `D7`).  Leave
+//! these pins configured as floating inputs.
+//!
+//! # Example
+//! ```
+//! let dp = atmega_hal::Peripherals::take().unwrap();
+//!
+//! let mut mains =
+//!     ComparatorCapture::<CoreClock>::new(dp.AC, dp.TC1, Prescaler::Prescale8, Edge::Rising);
+//!
+//! loop {
+//!     if let Some(period) = mains.poll() {
+//!         let millihertz = mains.frequency_millihertz(period);
+//!         // millihertz == 50_000 for 50 Hz mains
+//!     }
+//! }
+//! ```
+
+pub use avr_hal_generic::counter::Edge;
+pub use avr_hal_generic::simple_pwm::Prescaler;
+
+/// Measure the period of the analog comparator output with the input capture unit of `TC1`.
+#[cfg(any(
+	feature = "atmega48p",
+	feature = "atmega88p",
+	feature = "atmega164pa",
+	feature = "atmega168",
+	feature = "atmega168pb",
+	feature = "atmega328p",
+	feature = "atmega328pb",
+	feature = "atmega32u4",
+	feature = "atmega1280",
+	feature = "atmega1284p",
+	feature = "atmega2560"
+))]
+pub struct ComparatorCapture<CLOCK> {
+	ac: crate::pac::AC,
+	timer: crate::pac::TC1,
+	prescaler: Prescaler,
+	last: Option<u16>,
+	_clock: core::marker::PhantomData<CLOCK>,
+}
+
+#[cfg(any(
+	feature = "atmega48p",
+	feature = "atmega88p",
+	feature = "atmega164pa",
+	feature = "atmega168",
+	feature = "atmega168pb",
+	feature = "atmega328p",
+	feature = "atmega328pb",
+	feature = "atmega32u4",
+	feature = "atmega1280",
+	feature = "atmega1284p",
+	feature = "atmega2560"
+))]
+impl<CLOCK: avr_hal_generic::clock::Clock> ComparatorCapture<CLOCK> {
+	/// Enable the comparator and start `TC1` as a free-running timer capturing its output.
+	///
+	/// `edge` selects which transition of the comparator output is captured: [`Edge::Rising`]
+	/// when `AIN0` rises above `AIN1`, [`Edge::Falling`] when it drops below.
+	pub fn new(
+		ac: crate::pac::AC,
+		timer: crate::pac::TC1,
+		prescaler: Prescaler,
+		edge: Edge,
+	) -> Self {
+		// Comparator enabled, no interrupt, output routed to the input capture unit
+		ac.acsr().write(|w| w.acic().set_bit());
+
+		timer.tccr1a().reset();
+		timer.tcnt1().reset();
+		timer.tccr1b().write(|w| {
+			// The CS1 variant names differ between devices, but the values are the same.
+			let cs = match prescaler {
+				Prescaler::Direct => 0b001,
+				Prescaler::Prescale8 => 0b010,
+				Prescaler::Prescale64 => 0b011,
+				Prescaler::Prescale256 => 0b100,
+				Prescaler::Prescale1024 => 0b101,
+			};
+			w.icnc1()
+				.set_bit()
+				.ices1()
+				.bit(edge == Edge::Rising)
+				.cs1()
+				.set(cs)
+		});
+		timer.tifr1().write(|w| w.icf1().set_bit());
+
+		Self {
+			ac,
+			timer,
+			prescaler,
+			last: None,
+			_clock: core::marker::PhantomData,
+		}
+	}
+
+	/// Check for a new capture and return the period since the previous one, in timer ticks.
+	///
+	/// Returns `None` if nothing was captured since the last call, and for the very first capture
+	/// (which has no predecessor).  This must be called at least once per period, otherwise
+	/// captures are lost and the next period is measured from an older one.
+	pub fn poll(&mut self) -> Option<u16> {
+		if self.timer.tifr1().read().icf1().bit_is_clear() {
+			return None;
+		}
+		let captured = self.timer.icr1().read().bits();
+		self.timer.tifr1().write(|w| w.icf1().set_bit());
+
+		let period = self.last.map(|last| captured.wrapping_sub(last));
+		self.last = Some(captured);
+		period
+	}
+
+	/// Forget the previous capture, e.g. after not polling for a while.
+	pub fn reset(&mut self) {
+		self.last = None;
+	}
+
+	/// Frequency of the timer ticks in Hz.
+	pub fn tick_hz(&self) -> u32 {
+		CLOCK::FREQ / self.prescaler.divider()
+	}
+
+	/// Convert a period in timer ticks to microseconds.
+	pub fn period_us(&self, period: u16) -> u32 {
+		(u64::from(period) * 1_000_000 / u64::from(self.tick_hz())) as u32
+	}
+
+	/// Convert a period in timer ticks to a frequency in millihertz, e.g. `50_000` for 50 Hz.
+	pub fn frequency_millihertz(&self, period: u16) -> u32 {
+		(u64::from(self.tick_hz()) * 1000 / u64::from(period.max(1))) as u32
+	}
+
+	/// Stop the timer, disconnect the comparator from it, and return both peripherals.
+	pub fn release(self) -> (crate::pac::AC, crate::pac::TC1) {
+		self.timer.tccr1b().reset();
+		self.ac.acsr().reset();
+		(self.ac, self.timer)
+	}
+}
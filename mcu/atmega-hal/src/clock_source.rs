@@ -0,0 +1,541 @@
+//! System clock source
+//!
+//! On classic ATmega devices, the clock source is selected by the `CKSEL` fuses when the MCU is
+//! programmed and cannot be changed by the running application.  [`current()`] reads the fuses
+//! to report which source is in use, e.g. to detect a board which was flashed with the wrong
+//! fuse settings.
+//!
+//! These devices don't have a clock failure detector either: if the selected crystal fails to
+//! start, the MCU simply does not run.  The only exception is the ATmega32U4, which can switch
+//! between the internal RC oscillator and the external crystal or clock at runtime, see
+//! [`switch_to_rc()`] and [`switch_to_external()`].
+//!
+//! # Switching while peripherals are active
+//! All drivers compute their timing from the `CLOCK` type parameter, which is fixed at compile
+//! time.  After switching to a source with a different frequency, baudrates, delays, PWM
+//! frequencies, and the like are off by the ratio of the two frequencies.  Switching is safe in
+//! the sense that no peripheral state is lost, but drivers need to be reinitialized with a
+//! matching `CLOCK` to work correctly again.  USB needs the external crystal (for the PLL) and
+//! stops working on the RC oscillator.
+//!
+//! # Oscillator start-up after sleep
+//! In the power-down and power-save sleep modes, the main oscillator is stopped.  On wake-up,
+//! the hardware restarts it and holds the CPU for the start-up time selected by the `SUT` and
+//! `CKSEL` fuses before executing the first instruction.  In all other sleep modes (idle, ADC
+//! noise reduction, standby, extended standby), the oscillator keeps running and there is no
+//! start-up delay at all.
+//!
+//! The fuse setting is a trade-off between wake-up latency and stability: the short settings
+//! meant for ceramic resonators (258 or 1K cycles) are not enough for a quartz crystal to reach
+//! a stable amplitude and frequency, and code running right after wake-up then sees a wrong
+//! baudrate or timer rate for a few milliseconds.  Instead of reflashing the fuses,
+//! [`stabilize_after_wake()`] can be called right after waking up from power-down or power-save
+//! to add the missing time at runtime.  It is not needed after the other sleep modes.
+//!
+//! # Detecting the clock frequency
+//! The fuses tell the clock source, but not its frequency: a board with an 8 MHz crystal runs the
+//! same code as one with a 16 MHz crystal, only with all delays and baudrates off by a factor of
+//! two.  [`detect()`] measures the actual frequency at runtime by counting system clock cycles
+//! (with `TC1`) during a period of the watchdog timer, which runs from its own 128 kHz
+//! oscillator:
+//!
+//! ```ignore
+//! let estimate = clock_source::detect(&mut dp.WDT, &mut dp.TC1);
+//! if !estimate.matches::<arduino_hal::DefaultClock>() {
+//!     // Built for the wrong clock, e.g. blink an error code
+//! }
+//! ```
+//!
+//! The watchdog oscillator is not calibrated.  Its frequency depends on supply voltage and
+//! temperature and typically deviates by up to 10% from the nominal value, in extreme conditions
+//! more than 20%.  This is good enough to tell 8 MHz from 16 MHz, or 1 MHz from 8 MHz, but not
+//! 16 MHz from 20 MHz reliably, and nowhere near accurate enough to calibrate anything.
+//!
+//! # RC oscillator calibration over temperature
+//! The calibrated internal RC oscillator is tuned through the `OSCCAL` register.  The hardware
+//! loads a factory calibration value into it on every reset, but that value only holds at the
+//! conditions it was measured at.  Which values the supported devices store:
+//!
+//! | Device | Factory calibration values |
+//! | --- | --- |
+//! | ATmega8, ATmega16, ATmega32A, ATmega128A | one per frequency (1, 2, 4 and 8 MHz) at 25 °C and 5 V; only the 1 MHz one is loaded at reset, the others can only be read by a programmer |
+//! | all others | one, for 8 MHz at 25 °C and 3 V |
+//!
+//! None of them stores values for different temperatures (only some newer devices like the
+//! ATtiny441/841 have temperature compensation registers), so temperature compensation has to be
+//! characterized per board: measure the `OSCCAL` value giving the right frequency at a few
+//! temperatures, e.g. by tuning until a known baudrate or an external reference (a 32.768 kHz
+//! crystal, a GPS 1 PPS signal) measures correctly, and put them into a [`TemperatureTrim`].  At
+//! runtime, it picks the value for the current temperature, e.g. from the ADC's temperature
+//! sensor:
+//!
+//! ```ignore
+//! // OSCCAL values measured for this board at -20 °C, 25 °C and 70 °C
+//! const TRIM: TemperatureTrim<3> = TemperatureTrim::new([(-20, 0x9a), (25, 0x94), (70, 0x8d)]);
+//!
+//! let celsius = read_temperature(&mut adc);
+//! TRIM.apply(&dp.CPU, celsius);
+//! ```
+//!
+//! The factory calibration is only specified to ±10% (±1% after calibration at a fixed
+//! temperature), and the frequency drifts by a few percent more between -40 °C and 85 °C.  A
+//! USART needs the clocks of both sides to match within about 2%, so without compensation,
+//! crystal-less serial communication fails at the temperature extremes.  With a trim table of
+//! three to five points, the error stays within about ±1% over the whole range.  Call
+//! [`TemperatureTrim::apply()`] whenever the temperature changed by a few degrees, while no
+//! USART transfer is in progress.
+
+/// Clock source selected by the `CKSEL` fuses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockSource {
+	/// External clock signal on `XTAL1`.
+	ExternalClock,
+	/// Calibrated internal RC oscillator.
+	CalibratedRc,
+	/// Internal 128 kHz RC oscillator.
+	LowFrequencyRc,
+	/// External RC oscillator.
+	ExternalRc,
+	/// Low frequency (32.768 kHz) watch crystal.
+	LowFrequencyCrystal,
+	/// Full swing crystal oscillator.
+	FullSwingCrystal,
+	/// Low power crystal oscillator.
+	LowPowerCrystal,
+	/// Crystal or ceramic resonator.
+	Crystal,
+	/// Reserved `CKSEL` value.
+	Reserved(u8),
+}
+
+impl ClockSource {
+	/// Decode the `CKSEL3:0` fuse bits.
+	#[cfg(any(
+		feature = "atmega8",
+		feature = "atmega16",
+		feature = "atmega32a",
+		feature = "atmega128a"
+	))]
+	pub fn from_cksel(cksel: u8) -> Self {
+		match cksel & 0x0f {
+			0b0000 => ClockSource::ExternalClock,
+			0b0001..=0b0100 => ClockSource::CalibratedRc,
+			0b0101..=0b1000 => ClockSource::ExternalRc,
+			0b1001 => ClockSource::LowFrequencyCrystal,
+			_ => ClockSource::Crystal,
+		}
+	}
+
+	/// Decode the `CKSEL3:0` fuse bits.
+	#[cfg(feature = "atmega32u4")]
+	pub fn from_cksel(cksel: u8) -> Self {
+		match cksel & 0x0f {
+			0b0000 => ClockSource::ExternalClock,
+			0b0010 => ClockSource::CalibratedRc,
+			0b1000..=0b1111 => ClockSource::LowPowerCrystal,
+			other => ClockSource::Reserved(other),
+		}
+	}
+
+	/// Decode the `CKSEL3:0` fuse bits.
+	#[cfg(not(any(
+		feature = "atmega8",
+		feature = "atmega16",
+		feature = "atmega32a",
+		feature = "atmega128a",
+		feature = "atmega32u4"
+	)))]
+	pub fn from_cksel(cksel: u8) -> Self {
+		match cksel & 0x0f {
+			0b0000 => ClockSource::ExternalClock,
+			0b0010 => ClockSource::CalibratedRc,
+			0b0011 => ClockSource::LowFrequencyRc,
+			0b0100 | 0b0101 => ClockSource::LowFrequencyCrystal,
+			0b0110 | 0b0111 => ClockSource::FullSwingCrystal,
+			0b1000..=0b1111 => ClockSource::LowPowerCrystal,
+			other => ClockSource::Reserved(other),
+		}
+	}
+}
+
+impl ClockSource {
+	/// Number of oscillator cycles the source needs after starting until it runs stably.
+	///
+	/// These are the datasheet recommendations for the longest start-up time of each source.  For
+	/// [`ClockSource::LowPowerCrystal`] and [`ClockSource::Crystal`], which can also be used with
+	/// ceramic resonators, the value for a quartz crystal is returned.
+	pub fn startup_cycles(self) -> u32 {
+		match self {
+			ClockSource::ExternalClock
+			| ClockSource::CalibratedRc
+			| ClockSource::LowFrequencyRc => 6,
+			ClockSource::ExternalRc => 18,
+			ClockSource::LowFrequencyCrystal => 32 * 1024,
+			ClockSource::FullSwingCrystal
+			| ClockSource::LowPowerCrystal
+			| ClockSource::Crystal
+			| ClockSource::Reserved(_) => 16 * 1024,
+		}
+	}
+}
+
+/// Busy-wait until the clock source has stabilized after waking up from power-down or
+/// power-save sleep.
+///
+/// Waits [`ClockSource::startup_cycles()`] of the [current] source, regardless of the start-up
+/// time the hardware already waited, so this errs on the long side: about 1 ms for a 16 MHz
+/// crystal, 1 s for a 32.768 kHz watch crystal, and nothing worth mentioning for the RC
+/// oscillators.  See the [module documentation](self).
+///
+/// [current]: current()
+pub fn stabilize_after_wake() {
+	avr_device::asm::delay_cycles(current().startup_cycles());
+}
+
+/// Report the clock source the MCU is currently running from.
+///
+/// On the ATmega32U4, this takes runtime switching into account.
+pub fn current() -> ClockSource {
+	#[cfg(feature = "atmega32u4")]
+	{
+		// SAFETY: Only reading a register which is not modified elsewhere in this crate.
+		let cpu = unsafe { &*crate::pac::CPU::ptr() };
+		if cpu.clksel0().read().clks().bit_is_clear() {
+			return ClockSource::CalibratedRc;
+		}
+	}
+	ClockSource::from_cksel(crate::fuse::low())
+}
+
+/// Frequency of the system clock, as measured by [`detect()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockEstimate {
+	/// Measured frequency in Hz.  See the [module documentation](self) for its accuracy.
+	pub measured_hz: u32,
+}
+
+impl ClockEstimate {
+	/// Whether the measured frequency is within 25% of `CLOCK`, the frequency the code was built
+	/// for.
+	pub fn matches<CLOCK: crate::clock::Clock>(&self) -> bool {
+		let expected = u64::from(CLOCK::FREQ);
+		let measured = u64::from(self.measured_hz);
+		measured * 4 >= expected * 3 && measured * 4 <= expected * 5
+	}
+
+	/// The common crystal or RC frequency (1, 2, 4, 8, 12, 16 or 20 MHz) closest to the measured
+	/// one.
+	pub fn nearest_common_hz(&self) -> u32 {
+		const COMMON: [u32; 7] = [
+			1_000_000, 2_000_000, 4_000_000, 8_000_000, 12_000_000, 16_000_000,
+			20_000_000,
+		];
+		COMMON.iter()
+			.copied()
+			.min_by_key(|&f| f.abs_diff(self.measured_hz))
+			.unwrap_or(self.measured_hz)
+	}
+
+	/// Estimate from the `TC1` count at clk/256 during one 64 ms watchdog period (8K cycles of the
+	/// 128 kHz watchdog oscillator).
+	#[cfg(not(any(
+		feature = "atmega8",
+		feature = "atmega16",
+		feature = "atmega32a",
+		feature = "atmega128a"
+	)))]
+	fn from_ticks(ticks: u16) -> Self {
+		const PERIOD_US: u64 = 64_000;
+		const PRESCALER: u64 = 256;
+
+		Self {
+			measured_hz: (u64::from(ticks) * PRESCALER * 1_000_000 / PERIOD_US) as u32,
+		}
+	}
+}
+
+/// Measure the system clock frequency against the watchdog oscillator.
+///
+/// Takes about 130 ms with interrupts disabled.  `TC1` and the watchdog are reset afterwards, so
+/// call this before setting them up for other purposes.  This does not work if the `WDTON` fuse
+/// is programmed, as the watchdog then resets the MCU instead.  See the
+/// [module documentation](self).
+#[cfg(not(any(
+	feature = "atmega8",
+	feature = "atmega16",
+	feature = "atmega32a",
+	feature = "atmega128a"
+)))]
+pub fn detect(wdt: &mut crate::pac::WDT, tc1: &mut crate::pac::TC1) -> ClockEstimate {
+	let ticks = avr_device::interrupt::free(|_| {
+		// Interrupt mode sets WDIF at the end of every period, which is polled here with
+		// interrupts disabled.
+		avr_device::asm::wdr();
+		wdt.wdtcsr()
+			.modify(|_, w| w.wdce().set_bit().wde().set_bit());
+		wdt.wdtcsr()
+			.write(|w| w.wdpl().cycles_8k().wdie().set_bit());
+
+		// The first period is used to synchronize with the watchdog.
+		while wdt.wdtcsr().read().wdif().bit_is_clear() {}
+		wdt.wdtcsr().modify(|_, w| w.wdif().set_bit());
+		tc1.tccr1a().reset();
+		tc1.tcnt1().reset();
+		// CS1 = 0b100: normal mode, clk/256.  The variant names differ between devices.
+		tc1.tccr1b().write(|w| unsafe { w.bits(0b100) });
+
+		while wdt.wdtcsr().read().wdif().bit_is_clear() {}
+		let ticks = tc1.tcnt1().read().bits();
+
+		tc1.tccr1b().reset();
+		tc1.tcnt1().reset();
+		avr_device::asm::wdr();
+		wdt.wdtcsr()
+			.modify(|_, w| w.wdif().set_bit().wdce().set_bit().wde().set_bit());
+		wdt.wdtcsr().reset();
+		ticks
+	});
+
+	ClockEstimate::from_ticks(ticks)
+}
+
+/// Error returned by [`switch_to_external()`] when the external oscillator did not start.
+#[cfg(feature = "atmega32u4")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OscillatorNotRunning;
+
+/// Switch the system clock to the calibrated internal RC oscillator.
+///
+/// The external oscillator keeps running, so switching back is quick.  See the [module
+/// documentation](self) for the consequences of switching.
+#[cfg(feature = "atmega32u4")]
+pub fn switch_to_rc(cpu: &crate::pac::CPU) {
+	cpu.clksel0().modify(|_, w| w.rce().set_bit());
+	while cpu.clksta().read().rcon().bit_is_clear() {}
+	cpu.clksel0().modify(|_, w| w.clks().clear_bit());
+}
+
+/// Switch the system clock to the external crystal or clock selected by the `CKSEL` fuses.
+///
+/// Gives up and stays on the current source if the external oscillator does not report to be
+/// running after about 100k polls, e.g. because the crystal is broken.  See the [module
+/// documentation](self) for the consequences of switching.
+#[cfg(feature = "atmega32u4")]
+pub fn switch_to_external(cpu: &crate::pac::CPU) -> Result<(), OscillatorNotRunning> {
+	cpu.clksel0().modify(|_, w| w.exte().set_bit());
+	let mut polls: u32 = 100_000;
+	while cpu.clksta().read().exton().bit_is_clear() {
+		polls = polls.checked_sub(1).ok_or(OscillatorNotRunning)?;
+	}
+	cpu.clksel0().modify(|_, w| w.clks().set_bit());
+	Ok(())
+}
+
+/// The current value of the `OSCCAL` register.
+///
+/// Right after reset, this is the factory calibration value.
+pub fn osccal(cpu: &crate::pac::CPU) -> u8 {
+	cpu.osccal().read().bits()
+}
+
+/// Change `OSCCAL` to `value`.
+///
+/// The frequency must not change by more than 2% from one cycle to the next, so the register is
+/// moved towards `value` one step at a time.
+///
+/// # Panics
+/// Except on the ATmega8, ATmega16, ATmega32A, and ATmega128A, bit 7 of `OSCCAL` selects one of
+/// two overlapping frequency ranges, and `0x7f` runs faster than `0x80`.  Stepping across that
+/// boundary would make the frequency jump, so this panics if `value` is not in the same range as
+/// the current value.
+pub fn set_osccal(cpu: &crate::pac::CPU, value: u8) {
+	let mut current = osccal(cpu);
+	#[cfg(not(any(
+		feature = "atmega8",
+		feature = "atmega16",
+		feature = "atmega32a",
+		feature = "atmega128a"
+	)))]
+	assert!(
+		same_osccal_range(current, value),
+		"OSCCAL value is in the other frequency range"
+	);
+	while current != value {
+		current = if current < value {
+			current + 1
+		} else {
+			current - 1
+		};
+		cpu.osccal().write(|w| unsafe { w.bits(current) });
+	}
+}
+
+/// Whether two `OSCCAL` values select the same frequency range (bit 7).
+#[cfg(not(any(
+	feature = "atmega8",
+	feature = "atmega16",
+	feature = "atmega32a",
+	feature = "atmega128a"
+)))]
+fn same_osccal_range(a: u8, b: u8) -> bool {
+	(a ^ b) & 0x80 == 0
+}
+
+/// `OSCCAL` values for several temperatures, see the [module
+/// documentation](self#rc-oscillator-calibration-over-temperature).
+#[derive(Debug, Clone, Copy)]
+pub struct TemperatureTrim<const N: usize> {
+	points: [(i8, u8); N],
+}
+
+impl<const N: usize> TemperatureTrim<N> {
+	/// Interpolate between `points` of `(temperature in °C, OSCCAL value)`.
+	///
+	/// # Panics
+	/// Panics if there are no points or the temperatures are not strictly ascending.  Except on
+	/// the ATmega8, ATmega16, ATmega32A, and ATmega128A, bit 7 of `OSCCAL` selects one of two
+	/// overlapping frequency ranges, so it also panics if the values are not all in the same
+	/// range.
+	pub const fn new(points: [(i8, u8); N]) -> Self {
+		assert!(N > 0, "at least one calibration point is needed");
+		let mut i = 1;
+		while i < N {
+			assert!(
+				points[i - 1].0 < points[i].0,
+				"temperatures must be ascending"
+			);
+			#[cfg(not(any(
+				feature = "atmega8",
+				feature = "atmega16",
+				feature = "atmega32a",
+				feature = "atmega128a"
+			)))]
+			assert!(
+				(points[i].1 ^ points[0].1) & 0x80 == 0,
+				"all values must be in the same OSCCAL range"
+			);
+			i += 1;
+		}
+		Self { points }
+	}
+
+	/// The `OSCCAL` value for `celsius`, interpolated linearly between the two nearest points.
+	///
+	/// Outside the range of the points, the value of the nearest one is used.
+	pub fn value_for(&self, celsius: i8) -> u8 {
+		let (first_temp, first_value) = self.points[0];
+		if celsius <= first_temp {
+			return first_value;
+		}
+		for pair in self.points.windows(2) {
+			let ((t0, v0), (t1, v1)) = (pair[0], pair[1]);
+			if celsius <= t1 {
+				let span = i32::from(t1) - i32::from(t0);
+				let offset = i32::from(celsius) - i32::from(t0);
+				let delta = i32::from(v1) - i32::from(v0);
+				// Rounded to the nearest step.  `2 * delta * offset` can exceed `i16` (up to
+				// 2 * 255 * 255), so this is computed in `i32`.
+				let step =
+					(2 * delta * offset + span * delta.signum()) / (2 * span);
+				return (i32::from(v0) + step) as u8;
+			}
+		}
+		self.points[N - 1].1
+	}
+
+	/// Set `OSCCAL` to the value for `celsius` and return it.
+	///
+	/// # Panics
+	/// Panics if the current `OSCCAL` value is in the other frequency range, see
+	/// [`set_osccal()`].
+	pub fn apply(&self, cpu: &crate::pac::CPU, celsius: i8) -> u8 {
+		let value = self.value_for(celsius);
+		set_osccal(cpu, value);
+		value
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn trim_interpolates() {
+		let trim = TemperatureTrim::new([(-20, 0x9a), (25, 0x94), (70, 0x8d)]);
+		assert_eq!(trim.value_for(-40), 0x9a);
+		assert_eq!(trim.value_for(-20), 0x9a);
+		// -2.67 steps, rounded
+		assert_eq!(trim.value_for(0), 0x97);
+		assert_eq!(trim.value_for(25), 0x94);
+		assert_eq!(trim.value_for(70), 0x8d);
+		assert_eq!(trim.value_for(100), 0x8d);
+	}
+
+	#[test]
+	fn trim_wide_span_does_not_overflow() {
+		let trim = TemperatureTrim::new([(-100, 0x00), (100, 0x7f)]);
+		assert_eq!(trim.value_for(99), 126);
+		assert_eq!(trim.value_for(100), 127);
+		assert_eq!(trim.value_for(0), 64);
+	}
+
+	#[cfg(not(any(
+		feature = "atmega8",
+		feature = "atmega16",
+		feature = "atmega32a",
+		feature = "atmega128a"
+	)))]
+	#[test]
+	fn osccal_ranges() {
+		assert!(same_osccal_range(0x00, 0x7f));
+		assert!(same_osccal_range(0x80, 0xff));
+		assert!(!same_osccal_range(0x7f, 0x80));
+	}
+
+	#[test]
+	fn estimate_matches_built_clock() {
+		let estimate = ClockEstimate {
+			measured_hz: 14_500_000,
+		};
+		assert!(estimate.matches::<crate::clock::MHz16>());
+		assert!(!estimate.matches::<crate::clock::MHz8>());
+		assert_eq!(estimate.nearest_common_hz(), 16_000_000);
+
+		// 25% either way
+		let fast = ClockEstimate {
+			measured_hz: 10_000_000,
+		};
+		assert!(fast.matches::<crate::clock::MHz8>());
+		assert!(!ClockEstimate {
+			measured_hz: 10_000_001
+		}
+		.matches::<crate::clock::MHz8>());
+		assert!(!ClockEstimate {
+			measured_hz: 5_999_999
+		}
+		.matches::<crate::clock::MHz8>());
+		assert_eq!(fast.nearest_common_hz(), 8_000_000);
+		assert_eq!(
+			ClockEstimate { measured_hz: 0 }.nearest_common_hz(),
+			1_000_000
+		);
+	}
+
+	#[cfg(not(any(
+		feature = "atmega8",
+		feature = "atmega16",
+		feature = "atmega32a",
+		feature = "atmega128a"
+	)))]
+	#[test]
+	fn estimate_from_watchdog_period() {
+		assert_eq!(ClockEstimate::from_ticks(4000).measured_hz, 16_000_000);
+		assert_eq!(ClockEstimate::from_ticks(2000).measured_hz, 8_000_000);
+		// A watchdog oscillator 10% slow makes the period longer
+		let slow_wdt = ClockEstimate::from_ticks(4444);
+		assert!(slow_wdt.matches::<crate::clock::MHz16>());
+		assert_eq!(slow_wdt.nearest_common_hz(), 16_000_000);
+		// The largest count converts without overflowing
+		assert_eq!(ClockEstimate::from_ticks(u16::MAX).measured_hz, 262_140_000);
+	}
+}
@@ -0,0 +1,315 @@
+//! Hardware event counters
+//!
+//! Check the documentation of [`avr_hal_generic::counter`] for details.
+//!
+//! # Example
+//! ```
+//! let dp = atmega_hal::Peripherals::take().unwrap();
+//! let pins = atmega_hal::pins!(dp);
+//!
+//! let mut pulses = Timer1Counter::new(dp.TC1, pins.pd5, Edge::Rising);
+//! // or, equivalently, with `IntoCounter` in scope:
+//! // let mut pulses = dp.TC1.into_counter(pins.pd5, Edge::Rising);
+//!
+//! loop {
+//!     delay_ms(1000);
+//!     let per_second = pulses.count();
+//!     pulses.reset();
+//! }
+//! ```
+
+pub use avr_hal_generic::counter::Edge;
+pub use avr_hal_generic::counter::IntoCounter;
+
+#[allow(unused_imports)]
+use crate::port::*;
+
+#[cfg(any(
+	feature = "atmega48p",
+	feature = "atmega88p",
+	feature = "atmega168",
+	feature = "atmega168pb",
+	feature = "atmega328p",
+	feature = "atmega328pb"
+))]
+avr_hal_generic::impl_counter! {
+    /// Use `TC0` to count edges on `T0` (pin `PD4`)
+    ///
+    /// This is an 8-bit counter, it wraps around after 256 edges.
+    pub struct Timer0Counter {
+	timer: crate::pac::TC0,
+	pin: PD4,
+	counter: tcnt0,
+	init: |tim, edge| {
+	    tim.tccr0a().reset();
+	    tim.tccr0b().write(|w| match edge {
+		Edge::Falling => w.cs0().ext_falling(),
+		Edge::Rising => w.cs0().ext_rising(),
+	    });
+	},
+	stop: |tim| {
+	    tim.tccr0b().reset();
+	},
+    }
+}
+
+#[cfg(any(
+	feature = "atmega48p",
+	feature = "atmega88p",
+	feature = "atmega168",
+	feature = "atmega168pb",
+	feature = "atmega328p",
+	feature = "atmega328pb"
+))]
+avr_hal_generic::impl_counter! {
+    /// Use `TC1` to count edges on `T1` (pin `PD5`)
+    pub struct Timer1Counter {
+	timer: crate::pac::TC1,
+	pin: PD5,
+	counter: tcnt1,
+	init: |tim, edge| {
+	    tim.tccr1a().reset();
+	    tim.tccr1b().write(|w| match edge {
+		Edge::Falling => w.cs1().ext_falling(),
+		Edge::Rising => w.cs1().ext_rising(),
+	    });
+	},
+	stop: |tim| {
+	    tim.tccr1b().reset();
+	},
+    }
+}
+
+#[cfg(any(feature = "atmega32u4", feature = "atmega1280", feature = "atmega2560"))]
+avr_hal_generic::impl_counter! {
+    /// Use `TC0` to count edges on `T0` (pin `PD7`)
+    ///
+    /// This is an 8-bit counter, it wraps around after 256 edges.
+    pub struct Timer0Counter {
+	timer: crate::pac::TC0,
+	pin: PD7,
+	counter: tcnt0,
+	init: |tim, edge| {
+	    tim.tccr0a().reset();
+	    tim.tccr0b().write(|w| match edge {
+		Edge::Falling => w.cs0().ext_falling(),
+		Edge::Rising => w.cs0().ext_rising(),
+	    });
+	},
+	stop: |tim| {
+	    tim.tccr0b().reset();
+	},
+    }
+}
+
+#[cfg(any(feature = "atmega32u4", feature = "atmega1280", feature = "atmega2560"))]
+avr_hal_generic::impl_counter! {
+    /// Use `TC1` to count edges on `T1` (pin `PD6`)
+    pub struct Timer1Counter {
+	timer: crate::pac::TC1,
+	pin: PD6,
+	counter: tcnt1,
+	init: |tim, edge| {
+	    tim.tccr1a().reset();
+	    tim.tccr1b().write(|w| match edge {
+		Edge::Falling => w.cs1().ext_falling(),
+		Edge::Rising => w.cs1().ext_rising(),
+	    });
+	},
+	stop: |tim| {
+	    tim.tccr1b().reset();
+	},
+    }
+}
+
+/// Position of a fast incremental encoder, counted by `TC1`.
+///
+/// Software decoding of a quadrature encoder needs an interrupt for every edge, which limits it
+/// to some ten thousand edges per second.  The AVR timers have no quadrature decoder and can only
+/// count up, so this is an approximation: `TC1` counts the steps on its `T1` input without any
+/// CPU involvement, and [`update()`][HwEncoder::update] adds the steps counted since its last
+/// call to the position, in the direction given by the level of a direction input at that time.
+///
+/// The direction input must be a steady level, not the raw phase B, which toggles with every
+/// step.  It can come from
+///
+/// - an encoder interface IC with step/direction outputs, like the LS7184, or a magnetic encoder
+///   in step/direction mode, or
+/// - a D flip-flop (e.g. 74HC74) with phase A on the clock and phase B on `D`, whose output
+///   latches the level of B on every rising edge of A, which is the direction of rotation.
+///   Phase A then also goes to `T1`.
+///
+/// ```ignore
+/// let counter = Timer1Counter::new(dp.TC1, pins.pd5, Edge::Rising);
+/// let mut encoder = HwEncoder::new(counter, pins.pd4.into_floating_input());
+///
+/// loop {
+///     let position = encoder.update();
+/// }
+/// ```
+///
+/// # Limitations
+/// - Only one edge per encoder cycle is counted, so the resolution is a quarter of what a full
+///   quadrature decoder gets.
+/// - All steps counted between two calls to `update()` are attributed to the direction at the
+///   time of the call.  If the direction changes in between, the position is off by up to twice
+///   the steps counted since the reversal.  This suits encoders that turn fast but rarely
+///   reverse, like on spindles or conveyor motors; call `update()` often when the direction
+///   changes.
+/// - The counter wraps after 65536 steps, so `update()` must be called before that many steps
+///   accumulate, e.g. at least every 65 ms at 1 MHz.
+/// - The steps are counted like any other external clock, so their rate must stay below
+///   `CLK_io / 2.5`, see [`avr_hal_generic::counter`].
+#[cfg(any(
+	feature = "atmega48p",
+	feature = "atmega88p",
+	feature = "atmega168",
+	feature = "atmega168pb",
+	feature = "atmega328p",
+	feature = "atmega328pb",
+	feature = "atmega32u4",
+	feature = "atmega1280",
+	feature = "atmega2560"
+))]
+pub struct HwEncoder<DIR> {
+	counter: Timer1Counter,
+	dir: Pin<mode::Input, DIR>,
+	last_count: u16,
+	position: i32,
+}
+
+#[cfg(any(
+	feature = "atmega48p",
+	feature = "atmega88p",
+	feature = "atmega168",
+	feature = "atmega168pb",
+	feature = "atmega328p",
+	feature = "atmega328pb",
+	feature = "atmega32u4",
+	feature = "atmega1280",
+	feature = "atmega2560"
+))]
+impl<DIR: PinOps> HwEncoder<DIR> {
+	/// Track the position from the steps counted by `counter` and the direction input `dir`.
+	/// A high direction input counts upwards.
+	///
+	/// The position starts at zero.
+	pub fn new<IMODE: mode::InputMode>(
+		counter: Timer1Counter,
+		dir: Pin<mode::Input<IMODE>, DIR>,
+	) -> Self {
+		let last_count = counter.count();
+		Self {
+			counter,
+			dir: dir.forget_imode(),
+			last_count,
+			position: 0,
+		}
+	}
+
+	/// Add the steps counted since the last call and return the new position.
+	pub fn update(&mut self) -> i32 {
+		let count = self.counter.count();
+		self.position = advance(self.position, self.last_count, count, self.dir.is_high());
+		self.last_count = count;
+		self.position
+	}
+
+	/// The position as of the last [`update()`][Self::update].
+	pub fn position(&self) -> i32 {
+		self.position
+	}
+
+	/// Redefine the current position, e.g. at a reference mark.
+	pub fn set_position(&mut self, position: i32) {
+		self.update();
+		self.position = position;
+	}
+
+	/// Return the counter and the direction input.
+	pub fn release(self) -> (Timer1Counter, Pin<mode::Input, DIR>) {
+		(self.counter, self.dir)
+	}
+}
+
+/// `position` moved by the steps between the counter values `last_count` and `count`, upwards
+/// if `up`.
+#[cfg(any(
+	feature = "atmega48p",
+	feature = "atmega88p",
+	feature = "atmega168",
+	feature = "atmega168pb",
+	feature = "atmega328p",
+	feature = "atmega328pb",
+	feature = "atmega32u4",
+	feature = "atmega1280",
+	feature = "atmega2560"
+))]
+fn advance(position: i32, last_count: u16, count: u16, up: bool) -> i32 {
+	let steps = i32::from(count.wrapping_sub(last_count));
+	if up {
+		position.wrapping_add(steps)
+	} else {
+		position.wrapping_sub(steps)
+	}
+}
+
+#[cfg(test)]
+#[cfg(any(
+	feature = "atmega48p",
+	feature = "atmega88p",
+	feature = "atmega168",
+	feature = "atmega168pb",
+	feature = "atmega328p",
+	feature = "atmega328pb",
+	feature = "atmega32u4",
+	feature = "atmega1280",
+	feature = "atmega2560"
+))]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn encoder_steps_in_direction() {
+		assert_eq!(advance(0, 100, 150, true), 50);
+		assert_eq!(advance(50, 150, 150, false), 50);
+		assert_eq!(advance(50, 150, 250, false), -50);
+	}
+
+	#[test]
+	fn encoder_counter_wraps() {
+		// 16 steps across the 16-bit wrap of TCNT1
+		assert_eq!(advance(1000, 0xfff8, 0x0008, true), 1016);
+		assert_eq!(advance(1000, 0xfff8, 0x0008, false), 984);
+		// Almost a full counter period is still counted forwards
+		assert_eq!(advance(0, 1, 0, true), 65535);
+		assert_eq!(advance(i32::MAX, 0, 1, true), i32::MIN);
+	}
+
+	#[test]
+	fn timer_into_counter() {
+		// Compile-time check that each timer converts into its counter on the right pin
+		fn into_counter<T: IntoCounter<C, Pin = P>, C, P>() {}
+
+		#[cfg(not(any(
+			feature = "atmega32u4",
+			feature = "atmega1280",
+			feature = "atmega2560"
+		)))]
+		{
+			into_counter::<crate::pac::TC0, Timer0Counter, PD4>();
+			into_counter::<crate::pac::TC1, Timer1Counter, PD5>();
+		}
+		#[cfg(
+			any(
+				feature = "atmega32u4",
+				feature = "atmega1280",
+				feature = "atmega2560"
+			)
+		)]
+		{
+			into_counter::<crate::pac::TC0, Timer0Counter, PD7>();
+			into_counter::<crate::pac::TC1, Timer1Counter, PD6>();
+		}
+	}
+}
@@ -18,10 +18,20 @@
 //! ufmt::uwriteln!(&mut serial, "Boot count: {}", boot_count).unwrap();
 //! ```
 
+pub use avr_hal_generic::eeprom::Config;
 pub use avr_hal_generic::eeprom::EepromOps;
+pub use avr_hal_generic::eeprom::EepromRing;
+pub use avr_hal_generic::eeprom::EepromWriteModeOps;
+pub use avr_hal_generic::eeprom::Error;
+pub use avr_hal_generic::eeprom::LoadError;
 pub use avr_hal_generic::eeprom::OutOfBoundsError;
+pub use avr_hal_generic::eeprom::Persist;
+pub use avr_hal_generic::eeprom::SupplyVoltage;
+pub use avr_hal_generic::eeprom::WriteMode;
 
 pub type Eeprom = avr_hal_generic::eeprom::Eeprom<crate::Atmega, crate::pac::EEPROM>;
+pub type GuardedEeprom<V> =
+	avr_hal_generic::eeprom::GuardedEeprom<crate::Atmega, crate::pac::EEPROM, V>;
 
 ///////////////////////////////////////////////////////////
 #[cfg(feature = "atmega48p")]
@@ -46,7 +56,11 @@ avr_hal_generic::impl_eeprom_atmega! {
     },
 }
 
-#[cfg(any(feature = "atmega168", feature = "atmega164pa"))]
+#[cfg(any(
+	feature = "atmega168",
+	feature = "atmega168pb",
+	feature = "atmega164pa"
+))]
 avr_hal_generic::impl_eeprom_atmega! {
     hal: crate::Atmega,
     peripheral: crate::pac::EEPROM,
@@ -0,0 +1,157 @@
+//! Reading fuse and lock bits at runtime
+//!
+//! The fuses are configured when programming the MCU (e.g. with `avrdude -U lfuse:w:...`), but the
+//! running application can still read them back.  This is used by
+//! [`clock_source::current()`][crate::clock_source::current] and is useful for diagnostics.
+//!
+//! Note that a programmed fuse bit reads as `0` and an unprogrammed one as `1`.
+//!
+//! All ATmega devices supported by this crate can read their fuses at runtime, through the
+//! `BLBSET` bit of `SPMCSR`.  The ATtiny devices have no such mechanism in `attiny-hal`, and
+//! most reduced-core AVRs cannot read their fuses at all.
+//!
+//! # Brown-out detection
+//! The brown-out detector resets the MCU when the supply voltage drops below a level selected by
+//! the `BODLEVEL` fuses.  [`bod_level()`] reports this level, e.g. to check that the safety
+//! margin for EEPROM writes or a given clock speed is actually in place, and to interpret a
+//! brown-out [reset cause][crate::init::ResetCause].
+
+/// Read the low fuse byte.
+pub fn low() -> u8 {
+	read(0x0000)
+}
+
+/// Read the high fuse byte.
+pub fn high() -> u8 {
+	read(0x0003)
+}
+
+/// Read the extended fuse byte.
+///
+/// Unused bits read as `1`.  Devices without an extended fuse byte return `0xff`.
+pub fn extended() -> u8 {
+	read(0x0002)
+}
+
+/// Read the lock bits.
+pub fn lock() -> u8 {
+	read(0x0001)
+}
+
+/// Brown-out detection level configured by the fuses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodLevel {
+	/// The brown-out detector is disabled.
+	Disabled,
+	/// The MCU is held in reset below this typical supply voltage, in millivolts.
+	Millivolts(u16),
+	/// The fuses hold a reserved value.
+	Unknown,
+}
+
+/// Read the brown-out detection level from the fuses.
+///
+/// The levels are the typical trigger voltages from the datasheet; the actual threshold varies
+/// between parts by about ±0.2 V.
+pub fn bod_level() -> BodLevel {
+	#[cfg(any(
+		feature = "atmega8",
+		feature = "atmega16",
+		feature = "atmega32a",
+		feature = "atmega128a"
+	))]
+	{
+		// BODEN (bit 6) and BODLEVEL (bit 7) in the low fuse byte
+		let fuse = low();
+		if fuse & (1 << 6) != 0 {
+			BodLevel::Disabled
+		} else if fuse & (1 << 7) != 0 {
+			BodLevel::Millivolts(2700)
+		} else {
+			BodLevel::Millivolts(4000)
+		}
+	}
+
+	#[cfg(feature = "atmega32u4")]
+	{
+		match extended() & 0b111 {
+			0b111 => BodLevel::Disabled,
+			0b110 => BodLevel::Millivolts(2000),
+			0b101 => BodLevel::Millivolts(2200),
+			0b100 => BodLevel::Millivolts(2400),
+			0b011 => BodLevel::Millivolts(2600),
+			0b010 => BodLevel::Millivolts(3400),
+			0b001 => BodLevel::Millivolts(3500),
+			_ => BodLevel::Millivolts(4300),
+		}
+	}
+
+	#[cfg(not(any(
+		feature = "atmega8",
+		feature = "atmega16",
+		feature = "atmega32a",
+		feature = "atmega128a",
+		feature = "atmega32u4"
+	)))]
+	{
+		// BODLEVEL2:0 are in the high fuse byte on the smaller devices of the 328P family, and
+		// in the extended fuse byte everywhere else.
+		#[cfg(any(
+			feature = "atmega48p",
+			feature = "atmega88p",
+			feature = "atmega168",
+			feature = "atmega168pb"
+		))]
+		let fuse = high();
+		#[cfg(not(any(
+			feature = "atmega48p",
+			feature = "atmega88p",
+			feature = "atmega168",
+			feature = "atmega168pb"
+		)))]
+		let fuse = extended();
+
+		match fuse & 0b111 {
+			0b111 => BodLevel::Disabled,
+			0b110 => BodLevel::Millivolts(1800),
+			0b101 => BodLevel::Millivolts(2700),
+			0b100 => BodLevel::Millivolts(4300),
+			_ => BodLevel::Unknown,
+		}
+	}
+}
+
+fn read(address: u16) -> u8 {
+	// LPM must follow within three cycles after setting BLBSET, so no interrupt may intervene.
+	avr_device::interrupt::free(|_| read_raw(address))
+}
+
+#[cfg(target_arch = "avr")]
+fn read_raw(address: u16) -> u8 {
+	// Memory-mapped address of `SPMCSR` (`SPMCR` on some devices).
+	#[cfg(not(feature = "atmega128a"))]
+	const SPMCSR: u16 = 0x57;
+	#[cfg(feature = "atmega128a")]
+	const SPMCSR: u16 = 0x68;
+	// `BLBSET | SPMEN`
+	const READ_FUSE_AND_LOCK_BITS: u8 = (1 << 3) | (1 << 0);
+
+	let value: u8;
+	unsafe {
+		core::arch::asm!(
+			"st X, {cmd}",
+			"lpm {value}, Z",
+			cmd = in(reg) READ_FUSE_AND_LOCK_BITS,
+			value = out(reg) value,
+			in("X") SPMCSR,
+			in("Z") address,
+			options(nostack),
+		);
+	}
+	value
+}
+
+#[cfg(not(target_arch = "avr"))]
+fn read_raw(_address: u16) -> u8 {
+	unimplemented!("Implementation is only available for avr targets!")
+}
@@ -93,7 +93,7 @@ avr_hal_generic::impl_i2c_twi! {
     scl: port::PC5,
 }
 
-#[cfg(any(feature = "atmega328pb"))]
+#[cfg(any(feature = "atmega168pb", feature = "atmega328pb"))]
 pub type I2c0<CLOCK> = avr_hal_generic::i2c::I2c<
 	crate::Atmega,
 	crate::pac::TWI0,
@@ -101,13 +101,16 @@ pub type I2c0<CLOCK> = avr_hal_generic::i2c::I2c<
 	port::Pin<port::mode::Input, port::PC5>,
 	CLOCK,
 >;
-#[cfg(any(feature = "atmega328pb"))]
+#[cfg(any(feature = "atmega168pb", feature = "atmega328pb"))]
 avr_hal_generic::impl_i2c_twi! {
     hal: crate::Atmega,
     peripheral: crate::pac::TWI0,
     sda: port::PC4,
     scl: port::PC5,
 }
+/// The ATmega168PB only has a single TWI peripheral.
+#[cfg(feature = "atmega168pb")]
+pub type I2c<CLOCK> = I2c0<CLOCK>;
 #[cfg(any(feature = "atmega328pb"))]
 pub type I2c1<CLOCK> = avr_hal_generic::i2c::I2c<
 	crate::Atmega,
@@ -0,0 +1,294 @@
+//! Early-boot initialization
+//!
+//! A handful of things should happen right at the start of `main()`, and some of them in a
+//! particular order: the reset cause has to be read before the watchdog flag gets cleared, and the
+//! watchdog has to be disabled before anything slow happens because it stays armed after a
+//! watchdog reset.  [`Init`] bundles these steps.  Each step is opt-in and they always run in the
+//! correct order, no matter in which order they were selected.
+//!
+//! # Example
+//! ```
+//! let dp = atmega_hal::Peripherals::take().unwrap();
+//! let (dp, reset_cause) = atmega_hal::init::Init::new()
+//!     .capture_reset_cause()
+//!     .disable_watchdog()
+//!     .run(dp);
+//! let pins = atmega_hal::pins!(dp);
+//!
+//! if reset_cause.map_or(false, |c| c.watchdog) {
+//!     // recover from a hang ...
+//! }
+//! ```
+//!
+//! Power reduction (`PRR`) is deliberately not part of this: disabling a peripheral there also
+//! stops the corresponding driver from working.
+#[cfg(not(feature = "atmega16"))]
+use avr_hal_generic::wdt::WdtOps;
+
+/// Reset flags read from `MCUSR` (`MCUCSR` on older devices).
+///
+/// More than one flag can be set, e.g. after a brown-out which happened while the MCU was still
+/// held in reset by the reset pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResetCause {
+	/// The supply voltage came up (power-on reset).
+	pub power_on: bool,
+	/// The reset pin was pulled low.
+	pub external: bool,
+	/// The supply voltage dropped below the brown-out level.
+	pub brown_out: bool,
+	/// The watchdog timed out.
+	pub watchdog: bool,
+	/// A reset was requested through the JTAG interface.  Always `false` on devices without
+	/// JTAG.
+	pub jtag: bool,
+	/// The USB controller detected a USB bus reset while the USB reset was enabled (`USBRF`).
+	#[cfg(feature = "atmega32u4")]
+	pub usb: bool,
+}
+
+impl ResetCause {
+	fn from_bits(bits: u8) -> Self {
+		Self {
+			power_on: bits & (1 << 0) != 0,
+			external: bits & (1 << 1) != 0,
+			brown_out: bits & (1 << 2) != 0,
+			watchdog: bits & (1 << 3) != 0,
+			jtag: bits & (1 << 4) != 0,
+			#[cfg(feature = "atmega32u4")]
+			usb: bits & (1 << 5) != 0,
+		}
+	}
+}
+
+/// Mask of all reset flags in `MCUSR`/`MCUCSR`.
+#[cfg(not(feature = "atmega32u4"))]
+const RESET_FLAGS: u8 = 0x1f;
+#[cfg(feature = "atmega32u4")]
+const RESET_FLAGS: u8 = 0x3f;
+
+/// Division factor of the system clock prescaler (`CLKPR`).
+#[cfg(not(any(
+	feature = "atmega8",
+	feature = "atmega16",
+	feature = "atmega32a",
+	feature = "atmega128a"
+)))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ClockPrescaler {
+	Div1 = 0,
+	Div2 = 1,
+	Div4 = 2,
+	Div8 = 3,
+	Div16 = 4,
+	Div32 = 5,
+	Div64 = 6,
+	Div128 = 7,
+	Div256 = 8,
+}
+
+/// Builder for the early-boot initialization steps.
+///
+/// See the [module documentation](self).
+#[derive(Debug, Default)]
+pub struct Init {
+	capture_reset_cause: bool,
+	#[cfg(not(feature = "atmega16"))]
+	disable_watchdog: bool,
+	#[cfg(any(
+		feature = "atmega16",
+		feature = "atmega32a",
+		feature = "atmega128a",
+		feature = "atmega164pa",
+		feature = "atmega1284p",
+		feature = "atmega32u4",
+		feature = "atmega1280",
+		feature = "atmega2560"
+	))]
+	disable_jtag: bool,
+	#[cfg(not(any(
+		feature = "atmega8",
+		feature = "atmega16",
+		feature = "atmega32a",
+		feature = "atmega128a"
+	)))]
+	clock_prescaler: Option<ClockPrescaler>,
+}
+
+impl Init {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Read the reset flags and clear them, so the next reset reports only its own cause.
+	///
+	/// This runs before all other steps.
+	pub fn capture_reset_cause(mut self) -> Self {
+		self.capture_reset_cause = true;
+		self
+	}
+
+	/// Disable the watchdog.
+	///
+	/// After a watchdog reset, the watchdog stays enabled with its shortest timeout and would
+	/// reset the MCU again right away.  This also clears the watchdog reset flag, which is why the
+	/// reset cause has to be captured before.
+	#[cfg(not(feature = "atmega16"))]
+	pub fn disable_watchdog(mut self) -> Self {
+		self.disable_watchdog = true;
+		self
+	}
+
+	/// Disable the JTAG interface, freeing its pins for general use.
+	///
+	/// The JTAG pins are on port F on the ATmega32U4 and ATmega1280/2560 and on port C on the
+	/// others.  As long as the `JTAGEN` fuse is programmed, these pins are not usable as GPIO
+	/// unless JTAG is disabled at runtime.
+	#[cfg(any(
+		feature = "atmega16",
+		feature = "atmega32a",
+		feature = "atmega128a",
+		feature = "atmega164pa",
+		feature = "atmega1284p",
+		feature = "atmega32u4",
+		feature = "atmega1280",
+		feature = "atmega2560"
+	))]
+	pub fn disable_jtag(mut self) -> Self {
+		self.disable_jtag = true;
+		self
+	}
+
+	/// Set the system clock prescaler.
+	///
+	/// **Note**: All drivers compute their timing from the `CLOCK` type parameter, which has to
+	/// match the resulting clock frequency.
+	#[cfg(not(any(
+		feature = "atmega8",
+		feature = "atmega16",
+		feature = "atmega32a",
+		feature = "atmega128a"
+	)))]
+	pub fn clock_prescaler(mut self, prescaler: ClockPrescaler) -> Self {
+		self.clock_prescaler = Some(prescaler);
+		self
+	}
+
+	/// Run the selected steps and hand the peripherals back.
+	///
+	/// The reset cause is only returned if [`capture_reset_cause()`][Init::capture_reset_cause]
+	/// was selected.
+	#[cfg_attr(feature = "atmega16", allow(unused_mut))]
+	pub fn run(self, mut dp: crate::Peripherals) -> (crate::Peripherals, Option<ResetCause>) {
+		#[cfg(not(any(
+			feature = "atmega8",
+			feature = "atmega16",
+			feature = "atmega32a",
+			feature = "atmega128a"
+		)))]
+		let mcusr = dp.CPU.mcusr();
+		#[cfg(any(
+			feature = "atmega8",
+			feature = "atmega16",
+			feature = "atmega32a",
+			feature = "atmega128a"
+		))]
+		let mcusr = dp.CPU.mcucsr();
+
+		let reset_cause = if self.capture_reset_cause {
+			let bits = mcusr.read().bits();
+			// The upper bits of MCUCSR hold unrelated settings (e.g. JTD), so keep them.
+			mcusr.modify(|r, w| unsafe { w.bits(r.bits() & !RESET_FLAGS) });
+			Some(ResetCause::from_bits(bits))
+		} else {
+			None
+		};
+
+		#[cfg(not(feature = "atmega16"))]
+		if self.disable_watchdog {
+			dp.WDT.raw_init(mcusr);
+			dp.WDT.raw_stop();
+		}
+
+		#[cfg(any(
+			feature = "atmega164pa",
+			feature = "atmega1284p",
+			feature = "atmega32u4",
+			feature = "atmega1280",
+			feature = "atmega2560"
+		))]
+		if self.disable_jtag {
+			disable_jtag(dp.CPU.mcucr());
+		}
+		#[cfg(any(feature = "atmega16", feature = "atmega32a", feature = "atmega128a"))]
+		if self.disable_jtag {
+			disable_jtag(dp.CPU.mcucsr());
+		}
+
+		#[cfg(not(any(
+			feature = "atmega8",
+			feature = "atmega16",
+			feature = "atmega32a",
+			feature = "atmega128a"
+		)))]
+		if let Some(prescaler) = self.clock_prescaler {
+			// CLKPS has to be written within four cycles after setting CLKPCE.
+			avr_device::interrupt::free(|_| {
+				dp.CPU.clkpr().write(|w| w.clkpce().set_bit());
+				dp.CPU.clkpr().write(|w| unsafe { w.bits(prescaler as u8) });
+			});
+		}
+
+		(dp, reset_cause)
+	}
+}
+
+/// Set `JTD` twice within four cycles, as required to disable JTAG.
+#[cfg(any(
+	feature = "atmega16",
+	feature = "atmega32a",
+	feature = "atmega128a",
+	feature = "atmega164pa",
+	feature = "atmega1284p",
+	feature = "atmega32u4",
+	feature = "atmega1280",
+	feature = "atmega2560"
+))]
+fn disable_jtag<REG>(reg: &avr_device::generic::Reg<REG>)
+where
+	REG: avr_device::generic::RegisterSpec<Ux = u8>
+		+ avr_device::generic::Readable
+		+ avr_device::generic::Writable
+		+ avr_device::generic::Resettable,
+{
+	avr_device::interrupt::free(|_| {
+		let value = reg.read().bits() | (1 << 7);
+		reg.write(|w| unsafe { w.bits(value) });
+		reg.write(|w| unsafe { w.bits(value) });
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn reset_cause_from_bits() {
+		let cause = ResetCause::from_bits(0b0000_1010);
+		assert!(!cause.power_on);
+		assert!(cause.external);
+		assert!(!cause.brown_out);
+		assert!(cause.watchdog);
+		assert!(!cause.jtag);
+		#[cfg(feature = "atmega32u4")]
+		assert!(!cause.usb);
+	}
+
+	#[cfg(feature = "atmega32u4")]
+	#[test]
+	fn usb_reset_flag() {
+		assert!(ResetCause::from_bits(1 << 5).usb);
+		assert_eq!(RESET_FLAGS & (1 << 5), 1 << 5);
+	}
+}
@@ -0,0 +1,72 @@
+//! Interrupt vector table placement
+//!
+//! Devices with a boot loader section can move the interrupt vector table from the start of the
+//! application flash to the start of the boot section, by setting the `IVSEL` bit (in `MCUCR`, or
+//! `GICR` on the ATmega8/16/32A).  A boot loader which uses interrupts has to do this, and has to
+//! move the table back before jumping to the application.  [`set_vector_table()`] performs the
+//! required timed write sequence.
+//!
+//! # Timed write
+//! To prevent unintentional changes, `IVSEL` can only be written within four clock cycles after
+//! the change enable bit `IVCE` was set.  Interrupts are disabled by hardware during that window,
+//! and additionally by this function, so the two writes are not torn apart.  Note that the boot
+//! section address depends on the `BOOTSZ` fuses, which have to match the linker script of the
+//! boot loader.
+//!
+//! The boot lock bits interact with this: with the table in the boot section and `BLB12`
+//! programmed, interrupts are disabled while executing from the application section.  With the
+//! table in the application section and `BLB02` programmed, interrupts are disabled while
+//! executing from the boot section.
+//!
+//! # Example
+//! ```
+//! use atmega_hal::interrupt::VectorTable;
+//!
+//! let dp = atmega_hal::Peripherals::take().unwrap();
+//! // In the boot loader, before enabling interrupts:
+//! atmega_hal::interrupt::set_vector_table(&dp.CPU, VectorTable::Boot);
+//! // ... and before jumping to the application:
+//! atmega_hal::interrupt::set_vector_table(&dp.CPU, VectorTable::Application);
+//! ```
+
+/// Location of the interrupt vector table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorTable {
+	/// Start of the flash (default after reset unless `BOOTRST` is programmed).
+	Application,
+	/// Start of the boot loader section.
+	Boot,
+}
+
+const IVCE: u8 = 1 << 0;
+const IVSEL: u8 = 1 << 1;
+
+/// Move the interrupt vector table.  See the [module documentation](self).
+#[cfg(not(any(feature = "atmega8", feature = "atmega16", feature = "atmega32a")))]
+pub fn set_vector_table(cpu: &crate::pac::CPU, table: VectorTable) {
+	timed_write(cpu.mcucr(), table);
+}
+
+/// Move the interrupt vector table.  See the [module documentation](self).
+#[cfg(any(feature = "atmega8", feature = "atmega16", feature = "atmega32a"))]
+pub fn set_vector_table(exint: &crate::pac::EXINT, table: VectorTable) {
+	timed_write(exint.gicr(), table);
+}
+
+fn timed_write<REG>(reg: &avr_device::generic::Reg<REG>, table: VectorTable)
+where
+	REG: avr_device::generic::RegisterSpec<Ux = u8>
+		+ avr_device::generic::Readable
+		+ avr_device::generic::Writable
+		+ avr_device::generic::Resettable,
+{
+	avr_device::interrupt::free(|_| {
+		let other = reg.read().bits() & !(IVCE | IVSEL);
+		let select = match table {
+			VectorTable::Application => other,
+			VectorTable::Boot => other | IVSEL,
+		};
+		reg.write(|w| unsafe { w.bits(other | IVCE) });
+		reg.write(|w| unsafe { w.bits(select) });
+	});
+}
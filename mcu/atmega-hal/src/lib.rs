@@ -1,4 +1,5 @@
 #![no_std]
+#![feature(asm_experimental_arch)]
 
 //! `atmega-hal`
 //! =============
@@ -9,6 +10,7 @@
 #![cfg_attr(feature = "atmega16", doc = "**ATmega16**.")]
 #![cfg_attr(feature = "atmega164pa", doc = "**ATmega164PA**.")]
 #![cfg_attr(feature = "atmega168", doc = "**ATmega168**.")]
+#![cfg_attr(feature = "atmega168pb", doc = "**ATmega168PB**.")]
 #![cfg_attr(feature = "atmega328p", doc = "**ATmega328P**.")]
 #![cfg_attr(feature = "atmega328pb", doc = "**ATmega328PB**.")]
 #![cfg_attr(feature = "atmega32a", doc = "**ATmega32a**.")]
@@ -39,6 +41,7 @@ compile_error!(
     * atmega16
     * atmega164pa
     * atmega168
+    * atmega168pb
     * atmega328p
     * atmega328pb
     * atmega32u4
@@ -85,7 +88,11 @@ pub use avr_device::atmega2560 as pac;
 pub use avr_device::atmega328p as pac;
 /// Reexport of `atmega328pb` from `avr-device`
 ///
-#[cfg(feature = "atmega328pb")]
+/// This is also used for the ATmega168PB, which `avr-device` does not know.  Its registers are a
+/// subset of the ATmega328PB's and its interrupt vectors are numbered identically.  Only the
+/// peripherals which actually exist on the ATmega168PB are wired up by this crate.  When declaring
+/// interrupt handlers, use `#[avr_device::interrupt(atmega328pb)]`.
+#[cfg(any(feature = "atmega168pb", feature = "atmega328pb"))]
 pub use avr_device::atmega328pb as pac;
 /// Reexport of `atmega32a` from `avr-device`
 ///
@@ -142,6 +149,44 @@ pub use port::Pins;
 #[cfg(feature = "device-selected")]
 pub mod simple_pwm;
 
+#[cfg(feature = "device-selected")]
+pub mod analog_comparator;
+
+#[cfg(feature = "device-selected")]
+pub mod clock_source;
+
+#[cfg(feature = "device-selected")]
+pub mod counter;
+
+#[cfg(feature = "device-selected")]
+pub mod fuse;
+
+#[cfg(feature = "device-selected")]
+pub mod oneshot;
+
+#[cfg(feature = "device-selected")]
+pub mod square_wave;
+
+#[cfg(all(
+	feature = "device-selected",
+	not(any(
+		feature = "atmega8",
+		feature = "atmega16",
+		feature = "atmega32a",
+		feature = "atmega128a"
+	))
+))]
+pub mod power;
+
+#[cfg(feature = "device-selected")]
+pub mod init;
+
+#[cfg(all(feature = "device-selected", not(feature = "atmega48p")))]
+pub mod interrupt;
+
+#[cfg(feature = "device-selected")]
+pub mod mcu;
+
 #[cfg(feature = "device-selected")]
 pub mod usart;
 #[cfg(feature = "device-selected")]
@@ -157,6 +202,9 @@ pub mod eeprom;
 #[cfg(feature = "device-selected")]
 pub use eeprom::Eeprom;
 
+#[cfg(feature = "atmega32u4")]
+pub mod usb;
+
 pub struct Atmega;
 
 #[cfg(any(
@@ -178,7 +226,7 @@ macro_rules! pins {
 		$crate::Pins::new($p.PORTA, $p.PORTB, $p.PORTC, $p.PORTD)
 	};
 }
-#[cfg(feature = "atmega328pb")]
+#[cfg(any(feature = "atmega168pb", feature = "atmega328pb"))]
 #[macro_export]
 macro_rules! pins {
 	($p:expr) => {
@@ -0,0 +1,48 @@
+//! Memory sizes of the selected MCU
+//!
+//! These allow sizing buffers and checking limits at compile time:
+//!
+//! ```
+//! use atmega_hal::mcu;
+//!
+//! const BUF_LEN: usize = 512;
+//! // Leave at least half of the RAM for the stack and everything else.
+//! const _: () = assert!(BUF_LEN <= mcu::RAM_SIZE / 2);
+//! ```
+
+macro_rules! memory_sizes {
+	(flash: $flash:expr, ram: $ram:expr, eeprom: $eeprom:expr $(,)?) => {
+		/// Size of the program flash in bytes, including the bootloader section.
+		///
+		/// This is a `u32` because the flash of the larger devices exceeds the 16-bit `usize`.
+		pub const FLASH_SIZE: u32 = $flash;
+		/// Size of the internal SRAM in bytes, excluding registers and I/O space.
+		pub const RAM_SIZE: usize = $ram;
+		/// Size of the EEPROM in bytes.
+		pub const EEPROM_SIZE: usize = $eeprom;
+	};
+}
+
+#[cfg(feature = "atmega48p")]
+memory_sizes!(flash: 4 * 1024, ram: 512, eeprom: 256);
+#[cfg(any(feature = "atmega8", feature = "atmega88p"))]
+memory_sizes!(flash: 8 * 1024, ram: 1024, eeprom: 512);
+#[cfg(any(
+	feature = "atmega16",
+	feature = "atmega164pa",
+	feature = "atmega168",
+	feature = "atmega168pb"
+))]
+memory_sizes!(flash: 16 * 1024, ram: 1024, eeprom: 512);
+#[cfg(any(feature = "atmega32a", feature = "atmega328p", feature = "atmega328pb"))]
+memory_sizes!(flash: 32 * 1024, ram: 2048, eeprom: 1024);
+#[cfg(feature = "atmega32u4")]
+memory_sizes!(flash: 32 * 1024, ram: 2560, eeprom: 1024);
+#[cfg(feature = "atmega128a")]
+memory_sizes!(flash: 128 * 1024, ram: 4096, eeprom: 4096);
+#[cfg(feature = "atmega1280")]
+memory_sizes!(flash: 128 * 1024, ram: 8192, eeprom: 4096);
+#[cfg(feature = "atmega1284p")]
+memory_sizes!(flash: 128 * 1024, ram: 16384, eeprom: 4096);
+#[cfg(feature = "atmega2560")]
+memory_sizes!(flash: 256 * 1024, ram: 8192, eeprom: 4096);
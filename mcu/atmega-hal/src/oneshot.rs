@@ -0,0 +1,71 @@
+//! One-shot timer callbacks
+//!
+//! Check the documentation of [`avr_hal_generic::oneshot`] for details.
+//!
+//! # Example
+//! ```
+//! #[avr_device::interrupt(atmega328p)]
+//! fn TIMER1_COMPA() {
+//!     Timer1OneShot::on_interrupt();
+//! }
+//!
+//! let dp = atmega_hal::Peripherals::take().unwrap();
+//! let mut oneshot = Timer1OneShot::new::<CoreClock>(dp.TC1, Prescaler::Prescale8);
+//! unsafe { avr_device::interrupt::enable() };
+//!
+//! oneshot.schedule(500, || { /* 500 µs later */ }).unwrap();
+//! ```
+
+pub use avr_hal_generic::oneshot::ScheduleError;
+pub use avr_hal_generic::simple_pwm::Prescaler;
+
+#[cfg(any(
+	feature = "atmega48p",
+	feature = "atmega88p",
+	feature = "atmega164pa",
+	feature = "atmega168",
+	feature = "atmega168pb",
+	feature = "atmega328p",
+	feature = "atmega328pb",
+	feature = "atmega32u4",
+	feature = "atmega1280",
+	feature = "atmega1284p",
+	feature = "atmega2560"
+))]
+avr_hal_generic::impl_oneshot! {
+    /// Use `TC1` and its `TIMER1_COMPA` interrupt for one-shot callbacks
+    pub struct Timer1OneShot {
+	timer: crate::pac::TC1,
+	vector: crate::pac::Interrupt::TIMER1_COMPA as u8,
+	init: |tim, prescaler| {
+	    // Normal mode, free running.  The CS1 variant names differ between devices, but the
+	    // values are the same.
+	    let cs = match prescaler {
+		Prescaler::Direct => 0b001,
+		Prescaler::Prescale8 => 0b010,
+		Prescaler::Prescale64 => 0b011,
+		Prescaler::Prescale256 => 0b100,
+		Prescaler::Prescale1024 => 0b101,
+	    };
+	    tim.timsk1().modify(|_, w| w.ocie1a().clear_bit());
+	    tim.tccr1a().reset();
+	    tim.tccr1b().write(|w| w.cs1().set(cs));
+	},
+	arm: |tim, ticks| {
+	    let now = tim.tcnt1().read().bits();
+	    tim.ocr1a().write(|w| w.set(now.wrapping_add(ticks)));
+	    tim.tifr1().write(|w| w.ocf1a().set_bit());
+	    tim.timsk1().modify(|_, w| w.ocie1a().set_bit());
+	    // If the counter reached the compare value before the flag was cleared, the match is
+	    // lost; if it passed before the compare value was written, there is none until the
+	    // counter wraps around.  Read the counter before the flag, so a match in between is
+	    // still seen as pending.
+	    let counter = tim.tcnt1().read().bits();
+	    avr_hal_generic::oneshot::compare_passed(now, ticks, counter)
+		&& tim.tifr1().read().ocf1a().bit_is_clear()
+	},
+	disarm: |tim| {
+	    tim.timsk1().modify(|_, w| w.ocie1a().clear_bit());
+	},
+    }
+}
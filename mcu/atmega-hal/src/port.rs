@@ -18,6 +18,7 @@
 //! ```
 
 pub use avr_hal_generic::port::mode;
+pub use avr_hal_generic::port::PinLocation;
 pub use avr_hal_generic::port::PinMode;
 pub use avr_hal_generic::port::PinOps;
 
@@ -45,7 +46,7 @@ avr_hal_generic::impl_port_traditional_old! {
     }
 }
 
-#[cfg(feature = "atmega328pb")]
+#[cfg(any(feature = "atmega168pb", feature = "atmega328pb"))]
 avr_hal_generic::impl_port_traditional! {
     enum Ports {
 	B: crate::pac::PORTB = [0, 1, 2, 3, 4, 5, 6, 7],
@@ -114,3 +115,30 @@ avr_hal_generic::impl_port_traditional_old! {
 	D: crate::pac::PORTD = [0, 1, 2, 3, 4, 5, 6, 7],
     }
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn transaction_stages_per_port() {
+		let mut tx = PortTransaction::new();
+		tx.set_high(&PB5 { _private: () })
+			.set_low(&PB0 { _private: () })
+			.set_high(&PD2 { _private: () });
+		assert_eq!((tx.set_b, tx.clear_b), (0b0010_0000, 0b0000_0001));
+		assert_eq!((tx.set_d, tx.clear_d), (0b0000_0100, 0));
+		assert_eq!((tx.set_c, tx.clear_c), (0, 0));
+	}
+
+	#[test]
+	fn transaction_last_write_wins() {
+		let mut tx = PortTransaction::new();
+		tx.set_high(&PB5 { _private: () })
+			.set_low(&PB5 { _private: () });
+		assert_eq!((tx.set_b, tx.clear_b), (0, 0b0010_0000));
+
+		tx.set(&PB5 { _private: () }, true);
+		assert_eq!((tx.set_b, tx.clear_b), (0b0010_0000, 0));
+	}
+}
@@ -0,0 +1,74 @@
+//! Low-power operation
+//!
+//! Battery-powered sensor nodes usually spend almost all of their time in the power-down sleep
+//! mode and only wake up every few seconds to take a measurement.  In power-down, all clocks are
+//! stopped, so the only periodic wake-up source is the watchdog, which runs from its own 128 kHz
+//! oscillator.  [`periodic_wake()`] bundles this pattern: it starts the watchdog in interrupt
+//! mode, enters power-down, and runs the work closure after every wake-up.
+//!
+//! The watchdog interrupt must be handled, even if the handler does nothing, otherwise it jumps
+//! to the reset vector:
+//!
+//! ```ignore
+//! #[avr_device::interrupt(atmega328p)]
+//! fn WDT() {}
+//!
+//! let mut watchdog = Wdt::new(dp.WDT, &dp.CPU.mcusr());
+//!
+//! // Sleep for 8 s, take a measurement, repeat
+//! power::periodic_wake(&mut watchdog, &dp.CPU, Timeout::Ms8000, || {
+//!     let reading = sensor.read().unwrap();
+//!     radio.send(reading);
+//! });
+//! ```
+//!
+//! # Sleep current
+//! An ATmega328P in power-down with the watchdog running draws about 4 µA at 3 V (about 6 µA at
+//! 5 V), compared to several mA when active.  These figures are only reached if nothing else is
+//! left running:
+//!
+//! - The ADC keeps drawing current in power-down; disable it (`ADCSRA.ADEN`) before sleeping.
+//! - The brown-out detector adds about 20 µA.  Disable it through the fuses if the supply allows.
+//! - Input pins which are left floating draw current through their input buffers; enable the
+//!   pull-ups or drive them.
+//!
+//! On Arduino boards, the voltage regulator, the USB-serial chip and the power LED draw far more
+//! than the MCU (tens of mA on an Uno), so the figures above only apply to a bare chip.
+//!
+//! The watchdog oscillator is not calibrated: the actual period can be off by 10% or more and
+//! varies with supply voltage and temperature.  Use an external RTC if the wake-ups must be
+//! accurate.
+use crate::wdt::Timeout;
+use crate::wdt::Wdt;
+
+/// Sleep in power-down and call `work` every `timeout`, forever.  See the
+/// [module documentation](self).
+///
+/// Interrupts are enabled globally.  Other interrupts (e.g. a pin change) also wake the MCU and
+/// thus run `work` early.
+///
+/// # Panics
+/// Panics if `timeout` is not supported by this MCU.
+pub fn periodic_wake<F: FnMut()>(
+	wdt: &mut Wdt,
+	cpu: &crate::pac::CPU,
+	timeout: Timeout,
+	mut work: F,
+) -> ! {
+	wdt.start_interrupt(timeout)
+		.expect("watchdog timeout not supported by this MCU");
+	// SAFETY: The watchdog interrupt has to be enabled to wake up at all.
+	unsafe { avr_device::interrupt::enable() };
+	loop {
+		power_down(cpu);
+		work();
+	}
+}
+
+/// Enter power-down until the next interrupt.
+fn power_down(cpu: &crate::pac::CPU) {
+	cpu.smcr().write(|w| w.sm().pdown().se().set_bit());
+	avr_device::asm::sleep();
+	// Clearing SE after waking up prevents accidentally entering sleep mode.
+	cpu.smcr().write(|w| w.se().clear_bit());
+}
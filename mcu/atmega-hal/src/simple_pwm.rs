@@ -1,6 +1,10 @@
 pub use avr_hal_generic::simple_pwm::IntoPwmPin;
 pub use avr_hal_generic::simple_pwm::Prescaler;
+pub use avr_hal_generic::simple_pwm::PwmDac;
 pub use avr_hal_generic::simple_pwm::PwmPinOps;
+pub use avr_hal_generic::simple_pwm::WaveGen;
+pub use avr_hal_generic::simple_pwm::SINE_TABLE;
+pub use avr_hal_generic::simple_pwm::WAVE_TABLE_LEN;
 
 #[allow(unused_imports)]
 use crate::port::*;
@@ -9,6 +13,7 @@ use crate::port::*;
 	feature = "atmega48p",
 	feature = "atmega88p",
 	feature = "atmega168",
+	feature = "atmega168pb",
 	feature = "atmega328p",
 	feature = "atmega328pb"
 ))]
@@ -27,6 +32,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer0Pwm {
 	timer: crate::pac::TC0,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr0a().modify(|_r, w| w.wgm0().pwm_fast());
 	    tim.tccr0b().modify(|_r, w| match prescaler {
@@ -63,6 +69,7 @@ avr_hal_generic::impl_simple_pwm! {
 	feature = "atmega48p",
 	feature = "atmega88p",
 	feature = "atmega168",
+	feature = "atmega168pb",
 	feature = "atmega328p",
 	feature = "atmega328pb"
 ))]
@@ -81,6 +88,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer1Pwm {
 	timer: crate::pac::TC1,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr1a().modify(|_r, w| w.wgm1().set(0b01));
 	    tim.tccr1b().modify(|_r, w| {
@@ -121,6 +129,7 @@ avr_hal_generic::impl_simple_pwm! {
 	feature = "atmega48p",
 	feature = "atmega88p",
 	feature = "atmega168",
+	feature = "atmega168pb",
 	feature = "atmega328p",
 	feature = "atmega328pb"
 ))]
@@ -139,6 +148,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer2Pwm {
 	timer: crate::pac::TC2,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr2a().modify(|_r, w| w.wgm2().pwm_fast());
 	    tim.tccr2b().modify(|_r, w| match prescaler {
@@ -176,6 +186,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// Use `TC3` for PWM (pins `PD0`, `PD2`)
     pub struct Timer3Pwm {
 	timer: crate::pac::TC3,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr3a().modify(|_r, w| w.wgm3().set(0b01));
 	    tim.tccr3b().modify(|_r, w| {
@@ -217,6 +228,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// Use `TC4` for PWM (pins `PD1`, `PD2`)
     pub struct Timer4Pwm {
 	timer: crate::pac::TC4,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr4a().modify(|_r, w| w.wgm4().set(0b01));
 	    tim.tccr4b().modify(|_r, w| {
@@ -269,6 +281,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer0Pwm {
 	timer: crate::pac::TC0,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr0a().modify(|_r, w| w.wgm0().pwm_fast());
 	    tim.tccr0b().modify(|_r, w| match prescaler {
@@ -318,6 +331,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer1Pwm {
 	timer: crate::pac::TC1,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr1a().modify(|_r, w| w.wgm1().set(0b01));
 	    tim.tccr1b().modify(|_r, w| {
@@ -380,6 +394,8 @@ avr_hal_generic::impl_simple_pwm! {
 
     pub struct Timer2Pwm {
 	timer: crate::pac::TC2,
+	// Phase correct PWM, counting up and down again
+	period: 510,
 	init: |tim, prescaler| {
 	    tim.tccr2a().modify(|_r, w| w.wgm2().set(0b01));
 	    tim.tccr2b().modify(|_r, w| {
@@ -433,6 +449,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer3Pwm {
 	timer: crate::pac::TC3,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr3a().modify(|_r, w| w.wgm3().set(0b01));
 	    tim.tccr3b().modify(|_r, w| {
@@ -496,6 +513,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer4Pwm {
 	timer: crate::pac::TC4,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr4a().modify(|_r, w| w.wgm4().set(0b01));
 	    tim.tccr4b().modify(|_r, w| {
@@ -559,6 +577,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer5Pwm {
 	timer: crate::pac::TC5,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr5a().modify(|_r, w| w.wgm5().set(0b01));
 	    tim.tccr5b().modify(|_r, w| {
@@ -621,6 +640,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer0Pwm {
 	timer: crate::pac::TC0,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr0a().modify(|_r, w| w.wgm0().pwm_fast());
 	    tim.tccr0b().modify(|_r, w| match prescaler {
@@ -670,6 +690,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer1Pwm {
 	timer: crate::pac::TC1,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr1a().modify(|_r, w| w.wgm1().set(0b01));
 	    tim.tccr1b().modify(|_r, w| w.wgm1().set(0b01));
@@ -728,6 +749,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer3Pwm {
 	timer: crate::pac::TC3,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr3a().modify(|_r, w| w.wgm3().set(0b01));
 	    tim.tccr3b().modify(|_r, w| w.wgm3().set(0b01));
@@ -770,6 +792,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer4Pwm {
 	timer: crate::pac::TC4,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr4a().modify(|_r, w| w.pwm4a().set_bit());
 	    tim.tccr4a().modify(|_r, w| w.pwm4b().set_bit());
@@ -830,6 +853,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer0Pwm {
 	timer: crate::pac::TC0,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr0a().modify(|_r, w| w.wgm0().pwm_fast());
 	    tim.tccr0b().modify(|_r, w| match prescaler {
@@ -878,6 +902,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer1Pwm {
 	timer: crate::pac::TC1,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr1a().modify(|_r, w| w.wgm1().set(0b01));
 	    tim.tccr1b().modify(|_r, w| {
@@ -930,6 +955,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer2Pwm {
 	timer: crate::pac::TC2,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr2a().modify(|_r, w| w.wgm2().pwm_fast());
 	    tim.tccr2b().modify(|_r, w| match prescaler {
@@ -967,6 +993,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// Use `TC3` for PWM (pins `PB6`, `PB7`)
     pub struct Timer3Pwm {
 	timer: crate::pac::TC3,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr3a().modify(|_r, w| w.wgm3().set(0b01));
 	    tim.tccr3b().modify(|_r, w| {
@@ -1019,6 +1046,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer1Pwm {
 	timer: crate::pac::TC1,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr1a().modify(|_r, w| w.wgm1().set(0b01));
 	    tim.tccr1b().modify(|_r, w| {
@@ -1071,6 +1099,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer2Pwm {
 	timer: crate::pac::TC2,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr2().modify(|_r, w| w.wgm20().set_bit().wgm21().set_bit());
 	    tim.tccr2().modify(|_r, w| match prescaler {
@@ -1109,6 +1138,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer0Pwm {
 	timer: crate::pac::TC0,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr0a().modify(|_r, w| w.wgm0().set(0b11));
 	    tim.tccr0a().modify(|_r, w| w.com0a().set(0b00));
@@ -1152,6 +1182,8 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer1Pwm {
 	timer: crate::pac::TC1,
+	// Phase correct PWM, counting up and down again
+	period: 510,
 	init: |tim, prescaler| {
 	    tim.tccr1a().modify(|_r, w| w.wgm1().set(0b01));
 	    tim.tccr1a().modify(|_r, w| w.com1a().set(0b00));
@@ -1193,3 +1225,35 @@ avr_hal_generic::impl_simple_pwm! {
 	},
     }
 }
+
+#[cfg(test)]
+mod tests {
+	#[allow(unused_imports)]
+	use super::*;
+	#[allow(unused_imports)]
+	use avr_hal_generic::simple_pwm::FAST_PWM_PERIOD;
+	#[allow(unused_imports)]
+	use avr_hal_generic::simple_pwm::PHASE_CORRECT_PWM_PERIOD;
+
+	#[cfg(any(feature = "atmega1280", feature = "atmega2560"))]
+	#[test]
+	fn mega_timer2_is_phase_correct() {
+		assert_eq!(Timer0Pwm::PERIOD_TICKS, FAST_PWM_PERIOD);
+		assert_eq!(Timer1Pwm::PERIOD_TICKS, FAST_PWM_PERIOD);
+		assert_eq!(Timer2Pwm::PERIOD_TICKS, PHASE_CORRECT_PWM_PERIOD);
+	}
+
+	#[cfg(any(feature = "atmega164pa", feature = "atmega16"))]
+	#[test]
+	fn timer1_is_phase_correct() {
+		assert_eq!(Timer1Pwm::PERIOD_TICKS, PHASE_CORRECT_PWM_PERIOD);
+	}
+
+	#[cfg(any(feature = "atmega48p", feature = "atmega168", feature = "atmega328p"))]
+	#[test]
+	fn all_timers_fast_pwm() {
+		assert_eq!(Timer0Pwm::PERIOD_TICKS, FAST_PWM_PERIOD);
+		assert_eq!(Timer1Pwm::PERIOD_TICKS, FAST_PWM_PERIOD);
+		assert_eq!(Timer2Pwm::PERIOD_TICKS, FAST_PWM_PERIOD);
+	}
+}
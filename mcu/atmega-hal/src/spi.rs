@@ -92,7 +92,7 @@ avr_hal_generic::impl_spi! {
     cs: port::PB2,
 }
 
-#[cfg(feature = "atmega328pb")]
+#[cfg(any(feature = "atmega168pb", feature = "atmega328pb"))]
 pub type Spi0 = avr_hal_generic::spi::Spi<
 	crate::Atmega,
 	crate::pac::SPI0,
@@ -101,7 +101,7 @@ pub type Spi0 = avr_hal_generic::spi::Spi<
 	port::PB4,
 	port::PB2,
 >;
-#[cfg(feature = "atmega328pb")]
+#[cfg(any(feature = "atmega168pb", feature = "atmega328pb"))]
 avr_hal_generic::impl_spi! {
     hal: crate::Atmega,
     peripheral: crate::pac::SPI0,
@@ -110,6 +110,9 @@ avr_hal_generic::impl_spi! {
     miso: port::PB4,
     cs: port::PB2,
 }
+/// The ATmega168PB only has a single SPI peripheral.
+#[cfg(feature = "atmega168pb")]
+pub type Spi = Spi0;
 #[cfg(feature = "atmega328pb")]
 pub type Spi1 = avr_hal_generic::spi::Spi<
 	crate::Atmega,
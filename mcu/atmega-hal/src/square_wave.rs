@@ -0,0 +1,119 @@
+//! Square wave generation
+//!
+//! Check the documentation of [`avr_hal_generic::square_wave`] for details.
+//!
+//! # Example
+//! ```
+//! let dp = atmega_hal::Peripherals::take().unwrap();
+//! let pins = atmega_hal::pins!(dp);
+//!
+//! // 32.768 kHz clock for an external RTC chip
+//! let wave = Timer1SquareWave::<CoreClock>::new(dp.TC1, pins.pb1.into_output(), 32_768);
+//! let f = wave.frequency();
+//! // f.achieved == 32_787, f.error == 19 at 16 MHz
+//! ```
+
+pub use avr_hal_generic::simple_pwm::Prescaler;
+pub use avr_hal_generic::square_wave::Frequency;
+pub use avr_hal_generic::square_wave::Melody;
+pub use avr_hal_generic::square_wave::ToneOutput;
+
+#[allow(unused_imports)]
+use crate::port::*;
+
+#[cfg(any(
+	feature = "atmega48p",
+	feature = "atmega88p",
+	feature = "atmega168",
+	feature = "atmega168pb",
+	feature = "atmega328p",
+	feature = "atmega328pb"
+))]
+avr_hal_generic::impl_square_wave! {
+    /// Use `TC1` to output a square wave on `OC1A` (pin `PB1`)
+    pub struct Timer1SquareWave {
+	timer: crate::pac::TC1,
+	pin: PB1,
+	start: |tim, prescaler, ocr| {
+	    tim.tccr1b().reset();
+	    // Toggle OC1A on compare match, CTC mode with TOP = OCR1A
+	    tim.tccr1a().write(|w| w.com1a().match_toggle().wgm1().set(0b00));
+	    tim.ocr1a().write(|w| w.set(ocr));
+	    tim.tcnt1().reset();
+	    tim.tccr1b().write(|w| {
+		w.wgm1().set(0b01);
+		match prescaler {
+		    Prescaler::Direct => w.cs1().direct(),
+		    Prescaler::Prescale8 => w.cs1().prescale_8(),
+		    Prescaler::Prescale64 => w.cs1().prescale_64(),
+		    Prescaler::Prescale256 => w.cs1().prescale_256(),
+		    Prescaler::Prescale1024 => w.cs1().prescale_1024(),
+		}
+	    });
+	},
+	stop: |tim| {
+	    tim.tccr1b().reset();
+	    tim.tccr1a().reset();
+	},
+    }
+}
+
+#[cfg(any(feature = "atmega32u4", feature = "atmega1280", feature = "atmega2560"))]
+avr_hal_generic::impl_square_wave! {
+    /// Use `TC1` to output a square wave on `OC1A` (pin `PB5`)
+    pub struct Timer1SquareWave {
+	timer: crate::pac::TC1,
+	pin: PB5,
+	start: |tim, prescaler, ocr| {
+	    tim.tccr1b().reset();
+	    // Toggle OC1A on compare match, CTC mode with TOP = OCR1A
+	    tim.tccr1a().write(|w| w.com1a().match_toggle().wgm1().set(0b00));
+	    tim.ocr1a().write(|w| w.set(ocr));
+	    tim.tcnt1().reset();
+	    tim.tccr1b().write(|w| {
+		w.wgm1().set(0b01);
+		match prescaler {
+		    Prescaler::Direct => w.cs1().direct(),
+		    Prescaler::Prescale8 => w.cs1().prescale_8(),
+		    Prescaler::Prescale64 => w.cs1().prescale_64(),
+		    Prescaler::Prescale256 => w.cs1().prescale_256(),
+		    Prescaler::Prescale1024 => w.cs1().prescale_1024(),
+		}
+	    });
+	},
+	stop: |tim| {
+	    tim.tccr1b().reset();
+	    tim.tccr1a().reset();
+	},
+    }
+}
+
+#[cfg(feature = "atmega1284p")]
+avr_hal_generic::impl_square_wave! {
+    /// Use `TC1` to output a square wave on `OC1A` (pin `PD5`)
+    pub struct Timer1SquareWave {
+	timer: crate::pac::TC1,
+	pin: PD5,
+	start: |tim, prescaler, ocr| {
+	    tim.tccr1b().reset();
+	    // Toggle OC1A on compare match, CTC mode with TOP = OCR1A
+	    tim.tccr1a().write(|w| w.com1a().match_toggle().wgm1().set(0b00));
+	    tim.ocr1a().write(|w| w.set(ocr));
+	    tim.tcnt1().reset();
+	    tim.tccr1b().write(|w| {
+		w.wgm1().set(0b01);
+		match prescaler {
+		    Prescaler::Direct => w.cs1().direct(),
+		    Prescaler::Prescale8 => w.cs1().prescale_8(),
+		    Prescaler::Prescale64 => w.cs1().prescale_64(),
+		    Prescaler::Prescale256 => w.cs1().prescale_256(),
+		    Prescaler::Prescale1024 => w.cs1().prescale_1024(),
+		}
+	    });
+	},
+	stop: |tim| {
+	    tim.tccr1b().reset();
+	    tim.tccr1a().reset();
+	},
+    }
+}
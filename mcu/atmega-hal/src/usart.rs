@@ -39,6 +39,10 @@ pub type UsartWriter<USART, RX, TX, CLOCK> =
 	avr_hal_generic::usart::UsartWriter<crate::Atmega, USART, RX, TX, CLOCK>;
 pub type UsartReader<USART, RX, TX, CLOCK> =
 	avr_hal_generic::usart::UsartReader<crate::Atmega, USART, RX, TX, CLOCK>;
+pub type Rs485Usart<USART, RX, TX, CLOCK, DIR> =
+	avr_hal_generic::usart::Rs485Usart<crate::Atmega, USART, RX, TX, CLOCK, DIR>;
+pub type BackgroundWriter<USART, RX, TX, CLOCK> =
+	avr_hal_generic::usart::BackgroundWriter<crate::Atmega, USART, RX, TX, CLOCK>;
 
 #[cfg(any(feature = "atmega16"))]
 pub type Usart0<CLOCK> = Usart<
@@ -50,6 +54,7 @@ pub type Usart0<CLOCK> = Usart<
 #[cfg(any(
 	feature = "atmega88p",
 	feature = "atmega168",
+	feature = "atmega168pb",
 	feature = "atmega328p",
 	feature = "atmega328pb",
 	feature = "atmega1284p",
@@ -61,9 +66,29 @@ pub type Usart0<CLOCK> = Usart<
 	port::Pin<port::mode::Output, port::PD1>,
 	CLOCK,
 >;
+#[cfg(any(feature = "atmega88p", feature = "atmega168", feature = "atmega328p"))]
+const USART0_VECTORS: [u8; 3] = [
+	crate::pac::Interrupt::USART_RX as u8,
+	crate::pac::Interrupt::USART_UDRE as u8,
+	crate::pac::Interrupt::USART_TX as u8,
+];
+#[cfg(any(
+	feature = "atmega168pb",
+	feature = "atmega328pb",
+	feature = "atmega1280",
+	feature = "atmega1284p",
+	feature = "atmega164pa",
+	feature = "atmega2560"
+))]
+const USART0_VECTORS: [u8; 3] = [
+	crate::pac::Interrupt::USART0_RX as u8,
+	crate::pac::Interrupt::USART0_UDRE as u8,
+	crate::pac::Interrupt::USART0_TX as u8,
+];
 #[cfg(any(
 	feature = "atmega88p",
 	feature = "atmega168",
+	feature = "atmega168pb",
 	feature = "atmega328p",
 	feature = "atmega328pb",
 	feature = "atmega1284p",
@@ -75,6 +100,7 @@ avr_hal_generic::impl_usart_traditional! {
     register_suffix: 0,
     rx: port::PD0,
     tx: port::PD1,
+    vectors: USART0_VECTORS,
 }
 
 #[cfg(feature = "atmega328pb")]
@@ -84,6 +110,19 @@ pub type Usart1<CLOCK> = Usart<
 	port::Pin<port::mode::Output, port::PB3>,
 	CLOCK,
 >;
+#[cfg(any(
+	feature = "atmega328pb",
+	feature = "atmega32u4",
+	feature = "atmega1280",
+	feature = "atmega2560",
+	feature = "atmega1284p",
+	feature = "atmega164pa"
+))]
+const USART1_VECTORS: [u8; 3] = [
+	crate::pac::Interrupt::USART1_RX as u8,
+	crate::pac::Interrupt::USART1_UDRE as u8,
+	crate::pac::Interrupt::USART1_TX as u8,
+];
 #[cfg(feature = "atmega328pb")]
 avr_hal_generic::impl_usart_traditional! {
     hal: crate::Atmega,
@@ -91,6 +130,7 @@ avr_hal_generic::impl_usart_traditional! {
     register_suffix: 1,
     rx: port::PB4,
     tx: port::PB3,
+    vectors: USART1_VECTORS,
 }
 
 #[cfg(any(
@@ -120,6 +160,7 @@ avr_hal_generic::impl_usart_traditional! {
     register_suffix: 1,
     rx: port::PD2,
     tx: port::PD3,
+    vectors: USART1_VECTORS,
 }
 
 #[cfg(any(feature = "atmega128a", feature = "atmega1280", feature = "atmega2560"))]
@@ -136,6 +177,7 @@ avr_hal_generic::impl_usart_traditional! {
     register_suffix: 0,
     rx: port::PE0,
     tx: port::PE1,
+    vectors: USART0_VECTORS,
 }
 
 #[cfg(any(feature = "atmega1280", feature = "atmega2560"))]
@@ -146,12 +188,19 @@ pub type Usart2<CLOCK> = Usart<
 	CLOCK,
 >;
 #[cfg(any(feature = "atmega1280", feature = "atmega2560"))]
+const USART2_VECTORS: [u8; 3] = [
+	crate::pac::Interrupt::USART2_RX as u8,
+	crate::pac::Interrupt::USART2_UDRE as u8,
+	crate::pac::Interrupt::USART2_TX as u8,
+];
+#[cfg(any(feature = "atmega1280", feature = "atmega2560"))]
 avr_hal_generic::impl_usart_traditional! {
     hal: crate::Atmega,
     peripheral: crate::pac::USART2,
     register_suffix: 2,
     rx: port::PH0,
     tx: port::PH1,
+    vectors: USART2_VECTORS,
 }
 
 #[cfg(any(feature = "atmega1280", feature = "atmega2560"))]
@@ -162,12 +211,19 @@ pub type Usart3<CLOCK> = Usart<
 	CLOCK,
 >;
 #[cfg(any(feature = "atmega1280", feature = "atmega2560"))]
+const USART3_VECTORS: [u8; 3] = [
+	crate::pac::Interrupt::USART3_RX as u8,
+	crate::pac::Interrupt::USART3_UDRE as u8,
+	crate::pac::Interrupt::USART3_TX as u8,
+];
+#[cfg(any(feature = "atmega1280", feature = "atmega2560"))]
 avr_hal_generic::impl_usart_traditional! {
     hal: crate::Atmega,
     peripheral: crate::pac::USART3,
     register_suffix: 3,
     rx: port::PJ0,
     tx: port::PJ1,
+    vectors: USART3_VECTORS,
 }
 
 #[cfg(any(feature = "atmega8", feature = "atmega32a"))]
@@ -241,10 +297,26 @@ impl
 		// Call flush to make sure the data-register is empty
 		self.raw_flush()?;
 
-		self.udr().write(|w| w.set(byte));
+		avr_hal_generic::avr_device::interrupt::free(|_| {
+			self.udr().write(|w| w.set(byte));
+			// Clear TXC so it only gets set again once this byte was sent.  FE, DOR and UPE
+			// must be written as zero, so only U2X and MPCM are kept.
+			let a = self.ucsra().read();
+			self.ucsra().write(|w| {
+				w.u2x().bit(a.u2x().bit())
+					.mpcm()
+					.bit(a.mpcm().bit())
+					.txc()
+					.set_bit()
+			});
+		});
 		Ok(())
 	}
 
+	fn raw_tx_complete(&mut self) -> bool {
+		self.ucsra().read().txc().bit_is_set()
+	}
+
 	fn raw_read(&mut self) -> avr_hal_generic::nb::Result<u8, core::convert::Infallible> {
 		if self.ucsra().read().rxc().bit_is_clear() {
 			return Err(avr_hal_generic::nb::Error::WouldBlock);
@@ -266,6 +338,54 @@ impl
 			}
 		}
 	}
+
+	unsafe fn raw_mask_interrupt(event: crate::usart::Event) {
+		Self::steal().raw_interrupt(event, false);
+	}
+
+	fn raw_vector(event: crate::usart::Event) -> u8 {
+		match event {
+			crate::usart::Event::RxComplete => crate::pac::Interrupt::USART_RXC as u8,
+			crate::usart::Event::DataRegisterEmpty => {
+				crate::pac::Interrupt::USART_UDRE as u8
+			}
+			crate::usart::Event::TxComplete => crate::pac::Interrupt::USART_TXC as u8,
+		}
+	}
+
+	fn raw_set_receiver(&mut self, enable: bool) {
+		self.ucsrb().modify(|_, w| w.rxen().bit(enable));
+	}
+
+	fn raw_set_transmitter(&mut self, enable: bool) {
+		self.ucsrb().modify(|_, w| w.txen().bit(enable));
+	}
+
+	fn raw_set_nine_bit(&mut self, enable: bool) {
+		self.ucsrb().modify(|_, w| w.ucsz2().bit(enable));
+	}
+
+	fn raw_set_mpcm(&mut self, enable: bool) {
+		// TXC is cleared by writing a one, and FE, DOR and UPE must be written as zero, so only
+		// U2X is kept.
+		let a = self.ucsra().read();
+		self.ucsra()
+			.write(|w| w.u2x().bit(a.u2x().bit()).mpcm().bit(enable));
+	}
+
+	fn raw_set_tx_bit8(&mut self, bit: bool) {
+		self.ucsrb().modify(|_, w| w.txb8().bit(bit));
+	}
+
+	fn raw_read_9bit(&mut self) -> avr_hal_generic::nb::Result<u16, core::convert::Infallible> {
+		if self.ucsra().read().rxc().bit_is_clear() {
+			return Err(avr_hal_generic::nb::Error::WouldBlock);
+		}
+
+		// RXB8 must be read before UDR, which pops the frame from the buffer.
+		let bit8 = self.ucsrb().read().rxb8().bit();
+		Ok((u16::from(bit8) << 8) | u16::from(self.udr().read().bits()))
+	}
 }
 
 // TODO: ATmega128A USART1 is also different from other atmegas
@@ -324,10 +444,26 @@ impl
 		// Call flush to make sure the data-register is empty
 		self.raw_flush()?;
 
-		self.udr1().write(|w| w.set(byte));
+		avr_hal_generic::avr_device::interrupt::free(|_| {
+			self.udr1().write(|w| w.set(byte));
+			// Clear TXC so it only gets set again once this byte was sent.  FE, DOR and UPE
+			// must be written as zero, so only U2X and MPCM are kept.
+			let a = self.ucsr1a().read();
+			self.ucsr1a().write(|w| {
+				w.u2x1().bit(a.u2x1().bit())
+					.mpcm1()
+					.bit(a.mpcm1().bit())
+					.txc1()
+					.set_bit()
+			});
+		});
 		Ok(())
 	}
 
+	fn raw_tx_complete(&mut self) -> bool {
+		self.ucsr1a().read().txc1().bit_is_set()
+	}
+
 	fn raw_read(&mut self) -> avr_hal_generic::nb::Result<u8, core::convert::Infallible> {
 		if self.ucsr1a().read().rxc1().bit_is_clear() {
 			return Err(avr_hal_generic::nb::Error::WouldBlock);
@@ -349,6 +485,54 @@ impl
 			}
 		}
 	}
+
+	unsafe fn raw_mask_interrupt(event: crate::usart::Event) {
+		Self::steal().raw_interrupt(event, false);
+	}
+
+	fn raw_vector(event: crate::usart::Event) -> u8 {
+		match event {
+			crate::usart::Event::RxComplete => crate::pac::Interrupt::USART1_RX as u8,
+			crate::usart::Event::DataRegisterEmpty => {
+				crate::pac::Interrupt::USART1_UDRE as u8
+			}
+			crate::usart::Event::TxComplete => crate::pac::Interrupt::USART1_TX as u8,
+		}
+	}
+
+	fn raw_set_receiver(&mut self, enable: bool) {
+		self.ucsr1b().modify(|_, w| w.rxen1().bit(enable));
+	}
+
+	fn raw_set_transmitter(&mut self, enable: bool) {
+		self.ucsr1b().modify(|_, w| w.txen1().bit(enable));
+	}
+
+	fn raw_set_nine_bit(&mut self, enable: bool) {
+		self.ucsr1b().modify(|_, w| w.ucsz12().bit(enable));
+	}
+
+	fn raw_set_mpcm(&mut self, enable: bool) {
+		// TXC is cleared by writing a one, and FE, DOR and UPE must be written as zero, so only
+		// U2X is kept.
+		let a = self.ucsr1a().read();
+		self.ucsr1a()
+			.write(|w| w.u2x1().bit(a.u2x1().bit()).mpcm1().bit(enable));
+	}
+
+	fn raw_set_tx_bit8(&mut self, bit: bool) {
+		self.ucsr1b().modify(|_, w| w.txb81().bit(bit));
+	}
+
+	fn raw_read_9bit(&mut self) -> avr_hal_generic::nb::Result<u16, core::convert::Infallible> {
+		if self.ucsr1a().read().rxc1().bit_is_clear() {
+			return Err(avr_hal_generic::nb::Error::WouldBlock);
+		}
+
+		// RXB8 must be read before UDR, which pops the frame from the buffer.
+		let bit8 = self.ucsr1b().read().rxb81().bit();
+		Ok((u16::from(bit8) << 8) | u16::from(self.udr1().read().bits()))
+	}
 }
 
 // TODO: ATmega128A USART0 is also different from other atmegas
@@ -405,10 +589,26 @@ impl
 		// Call flush to make sure the data-register is empty
 		self.raw_flush()?;
 
-		self.udr0().write(|w| w.set(byte));
+		avr_hal_generic::avr_device::interrupt::free(|_| {
+			self.udr0().write(|w| w.set(byte));
+			// Clear TXC so it only gets set again once this byte was sent.  FE, DOR and UPE
+			// must be written as zero, so only U2X and MPCM are kept.
+			let a = self.ucsr0a().read();
+			self.ucsr0a().write(|w| {
+				w.u2x0().bit(a.u2x0().bit())
+					.mpcm0()
+					.bit(a.mpcm0().bit())
+					.txc0()
+					.set_bit()
+			});
+		});
 		Ok(())
 	}
 
+	fn raw_tx_complete(&mut self) -> bool {
+		self.ucsr0a().read().txc0().bit_is_set()
+	}
+
 	fn raw_read(&mut self) -> avr_hal_generic::nb::Result<u8, core::convert::Infallible> {
 		if self.ucsr0a().read().rxc0().bit_is_clear() {
 			return Err(avr_hal_generic::nb::Error::WouldBlock);
@@ -430,4 +630,52 @@ impl
 			}
 		}
 	}
+
+	unsafe fn raw_mask_interrupt(event: crate::usart::Event) {
+		Self::steal().raw_interrupt(event, false);
+	}
+
+	fn raw_vector(event: crate::usart::Event) -> u8 {
+		match event {
+			crate::usart::Event::RxComplete => crate::pac::Interrupt::USART0_RX as u8,
+			crate::usart::Event::DataRegisterEmpty => {
+				crate::pac::Interrupt::USART0_UDRE as u8
+			}
+			crate::usart::Event::TxComplete => crate::pac::Interrupt::USART0_TX as u8,
+		}
+	}
+
+	fn raw_set_receiver(&mut self, enable: bool) {
+		self.ucsr0b().modify(|_, w| w.rxen0().bit(enable));
+	}
+
+	fn raw_set_transmitter(&mut self, enable: bool) {
+		self.ucsr0b().modify(|_, w| w.txen0().bit(enable));
+	}
+
+	fn raw_set_nine_bit(&mut self, enable: bool) {
+		self.ucsr0b().modify(|_, w| w.ucsz02().bit(enable));
+	}
+
+	fn raw_set_mpcm(&mut self, enable: bool) {
+		// TXC is cleared by writing a one, and FE, DOR and UPE must be written as zero, so only
+		// U2X is kept.
+		let a = self.ucsr0a().read();
+		self.ucsr0a()
+			.write(|w| w.u2x0().bit(a.u2x0().bit()).mpcm0().bit(enable));
+	}
+
+	fn raw_set_tx_bit8(&mut self, bit: bool) {
+		self.ucsr0b().modify(|_, w| w.txb80().bit(bit));
+	}
+
+	fn raw_read_9bit(&mut self) -> avr_hal_generic::nb::Result<u16, core::convert::Infallible> {
+		if self.ucsr0a().read().rxc0().bit_is_clear() {
+			return Err(avr_hal_generic::nb::Error::WouldBlock);
+		}
+
+		// RXB8 must be read before UDR, which pops the frame from the buffer.
+		let bit8 = self.ucsr0b().read().rxb80().bit();
+		Ok((u16::from(bit8) << 8) | u16::from(self.udr0().read().bits()))
+	}
 }
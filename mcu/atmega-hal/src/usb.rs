@@ -0,0 +1,594 @@
+//! USB controller helpers
+//!
+//! `atmega-hal` does not contain a USB device stack.  This module provides the surrounding
+//! pieces a stack and the firmware need: the power configuration which ends up in the
+//! configuration descriptor and sensing of the `VBUS` line to detect cable connection.
+//!
+//! # Example
+//! ```
+//! const POWER: PowerConfig = PowerConfig::bus_powered(100);
+//!
+//! let dp = atmega_hal::Peripherals::take().unwrap();
+//! usb::enable_vbus_sensing(&dp.USB_DEVICE);
+//!
+//! loop {
+//!     if usb::vbus_present() {
+//!         // Attach to the bus, using `POWER.attributes()` and `POWER.max_power()` in the
+//!         // configuration descriptor.
+//!     }
+//! }
+//! ```
+//!
+//! # VBUS Sensing and Self-Powered Designs
+//! A bus-powered device is only running while the cable is connected, so `VBUS` is always
+//! present.  A self-powered device keeps running without a host.  The USB specification forbids
+//! such a device from pulling up `D+`/`D-` while `VBUS` is absent, so it must only attach once
+//! [`vbus_present()`] reports a connection and detach when it goes away.  For this to work, the
+//! `VBUS` pin of the ATmega32U4 must be wired to the `VBUS` line of the connector even if the
+//! board is not powered from it.
+//!
+//! # Frame Number as a Timebase
+//! While connected, the host sends a start-of-frame (SOF) packet every millisecond and the
+//! controller stores its 11-bit frame number in `UDFNUM`.  This is a free, crystal-accurate
+//! millisecond tick without dedicating a timer: [`frame_number()`] reads the counter, and
+//! [`take_sof()`] reports (and acknowledges) that a new frame started since the last call.
+//! Alternatively, [`enable_sof_interrupt()`] makes every SOF raise the `USB_GEN` interrupt, so
+//! the application can hook its own 1 ms tick (the handler must call [`take_sof()`] to clear the
+//! flag).
+//!
+//! **Note**: The frame number only advances while the device is attached and the host has
+//! enumerated it.  It stops when the cable is removed or the bus is suspended (the host stops
+//! sending SOF packets), and the controller clock has to be running, which is the job of the USB
+//! stack.
+//!
+//! ```
+//! let mut last = usb::frame_number();
+//! loop {
+//!     // ... poll the USB stack ...
+//!
+//!     let frame = usb::frame_number();
+//!     if usb::frames_since(last, frame) >= 1000 {
+//!         last = frame;
+//!         led.toggle();
+//!     }
+//! }
+//! ```
+//!
+//! # Endpoint Budget and Composite Devices
+//! The controller has 7 endpoints, including the control endpoint 0, which share 832 bytes of
+//! endpoint memory (DPRAM):
+//!
+//! | Endpoint | Max. size  | Banks  |
+//! |----------|------------|--------|
+//! | 0        | 64 bytes   | 1      |
+//! | 1        | 256 bytes  | 1 or 2 |
+//! | 2 to 6   | 64 bytes   | 1 or 2 |
+//!
+//! Endpoint sizes are powers of two from 8 bytes, and a double-banked endpoint takes twice its
+//! size in memory.  The direction is fixed per endpoint, so an IN and an OUT endpoint never share
+//! a number.
+//!
+//! This is tight for a composite device: a CDC-ACM serial port needs three endpoints (interrupt
+//! IN for notifications, bulk IN and bulk OUT for the data), and each HID interface one or two
+//! more.  Serial and keyboard take 5 of the 7 endpoints, serial, keyboard and mouse 6, and two
+//! serial ports all of them, leaving nothing for a HID interface.  [`EndpointBudget`] checks a
+//! plan at compile time, in the order the USB stack allocates the endpoints (class by class, in
+//! the order the classes are created):
+//!
+//! ```
+//! use atmega_hal::usb::EndpointBudget;
+//!
+//! // Serial console and keyboard
+//! const ENDPOINTS: EndpointBudget = EndpointBudget::new(64)
+//!     // CDC-ACM: notifications, then data out and in
+//!     .with(8, false)
+//!     .with(64, false)
+//!     .with(64, false)
+//!     // HID keyboard: reports in
+//!     .with(8, false);
+//! ```
+//!
+//! A plan which does not fit fails to compile instead of failing at enumeration, where the stack
+//! can only report an error (or the device silently does not show up).  The serial and HID
+//! classes also need an interface association descriptor (IAD) for the host to bind the CDC
+//! driver to both of its interfaces; with `usb-device`, this means building the device with
+//! `composite_with_iads()`.
+//!
+//! **Note**: A ready-made serial + HID composite device and a matching board example need a USB
+//! device stack (a `UsbBus` implementation and `default_usb_bus!`), which `atmega-hal` does not
+//! have yet.  Until then, this module only covers the endpoint budget of such a device.
+//!
+//! # Double-Banked Endpoints
+//! Endpoints 1 to 6 can have two banks ("ping-pong" mode).  With one bank, the controller NAKs
+//! the host while the firmware empties or fills the bank, so every packet waits for the
+//! firmware.  With two, the controller transfers one bank while the firmware handles the other,
+//! and the endpoint keeps streaming as long as the firmware keeps up.  For bulk endpoints
+//! (CDC-ACM data, mass storage) this roughly doubles the throughput when the firmware is the
+//! bottleneck; interrupt endpoints only send one packet per polling interval and gain nothing.
+//!
+//! The second bank costs another full endpoint size of DPRAM: a 64-byte bulk endpoint takes
+//! 128 bytes, and a 256-byte endpoint 1 takes 512, more than half the memory.  A CDC-ACM port
+//! with double-banked data endpoints takes 264 of the 832 bytes instead of 136.  Plan it
+//! with the `double_bank` argument of [`EndpointBudget::with()`], and configure the endpoint
+//! with [`endpoint_config()`].
+//!
+//! The firmware always accesses the current bank through `UEDATX`, and hands it over by
+//! clearing `FIFOCON` in `UEINTX`, after which the controller switches to the other one.  `TXINI`
+//! (IN) or `RXOUTI` (OUT) signal that the current bank is free or full, and `NBUSYBK` in
+//! `UESTA0X` how many banks are busy, so a stack must not assume a single bank is in flight.
+//! [`configure_endpoint()`], [`write_packet()`] and [`read_packet()`] implement this flow for a
+//! USB stack or a bare-metal driver:
+//!
+//! ```
+//! // Endpoints must be configured in ascending order, after every USB reset.
+//! usb::configure_endpoint(&dp.USB_DEVICE, 1, EndpointType::Bulk, EndpointDirection::In, 64, true)
+//!     .unwrap();
+//!
+//! // With two banks, the second packet is accepted while the first one is still waiting for
+//! // the host.
+//! nb::block!(usb::write_packet(&dp.USB_DEVICE, 1, &first)).unwrap();
+//! nb::block!(usb::write_packet(&dp.USB_DEVICE, 1, &second)).unwrap();
+//! ```
+//!
+//! **Note**: The throughput gain depends on the host, the USB stack and the firmware, and can
+//! only be measured on hardware with a complete stack, which `atmega-hal` does not contain.
+
+use avr_hal_generic::nb;
+
+/// Transfer type of an endpoint, see [`configure_endpoint()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndpointType {
+	Control,
+	Isochronous,
+	Bulk,
+	Interrupt,
+}
+
+/// Direction of an endpoint, as seen from the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndpointDirection {
+	/// Data from the host to the device.
+	Out,
+	/// Data from the device to the host.
+	In,
+}
+
+/// Errors of [`write_packet()`] and [`read_packet()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferError {
+	/// The data is longer than a bank of the endpoint.
+	PacketTooLong,
+	/// The received packet does not fit into the buffer.  It is left in the bank, so it can be
+	/// read again with a larger buffer.
+	BufferTooSmall,
+}
+
+/// Where the device draws its power from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerSource {
+	/// Powered from the `VBUS` line of the host.
+	BusPowered,
+	/// Powered from its own supply.
+	SelfPowered,
+}
+
+/// Power configuration reported to the host in the configuration descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerConfig {
+	pub source: PowerSource,
+	/// Maximum current drawn from `VBUS` in milliamps.  At most 500 mA can be requested.
+	pub max_power_ma: u16,
+}
+
+impl PowerConfig {
+	pub const fn bus_powered(max_power_ma: u16) -> Self {
+		Self {
+			source: PowerSource::BusPowered,
+			max_power_ma,
+		}
+	}
+
+	pub const fn self_powered(max_power_ma: u16) -> Self {
+		Self {
+			source: PowerSource::SelfPowered,
+			max_power_ma,
+		}
+	}
+
+	/// Value for the `bmAttributes` field of the configuration descriptor.
+	pub const fn attributes(&self) -> u8 {
+		match self.source {
+			PowerSource::BusPowered => 0x80,
+			PowerSource::SelfPowered => 0xc0,
+		}
+	}
+
+	/// Value for the `bMaxPower` field of the configuration descriptor (in units of 2 mA).
+	pub const fn max_power(&self) -> u8 {
+		let ma = if self.max_power_ma > 500 {
+			500
+		} else {
+			self.max_power_ma
+		};
+		ma.div_ceil(2) as u8
+	}
+}
+
+/// Enable the `VBUS` pad so [`vbus_present()`] reports the state of the `VBUS` line.
+///
+/// This also enables the USB controller itself, as its status registers are held in reset
+/// otherwise.  The controller clock is left as it is (frozen after reset), so no PLL is needed.
+pub fn enable_vbus_sensing(usb: &crate::pac::USB_DEVICE) {
+	usb.usbcon()
+		.modify(|_, w| w.usbe().set_bit().otgpade().set_bit());
+}
+
+/// Check whether a USB host is connected, by reading `USBSTA.VBUS`.
+///
+/// Only valid after [`enable_vbus_sensing()`] was called.
+pub fn vbus_present() -> bool {
+	// SAFETY: Reading USBSTA has no side effects.
+	unsafe { &*crate::pac::USB_DEVICE::ptr() }
+		.usbsta()
+		.read()
+		.vbus()
+		.bit_is_set()
+}
+
+/// Read the number of the last start-of-frame packet from `UDFNUM`.
+///
+/// The frame number is 11 bits wide and wraps around every 2048 ms.  Use [`frames_since()`] to
+/// compute elapsed frames across the wrap-around.
+pub fn frame_number() -> u16 {
+	// SAFETY: Reading UDFNUM has no side effects.
+	unsafe { &*crate::pac::USB_DEVICE::ptr() }
+		.udfnum()
+		.read()
+		.bits() & 0x07ff
+}
+
+/// Number of frames (milliseconds) from frame number `earlier` to `later`, modulo 2048.
+pub fn frames_since(earlier: u16, later: u16) -> u16 {
+	later.wrapping_sub(earlier) & 0x07ff
+}
+
+/// Enable or disable the `USB_GEN` interrupt on each start-of-frame packet.
+pub fn enable_sof_interrupt(usb: &crate::pac::USB_DEVICE, enable: bool) {
+	usb.udien().modify(|_, w| w.sofe().bit(enable));
+}
+
+/// Check whether a start-of-frame packet was received since the last call and clear the flag.
+pub fn take_sof() -> bool {
+	// SAFETY: Only the SOFI flag is cleared; writing one to the other flags has no effect, so
+	// flags raised concurrently are not lost.
+	let usb = unsafe { &*crate::pac::USB_DEVICE::ptr() };
+	if usb.udint().read().sofi().bit_is_clear() {
+		return false;
+	}
+	usb.udint()
+		.write(|w| unsafe { w.bits(0xff) }.sofi().clear_bit());
+	true
+}
+
+/// Number of endpoints of the USB controller, including the control endpoint 0.
+pub const ENDPOINT_COUNT: u8 = 7;
+
+/// Size of the endpoint memory (DPRAM) in bytes, shared by all endpoints.
+pub const ENDPOINT_MEMORY: u16 = 832;
+
+/// Reasons an endpoint doesn't fit into the controller, see [`EndpointBudget::allocate()`] and
+/// [`configure_endpoint()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndpointError {
+	/// All [`ENDPOINT_COUNT`] endpoints are taken.
+	NoEndpointLeft,
+	/// The size is not a power of two from 8 bytes up to the maximum of the endpoint.
+	InvalidSize,
+	/// The endpoint memory is exhausted.
+	OutOfMemory,
+}
+
+/// Allocation plan for the endpoints of the USB controller.  See the
+/// [module documentation](self#endpoint-budget-and-composite-devices).
+///
+/// Endpoints are numbered in allocation order, starting with 1 after the control endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EndpointBudget {
+	endpoints: u8,
+	memory: u16,
+}
+
+impl EndpointBudget {
+	/// Start a plan with the control endpoint 0 of `ep0_size` bytes (8 to 64).
+	///
+	/// # Panics
+	/// Panics (at compile time in a `const`) if `ep0_size` is invalid.
+	pub const fn new(ep0_size: u16) -> Self {
+		let empty = Self {
+			endpoints: 0,
+			memory: 0,
+		};
+		match empty.allocate(ep0_size, false) {
+			Ok(budget) => budget,
+			Err(_) => panic!("invalid size for endpoint 0"),
+		}
+	}
+
+	/// Add the next endpoint with `size` bytes per bank, double-banked if `double_bank` is set.
+	///
+	/// Double-banking lets the controller fill one bank while the firmware handles the other,
+	/// which improves the throughput of bulk endpoints at the cost of memory.
+	pub const fn allocate(self, size: u16, double_bank: bool) -> Result<Self, EndpointError> {
+		if self.endpoints >= ENDPOINT_COUNT {
+			return Err(EndpointError::NoEndpointLeft);
+		}
+		let max_size = if self.endpoints == 1 { 256 } else { 64 };
+		if size < 8 || size > max_size || !size.is_power_of_two() {
+			return Err(EndpointError::InvalidSize);
+		}
+		// Endpoint 0 can't be double-banked.
+		let banks = if double_bank && self.endpoints != 0 {
+			2
+		} else {
+			1
+		};
+		let memory = self.memory + size * banks;
+		if memory > ENDPOINT_MEMORY {
+			return Err(EndpointError::OutOfMemory);
+		}
+		Ok(Self {
+			endpoints: self.endpoints + 1,
+			memory,
+		})
+	}
+
+	/// Like [`allocate()`][EndpointBudget::allocate], but panics if the endpoint doesn't fit.
+	/// In a `const`, this turns an impossible plan into a compile error.
+	pub const fn with(self, size: u16, double_bank: bool) -> Self {
+		match self.allocate(size, double_bank) {
+			Ok(budget) => budget,
+			Err(EndpointError::NoEndpointLeft) => panic!("no USB endpoint left"),
+			Err(EndpointError::InvalidSize) => panic!("invalid USB endpoint size"),
+			Err(EndpointError::OutOfMemory) => panic!("USB endpoint memory exhausted"),
+		}
+	}
+
+	/// Number of the endpoint the next allocation gets.
+	pub const fn next_endpoint(&self) -> u8 {
+		self.endpoints
+	}
+
+	/// Endpoints still available.
+	pub const fn endpoints_left(&self) -> u8 {
+		ENDPOINT_COUNT - self.endpoints
+	}
+
+	/// Endpoint memory still available in bytes.
+	pub const fn memory_left(&self) -> u16 {
+		ENDPOINT_MEMORY - self.memory
+	}
+}
+
+/// Encoding of an endpoint size in the `EPSIZE` field of `UECFG1X`.
+const fn epsize(size: u16) -> Option<u8> {
+	match size {
+		8 => Some(0b000),
+		16 => Some(0b001),
+		32 => Some(0b010),
+		64 => Some(0b011),
+		128 => Some(0b100),
+		256 => Some(0b101),
+		_ => None,
+	}
+}
+
+/// Size of one bank in bytes, decoded from a `UECFG1X` value.
+const fn bank_size(uecfg1x: u8) -> u16 {
+	8 << ((uecfg1x >> 4) & 0b111)
+}
+
+/// Value for the `UECFG1X` register: allocate an endpoint of `size` bytes per bank (8 to 256, a
+/// power of two), double-banked if `double_bank` is set.
+///
+/// # Panics
+/// Panics (at compile time in a `const`) if `size` is invalid.
+pub const fn endpoint_config(size: u16, double_bank: bool) -> u8 {
+	let epsize = match epsize(size) {
+		Some(epsize) => epsize,
+		None => panic!("invalid USB endpoint size"),
+	};
+	// EPSIZE in bits 6:4, EPBK (0b01 for two banks) in bits 3:2, ALLOC in bit 1.
+	let epbk = if double_bank { 0b01 } else { 0b00 };
+	(epsize << 4) | (epbk << 2) | (1 << 1)
+}
+
+/// Enable and allocate endpoint `number` (1 to 6), with `size` bytes per bank and a second bank
+/// if `double_bank` is set.
+///
+/// The controller allocates the endpoint memory in the order of the endpoint numbers, so
+/// endpoints must be configured in ascending order.  All endpoints are deconfigured on a USB
+/// reset and have to be configured again.
+pub fn configure_endpoint(
+	usb: &crate::pac::USB_DEVICE,
+	number: u8,
+	ep_type: EndpointType,
+	direction: EndpointDirection,
+	size: u16,
+	double_bank: bool,
+) -> Result<(), EndpointError> {
+	if number == 0 || number >= ENDPOINT_COUNT {
+		return Err(EndpointError::NoEndpointLeft);
+	}
+	let max_size = if number == 1 { 256 } else { 64 };
+	if size > max_size || epsize(size).is_none() {
+		return Err(EndpointError::InvalidSize);
+	}
+
+	avr_device::interrupt::free(|_| {
+		select_endpoint(usb, number);
+		usb.ueconx().write(|w| w.epen().set_bit());
+		usb.uecfg0x().write(|w| {
+			w.eptype()
+				.set(ep_type as u8)
+				.epdir()
+				.bit(direction == EndpointDirection::In)
+		});
+		usb.uecfg1x()
+			.write(|w| unsafe { w.bits(endpoint_config(size, double_bank)) });
+
+		if usb.uesta0x().read().cfgok().bit_is_set() {
+			Ok(())
+		} else {
+			usb.ueconx().reset();
+			usb.uecfg1x().reset();
+			Err(EndpointError::OutOfMemory)
+		}
+	})
+}
+
+/// Send `data` as one packet on IN endpoint `number`.
+///
+/// Returns `WouldBlock` while no bank is free.  On a double-banked endpoint, a second packet is
+/// accepted while the first one still waits for the host.  `data` can be empty to send a
+/// zero-length packet.
+pub fn write_packet(
+	usb: &crate::pac::USB_DEVICE,
+	number: u8,
+	data: &[u8],
+) -> nb::Result<(), TransferError> {
+	avr_device::interrupt::free(|_| {
+		select_endpoint(usb, number);
+		if data.len() > usize::from(bank_size(usb.uecfg1x().read().bits())) {
+			return Err(nb::Error::Other(TransferError::PacketTooLong));
+		}
+		// TXINI is set while the current bank is free.
+		if usb.ueintx().read().txini().bit_is_clear() {
+			return Err(nb::Error::WouldBlock);
+		}
+		for &byte in data {
+			usb.uedatx().write(|w| unsafe { w.bits(byte) });
+		}
+		// Hand the bank to the controller, which switches to the other bank (if any).  Writing
+		// one to the other flags has no effect.
+		usb.ueintx().write(|w| {
+			unsafe { w.bits(0xff) }
+				.txini()
+				.clear_bit()
+				.fifocon()
+				.clear_bit()
+		});
+		Ok(())
+	})
+}
+
+/// Receive one packet from OUT endpoint `number` into `buf` and return its length.
+///
+/// Returns `WouldBlock` while no bank holds a packet.  On a double-banked endpoint, the host can
+/// already send the next packet while this one is read.
+pub fn read_packet(
+	usb: &crate::pac::USB_DEVICE,
+	number: u8,
+	buf: &mut [u8],
+) -> nb::Result<usize, TransferError> {
+	avr_device::interrupt::free(|_| {
+		select_endpoint(usb, number);
+		// RXOUTI is set while the current bank holds a packet.
+		if usb.ueintx().read().rxouti().bit_is_clear() {
+			return Err(nb::Error::WouldBlock);
+		}
+		let len = usize::from(u16::from_le_bytes([
+			usb.uebclx().read().bits(),
+			usb.uebchx().read().bits() & 0b111,
+		]));
+		if len > buf.len() {
+			return Err(nb::Error::Other(TransferError::BufferTooSmall));
+		}
+		for byte in &mut buf[..len] {
+			*byte = usb.uedatx().read().bits();
+		}
+		// Release the bank, so the controller can fill it with the next packet.
+		usb.ueintx().write(|w| {
+			unsafe { w.bits(0xff) }
+				.rxouti()
+				.clear_bit()
+				.fifocon()
+				.clear_bit()
+		});
+		Ok(len)
+	})
+}
+
+/// Number of banks of endpoint `number` which are busy (0 to 2).
+///
+/// For an IN endpoint, these hold packets waiting for the host, for an OUT endpoint packets
+/// waiting for the firmware.
+pub fn busy_banks(usb: &crate::pac::USB_DEVICE, number: u8) -> u8 {
+	avr_device::interrupt::free(|_| {
+		select_endpoint(usb, number);
+		usb.uesta0x().read().nbusybk().bits()
+	})
+}
+
+fn select_endpoint(usb: &crate::pac::USB_DEVICE, number: u8) {
+	usb.uenum().write(|w| unsafe { w.bits(number & 0b111) });
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn double_banked_cdc_budget() {
+		let ep0 = EndpointBudget::new(64);
+		let single = ep0.with(8, false).with(64, false).with(64, false);
+		let double = ep0.with(8, false).with(64, true).with(64, true);
+		assert_eq!(ep0.memory_left() - single.memory_left(), 136);
+		assert_eq!(ep0.memory_left() - double.memory_left(), 264);
+		assert_eq!(double.next_endpoint(), 4);
+		assert_eq!(double.endpoints_left(), 3);
+	}
+
+	#[test]
+	fn endpoint_limits() {
+		let ep0 = EndpointBudget::new(64);
+		assert_eq!(ep0.memory_left(), ENDPOINT_MEMORY - 64);
+		assert_eq!(ep0.next_endpoint(), 1);
+
+		// Only endpoint 1 goes up to 256 bytes
+		let ep1 = ep0.with(256, true);
+		assert_eq!(ep1.memory_left(), 256);
+		assert_eq!(ep1.allocate(128, false), Err(EndpointError::InvalidSize));
+		assert_eq!(ep1.allocate(48, false), Err(EndpointError::InvalidSize));
+		// Exactly fills the memory
+		let full = ep1.with(64, true).with(64, false).with(64, false);
+		assert_eq!(full.memory_left(), 0);
+		assert_eq!(full.allocate(8, false), Err(EndpointError::OutOfMemory));
+
+		let full = (1..ENDPOINT_COUNT).fold(ep0, |budget, _| budget.with(8, false));
+		assert_eq!(full.allocate(8, false), Err(EndpointError::NoEndpointLeft));
+	}
+
+	#[test]
+	fn endpoint_config_register() {
+		assert_eq!(endpoint_config(8, false), 0b0000_0010);
+		assert_eq!(endpoint_config(64, false), 0b0011_0010);
+		assert_eq!(endpoint_config(64, true), 0b0011_0110);
+		assert_eq!(endpoint_config(256, true), 0b0101_0110);
+	}
+
+	#[test]
+	fn bank_size_of_config() {
+		for size in [8, 16, 32, 64, 128, 256] {
+			assert_eq!(bank_size(endpoint_config(size, false)), size);
+			assert_eq!(bank_size(endpoint_config(size, true)), size);
+		}
+		assert_eq!(epsize(512), None);
+		assert_eq!(epsize(48), None);
+	}
+
+	#[test]
+	#[should_panic(expected = "invalid USB endpoint size")]
+	fn endpoint_config_invalid_size() {
+		endpoint_config(512, false);
+	}
+}
@@ -1,3 +1,4 @@
+pub use avr_hal_generic::wdt::guarded_loop;
 #[allow(unused_imports)]
 pub use avr_hal_generic::wdt::Timeout;
 #[allow(unused_imports)]
@@ -28,6 +29,8 @@ avr_hal_generic::impl_wdt! {
 	Timeout::Ms4000 => w.wdph().set_bit().wdpl().cycles_2k_512k(),
 	Timeout::Ms8000 => w.wdph().set_bit().wdpl().cycles_4k_1024k(),
     },
+    interrupt_enable: wdie,
+    vector: crate::pac::Interrupt::WDT as u8,
 }
 
 #[cfg(any(feature = "atmega8", feature = "atmega32a", feature = "atmega128a"))]
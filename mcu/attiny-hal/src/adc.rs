@@ -26,9 +26,16 @@
 //! ```
 
 use crate::port;
+pub use avr_hal_generic::adc::trimmed_mean;
 pub use avr_hal_generic::adc::AdcChannel;
 pub use avr_hal_generic::adc::AdcOps;
+pub use avr_hal_generic::adc::AnalogInput;
 pub use avr_hal_generic::adc::ClockDivider;
+pub use avr_hal_generic::adc::DiffCoding;
+pub use avr_hal_generic::adc::SchmittInput;
+pub use avr_hal_generic::adc::TwoPointCalibration;
+pub use avr_hal_generic::adc::Window;
+pub use avr_hal_generic::adc::WindowEvent;
 
 /// Select the voltage reference for the ADC peripheral
 ///
@@ -68,6 +75,14 @@ pub type Adc<CLOCK> = avr_hal_generic::adc::Adc<crate::Attiny, crate::pac::ADC,
 /// Check the [`avr_hal_generic::adc::Channel`] documentation.
 pub type Channel = avr_hal_generic::adc::Channel<crate::Attiny, crate::pac::ADC>;
 
+/// Check the [`avr_hal_generic::adc::CalibratedDiffAdc`] documentation.
+pub type CalibratedDiffAdc =
+	avr_hal_generic::adc::CalibratedDiffAdc<crate::Attiny, crate::pac::ADC>;
+
+/// Check the [`avr_hal_generic::adc::AdcInput`] documentation.
+pub type AdcInput<'a, CLOCK> =
+	avr_hal_generic::adc::AdcInput<'a, crate::Attiny, crate::pac::ADC, CLOCK>;
+
 /// Additional channels
 ///
 /// Some channels are not directly connected to pins.  This module provides types which can be used
@@ -18,10 +18,20 @@
 //! ufmt::uwriteln!(&mut serial, "Boot count: {}", boot_count).unwrap();
 //! ```
 
+pub use avr_hal_generic::eeprom::Config;
 pub use avr_hal_generic::eeprom::EepromOps;
+pub use avr_hal_generic::eeprom::EepromRing;
+pub use avr_hal_generic::eeprom::EepromWriteModeOps;
+pub use avr_hal_generic::eeprom::Error;
+pub use avr_hal_generic::eeprom::LoadError;
 pub use avr_hal_generic::eeprom::OutOfBoundsError;
+pub use avr_hal_generic::eeprom::Persist;
+pub use avr_hal_generic::eeprom::SupplyVoltage;
+pub use avr_hal_generic::eeprom::WriteMode;
 
 pub type Eeprom = avr_hal_generic::eeprom::Eeprom<crate::Attiny, crate::pac::EEPROM>;
+pub type GuardedEeprom<V> =
+	avr_hal_generic::eeprom::GuardedEeprom<crate::Attiny, crate::pac::EEPROM, V>;
 
 #[cfg(feature = "attiny2313")]
 avr_hal_generic::impl_eeprom_attiny! {
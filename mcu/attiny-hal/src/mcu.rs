@@ -0,0 +1,33 @@
+//! Memory sizes of the selected MCU
+//!
+//! These allow sizing buffers and checking limits at compile time:
+//!
+//! ```
+//! use attiny_hal::mcu;
+//!
+//! const BUF_LEN: usize = 64;
+//! // Leave at least half of the RAM for the stack and everything else.
+//! const _: () = assert!(BUF_LEN <= mcu::RAM_SIZE / 2);
+//! ```
+
+macro_rules! memory_sizes {
+	(flash: $flash:expr, ram: $ram:expr, eeprom: $eeprom:expr $(,)?) => {
+		/// Size of the program flash in bytes.
+		///
+		/// This is a `u32` because the flash of larger AVR devices exceeds the 16-bit `usize`.
+		pub const FLASH_SIZE: u32 = $flash;
+		/// Size of the internal SRAM in bytes, excluding registers and I/O space.
+		pub const RAM_SIZE: usize = $ram;
+		/// Size of the EEPROM in bytes.
+		pub const EEPROM_SIZE: usize = $eeprom;
+	};
+}
+
+#[cfg(feature = "attiny2313")]
+memory_sizes!(flash: 2 * 1024, ram: 128, eeprom: 128);
+#[cfg(feature = "attiny88")]
+memory_sizes!(flash: 8 * 1024, ram: 512, eeprom: 64);
+#[cfg(any(feature = "attiny84", feature = "attiny85"))]
+memory_sizes!(flash: 8 * 1024, ram: 512, eeprom: 512);
+#[cfg(feature = "attiny167")]
+memory_sizes!(flash: 16 * 1024, ram: 512, eeprom: 512);
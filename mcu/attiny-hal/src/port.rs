@@ -18,6 +18,7 @@
 //! ```
 
 pub use avr_hal_generic::port::mode;
+pub use avr_hal_generic::port::PinLocation;
 pub use avr_hal_generic::port::PinMode;
 pub use avr_hal_generic::port::PinOps;
 
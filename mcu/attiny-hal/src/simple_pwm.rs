@@ -1,6 +1,10 @@
 pub use avr_hal_generic::simple_pwm::IntoPwmPin;
 pub use avr_hal_generic::simple_pwm::Prescaler;
+pub use avr_hal_generic::simple_pwm::PwmDac;
 pub use avr_hal_generic::simple_pwm::PwmPinOps;
+pub use avr_hal_generic::simple_pwm::WaveGen;
+pub use avr_hal_generic::simple_pwm::SINE_TABLE;
+pub use avr_hal_generic::simple_pwm::WAVE_TABLE_LEN;
 
 #[cfg(any(feature = "attiny85", feature = "attiny84", feature = "attiny88"))]
 use crate::port::*;
@@ -10,6 +14,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// Use `TC0` for PWM (pins `PB2`, `PA7`)
     pub struct Timer0Pwm {
 	timer: crate::pac::TC0,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr0a().modify(|_r, w| w.wgm0().pwm_fast());
 	    tim.tccr0b().modify(|_r, w| match prescaler {
@@ -47,6 +52,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// Use `TC1` for PWM (pins `PA6`, 'PA5')
     pub struct Timer1Pwm {
 	timer: crate::pac::TC1,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr1a().modify(|_, w| w.wgm1().set(0b01));
 	    tim.tccr1b().modify(|_, w| w.wgm1().set(0b01));
@@ -97,6 +103,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer0Pwm {
 	timer: crate::pac::TC0,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr0a().modify(|_r, w| w.wgm0().pwm_fast());
 	    tim.tccr0b().modify(|_r, w| match prescaler {
@@ -144,6 +151,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer1Pwm {
 	timer: crate::pac::TC1,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.gtccr().modify(|_, w| w.pwm1b().bit(true));
 
@@ -184,6 +192,7 @@ avr_hal_generic::impl_simple_pwm! {
     /// ```
     pub struct Timer1Pwm {
 	timer: crate::pac::TC1,
+	period: 256,
 	init: |tim, prescaler| {
 	    tim.tccr1a().modify(|_, w| w.wgm1().set(0b01));
 	    tim.tccr1b().modify(|_, w| w.wgm1().set(0b01));
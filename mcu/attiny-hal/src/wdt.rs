@@ -1,3 +1,4 @@
+pub use avr_hal_generic::wdt::guarded_loop;
 #[allow(unused_imports)]
 pub use avr_hal_generic::wdt::Timeout;
 #[allow(unused_imports)]
@@ -5,6 +6,16 @@ pub use avr_hal_generic::wdt::WdtOps;
 
 pub type Wdt = avr_hal_generic::wdt::Wdt<crate::Attiny, crate::pac::WDT>;
 
+#[cfg(any(
+	feature = "attiny84",
+	feature = "attiny85",
+	feature = "attiny88",
+	feature = "attiny167"
+))]
+const WDT_VECTOR: u8 = crate::pac::Interrupt::WDT as u8;
+#[cfg(feature = "attiny2313")]
+const WDT_VECTOR: u8 = crate::pac::Interrupt::WDT_OVERFLOW as u8;
+
 #[cfg(any(feature = "attiny85", feature = "attiny167", feature = "attiny2313"))]
 avr_hal_generic::impl_wdt! {
     hal: crate::Attiny,
@@ -23,6 +34,8 @@ avr_hal_generic::impl_wdt! {
 	Timeout::Ms4000 => w.wdph().set_bit().wdpl().cycles_2k_512k(),
 	Timeout::Ms8000 => w.wdph().set_bit().wdpl().cycles_4k_1024k(),
     },
+    interrupt_enable: wdie,
+    vector: WDT_VECTOR,
 }
 
 #[cfg(any(feature = "attiny84", feature = "attiny88"))]
@@ -43,4 +56,6 @@ avr_hal_generic::impl_wdt! {
 	Timeout::Ms4000 => w.wdph().set_bit().wdpl().cycles_2k_512k(),
 	Timeout::Ms8000 => w.wdph().set_bit().wdpl().cycles_4k_1024k(),
     },
+    interrupt_enable: wdie,
+    vector: WDT_VECTOR,
 }